@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+use libbpf_rs::Btf;
+
+use crate::metadata;
+
+
+/// Generate minimized BTF files for `objects` from each of the full
+/// kernel BTF files in `btf_dir`, placing them under the same name into
+/// `output`.
+pub fn min_core_btf_single(
+    debug: bool,
+    objects: &[PathBuf],
+    btf_dir: &Path,
+    output: &Path,
+) -> Result<()> {
+    let mut btf_files = fs::read_dir(btf_dir)
+        .with_context(|| format!("failed to read directory {}", btf_dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to read directory {}", btf_dir.display()))?;
+    let () = btf_files.retain(|path| path.is_file());
+    let () = btf_files.sort();
+
+    if btf_files.is_empty() {
+        bail!("Did not find any BTF files in {}", btf_dir.display());
+    }
+
+    let () = fs::create_dir_all(output)
+        .with_context(|| format!("failed to create directory {}", output.display()))?;
+
+    for btf_file in btf_files {
+        // SANITY: We only ever consider files, which have a name.
+        let out_file = output.join(btf_file.file_name().unwrap());
+        if debug {
+            println!(
+                "Minimizing {} to {}",
+                btf_file.display(),
+                out_file.display()
+            );
+        }
+
+        let btf = Btf::from_path(&btf_file)
+            .with_context(|| format!("failed to parse BTF file {}", btf_file.display()))?;
+        let min = btf.min_core(objects).with_context(|| {
+            format!(
+                "failed to generate minimized BTF for {}",
+                btf_file.display()
+            )
+        })?;
+        let data = min.to_bytes()?;
+        let () = fs::write(&out_file, data)
+            .with_context(|| format!("failed to write {}", out_file.display()))?;
+    }
+    Ok(())
+}
+
+/// Determine the BPF object files to work on: either the explicitly
+/// provided `objects` or those of the project at `manifest_path`.
+pub fn resolve_objects(
+    debug: bool,
+    manifest_path: Option<&PathBuf>,
+    objects: Vec<PathBuf>,
+) -> Result<Vec<PathBuf>> {
+    if manifest_path.is_some() && !objects.is_empty() {
+        bail!("--manifest-path and --object cannot be used together");
+    }
+
+    let objects = if objects.is_empty() {
+        let (_target_dir, objs) = metadata::get(debug, manifest_path)?;
+        objs.into_iter()
+            .map(|obj| obj.out.join(format!("{}.bpf.o", obj.name)))
+            .collect::<Vec<_>>()
+    } else {
+        objects
+    };
+
+    if objects.is_empty() {
+        bail!("Did not find any bpf objects");
+    }
+    Ok(objects)
+}
+
+pub fn min_core_btf(
+    debug: bool,
+    manifest_path: Option<&PathBuf>,
+    objects: Vec<PathBuf>,
+    btf_dir: &Path,
+    output: &Path,
+) -> Result<()> {
+    let objects = resolve_objects(debug, manifest_path, objects)?;
+    if debug {
+        println!("Generating minimized BTF for bpf objs:");
+        for obj in &objects {
+            println!("\t{}", obj.display());
+        }
+    }
+
+    min_core_btf_single(debug, &objects, btf_dir, output)
+}
@@ -0,0 +1,89 @@
+use std::io::stdout;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+use libbpf_rs::btf::check_core_compat;
+use libbpf_rs::btf::KernelCompat;
+
+use crate::btfgen::resolve_objects;
+
+
+/// Write a human readable report of the CO-RE compatibility of
+/// `object` with the kernels described by `kernels` to `out`.
+///
+/// Return the total number of failed relocations.
+fn report(out: &mut dyn Write, object: &Path, kernels: &[KernelCompat]) -> Result<usize> {
+    writeln!(out, "{}:", object.display())?;
+
+    let mut total = 0;
+    for kernel in kernels {
+        let failures = kernel
+            .programs
+            .iter()
+            .map(|prog| prog.failures.len())
+            .sum::<usize>();
+        total += failures;
+        if failures == 0 {
+            writeln!(out, "  {}: ok", kernel.btf.display())?;
+            continue
+        }
+
+        writeln!(
+            out,
+            "  {}: {failures} failed relocation(s)",
+            kernel.btf.display()
+        )?;
+        for prog in kernel.programs.iter().filter(|prog| !prog.is_compatible()) {
+            writeln!(out, "    {} ({}):", prog.name, prog.section)?;
+            for failure in &prog.failures {
+                writeln!(
+                    out,
+                    "      insn #{}: {} {}: {}",
+                    failure.insn_idx, failure.kind, failure.spec, failure.reason
+                )?;
+                if let Some(source) = &failure.source {
+                    writeln!(out, "        at {source}: {}", source.text)?;
+                }
+            }
+        }
+    }
+    Ok(total)
+}
+
+/// Check the CO-RE relocations of bpf objects against the kernels in
+/// `btf_dir`, failing if any of them can't be resolved.
+pub fn check_core(
+    debug: bool,
+    manifest_path: Option<&PathBuf>,
+    objects: Vec<PathBuf>,
+    btf_dir: &Path,
+) -> Result<()> {
+    let objects = resolve_objects(debug, manifest_path, objects)?;
+    if debug {
+        println!("Checking CO-RE compatibility of bpf objs:");
+        for obj in &objects {
+            println!("\t{}", obj.display());
+        }
+    }
+
+    let mut out = stdout().lock();
+    let mut failures = 0;
+    for object in &objects {
+        let kernels = check_core_compat(object, btf_dir).with_context(|| {
+            format!(
+                "failed to check CO-RE compatibility of {}",
+                object.display()
+            )
+        })?;
+        failures += report(&mut out, object, &kernels)?;
+    }
+
+    if failures > 0 {
+        bail!("{failures} CO-RE relocation(s) failed to resolve");
+    }
+    Ok(())
+}
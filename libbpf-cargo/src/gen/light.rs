@@ -0,0 +1,480 @@
+//! Generation of light skeletons, which load their BPF object by means
+//! of a loader program generated by `libbpf`.
+
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::fmt::Write as fmt_write;
+use std::fs::File;
+use std::io;
+use std::mem::size_of;
+use std::path::Path;
+use std::slice;
+
+use anyhow::Context;
+use anyhow::Result;
+
+use libbpf_rs::libbpf_sys;
+use libbpf_rs::AsRawLibbpf;
+use libbpf_rs::Btf;
+use libbpf_rs::Map;
+use libbpf_rs::MapIter;
+use libbpf_rs::ProgIter;
+
+use memmap2::Mmap;
+
+use super::btf::GenBtf;
+use super::capitalize_first_letter;
+use super::escape_raw_name;
+use super::gen_skel_types;
+use super::get_map_name;
+use super::get_raw_map_name;
+use super::map_is_datasec;
+use super::map_is_readonly;
+use super::open_bpf_object;
+use super::MAP_NAME_KCONFIG;
+
+
+/// Data about a single BPF map of a light skeleton.
+struct LightMap {
+    name: String,
+    /// For maps backing global data sections: the initial contents
+    /// along with whether the map is read-only.
+    datasec: Option<(Vec<u8>, bool)>,
+}
+
+
+/// How a program of a light skeleton gets attached, mirroring
+/// `libbpf_rs::light_skel::LightAttach`.
+enum LightAttach {
+    None,
+    RawTracepoint(String),
+    Tracing,
+    Iter,
+}
+
+
+/// Data about a single BPF program of a light skeleton.
+struct LightProg {
+    name: String,
+    attach: LightAttach,
+}
+
+
+fn light_maps(object: &libbpf_sys::bpf_object) -> Result<Vec<LightMap>> {
+    // The loader program refers to maps by their index, so we need to
+    // consider all of them, including those not created by default.
+    MapIter::new(object)
+        .map(|ptr| {
+            // SAFETY: We never use the `AsFd` impl of the map.
+            let map = unsafe { Map::from_map_without_fd(ptr) };
+            let name = match get_map_name(&map)? {
+                Some(name) => name,
+                None => escape_raw_name(&get_raw_map_name(&map)?),
+            };
+            let datasec = if map_is_datasec(&map) && name != MAP_NAME_KCONFIG {
+                let mut size = 0;
+                // SAFETY: The map pointer is valid.
+                let value = unsafe { libbpf_sys::bpf_map__initial_value(ptr.as_ptr(), &mut size) };
+                let value = if value.is_null() {
+                    vec![0; size as usize]
+                } else {
+                    // SAFETY: `libbpf` reported the initial value to be
+                    //         `size` bytes large.
+                    unsafe { slice::from_raw_parts(value.cast::<u8>(), size as usize) }.to_vec()
+                };
+                Some((value, map_is_readonly(&map)))
+            } else {
+                None
+            };
+            Ok(LightMap { name, datasec })
+        })
+        .collect()
+}
+
+fn light_progs(object: &libbpf_sys::bpf_object) -> Result<Vec<LightProg>> {
+    ProgIter::new(object)
+        .map(|ptr| {
+            let ptr = ptr.as_ptr();
+            // SAFETY: The program pointer is valid and so are the
+            //         strings `libbpf` returns for it.
+            let (name, section, ty, attach_type) = unsafe {
+                (
+                    CStr::from_ptr(libbpf_sys::bpf_program__name(ptr)),
+                    CStr::from_ptr(libbpf_sys::bpf_program__section_name(ptr)),
+                    libbpf_sys::bpf_program__type(ptr),
+                    libbpf_sys::bpf_program__expected_attach_type(ptr),
+                )
+            };
+            let name = name
+                .to_str()
+                .context("program has invalid name")?
+                .to_string();
+            let section = section
+                .to_str()
+                .context("program has invalid section name")?;
+
+            let attach = match ty {
+                libbpf_sys::BPF_PROG_TYPE_RAW_TRACEPOINT
+                | libbpf_sys::BPF_PROG_TYPE_RAW_TRACEPOINT_WRITABLE => {
+                    match section.split_once('/') {
+                        Some((_, tp_name)) if !tp_name.is_empty() => {
+                            LightAttach::RawTracepoint(tp_name.to_string())
+                        }
+                        _ => LightAttach::None,
+                    }
+                }
+                libbpf_sys::BPF_PROG_TYPE_TRACING | libbpf_sys::BPF_PROG_TYPE_LSM => {
+                    if attach_type == libbpf_sys::BPF_TRACE_ITER {
+                        LightAttach::Iter
+                    } else {
+                        LightAttach::Tracing
+                    }
+                }
+                _ => LightAttach::None,
+            };
+            Ok(LightProg { name, attach })
+        })
+        .collect()
+}
+
+/// Record the loading of `object` with `libbpf`'s loader generator,
+/// returning the loader program's data blob and instructions.
+fn gen_loader(object: &mut libbpf_sys::bpf_object) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut opts = libbpf_sys::gen_loader_opts {
+        sz: size_of::<libbpf_sys::gen_loader_opts>() as _,
+        ..Default::default()
+    };
+    // SAFETY: The object pointer is valid and `opts` outlives the load
+    //         below, during which it is filled in.
+    let ret = unsafe { libbpf_sys::bpf_object__gen_loader(object, &mut opts) };
+    if ret != 0 {
+        return Err(io::Error::from_raw_os_error(-ret)).context("failed to enable loader generation")
+    }
+    // SAFETY: The object pointer is valid. Nothing gets loaded into the
+    //         kernel while loader generation is enabled.
+    let ret = unsafe { libbpf_sys::bpf_object__load(object) };
+    if ret != 0 {
+        return Err(io::Error::from_raw_os_error(-ret)).context("failed to generate loader program")
+    }
+
+    // SAFETY: `libbpf` provides the data blob and instructions in `opts`
+    //         and keeps them alive for as long as the object exists.
+    let (data, insns) = unsafe {
+        (
+            slice::from_raw_parts(opts.data.cast::<u8>(), opts.data_sz as usize).to_vec(),
+            slice::from_raw_parts(opts.insns.cast::<u8>(), opts.insns_sz as usize).to_vec(),
+        )
+    };
+    Ok((data, insns))
+}
+
+fn gen_light_skel_spec(skel: &mut String, maps: &[LightMap], progs: &[LightProg]) -> Result<()> {
+    write!(
+        skel,
+        "\
+        static SPEC: libbpf_rs::light_skel::LightSkelSpec = libbpf_rs::light_skel::LightSkelSpec {{
+            data: DATA,
+            insns: INSNS,
+            maps: &[
+        ",
+    )?;
+
+    for map in maps {
+        let (initial_value, read_only) = match &map.datasec {
+            Some((value, read_only)) => (format!("Some(&{value:?})"), *read_only),
+            None => ("None".to_string(), false),
+        };
+        write!(
+            skel,
+            "\
+                libbpf_rs::light_skel::LightMapSpec {{
+                    name: \"{name}\",
+                    initial_value: {initial_value},
+                    read_only: {read_only},
+                }},
+            ",
+            name = map.name,
+        )?;
+    }
+
+    write!(
+        skel,
+        "\
+            ],
+            progs: &[
+        ",
+    )?;
+
+    for prog in progs {
+        let attach = match &prog.attach {
+            LightAttach::None => "None".to_string(),
+            LightAttach::RawTracepoint(tp_name) => format!("RawTracepoint({tp_name:?})"),
+            LightAttach::Tracing => "Tracing".to_string(),
+            LightAttach::Iter => "Iter".to_string(),
+        };
+        write!(
+            skel,
+            "\
+                libbpf_rs::light_skel::LightProgSpec {{
+                    name: \"{name}\",
+                    attach: libbpf_rs::light_skel::LightAttach::{attach},
+                }},
+            ",
+            name = prog.name,
+        )?;
+    }
+
+    write!(
+        skel,
+        "\
+            ],
+        }};
+        ",
+    )?;
+    Ok(())
+}
+
+/// Generate accessors for the global data sections of a light
+/// skeleton, either the "open" (`open` is `true`) or the loaded one.
+fn gen_light_skel_data_getters(skel: &mut String, maps: &[LightMap], open: bool) -> Result<()> {
+    for (idx, map) in maps.iter().enumerate() {
+        let Some((_, read_only)) = &map.datasec else {
+            continue
+        };
+
+        write!(
+            skel,
+            "\
+            pub fn {name}(&self) -> &types::{name} {{
+                let data = self.skel.map_data({idx}).expect(\"BPF map `{name}` has no data\");
+                // SAFETY: The data is page aligned and large enough to
+                //         hold the section's contents.
+                unsafe {{ &*data.as_ptr().cast::<types::{name}>() }}
+            }}
+            ",
+            name = map.name,
+        )?;
+
+        // After "open" all sections are writable. That's the point, they
+        // can be modified.
+        if open || !read_only {
+            write!(
+                skel,
+                "\
+                pub fn {name}_mut(&mut self) -> &mut types::{name} {{
+                    let data = self.skel.map_data_mut({idx}).expect(\"BPF map `{name}` has no data\");
+                    // SAFETY: The data is page aligned and large enough to
+                    //         hold the section's contents.
+                    unsafe {{ &mut *data.as_mut_ptr().cast::<types::{name}>() }}
+                }}
+                ",
+                name = map.name,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Generate the contents of a single light skeleton.
+pub(super) fn gen_light_skel_contents(
+    _debug: bool,
+    raw_obj_name: &str,
+    obj_file_path: &Path,
+) -> Result<String> {
+    let mut skel = String::new();
+
+    write!(
+        skel,
+        "\
+        // SPDX-License-Identifier: (LGPL-2.1 OR BSD-2-Clause)
+        //
+        // THIS FILE IS AUTOGENERATED BY CARGO-LIBBPF-GEN!
+
+        pub use self::imp::*;
+
+        #[allow(dead_code)]
+        #[allow(non_snake_case)]
+        #[allow(non_camel_case_types)]
+        #[allow(clippy::absolute_paths)]
+        #[allow(clippy::upper_case_acronyms)]
+        #[allow(clippy::zero_repeat_side_effects)]
+        #[warn(single_use_lifetimes)]
+        mod imp {{
+        #[allow(unused_imports)]
+        use super::*;
+        "
+    )?;
+
+    let libbpf_obj_name = format!("{raw_obj_name}_bpf");
+    let obj_name = capitalize_first_letter(raw_obj_name);
+
+    let file = File::open(obj_file_path)
+        .with_context(|| format!("failed to open BPF object `{}`", obj_file_path.display()))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let object = open_bpf_object(&libbpf_obj_name, &mmap)?;
+
+    write!(
+        skel,
+        "\
+        pub mod types {{
+            #[allow(unused_imports)]
+            use super::*;
+        "
+    )?;
+    {
+        let btf =
+            Btf::from_bpf_object(unsafe { object.as_libbpf_object().as_ref() })?.map(GenBtf::from);
+        gen_skel_types(&mut skel, btf.as_ref(), &mut HashSet::new())?;
+    }
+    writeln!(skel, "}}")?;
+
+    let (maps, progs) = {
+        let obj = unsafe { object.as_libbpf_object().as_ref() };
+        (light_maps(obj)?, light_progs(obj)?)
+    };
+    let (data, insns) = gen_loader(unsafe { object.as_libbpf_object().as_mut() })?;
+
+    gen_light_skel_spec(&mut skel, &maps, &progs)?;
+
+    write!(
+        skel,
+        "\
+        #[derive(Debug, Default)]
+        pub struct {obj_name}LightSkelBuilder {{
+            /// The verifier log level used when loading programs.
+            pub log_level: u32,
+        }}
+
+        impl {obj_name}LightSkelBuilder {{
+            pub fn open(self) -> libbpf_rs::Result<Open{obj_name}LightSkel> {{
+                let mut skel = libbpf_rs::light_skel::OpenLightSkel::open(&SPEC)?;
+                let () = skel.set_log_level(self.log_level);
+                Ok(Open{obj_name}LightSkel {{ skel }})
+            }}
+        }}
+
+        #[derive(Debug)]
+        pub struct Open{obj_name}LightSkel {{
+            skel: libbpf_rs::light_skel::OpenLightSkel,
+        }}
+
+        impl Open{obj_name}LightSkel {{
+            pub fn load(self) -> libbpf_rs::Result<{obj_name}LightSkel> {{
+                let skel = self.skel.load()?;
+                Ok({obj_name}LightSkel {{ skel }})
+            }}
+
+            pub fn light_skel(&self) -> &libbpf_rs::light_skel::OpenLightSkel {{
+                &self.skel
+            }}
+
+            pub fn light_skel_mut(&mut self) -> &mut libbpf_rs::light_skel::OpenLightSkel {{
+                &mut self.skel
+            }}
+        ",
+    )?;
+
+    for (idx, map) in maps.iter().enumerate() {
+        write!(
+            skel,
+            "\
+            pub fn set_{name}_max_entries(&mut self, max_entries: u32) -> libbpf_rs::Result<()> {{
+                self.skel.set_max_entries({idx}, max_entries)
+            }}
+            ",
+            name = map.name,
+        )?;
+    }
+    gen_light_skel_data_getters(&mut skel, &maps, true)?;
+    writeln!(skel, "}}")?;
+
+    write!(
+        skel,
+        "\
+        pub struct {obj_name}LightMaps<'skel> {{
+        ",
+    )?;
+    for map in &maps {
+        writeln!(skel, "pub {}: &'skel libbpf_rs::MapHandle,", map.name)?;
+    }
+    write!(
+        skel,
+        "\
+            _phantom: std::marker::PhantomData<&'skel ()>,
+        }}
+
+        pub struct {obj_name}LightProgs<'skel> {{
+        ",
+    )?;
+    for prog in &progs {
+        writeln!(skel, "pub {}: &'skel libbpf_rs::ProgramHandle,", prog.name)?;
+    }
+    write!(
+        skel,
+        "\
+            _phantom: std::marker::PhantomData<&'skel ()>,
+        }}
+
+        #[derive(Debug)]
+        pub struct {obj_name}LightSkel {{
+            skel: libbpf_rs::light_skel::LightSkel,
+        }}
+
+        impl {obj_name}LightSkel {{
+            pub fn maps(&self) -> {obj_name}LightMaps<'_> {{
+                {obj_name}LightMaps {{
+        ",
+    )?;
+    for (idx, map) in maps.iter().enumerate() {
+        writeln!(
+            skel,
+            "{name}: self.skel.map({idx}).expect(\"BPF map `{name}` not present\"),",
+            name = map.name
+        )?;
+    }
+    write!(
+        skel,
+        "\
+                    _phantom: std::marker::PhantomData,
+                }}
+            }}
+
+            pub fn progs(&self) -> {obj_name}LightProgs<'_> {{
+                {obj_name}LightProgs {{
+        ",
+    )?;
+    for (idx, prog) in progs.iter().enumerate() {
+        writeln!(
+            skel,
+            "{name}: self.skel.prog({idx}).expect(\"BPF program `{name}` not present\"),",
+            name = prog.name
+        )?;
+    }
+    write!(
+        skel,
+        "\
+                    _phantom: std::marker::PhantomData,
+                }}
+            }}
+
+            pub fn attach(&mut self) -> libbpf_rs::Result<()> {{
+                self.skel.attach()
+            }}
+
+            pub fn light_skel(&self) -> &libbpf_rs::light_skel::LightSkel {{
+                &self.skel
+            }}
+
+            pub fn light_skel_mut(&mut self) -> &mut libbpf_rs::light_skel::LightSkel {{
+                &mut self.skel
+            }}
+        ",
+    )?;
+    gen_light_skel_data_getters(&mut skel, &maps, false)?;
+    writeln!(skel, "}}")?;
+
+    writeln!(skel, "const DATA: &[u8] = &{data:?};")?;
+    writeln!(skel, "const INSNS: &[u8] = &{insns:?};")?;
+    writeln!(skel, "}}")?;
+    Ok(skel)
+}
@@ -1,4 +1,6 @@
 pub mod btf;
+mod light;
+mod subskel;
 
 use std::borrow::Cow;
 use std::collections::BTreeMap;
@@ -45,6 +47,8 @@ use crate::metadata::UnprocessedObj;
 
 use self::btf::GenBtf;
 use self::btf::GenStructOps;
+use self::light::gen_light_skel_contents;
+use self::subskel::gen_subskel_contents;
 
 
 /// Name of the `.kconfig` map.
@@ -191,6 +195,21 @@ impl ProgsData {
 }
 
 
+/// The kind of skeleton to generate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SkelKind {
+    /// A regular skeleton, embedding the BPF object file.
+    #[default]
+    Regular,
+    /// A light skeleton, embedding a loader program generated by
+    /// `libbpf`.
+    Light,
+    /// A subskeleton for a library object, binding to the object it
+    /// ends up being linked into.
+    Subskeleton,
+}
+
+
 pub enum OutputDest<'a> {
     Stdout,
     /// Infer a filename and place file in specified directory
@@ -1192,10 +1211,15 @@ fn gen_skel(
     obj: &Path,
     out: OutputDest<'_>,
     rustfmt_path: Option<&PathBuf>,
+    kind: SkelKind,
 ) -> Result<()> {
     ensure!(!name.is_empty(), "Object file has no name");
 
-    let skel = gen_skel_contents(debug, name, obj)?;
+    let skel = match kind {
+        SkelKind::Regular => gen_skel_contents(debug, name, obj)?,
+        SkelKind::Light => gen_light_skel_contents(debug, name, obj)?,
+        SkelKind::Subskeleton => gen_subskel_contents(debug, name, obj)?,
+    };
     let skel = try_rustfmt(&skel, rustfmt_path)?;
 
     match out {
@@ -1263,6 +1287,7 @@ pub fn gen_single(
     obj_file: &Path,
     output: OutputDest<'_>,
     rustfmt_path: Option<&PathBuf>,
+    kind: SkelKind,
 ) -> Result<()> {
     let filename = match obj_file.file_name() {
         Some(n) => n,
@@ -1287,7 +1312,7 @@ pub fn gen_single(
         ),
     };
 
-    let () = gen_skel(debug, name, obj_file, output, rustfmt_path).with_context(|| {
+    let () = gen_skel(debug, name, obj_file, output, rustfmt_path, kind).with_context(|| {
         format!(
             "Failed to generate skeleton for {}",
             obj_file.to_string_lossy(),
@@ -1301,6 +1326,7 @@ fn gen_project(
     debug: bool,
     manifest_path: Option<&PathBuf>,
     rustfmt_path: Option<&PathBuf>,
+    kind: SkelKind,
 ) -> Result<()> {
     let (_target_dir, to_gen) = metadata::get(debug, manifest_path)?;
     if debug && !to_gen.is_empty() {
@@ -1328,6 +1354,7 @@ fn gen_project(
             obj_file_path.as_path(),
             OutputDest::Directory(skel_path.as_path()),
             rustfmt_path,
+            kind,
         )
         .with_context(|| {
             format!(
@@ -1357,14 +1384,15 @@ pub fn gen(
     manifest_path: Option<&PathBuf>,
     rustfmt_path: Option<&PathBuf>,
     object: Option<&PathBuf>,
+    kind: SkelKind,
 ) -> Result<()> {
     if manifest_path.is_some() && object.is_some() {
         bail!("--manifest-path and --object cannot be used together");
     }
 
     if let Some(obj_file) = object {
-        gen_single(debug, obj_file, OutputDest::Stdout, rustfmt_path)
+        gen_single(debug, obj_file, OutputDest::Stdout, rustfmt_path, kind)
     } else {
-        gen_project(debug, manifest_path, rustfmt_path)
+        gen_project(debug, manifest_path, rustfmt_path, kind)
     }
 }
@@ -0,0 +1,511 @@
+//! Generation of subskeletons, which provide access to the parts of a
+//! BPF object that a library object contributed to it when linked.
+
+use std::collections::HashSet;
+use std::fmt::Write as fmt_write;
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+
+use libbpf_rs::btf::types;
+use libbpf_rs::btf::types::Linkage;
+use libbpf_rs::libbpf_sys;
+use libbpf_rs::AsRawLibbpf;
+use libbpf_rs::Btf;
+
+use memmap2::Mmap;
+
+use super::btf::GenBtf;
+use super::capitalize_first_letter;
+use super::gen_skel_types;
+use super::map_is_mmapable;
+use super::maps;
+use super::open_bpf_object;
+use super::MapData;
+use super::MapMeta;
+use super::ProgsData;
+
+
+/// A global variable of a library object.
+struct SubskelVar {
+    /// The index of the variable in the subskeleton configuration.
+    idx: usize,
+    name: String,
+    /// The Rust type of the variable.
+    ty: String,
+}
+
+
+/// A BPF map of a library object.
+struct SubskelMap {
+    data: MapData,
+    /// The name `libbpf` knows the map by in any object the library
+    /// got linked into.
+    lookup_name: String,
+    mmaped: bool,
+    /// The global variables of the map, if it backs a global data
+    /// section.
+    vars: Vec<SubskelVar>,
+}
+
+impl SubskelMap {
+    /// The name of the types providing access to the map's global
+    /// variables.
+    fn vars_type(&self, open: bool) -> String {
+        let prefix = if open { "Open" } else { "" };
+        format!("{prefix}{}", capitalize_first_letter(&self.data.name))
+    }
+
+    fn read_only(&self) -> bool {
+        matches!(
+            self.data.meta,
+            MapMeta::Datasec {
+                read_only: true,
+                ..
+            }
+        )
+    }
+}
+
+
+/// Collect the global variables contained in the data section `sec_name`.
+fn datasec_vars(btf: &GenBtf<'_>, sec_name: &str, next_idx: &mut usize) -> Result<Vec<SubskelVar>> {
+    let datasec = btf
+        .type_by_kind::<types::DataSec<'_>>()
+        .find(|datasec| datasec.name().and_then(|name| name.to_str()) == Some(sec_name));
+    let Some(datasec) = datasec else {
+        return Ok(Vec::new())
+    };
+
+    let mut vars = Vec::new();
+    for datasec_var in datasec.iter() {
+        let var = btf
+            .type_by_id::<types::Var<'_>>(datasec_var.ty)
+            .context("BTF is invalid! Datasec var does not point to a var")?;
+
+        // Static variables are not visible outside of their
+        // compilation unit and may get renamed when linking.
+        if var.linkage() == Linkage::Static {
+            continue;
+        }
+
+        let name = var
+            .name()
+            .context("global variable has no name")?
+            .to_str()
+            .context("global variable has invalid name")?
+            .to_string();
+        let () = vars.push(SubskelVar {
+            idx: *next_idx,
+            name,
+            ty: btf.type_declaration(*var)?,
+        });
+        *next_idx += 1;
+    }
+    Ok(vars)
+}
+
+fn subskel_maps(object: &libbpf_rs::Object, btf: Option<&GenBtf<'_>>) -> Result<Vec<SubskelMap>> {
+    let mut next_var_idx = 0;
+    let mut subskel_maps = Vec::new();
+
+    for (idx, map) in maps(object).enumerate() {
+        let Some(data) = MapData::new(idx, &map)? else {
+            continue
+        };
+
+        // SAFETY: The map pointer is valid.
+        let internal = unsafe { libbpf_sys::bpf_map__is_internal(map.as_libbpf_object().as_ptr()) };
+        // `libbpf` prefixes the names of maps for global data sections
+        // with (part of) the object's name. Such maps can be looked up
+        // by their section name instead, which stays the same no matter
+        // which object the library ends up in.
+        let lookup_name = match data.raw_name.find('.') {
+            Some(dot) if internal => data.raw_name[dot..].to_string(),
+            _ => data.raw_name.clone(),
+        };
+
+        let vars = match (&data.meta, btf) {
+            (
+                MapMeta::Datasec {
+                    not_openable: false,
+                    ..
+                },
+                Some(btf),
+            ) => datasec_vars(btf, &lookup_name, &mut next_var_idx)?,
+            _ => Vec::new(),
+        };
+
+        let () = subskel_maps.push(SubskelMap {
+            mmaped: map_is_mmapable(&map),
+            data,
+            lookup_name,
+            vars,
+        });
+    }
+    Ok(subskel_maps)
+}
+
+fn gen_subskel_config(skel: &mut String, maps: &[SubskelMap], progs: &ProgsData) -> Result<()> {
+    write!(
+        skel,
+        "\
+        fn build_subskel_config(
+            object: std::ptr::NonNull<libbpf_sys::bpf_object>,
+        ) -> libbpf_rs::Result<libbpf_rs::__internal_skel::ObjectSubskeletonConfig> {{
+            #[allow(unused_mut)]
+            let mut builder = libbpf_rs::__internal_skel::ObjectSubskeletonConfigBuilder::new();
+        ",
+    )?;
+
+    for map in maps {
+        writeln!(
+            skel,
+            "builder.map(\"{name}\", {mmaped});",
+            name = map.lookup_name,
+            mmaped = map.mmaped
+        )?;
+    }
+
+    for name in progs.iter() {
+        writeln!(skel, "builder.prog(\"{name}\");")?;
+    }
+
+    for (idx, map) in maps.iter().enumerate() {
+        for var in &map.vars {
+            writeln!(skel, "builder.var({idx}, \"{name}\");", name = var.name)?;
+        }
+    }
+
+    write!(
+        skel,
+        "\
+            builder.build(object)
+        }}
+        ",
+    )?;
+    Ok(())
+}
+
+/// Generate the types providing access to the global variables of the
+/// library's data sections.
+fn gen_subskel_vars(skel: &mut String, maps: &[SubskelMap]) -> Result<()> {
+    write!(
+        skel,
+        "\
+        pub mod vars {{
+            #[allow(unused_imports)]
+            use super::types::*;
+        ",
+    )?;
+
+    for map in maps {
+        if !matches!(
+            map.data.meta,
+            MapMeta::Datasec {
+                not_openable: false,
+                ..
+            }
+        ) {
+            continue
+        }
+
+        for open in [true, false] {
+            // After "open" all variables are writable. That's the point,
+            // they can be modified.
+            let (ref_mut, ref_conv) = if open || !map.read_only() {
+                (" mut", "mut")
+            } else {
+                ("", "ref")
+            };
+            let vars_type = map.vars_type(open);
+
+            writeln!(skel, "pub struct {vars_type}<'obj> {{")?;
+            for var in &map.vars {
+                writeln!(
+                    skel,
+                    "pub {name}: &'obj{ref_mut} {ty},",
+                    name = var.name,
+                    ty = var.ty
+                )?;
+            }
+            write!(
+                skel,
+                "\
+                    _phantom: std::marker::PhantomData<&'obj ()>,
+                }}
+
+                impl {vars_type}<'_> {{
+                    #[allow(unused_variables)]
+                    pub(super) unsafe fn new(
+                        config: &libbpf_rs::__internal_skel::ObjectSubskeletonConfig,
+                    ) -> libbpf_rs::Result<Self> {{
+                        let slf = Self {{
+                ",
+            )?;
+            for var in &map.vars {
+                write!(
+                    skel,
+                    "\
+                            {name}: unsafe {{
+                                config
+                                    .var_ptr({idx})?
+                                    .cast::<{ty}>()
+                                    .as_{ref_conv}()
+                                    .expect(\"global variable `{name}` has no address\")
+                            }},
+                    ",
+                    name = var.name,
+                    idx = var.idx,
+                    ty = var.ty,
+                )?;
+            }
+            write!(
+                skel,
+                "\
+                            _phantom: std::marker::PhantomData,
+                        }};
+                        Ok(slf)
+                    }}
+                }}
+                ",
+            )?;
+        }
+    }
+
+    writeln!(skel, "}}")?;
+    Ok(())
+}
+
+fn gen_subskel_map_defs(
+    skel: &mut String,
+    maps: &[SubskelMap],
+    raw_obj_name: &str,
+    open: bool,
+) -> Result<()> {
+    let prefix = if open { "Open" } else { "" };
+    let obj_name = capitalize_first_letter(raw_obj_name);
+
+    writeln!(skel, "pub struct {prefix}{obj_name}SubskelMaps<'obj> {{")?;
+    for map in maps {
+        writeln!(
+            skel,
+            "pub {name}: libbpf_rs::{prefix}MapMut<'obj>,",
+            name = map.data.name
+        )?;
+        if let MapMeta::Datasec {
+            not_openable: false,
+            ..
+        } = map.data.meta
+        {
+            writeln!(
+                skel,
+                "pub {name}_data: vars::{vars_type}<'obj>,",
+                name = map.data.name,
+                vars_type = map.vars_type(open),
+            )?;
+        }
+    }
+    write!(
+        skel,
+        "\
+            _phantom: std::marker::PhantomData<&'obj ()>,
+        }}
+
+        impl {prefix}{obj_name}SubskelMaps<'_> {{
+            #[allow(unused_variables)]
+            unsafe fn new(
+                config: &libbpf_rs::__internal_skel::ObjectSubskeletonConfig,
+            ) -> libbpf_rs::Result<Self> {{
+                let slf = Self {{
+        ",
+    )?;
+
+    for (idx, map) in maps.iter().enumerate() {
+        write!(
+            skel,
+            "\
+                    {name}: libbpf_rs::{prefix}MapMut::new_mut(
+                        unsafe {{ config.map_ptr({idx})?.as_mut() }}
+                            .expect(\"BPF map `{name}` not present\"),
+                    ),
+            ",
+            name = map.data.name,
+        )?;
+        if let MapMeta::Datasec {
+            not_openable: false,
+            ..
+        } = map.data.meta
+        {
+            writeln!(
+                skel,
+                "{name}_data: unsafe {{ vars::{vars_type}::new(config)? }},",
+                name = map.data.name,
+                vars_type = map.vars_type(open),
+            )?;
+        }
+    }
+
+    write!(
+        skel,
+        "\
+                    _phantom: std::marker::PhantomData,
+                }};
+                Ok(slf)
+            }}
+        }}
+        ",
+    )?;
+    Ok(())
+}
+
+fn gen_subskel_prog_defs(
+    skel: &mut String,
+    progs: &ProgsData,
+    raw_obj_name: &str,
+    open: bool,
+) -> Result<()> {
+    let prefix = if open { "Open" } else { "" };
+    let obj_name = capitalize_first_letter(raw_obj_name);
+
+    writeln!(skel, "pub struct {prefix}{obj_name}SubskelProgs<'obj> {{")?;
+    for name in progs.iter() {
+        writeln!(skel, "pub {name}: libbpf_rs::{prefix}ProgramMut<'obj>,")?;
+    }
+    write!(
+        skel,
+        "\
+            _phantom: std::marker::PhantomData<&'obj ()>,
+        }}
+
+        impl {prefix}{obj_name}SubskelProgs<'_> {{
+            #[allow(unused_variables)]
+            unsafe fn new(
+                config: &libbpf_rs::__internal_skel::ObjectSubskeletonConfig,
+            ) -> libbpf_rs::Result<Self> {{
+                let slf = Self {{
+        ",
+    )?;
+
+    for (idx, name) in progs.iter().enumerate() {
+        write!(
+            skel,
+            "\
+                    {name}: libbpf_rs::{prefix}ProgramMut::new_mut(
+                        unsafe {{ config.prog_ptr({idx})?.as_mut() }}
+                            .expect(\"BPF program `{name}` not present\"),
+                    ),
+            ",
+        )?;
+    }
+
+    write!(
+        skel,
+        "\
+                    _phantom: std::marker::PhantomData,
+                }};
+                Ok(slf)
+            }}
+        }}
+        ",
+    )?;
+    Ok(())
+}
+
+/// Generate the contents of a single subskeleton.
+pub(super) fn gen_subskel_contents(
+    _debug: bool,
+    raw_obj_name: &str,
+    obj_file_path: &Path,
+) -> Result<String> {
+    let mut skel = String::new();
+
+    write!(
+        skel,
+        "\
+        // SPDX-License-Identifier: (LGPL-2.1 OR BSD-2-Clause)
+        //
+        // THIS FILE IS AUTOGENERATED BY CARGO-LIBBPF-GEN!
+
+        pub use self::imp::*;
+
+        #[allow(dead_code)]
+        #[allow(non_snake_case)]
+        #[allow(non_camel_case_types)]
+        #[allow(clippy::absolute_paths)]
+        #[allow(clippy::upper_case_acronyms)]
+        #[allow(clippy::zero_repeat_side_effects)]
+        #[warn(single_use_lifetimes)]
+        mod imp {{
+        #[allow(unused_imports)]
+        use super::*;
+        use libbpf_rs::libbpf_sys;
+        use libbpf_rs::AsRawLibbpf as _;
+        "
+    )?;
+
+    let libbpf_obj_name = format!("{raw_obj_name}_bpf");
+    let obj_name = capitalize_first_letter(raw_obj_name);
+
+    let file = File::open(obj_file_path)
+        .with_context(|| format!("failed to open BPF object `{}`", obj_file_path.display()))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let object = open_bpf_object(&libbpf_obj_name, &mmap)?;
+    let btf =
+        Btf::from_bpf_object(unsafe { object.as_libbpf_object().as_ref() })?.map(GenBtf::from);
+    let maps = subskel_maps(&object, btf.as_ref())?;
+    let progs = ProgsData::new(&object)?;
+
+    gen_subskel_config(&mut skel, &maps, &progs)?;
+
+    write!(
+        skel,
+        "\
+        pub mod types {{
+            #[allow(unused_imports)]
+            use super::*;
+        "
+    )?;
+    gen_skel_types(&mut skel, btf.as_ref(), &mut HashSet::new())?;
+    writeln!(skel, "}}")?;
+
+    gen_subskel_vars(&mut skel, &maps)?;
+    gen_subskel_map_defs(&mut skel, &maps, raw_obj_name, true)?;
+    gen_subskel_map_defs(&mut skel, &maps, raw_obj_name, false)?;
+    gen_subskel_prog_defs(&mut skel, &progs, raw_obj_name, true)?;
+    gen_subskel_prog_defs(&mut skel, &progs, raw_obj_name, false)?;
+
+    for (prefix, object_ty) in [("Open", "OpenObject"), ("", "Object")] {
+        write!(
+            skel,
+            "\
+            pub struct {prefix}{obj_name}Subskel<'obj> {{
+                pub maps: {prefix}{obj_name}SubskelMaps<'obj>,
+                pub progs: {prefix}{obj_name}SubskelProgs<'obj>,
+            }}
+
+            impl<'obj> {prefix}{obj_name}Subskel<'obj> {{
+                /// Bind to the maps, programs, and global variables that
+                /// the library contributed to `object`.
+                pub fn new(object: &'obj mut libbpf_rs::{object_ty}) -> libbpf_rs::Result<Self> {{
+                    let config = build_subskel_config(object.as_libbpf_object())?;
+                    // SAFETY: `config` was bound to `object`, which we
+                    //         borrow mutably for `'obj`.
+                    let slf = unsafe {{
+                        Self {{
+                            maps: {prefix}{obj_name}SubskelMaps::new(&config)?,
+                            progs: {prefix}{obj_name}SubskelProgs::new(&config)?,
+                        }}
+                    }};
+                    Ok(slf)
+                }}
+            }}
+            ",
+        )?;
+    }
+
+    writeln!(skel, "}}")?;
+    Ok(skel)
+}
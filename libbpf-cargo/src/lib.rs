@@ -49,6 +49,31 @@
 //! Be careful to run cargo-libbpf-build before running cargo-libbpf-gen. cargo-libbpf-gen reads
 //! object files from `package.metadata.libbpf.target_dir`.
 //!
+//! With `--light`, light skeletons are generated instead. Rather than the object file, these embed
+//! a loader program recorded by `libbpf`, which creates the maps and loads the programs from
+//! within the kernel. At runtime they only depend on the small `libbpf_rs::light_skel` module and
+//! do not parse any ELF.
+//!
+//! With `--subskeleton`, subskeletons are generated instead. These are meant for BPF objects used
+//! as libraries, which get statically linked into other objects, and bind to the maps, programs,
+//! and global variables the library contributed to such a final object.
+//!
+//! ## min-core-btf
+//!
+//! `cargo libbpf min-core-btf` generates minimized BTF files for kernels that were built without
+//! `CONFIG_DEBUG_INFO_BTF`. Given a directory of full kernel BTF files (`--btf-dir`), it writes a
+//! file of the same name into the `--output` directory for each of them, containing only the
+//! types that the CO-RE relocations of the project's BPF objects (or those passed via `--object`)
+//! refer to. At runtime, `ObjectBuilder::btf_custom_dir` picks the file for the running kernel.
+//!
+//! ## check-core
+//!
+//! `cargo libbpf check-core` checks, without loading anything, which CO-RE relocations of the
+//! project's BPF objects (or those passed via `--object`) can be performed on each of the kernels
+//! whose BTF files are contained in `--btf-dir`. Failed relocations are reported per program,
+//! along with the reason and the source line they stem from. Note that failures in code guarded
+//! by existence checks such as `bpf_core_field_exists` are expected and benign.
+//!
 //! ## make
 //!
 //! `cargo libbpf make` sequentially runs cargo-libbpf-build, cargo-libbpf-gen, and `cargo
@@ -68,6 +93,7 @@ use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::path::Path;
 use std::path::PathBuf;
+use std::slice;
 
 use anyhow::anyhow;
 use anyhow::Context as _;
@@ -79,8 +105,12 @@ use tempfile::TempDir;
 // libbpf-cargo binary is the primary consumer of the following modules. As such,
 // we do not use all the symbols. Silence any unused code warnings.
 #[allow(dead_code)]
+mod btfgen;
+#[allow(dead_code)]
 mod build;
 #[allow(dead_code)]
+mod compat;
+#[allow(dead_code)]
 mod gen;
 #[allow(dead_code)]
 mod make;
@@ -114,6 +144,8 @@ pub struct SkeletonBuilder {
     clang_args: Vec<OsString>,
     skip_clang_version_check: bool,
     rustfmt: PathBuf,
+    min_core_btf: Option<(PathBuf, PathBuf)>,
+    kind: gen::SkelKind,
     dir: Option<TempDir>,
 }
 
@@ -133,6 +165,8 @@ impl SkeletonBuilder {
             clang_args: Vec::new(),
             skip_clang_version_check: false,
             rustfmt: "rustfmt".into(),
+            min_core_btf: None,
+            kind: gen::SkelKind::Regular,
             dir: None,
         }
     }
@@ -213,6 +247,68 @@ impl SkeletonBuilder {
         self
     }
 
+    /// Generate minimized BTF files for the object when generating the
+    /// skeleton
+    ///
+    /// For each full kernel BTF file in `btf_dir`, a file of the same name
+    /// containing only the types referenced by the object's CO-RE
+    /// relocations is placed into `output_dir`. Such files can be shipped
+    /// for kernels not exposing BTF information and be picked up at
+    /// runtime using `ObjectBuilder::btf_custom_dir`.
+    ///
+    /// Default is None
+    pub fn min_core_btf<P, Q>(&mut self, btf_dir: P, output_dir: Q) -> &mut SkeletonBuilder
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        self.min_core_btf = Some((
+            btf_dir.as_ref().to_path_buf(),
+            output_dir.as_ref().to_path_buf(),
+        ));
+        self
+    }
+
+    /// Generate a light skeleton instead of a regular one
+    ///
+    /// A light skeleton embeds a loader program generated by `libbpf`
+    /// instead of the object file itself and uses the
+    /// `libbpf_rs::light_skel` runtime, which does not parse any ELF at
+    /// load time.
+    ///
+    /// Overrides any earlier call to [`SkeletonBuilder::subskeleton`].
+    ///
+    /// Default is `false`
+    pub fn light(&mut self, light: bool) -> &mut SkeletonBuilder {
+        self.kind = if light {
+            gen::SkelKind::Light
+        } else {
+            gen::SkelKind::Regular
+        };
+        self
+    }
+
+    /// Generate a subskeleton instead of a regular one
+    ///
+    /// A subskeleton is meant for BPF objects that are used as libraries,
+    /// i.e., that get statically linked (e.g., using
+    /// `libbpf_rs::Linker`) into one or more final objects. It does not
+    /// embed the object, but provides typed access to the library's maps,
+    /// programs, and global variables within whatever final object it got
+    /// linked into.
+    ///
+    /// Overrides any earlier call to [`SkeletonBuilder::light`].
+    ///
+    /// Default is `false`
+    pub fn subskeleton(&mut self, subskeleton: bool) -> &mut SkeletonBuilder {
+        self.kind = if subskeleton {
+            gen::SkelKind::Subskeleton
+        } else {
+            gen::SkelKind::Regular
+        };
+        self
+    }
+
     /// Build BPF programs and generate the skeleton at path `output`
     pub fn build_and_generate<P: AsRef<Path>>(&mut self, output: P) -> Result<()> {
         self.build()?;
@@ -277,9 +373,20 @@ impl SkeletonBuilder {
             objfile,
             gen::OutputDest::File(output.as_ref()),
             Some(&self.rustfmt),
+            self.kind,
         )
         .with_context(|| format!("failed to generate `{}`", objfile.display()))?;
 
+        if let Some((btf_dir, output_dir)) = &self.min_core_btf {
+            btfgen::min_core_btf_single(self.debug, slice::from_ref(objfile), btf_dir, output_dir)
+                .with_context(|| {
+                    format!(
+                        "failed to generate minimized BTF for `{}`",
+                        objfile.display()
+                    )
+                })?;
+        }
+
         Ok(())
     }
 }
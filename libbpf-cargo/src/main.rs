@@ -11,6 +11,8 @@ use clap::Subcommand;
 
 #[doc(hidden)]
 mod build;
+mod btfgen;
+mod compat;
 mod gen;
 mod make;
 mod metadata;
@@ -83,6 +85,43 @@ enum Command {
         ///
         /// When specified, skeletons for the rest of the project will not be generated
         object: Option<PathBuf>,
+        #[arg(long, conflicts_with = "subskeleton")]
+        /// Generate light skeletons, which embed a loader program instead of the object file
+        light: bool,
+        #[arg(long)]
+        /// Generate subskeletons, for objects that get linked into other objects as libraries
+        subskeleton: bool,
+    },
+    /// Check CO-RE relocations of bpf objects against a set of kernels
+    CheckCore {
+        #[arg(long, value_parser)]
+        /// Path to top level Cargo.toml
+        manifest_path: Option<PathBuf>,
+        #[arg(long = "object", value_parser)]
+        /// Object file to check; may be repeated
+        ///
+        /// When not specified, all objects of the project are checked
+        objects: Vec<PathBuf>,
+        #[arg(long, value_parser)]
+        /// Directory containing the BTF files of the kernels to check against
+        btf_dir: PathBuf,
+    },
+    /// Generate minimized BTF files for kernels without embedded BTF
+    MinCoreBtf {
+        #[arg(long, value_parser)]
+        /// Path to top level Cargo.toml
+        manifest_path: Option<PathBuf>,
+        #[arg(long = "object", value_parser)]
+        /// Object file to generate minimized BTF for; may be repeated
+        ///
+        /// When not specified, all objects of the project are used
+        objects: Vec<PathBuf>,
+        #[arg(long, value_parser)]
+        /// Directory containing the full BTF files of the kernels to support
+        btf_dir: PathBuf,
+        #[arg(long, value_parser)]
+        /// Directory to place the minimized BTF files into
+        output: PathBuf,
     },
     /// Build project
     Make {
@@ -130,12 +169,35 @@ fn main() -> Result<()> {
                 manifest_path,
                 rustfmt_path,
                 object,
-            } => gen::gen(
-                debug,
-                manifest_path.as_ref(),
-                rustfmt_path.as_ref(),
-                object.as_ref(),
-            ),
+                light,
+                subskeleton,
+            } => {
+                let kind = if light {
+                    gen::SkelKind::Light
+                } else if subskeleton {
+                    gen::SkelKind::Subskeleton
+                } else {
+                    gen::SkelKind::Regular
+                };
+                gen::gen(
+                    debug,
+                    manifest_path.as_ref(),
+                    rustfmt_path.as_ref(),
+                    object.as_ref(),
+                    kind,
+                )
+            }
+            Command::CheckCore {
+                manifest_path,
+                objects,
+                btf_dir,
+            } => compat::check_core(debug, manifest_path.as_ref(), objects, &btf_dir),
+            Command::MinCoreBtf {
+                manifest_path,
+                objects,
+                btf_dir,
+                output,
+            } => btfgen::min_core_btf(debug, manifest_path.as_ref(), objects, &btf_dir, &output),
             Command::Make {
                 manifest_path,
                 clang_opts:
@@ -35,7 +35,14 @@ pub fn make(
     if !quiet {
         println!("Generating skeletons");
     }
-    gen::gen(debug, manifest_path, None, rustfmt_path).context("Failed to generate skeletons")?;
+    gen::gen(
+        debug,
+        manifest_path,
+        None,
+        rustfmt_path,
+        gen::SkelKind::Regular,
+    )
+    .context("Failed to generate skeletons")?;
 
     let mut cmd = Command::new("cargo");
     cmd.arg("build");
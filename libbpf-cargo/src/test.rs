@@ -797,6 +797,331 @@ fn test_skeleton_builder_clang_opts() {
         .unwrap();
 }
 
+#[test]
+fn test_skeleton_builder_min_core_btf() {
+    let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+        #include <bpf/bpf_core_read.h>
+
+        int pid = 0;
+
+        SEC("tp_btf/sched_wakeup")
+        int handle(u64 *ctx)
+        {{
+                struct task_struct *p = (void *)ctx[0];
+
+                pid = BPF_CORE_READ(p, pid);
+                return 0;
+        }}
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    // Use the running kernel's BTF as the full BTF to minimize.
+    let btf_dir = proj_dir.join("btf");
+    create_dir(&btf_dir).expect("failed to create btf dir");
+    let vmlinux = read("/sys/kernel/btf/vmlinux").expect("failed to read vmlinux BTF");
+    write(btf_dir.join("vmlinux.btf"), &vmlinux).expect("failed to write vmlinux BTF");
+
+    let out_dir = proj_dir.join("min_btf");
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .debug(true)
+        .min_core_btf(&btf_dir, &out_dir)
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let min_path = out_dir.join("vmlinux.btf");
+    let min_size = read(&min_path).expect("failed to read minimized BTF").len();
+    assert!(min_size < vmlinux.len() / 10);
+
+    let btf = Btf::from_path(&min_path).expect("failed to parse minimized BTF");
+    let task = btf
+        .type_by_name::<types::Struct<'_>>("task_struct")
+        .expect("failed to find `task_struct`");
+    let members = task
+        .iter()
+        .filter_map(|member| member.name)
+        .map(|name| name.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(members, vec!["pid".to_string()]);
+}
+
+#[test]
+fn test_skeleton_builder_light() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        struct {{
+                __uint(type, BPF_MAP_TYPE_HASH);
+                __uint(max_entries, 1024);
+                __type(key, u32);
+                __type(value, u64);
+        }} mymap SEC(".maps");
+
+        const volatile int myconst = 0;
+        int myglobal = 0;
+
+        SEC("raw_tp/sys_enter")
+        int this_is_my_prog(u64 *ctx)
+        {{
+                myglobal = myconst;
+                return 0;
+        }}
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    // Generate skeleton file
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .debug(true)
+        .light(true)
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    // Make test project use our development libbpf-rs version
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display()
+    )
+    .expect("failed to write to Cargo.toml");
+
+    let mut source = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(proj_dir.join("src/main.rs"))
+        .expect("failed to open main.rs");
+
+    write!(
+        source,
+        r#"
+        #[path = "{skel_path}"]
+        mod skel;
+        use skel::*;
+
+        fn main() {{
+            let mut open_skel = ProgLightSkelBuilder::default()
+                .open()
+                .expect("failed to open skel");
+
+            // Check that map sizes and global data can be adjusted
+            let () = open_skel
+                .set_mymap_max_entries(42)
+                .expect("failed to set max entries");
+            open_skel.rodata_mut().myconst = 1;
+            open_skel.bss_mut().myglobal = 2;
+
+            let mut skel = open_skel.load().expect("failed to load skel");
+
+            // Check that we can grab handles to loaded maps/progs
+            let _map = skel.maps().mymap;
+            let _prog = skel.progs().this_is_my_prog;
+
+            // Check that read-only data is only exposed immutably
+            let _myconst = skel.rodata().myconst;
+            skel.bss_mut().myglobal = 3;
+
+            // Check that attach() is generated
+            skel.attach().expect("failed to attach progs");
+        }}
+        "#,
+        skel_path = skel.path().display(),
+    )
+    .expect("failed to write to main.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+}
+
+#[test]
+fn test_skeleton_builder_subskeleton() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        struct unique_key {{
+            int cap;
+            u32 tgid;
+        }};
+
+        struct {{
+                __uint(type, BPF_MAP_TYPE_HASH);
+                __uint(max_entries, 1024);
+                __type(key, struct unique_key);
+                __type(value, u64);
+        }} mymap SEC(".maps");
+
+        const volatile struct unique_key myconst = {{}};
+        int myglobal = 0;
+        static int mystatic = 0;
+
+        SEC("kprobe/foo")
+        int this_is_my_prog(u64 *ctx)
+        {{
+                mystatic++;
+                myglobal = myconst.cap + mystatic;
+                return 0;
+        }}
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    // Generate skeleton file
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .debug(true)
+        .subskeleton(true)
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    // Make test project use our development libbpf-rs version
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display()
+    )
+    .expect("failed to write to Cargo.toml");
+
+    let mut source = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(proj_dir.join("src/main.rs"))
+        .expect("failed to open main.rs");
+
+    write!(
+        source,
+        r#"
+        #[path = "{skel_path}"]
+        mod skel;
+        use skel::*;
+
+        fn main() {{
+            let mut open_obj = libbpf_rs::ObjectBuilder::default()
+                .open_file("final.bpf.o")
+                .expect("failed to open object");
+
+            {{
+                let mut open_skel = OpenProgSubskel::new(&mut open_obj)
+                    .expect("failed to open subskel");
+
+                // Check that we can grab handles to open maps/progs
+                let _open_map = &open_skel.maps.mymap;
+                let _open_prog = &open_skel.progs.this_is_my_prog;
+
+                // Check that all globals can be modified before load
+                open_skel.maps.rodata_data.myconst.cap = 1;
+                *open_skel.maps.bss_data.myglobal = 2;
+            }}
+
+            let mut obj = open_obj.load().expect("failed to load object");
+            let mut skel = ProgSubskel::new(&mut obj).expect("failed to open subskel");
+
+            // Check that we can grab handles to loaded maps/progs
+            let _map = &skel.maps.mymap;
+            let _prog = &skel.progs.this_is_my_prog;
+
+            // Check that read-only data is only exposed immutably
+            let _key: &types::unique_key = skel.maps.rodata_data.myconst;
+            *skel.maps.bss_data.myglobal = 3;
+        }}
+        "#,
+        skel_path = skel.path().display(),
+    )
+    .expect("failed to write to main.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+}
+
 #[test]
 fn test_skeleton_builder_arrays_ptrs() {
     let bpf_c = r#"
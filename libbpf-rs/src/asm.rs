@@ -0,0 +1,483 @@
+//! Assembly of BPF programs from individual instructions.
+//!
+//! The [`Assembler`] allows for creating programs at run time, without
+//! going through a compiler and an ELF object. Jumps refer to symbolic
+//! labels, which are resolved when the program is
+//! [finished][Assembler::finish]:
+//! ```no_run
+//! # use libbpf_rs::AluOp;
+//! # use libbpf_rs::Assembler;
+//! # use libbpf_rs::JmpOp;
+//! # use libbpf_rs::MemSize;
+//! # use libbpf_rs::ProgramHandle;
+//! # use libbpf_rs::ProgramLoadOpts;
+//! # use libbpf_rs::ProgramType;
+//! # use libbpf_rs::Reg;
+//! let mut asm = Assembler::new();
+//! asm.mov64_imm(Reg::R0, 0)
+//!     .ldx(MemSize::W, Reg::R2, Reg::R1, 0)
+//!     .jmp_imm(JmpOp::Lt, Reg::R2, 64, "out")
+//!     .mov64_imm(Reg::R0, -1)
+//!     .label("out")
+//!     .exit();
+//! let insns = asm.finish().unwrap();
+//! let prog = ProgramHandle::load(
+//!     ProgramType::SocketFilter,
+//!     Some("filter"),
+//!     "GPL",
+//!     &insns,
+//!     &ProgramLoadOpts::default(),
+//! )
+//! .unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::os::unix::io::AsRawFd as _;
+use std::os::unix::io::BorrowedFd;
+
+use libbpf_sys::bpf_func_id;
+use libbpf_sys::bpf_insn;
+
+use crate::Error;
+use crate::Result;
+
+
+/// A BPF register.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum Reg {
+    R0 = 0,
+    R1,
+    R2,
+    R3,
+    R4,
+    R5,
+    R6,
+    R7,
+    R8,
+    R9,
+    /// The read-only frame pointer.
+    R10,
+}
+
+
+/// An arithmetic or logic operation.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum AluOp {
+    Add = libbpf_sys::BPF_ADD,
+    Sub = libbpf_sys::BPF_SUB,
+    Mul = libbpf_sys::BPF_MUL,
+    Div = libbpf_sys::BPF_DIV,
+    Or = libbpf_sys::BPF_OR,
+    And = libbpf_sys::BPF_AND,
+    Lsh = libbpf_sys::BPF_LSH,
+    Rsh = libbpf_sys::BPF_RSH,
+    Mod = libbpf_sys::BPF_MOD,
+    Xor = libbpf_sys::BPF_XOR,
+    Mov = libbpf_sys::BPF_MOV,
+    /// Arithmetic (sign extending) right shift.
+    Arsh = libbpf_sys::BPF_ARSH,
+}
+
+
+/// A conditional jump operation.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum JmpOp {
+    Eq = libbpf_sys::BPF_JEQ,
+    Gt = libbpf_sys::BPF_JGT,
+    Ge = libbpf_sys::BPF_JGE,
+    /// Jump if `dst & src` is non-zero.
+    Set = libbpf_sys::BPF_JSET,
+    Ne = libbpf_sys::BPF_JNE,
+    /// Signed greater than.
+    Sgt = libbpf_sys::BPF_JSGT,
+    /// Signed greater or equal.
+    Sge = libbpf_sys::BPF_JSGE,
+    Lt = libbpf_sys::BPF_JLT,
+    Le = libbpf_sys::BPF_JLE,
+    /// Signed less than.
+    Slt = libbpf_sys::BPF_JSLT,
+    /// Signed less or equal.
+    Sle = libbpf_sys::BPF_JSLE,
+}
+
+
+/// The size of a memory access.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemSize {
+    /// One byte.
+    B = libbpf_sys::BPF_B,
+    /// Two bytes.
+    H = libbpf_sys::BPF_H,
+    /// Four bytes.
+    W = libbpf_sys::BPF_W,
+    /// Eight bytes.
+    DW = libbpf_sys::BPF_DW,
+}
+
+
+fn insn(code: u32, dst: Reg, src: u8, off: i16, imm: i32) -> bpf_insn {
+    bpf_insn {
+        code: code as u8,
+        _bitfield_align_1: [],
+        _bitfield_1: bpf_insn::new_bitfield_1(dst as u8, src),
+        off,
+        imm,
+    }
+}
+
+
+/// An assembler for BPF programs.
+///
+/// Map references are embedded as file descriptors, which have to stay
+/// open until the program is loaded. The `'fd` lifetime ties them to the
+/// assembler, but the instructions returned by
+/// [`finish`][Self::finish] are not tracked.
+#[derive(Debug, Default)]
+pub struct Assembler<'fd> {
+    insns: Vec<bpf_insn>,
+    labels: HashMap<String, usize>,
+    /// Jumps to labels, in the form of (instruction index, label).
+    jumps: Vec<(usize, String)>,
+    _fds: PhantomData<BorrowedFd<'fd>>,
+}
+
+impl<'fd> Assembler<'fd> {
+    /// Create a new, empty `Assembler`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a raw instruction.
+    pub fn raw(&mut self, insn: bpf_insn) -> &mut Self {
+        let () = self.insns.push(insn);
+        self
+    }
+
+    /// Define a label referring to the next instruction.
+    ///
+    /// Defining the same label twice results in an error during
+    /// [`finish`][Self::finish].
+    pub fn label(&mut self, name: &str) -> &mut Self {
+        if self
+            .labels
+            .insert(name.to_string(), self.insns.len())
+            .is_some()
+        {
+            // Poison the label so that `finish` reports the duplicate.
+            let _prev = self.labels.insert(name.to_string(), usize::MAX);
+        }
+        self
+    }
+
+    /// `dst op= imm`, operating on 64 bit.
+    pub fn alu64_imm(&mut self, op: AluOp, dst: Reg, imm: i32) -> &mut Self {
+        let code = libbpf_sys::BPF_ALU64 | op as u32 | libbpf_sys::BPF_K;
+        self.raw(insn(code, dst, 0, 0, imm))
+    }
+
+    /// `dst op= src`, operating on 64 bit.
+    pub fn alu64_reg(&mut self, op: AluOp, dst: Reg, src: Reg) -> &mut Self {
+        let code = libbpf_sys::BPF_ALU64 | op as u32 | libbpf_sys::BPF_X;
+        self.raw(insn(code, dst, src as u8, 0, 0))
+    }
+
+    /// `dst op= imm`, operating on the lower 32 bit and zeroing the upper
+    /// ones.
+    pub fn alu32_imm(&mut self, op: AluOp, dst: Reg, imm: i32) -> &mut Self {
+        let code = libbpf_sys::BPF_ALU | op as u32 | libbpf_sys::BPF_K;
+        self.raw(insn(code, dst, 0, 0, imm))
+    }
+
+    /// `dst op= src`, operating on the lower 32 bit and zeroing the upper
+    /// ones.
+    pub fn alu32_reg(&mut self, op: AluOp, dst: Reg, src: Reg) -> &mut Self {
+        let code = libbpf_sys::BPF_ALU | op as u32 | libbpf_sys::BPF_X;
+        self.raw(insn(code, dst, src as u8, 0, 0))
+    }
+
+    /// `dst = imm`, with `imm` being sign extended to 64 bit.
+    pub fn mov64_imm(&mut self, dst: Reg, imm: i32) -> &mut Self {
+        self.alu64_imm(AluOp::Mov, dst, imm)
+    }
+
+    /// `dst = src`
+    pub fn mov64_reg(&mut self, dst: Reg, src: Reg) -> &mut Self {
+        self.alu64_reg(AluOp::Mov, dst, src)
+    }
+
+    /// `dst = -dst`
+    pub fn neg64(&mut self, dst: Reg) -> &mut Self {
+        let code = libbpf_sys::BPF_ALU64 | libbpf_sys::BPF_NEG;
+        self.raw(insn(code, dst, 0, 0, 0))
+    }
+
+    fn ld_imm64_raw(&mut self, dst: Reg, src: u32, imm: u64) -> &mut Self {
+        let code = libbpf_sys::BPF_LD | libbpf_sys::BPF_DW | libbpf_sys::BPF_IMM;
+        self.raw(insn(code, dst, src as u8, 0, imm as u32 as i32));
+        self.raw(insn(0, Reg::R0, 0, 0, (imm >> 32) as u32 as i32))
+    }
+
+    /// `dst = imm`, loading a full 64 bit immediate.
+    ///
+    /// This instruction occupies two instruction slots.
+    pub fn ld_imm64(&mut self, dst: Reg, imm: u64) -> &mut Self {
+        self.ld_imm64_raw(dst, 0, imm)
+    }
+
+    /// Load a reference to the map referred to by `map` into `dst`, e.g.,
+    /// for passing it to a helper.
+    ///
+    /// This instruction occupies two instruction slots.
+    pub fn ld_map_fd(&mut self, dst: Reg, map: BorrowedFd<'fd>) -> &mut Self {
+        let fd = map.as_raw_fd() as u32;
+        self.ld_imm64_raw(dst, libbpf_sys::BPF_PSEUDO_MAP_FD, fd.into())
+    }
+
+    /// Load a pointer to the value at offset `off` of the array map
+    /// referred to by `map` into `dst`.
+    ///
+    /// This instruction occupies two instruction slots.
+    pub fn ld_map_value(&mut self, dst: Reg, map: BorrowedFd<'fd>, off: u32) -> &mut Self {
+        let fd = map.as_raw_fd() as u32;
+        let imm = u64::from(fd) | (u64::from(off) << 32);
+        self.ld_imm64_raw(dst, libbpf_sys::BPF_PSEUDO_MAP_VALUE, imm)
+    }
+
+    /// `dst = *(size *)(src + off)`
+    pub fn ldx(&mut self, size: MemSize, dst: Reg, src: Reg, off: i16) -> &mut Self {
+        let code = libbpf_sys::BPF_LDX | size as u32 | libbpf_sys::BPF_MEM;
+        self.raw(insn(code, dst, src as u8, off, 0))
+    }
+
+    /// `*(size *)(dst + off) = src`
+    pub fn stx(&mut self, size: MemSize, dst: Reg, src: Reg, off: i16) -> &mut Self {
+        let code = libbpf_sys::BPF_STX | size as u32 | libbpf_sys::BPF_MEM;
+        self.raw(insn(code, dst, src as u8, off, 0))
+    }
+
+    /// `*(size *)(dst + off) = imm`
+    pub fn st_imm(&mut self, size: MemSize, dst: Reg, off: i16, imm: i32) -> &mut Self {
+        let code = libbpf_sys::BPF_ST | size as u32 | libbpf_sys::BPF_MEM;
+        self.raw(insn(code, dst, 0, off, imm))
+    }
+
+    fn jmp(&mut self, insn: bpf_insn, label: &str) -> &mut Self {
+        let () = self.jumps.push((self.insns.len(), label.to_string()));
+        self.raw(insn)
+    }
+
+    /// `if dst op imm goto label`, comparing 64 bit.
+    pub fn jmp_imm(&mut self, op: JmpOp, dst: Reg, imm: i32, label: &str) -> &mut Self {
+        let code = libbpf_sys::BPF_JMP | op as u32 | libbpf_sys::BPF_K;
+        self.jmp(insn(code, dst, 0, 0, imm), label)
+    }
+
+    /// `if dst op src goto label`, comparing 64 bit.
+    pub fn jmp_reg(&mut self, op: JmpOp, dst: Reg, src: Reg, label: &str) -> &mut Self {
+        let code = libbpf_sys::BPF_JMP | op as u32 | libbpf_sys::BPF_X;
+        self.jmp(insn(code, dst, src as u8, 0, 0), label)
+    }
+
+    /// `if dst op imm goto label`, comparing the lower 32 bit.
+    pub fn jmp32_imm(&mut self, op: JmpOp, dst: Reg, imm: i32, label: &str) -> &mut Self {
+        let code = libbpf_sys::BPF_JMP32 | op as u32 | libbpf_sys::BPF_K;
+        self.jmp(insn(code, dst, 0, 0, imm), label)
+    }
+
+    /// `if dst op src goto label`, comparing the lower 32 bit.
+    pub fn jmp32_reg(&mut self, op: JmpOp, dst: Reg, src: Reg, label: &str) -> &mut Self {
+        let code = libbpf_sys::BPF_JMP32 | op as u32 | libbpf_sys::BPF_X;
+        self.jmp(insn(code, dst, src as u8, 0, 0), label)
+    }
+
+    /// `goto label`
+    pub fn ja(&mut self, label: &str) -> &mut Self {
+        let code = libbpf_sys::BPF_JMP | libbpf_sys::BPF_JA;
+        self.jmp(insn(code, Reg::R0, 0, 0, 0), label)
+    }
+
+    /// Call the helper with the given ID (e.g.,
+    /// [`libbpf_sys::BPF_FUNC_map_lookup_elem`]).
+    ///
+    /// Arguments are passed in `R1` to `R5` and the result is returned in
+    /// `R0`.
+    pub fn call_helper(&mut self, helper: bpf_func_id) -> &mut Self {
+        let code = libbpf_sys::BPF_JMP | libbpf_sys::BPF_CALL;
+        self.raw(insn(code, Reg::R0, 0, 0, helper as i32))
+    }
+
+    /// Call the BPF function starting at `label`.
+    ///
+    /// Arguments are passed in `R1` to `R5` and the result is returned in
+    /// `R0`.
+    pub fn call_local(&mut self, label: &str) -> &mut Self {
+        let code = libbpf_sys::BPF_JMP | libbpf_sys::BPF_CALL;
+        self.jmp(
+            insn(code, Reg::R0, libbpf_sys::BPF_PSEUDO_CALL as u8, 0, 0),
+            label,
+        )
+    }
+
+    /// Exit the program, returning the value in `R0`.
+    pub fn exit(&mut self) -> &mut Self {
+        let code = libbpf_sys::BPF_JMP | libbpf_sys::BPF_EXIT;
+        self.raw(insn(code, Reg::R0, 0, 0, 0))
+    }
+
+    /// Resolve all labels and retrieve the final instructions.
+    pub fn finish(&mut self) -> Result<Vec<bpf_insn>> {
+        if let Some((label, _)) = self.labels.iter().find(|(_, idx)| **idx == usize::MAX) {
+            return Err(Error::with_invalid_data(format!(
+                "label `{label}` is defined more than once"
+            )))
+        }
+
+        for (idx, label) in &self.jumps {
+            let target = match self.labels.get(label).copied() {
+                Some(target) => target,
+                None => {
+                    return Err(Error::with_invalid_data(format!(
+                        "label `{label}` is not defined"
+                    )))
+                }
+            };
+
+            let off = target as i64 - *idx as i64 - 1;
+            let insn = &mut self.insns[*idx];
+            // Calls encode their target in the immediate, jumps in the
+            // offset.
+            if u32::from(insn.code) == libbpf_sys::BPF_JMP | libbpf_sys::BPF_CALL {
+                insn.imm = i32::try_from(off).map_err(|_| {
+                    Error::with_invalid_data(format!(
+                        "call from instruction {idx} to label `{label}` is out of range"
+                    ))
+                })?;
+            } else {
+                insn.off = i16::try_from(off).map_err(|_| {
+                    Error::with_invalid_data(format!(
+                        "jump from instruction {idx} to label `{label}` is out of range"
+                    ))
+                })?;
+            }
+        }
+
+        Ok(self.insns.clone())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+    use std::os::unix::io::AsFd as _;
+
+    use crate::Disassembler;
+
+
+    /// Check that we assemble a program with forward and backward jumps
+    /// correctly.
+    #[test]
+    fn assemble_jumps() {
+        let mut asm = Assembler::new();
+        let insns = asm
+            .mov64_imm(Reg::R0, 0)
+            .label("loop")
+            .alu64_imm(AluOp::Add, Reg::R0, 1)
+            .jmp_imm(JmpOp::Ge, Reg::R0, 10, "out")
+            .ja("loop")
+            .label("out")
+            .ld_imm64(Reg::R1, 0x1234_5678_9abc)
+            .call_helper(libbpf_sys::BPF_FUNC_ktime_get_ns)
+            .exit()
+            .finish()
+            .unwrap();
+
+        let expected = r#"   0: (b7) r0 = 0
+   1: (07) r0 += 1
+   2: (35) if r0 >= 0xa goto pc+1
+   3: (05) goto pc-3
+   4: (18) r1 = 0x123456789abc
+   6: (85) call bpf_ktime_get_ns#5
+   7: (95) exit
+"#;
+        assert_eq!(Disassembler::new().disassemble(&insns), expected);
+    }
+
+    /// Check that calls to local functions are resolved.
+    #[test]
+    fn assemble_local_calls() {
+        let mut asm = Assembler::new();
+        let insns = asm
+            .call_local("func")
+            .exit()
+            .label("func")
+            .mov64_imm(Reg::R0, 1)
+            .exit()
+            .finish()
+            .unwrap();
+        assert_eq!(insns[0].imm, 1);
+        assert_eq!(insns[0].off, 0);
+        assert_eq!(
+            Disassembler::new().disassemble_insn(&insns, 0).as_deref(),
+            Some("call pc+1")
+        );
+    }
+
+    /// Check that map references are embedded properly.
+    #[test]
+    fn assemble_map_refs() {
+        let file = File::open("/dev/null").unwrap();
+        let fd = file.as_fd();
+        let mut asm = Assembler::new();
+        let insns = asm
+            .ld_map_fd(Reg::R1, fd)
+            .ld_map_value(Reg::R2, fd, 8)
+            .finish()
+            .unwrap();
+        let disasm = Disassembler::new().map_refs(crate::MapRefKind::Fd);
+        let raw_fd = fd.as_raw_fd();
+        assert_eq!(
+            disasm.disassemble_insn(&insns, 0),
+            Some(format!("r1 = map[fd:{raw_fd}]"))
+        );
+        assert_eq!(
+            disasm.disassemble_insn(&insns, 2),
+            Some(format!("r2 = map[fd:{raw_fd}][0]+8"))
+        );
+    }
+
+    /// Check that we report invalid labels.
+    #[test]
+    fn assemble_invalid_labels() {
+        let mut asm = Assembler::new();
+        let _err = asm.ja("nowhere").finish().unwrap_err();
+
+        let mut asm = Assembler::new();
+        let _err = asm
+            .label("twice")
+            .label("twice")
+            .ja("twice")
+            .finish()
+            .unwrap_err();
+
+        // Duplicates are reported even if no jump refers to them.
+        let mut asm = Assembler::new();
+        let _err = asm
+            .label("unused")
+            .mov64_imm(Reg::R0, 0)
+            .label("unused")
+            .exit()
+            .finish()
+            .unwrap_err();
+    }
+}
@@ -0,0 +1,318 @@
+//! Asynchronous consumption of ring and perf buffers on top of `tokio`.
+//!
+//! All futures and streams provided here are cancellation safe: data is
+//! only ever consumed from a buffer once it can be handed out, so
+//! dropping a pending future (e.g., as part of a `select!`) does not
+//! lose samples. Samples that are still in flight when shutting down a
+//! stream can be retrieved via the respective `close` method.
+
+use std::io;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+
+use futures_core::Stream;
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+
+use crate::util;
+use crate::MapCore;
+use crate::PerfBuffer;
+use crate::PerfBufferSource;
+use crate::PerfEvent;
+use crate::Result;
+use crate::RingBuffer;
+use crate::RingBufferSource;
+
+
+/// Adapter making an [`AsFd`] object usable with [`AsyncFd`].
+#[derive(Debug)]
+struct Fd<T>(T);
+
+impl<T> AsRawFd for Fd<T>
+where
+    T: AsFd,
+{
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+
+/// A file descriptor owned by somebody else.
+#[derive(Debug)]
+struct UnownedFd(RawFd);
+
+impl AsRawFd for UnownedFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+
+fn register<T>(inner: T) -> io::Result<AsyncFd<T>>
+where
+    T: AsRawFd,
+{
+    AsyncFd::with_interest(inner, Interest::READABLE)
+}
+
+
+/// An asynchronous wrapper around a [`RingBuffer`].
+#[derive(Debug)]
+pub struct AsyncRingBuffer<'cb> {
+    rb: AsyncFd<Fd<RingBuffer<'cb>>>,
+}
+
+impl<'cb> AsyncRingBuffer<'cb> {
+    /// Wrap the provided [`RingBuffer`].
+    ///
+    /// This function has to be called from within the context of a
+    /// `tokio` runtime.
+    pub fn new(rb: RingBuffer<'cb>) -> Result<Self> {
+        let rb = register(Fd(rb))?;
+        Ok(Self { rb })
+    }
+
+    /// Wait for data to become available and consume it from all rings,
+    /// calling the registered callbacks.
+    ///
+    /// Return the number of consumed samples.
+    pub async fn consume(&mut self) -> Result<usize> {
+        loop {
+            let mut guard = self.rb.readable_mut().await?;
+            let ret = guard.get_inner_mut().0.consume_raw();
+            let count = util::parse_ret_i32(ret)?;
+            if count > 0 {
+                // Don't clear the readiness, as more data may be
+                // available already.
+                return Ok(count as usize)
+            }
+            let () = guard.clear_ready();
+        }
+    }
+
+    /// Retrieve the wrapped [`RingBuffer`].
+    pub fn into_inner(self) -> RingBuffer<'cb> {
+        self.rb.into_inner().0
+    }
+}
+
+
+/// An asynchronous wrapper around a [`PerfBuffer`].
+#[derive(Debug)]
+pub struct AsyncPerfBuffer<'cb> {
+    pb: AsyncFd<Fd<PerfBuffer<'cb>>>,
+}
+
+impl<'cb> AsyncPerfBuffer<'cb> {
+    /// Wrap the provided [`PerfBuffer`].
+    ///
+    /// This function has to be called from within the context of a
+    /// `tokio` runtime.
+    pub fn new(pb: PerfBuffer<'cb>) -> Result<Self> {
+        let pb = register(Fd(pb))?;
+        Ok(Self { pb })
+    }
+
+    /// Wait for data to become available and consume it from all
+    /// per-CPU buffers, calling the registered callbacks.
+    pub async fn consume(&mut self) -> Result<()> {
+        let mut guard = self.pb.readable_mut().await?;
+        // The perf buffer does not report whether anything got
+        // consumed, so clear the readiness upfront. Data arriving
+        // from here on will mark the file descriptor ready again.
+        let () = guard.clear_ready();
+        guard.get_inner_mut().0.consume()
+    }
+
+    /// Retrieve the wrapped [`PerfBuffer`].
+    pub fn into_inner(self) -> PerfBuffer<'cb> {
+        self.pb.into_inner().0
+    }
+}
+
+
+/// A [`Stream`] of owned samples from a single `ringbuf` map.
+///
+/// The stream never ends on its own. Use [`RingBufferStream::close`] to
+/// shut it down.
+#[derive(Debug)]
+pub struct RingBufferStream {
+    source: AsyncFd<Fd<RingBufferSource>>,
+}
+
+impl RingBufferStream {
+    /// Create a new `RingBufferStream` for the provided `ringbuf` map.
+    ///
+    /// This function has to be called from within the context of a
+    /// `tokio` runtime.
+    pub fn new(map: &dyn MapCore) -> Result<Self> {
+        let source = RingBufferSource::new(map)?;
+        Self::from_source(source)
+    }
+
+    /// Create a new `RingBufferStream` from a [`RingBufferSource`].
+    ///
+    /// This function has to be called from within the context of a
+    /// `tokio` runtime.
+    pub fn from_source(source: RingBufferSource) -> Result<Self> {
+        let source = register(Fd(source))?;
+        Ok(Self { source })
+    }
+
+    /// Shut down the stream, returning all samples that are available
+    /// but have not been handed out yet.
+    pub fn close(self) -> Result<Vec<Vec<u8>>> {
+        let mut source = self.source.into_inner().0;
+        source.drain()
+    }
+}
+
+impl Stream for RingBufferStream {
+    type Item = Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            let mut guard = match ready!(this.source.poll_read_ready_mut(cx)) {
+                Ok(guard) => guard,
+                Err(err) => return Poll::Ready(Some(Err(err.into()))),
+            };
+
+            match guard.get_inner_mut().0.try_next() {
+                Ok(Some(sample)) => return Poll::Ready(Some(Ok(sample))),
+                Ok(None) => guard.clear_ready(),
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            }
+        }
+    }
+}
+
+
+/// A [`Stream`] of owned events from a single per-CPU buffer of a
+/// `PerfEventArray` map.
+///
+/// The stream never ends on its own. Use [`PerfBufferStream::close`] to
+/// shut it down.
+#[derive(Debug)]
+pub struct PerfBufferStream {
+    // Declared before `source`, so that the file descriptor is
+    // deregistered before the perf buffer owning it may be freed.
+    fd: AsyncFd<UnownedFd>,
+    buf_idx: usize,
+    source: Arc<Mutex<PerfBufferSource>>,
+}
+
+impl PerfBufferStream {
+    /// Create one `PerfBufferStream` per CPU buffer of the provided
+    /// `PerfEventArray` map, using buffers of `pages` pages each.
+    ///
+    /// This function has to be called from within the context of a
+    /// `tokio` runtime.
+    pub fn new<M>(map: &M, pages: usize) -> Result<Vec<Self>>
+    where
+        M: MapCore,
+    {
+        let source = PerfBufferSource::new(map, pages)?;
+        Self::from_source(source)
+    }
+
+    /// Create one `PerfBufferStream` per CPU buffer of the provided
+    /// [`PerfBufferSource`].
+    ///
+    /// This function has to be called from within the context of a
+    /// `tokio` runtime.
+    pub fn from_source(source: PerfBufferSource) -> Result<Vec<Self>> {
+        let fds = (0..source.buffer_cnt())
+            .map(|buf_idx| {
+                let fd = source.buffer_fd(buf_idx)?.as_raw_fd();
+                let fd = register(UnownedFd(fd))?;
+                Ok(fd)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let source = Arc::new(Mutex::new(source));
+        let streams = fds
+            .into_iter()
+            .enumerate()
+            .map(|(buf_idx, fd)| Self {
+                fd,
+                buf_idx,
+                source: source.clone(),
+            })
+            .collect();
+        Ok(streams)
+    }
+
+    /// Retrieve the index of the per-CPU buffer this stream reports
+    /// events from.
+    pub fn buffer_idx(&self) -> usize {
+        self.buf_idx
+    }
+
+    /// Shut down the stream, returning all events that are available
+    /// but have not been handed out yet.
+    pub fn close(self) -> Result<Vec<PerfEvent>> {
+        let Self {
+            fd,
+            buf_idx,
+            source,
+        } = self;
+        let _fd = fd.into_inner();
+
+        // SANITY: We never panic while holding the lock.
+        let mut source = source.lock().unwrap();
+        source.drain_buffer(buf_idx)
+    }
+}
+
+impl Stream for PerfBufferStream {
+    type Item = Result<PerfEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            let mut guard = match ready!(this.fd.poll_read_ready(cx)) {
+                Ok(guard) => guard,
+                Err(err) => return Poll::Ready(Some(Err(err.into()))),
+            };
+
+            // SANITY: We never panic while holding the lock.
+            let result = this.source.lock().unwrap().try_next(this.buf_idx);
+            match result {
+                Ok(Some(event)) => return Poll::Ready(Some(Ok(event))),
+                Ok(None) => guard.clear_ready(),
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Check that our streams are `Send`, so that they can be moved
+    /// into spawned tasks.
+    #[test]
+    fn streams_are_send() {
+        fn test<T>()
+        where
+            T: Send,
+        {
+        }
+
+        test::<RingBufferStream>();
+        test::<PerfBufferStream>();
+    }
+}
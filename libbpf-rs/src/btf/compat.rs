@@ -0,0 +1,328 @@
+//! Offline checking of the CO-RE compatibility of BPF objects with
+//! kernels, as described by their BTF information.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::ext::BtfExt;
+use super::ext::ExtSec;
+use super::ext::LineInfoRecord;
+use super::relo::CoreRelo;
+use super::relo::CoreReloKind;
+use super::relo::CoreTarget;
+use super::relo::Resolution;
+use super::Btf;
+use super::BtfType;
+use crate::elf::Elf;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+/// The size of a BPF instruction, in bytes.
+const INSN_SIZE: u32 = 8;
+
+
+/// A location in the source code of a BPF object.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLine {
+    /// The path of the source file, as recorded by the compiler.
+    pub file: String,
+    /// The line number.
+    pub line: u32,
+    /// The column.
+    pub column: u32,
+    /// The contents of the source line.
+    pub text: String,
+}
+
+impl Display for SourceLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+
+/// A CO-RE relocation that could not be performed.
+#[derive(Clone, Debug)]
+pub struct CoreReloFailure {
+    /// The kind of the relocation.
+    pub kind: CoreReloKind,
+    /// A description of the entity the relocation refers to, e.g.,
+    /// `struct task_struct.pid (0:1)`.
+    pub spec: String,
+    /// The index of the relocated instruction in its program.
+    pub insn_idx: usize,
+    /// The reason the relocation failed.
+    pub reason: String,
+    /// The source line the relocated instruction stems from, if known.
+    pub source: Option<SourceLine>,
+}
+
+
+/// The outcome of checking the CO-RE relocations of a single program.
+#[derive(Clone, Debug)]
+pub struct ProgramCompat {
+    /// The name of the program.
+    pub name: String,
+    /// The name of the ELF section containing the program.
+    pub section: String,
+    /// The number of CO-RE relocations of the program.
+    pub relos: usize,
+    /// The relocations that could not be performed.
+    pub failures: Vec<CoreReloFailure>,
+}
+
+impl ProgramCompat {
+    /// Check whether all of the program's relocations can be performed.
+    pub fn is_compatible(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+
+/// The outcome of checking the CO-RE relocations of a BPF object
+/// against the BTF information of a single kernel.
+#[derive(Clone, Debug)]
+pub struct KernelCompat {
+    /// The path to the kernel's BTF information.
+    pub btf: PathBuf,
+    /// The outcome for each of the object's programs.
+    pub programs: Vec<ProgramCompat>,
+}
+
+impl KernelCompat {
+    /// Check whether all of the object's relocations can be performed.
+    pub fn is_compatible(&self) -> bool {
+        self.programs.iter().all(ProgramCompat::is_compatible)
+    }
+}
+
+
+/// Find the last of `records` at or before the instruction at byte
+/// offset `insn_off`.
+fn find_record<T, F>(records: &[T], insn_off: u32, rec_insn_off: F) -> Option<&T>
+where
+    F: Fn(&T) -> u32,
+{
+    let idx = records.partition_point(|record| rec_insn_off(record) <= insn_off);
+    idx.checked_sub(1).map(|idx| &records[idx])
+}
+
+
+/// The local parts of a BPF object relevant for CO-RE relocation.
+struct LocalObject {
+    /// The object's BTF information.
+    btf: Btf<'static>,
+    /// The object's `.BTF.ext` information.
+    ext: BtfExt,
+}
+
+impl LocalObject {
+    fn open(path: &Path) -> Result<Self> {
+        let elf = Elf::open(path)?;
+        let mut ext = BtfExt::parse(&elf)
+            .with_context(|| format!("failed to parse `.BTF.ext` of `{}`", path.display()))?
+            .unwrap_or_default();
+        let btf = Btf::from_path(path)?;
+
+        for sec in &mut ext.func_infos {
+            let () = sec.records.sort_by_key(|record| record.insn_off);
+        }
+        for sec in &mut ext.line_infos {
+            let () = sec.records.sort_by_key(|record| record.insn_off);
+        }
+
+        Ok(Self { btf, ext })
+    }
+
+    fn section_name(&self, sec_name_off: u32) -> Result<String> {
+        self.btf
+            .name_at(sec_name_off)
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| Error::with_invalid_data("`.BTF.ext` has invalid section name"))
+    }
+
+    /// Find the records of `secs` belonging to the section `name`.
+    fn section_records<'slf, T>(&self, secs: &'slf [ExtSec<T>], name: &str) -> &'slf [T] {
+        secs.iter()
+            .find(|sec| self.btf.name_at(sec.sec_name_off).and_then(|n| n.to_str()) == Some(name))
+            .map(|sec| sec.records.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn source_line(&self, record: &LineInfoRecord) -> SourceLine {
+        let str_at = |off| {
+            self.btf
+                .name_at(off)
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        };
+
+        SourceLine {
+            file: str_at(record.file_name_off),
+            line: record.line(),
+            column: record.column(),
+            text: str_at(record.line_off).trim().to_string(),
+        }
+    }
+
+    /// Enumerate the object's programs, as described by its function
+    /// information.
+    fn programs(&self) -> Result<Vec<ProgramCompat>> {
+        let mut programs = Vec::new();
+        for sec in &self.ext.func_infos {
+            let section = self.section_name(sec.sec_name_off)?;
+            for record in &sec.records {
+                let name = self
+                    .btf
+                    .type_by_id::<BtfType<'_>>(record.type_id.into())
+                    .and_then(|ty| ty.name())
+                    .ok_or_else(|| {
+                        Error::with_invalid_data(format!(
+                            "function information refers to invalid type {}",
+                            record.type_id
+                        ))
+                    })?;
+                let () = programs.push(ProgramCompat {
+                    name: name.to_string_lossy().to_string(),
+                    section: section.clone(),
+                    relos: 0,
+                    failures: Vec::new(),
+                });
+            }
+        }
+        Ok(programs)
+    }
+
+    fn check(&self, target: &Btf<'_>) -> Result<Vec<ProgramCompat>> {
+        let target = CoreTarget::new(target);
+        let mut programs = self.programs()?;
+
+        for sec in &self.ext.core_relos {
+            let section = self.section_name(sec.sec_name_off)?;
+            let funcs = self.section_records(&self.ext.func_infos, &section);
+            let lines = self.section_records(&self.ext.line_infos, &section);
+
+            for record in &sec.records {
+                let (name, func_off) = match find_record(funcs, record.insn_off, |f| f.insn_off) {
+                    Some(func) => {
+                        // SANITY: All functions have been validated to
+                        //         have a name when enumerating programs.
+                        let name = self
+                            .btf
+                            .type_by_id::<BtfType<'_>>(func.type_id.into())
+                            .and_then(|ty| ty.name())
+                            .unwrap()
+                            .to_string_lossy()
+                            .to_string();
+                        (name, func.insn_off)
+                    }
+                    // Without function information we attribute the
+                    // relocation to the section as a whole.
+                    None => (section.clone(), 0),
+                };
+
+                let idx = match programs
+                    .iter()
+                    .position(|prog| prog.section == section && prog.name == name)
+                {
+                    Some(idx) => idx,
+                    None => {
+                        let () = programs.push(ProgramCompat {
+                            name,
+                            section: section.clone(),
+                            relos: 0,
+                            failures: Vec::new(),
+                        });
+                        programs.len() - 1
+                    }
+                };
+                let program = &mut programs[idx];
+                program.relos += 1;
+
+                let relo = CoreRelo::new(&self.btf, record)?;
+                let reason = match target.resolve(&relo) {
+                    Resolution::Matched(..) => continue,
+                    // Failed existence checks merely evaluate to zero.
+                    Resolution::Unmatched(..) if relo.kind.is_existence_check() => continue,
+                    Resolution::Unmatched(reason) => reason,
+                };
+
+                let () = program.failures.push(CoreReloFailure {
+                    kind: relo.kind,
+                    spec: relo.describe(),
+                    insn_idx: ((record.insn_off - func_off) / INSN_SIZE) as usize,
+                    reason,
+                    source: find_record(lines, record.insn_off, |line| line.insn_off)
+                        .map(|line| self.source_line(line)),
+                });
+            }
+        }
+        Ok(programs)
+    }
+}
+
+
+impl Btf<'_> {
+    /// Check which of the CO-RE relocations of the BPF object at
+    /// `object` can be performed against this BTF information, without
+    /// loading the object.
+    ///
+    /// The outcome is reported for each of the object's programs
+    /// (including subprograms). Note that `libbpf` does not reject an
+    /// object just because a relocation failed: the affected
+    /// instruction is poisoned instead and loading only fails if the
+    /// verifier deems it reachable. Failures in code guarded by, say,
+    /// `bpf_core_field_exists` are hence expected and benign.
+    pub fn check_core<P: AsRef<Path>>(&self, object: P) -> Result<Vec<ProgramCompat>> {
+        let object = object.as_ref();
+        LocalObject::open(object)?
+            .check(self)
+            .with_context(|| format!("invalid CO-RE relocation in `{}`", object.display()))
+    }
+}
+
+
+/// Check the CO-RE compatibility of the BPF object at `object` with
+/// each of the kernels whose BTF information is contained in `btf_dir`.
+///
+/// See [`Btf::check_core`] for details. Results are ordered by the path
+/// of the BTF file.
+pub fn check_core_compat<P, Q>(object: P, btf_dir: Q) -> Result<Vec<KernelCompat>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let object = object.as_ref();
+    let btf_dir = btf_dir.as_ref();
+    let local = LocalObject::open(object)?;
+
+    let mut btf_files = fs::read_dir(btf_dir)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .with_context(|| format!("failed to read directory `{}`", btf_dir.display()))?;
+    let () = btf_files.retain(|path| path.is_file());
+    let () = btf_files.sort();
+
+    btf_files
+        .into_iter()
+        .map(|path| {
+            let target = Btf::from_path(&path)?;
+            let programs = local
+                .check(&target)
+                .with_context(|| format!("invalid CO-RE relocation in `{}`", object.display()))?;
+            Ok(KernelCompat {
+                btf: path,
+                programs,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,270 @@
+//! Parsing of the `.BTF.ext` section of BPF object files.
+
+use crate::elf::Elf;
+use crate::Error;
+use crate::Result;
+
+
+/// The magic number at the start of a `.BTF.ext` section.
+const BTF_EXT_MAGIC: u16 = 0xeb9f;
+/// The size of the minimal `.BTF.ext` header, not including CO-RE
+/// relocation information.
+const BTF_EXT_HDR_MIN_LEN: u32 = 24;
+/// The size of a `.BTF.ext` header including CO-RE relocation
+/// information.
+const BTF_EXT_HDR_CORE_LEN: u32 = 32;
+/// The size of a `struct bpf_func_info`.
+const FUNC_INFO_REC_SIZE: u32 = 8;
+/// The size of a `struct bpf_line_info`.
+const LINE_INFO_REC_SIZE: u32 = 16;
+/// The size of a `struct bpf_core_relo`.
+const CORE_RELO_REC_SIZE: u32 = 16;
+
+
+/// A function information record, as emitted by the compiler.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FuncInfoRecord {
+    /// The byte offset of the function's first instruction in its
+    /// section.
+    pub insn_off: u32,
+    /// The local BTF type ID of the function.
+    pub type_id: u32,
+}
+
+
+/// A line information record, as emitted by the compiler.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LineInfoRecord {
+    /// The byte offset of the first instruction belonging to the line
+    /// in its section.
+    pub insn_off: u32,
+    /// The offset of the source file name in the BTF string section.
+    pub file_name_off: u32,
+    /// The offset of the source line in the BTF string section.
+    pub line_off: u32,
+    /// The line number (upper 22 bits) and column (lower 10 bits).
+    pub line_col: u32,
+}
+
+impl LineInfoRecord {
+    /// The line number of the record.
+    pub fn line(&self) -> u32 {
+        self.line_col >> 10
+    }
+
+    /// The column of the record.
+    pub fn column(&self) -> u32 {
+        self.line_col & 0x3ff
+    }
+}
+
+
+/// A CO-RE relocation record, as emitted by the compiler.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CoreReloRecord {
+    /// The byte offset of the relocated instruction in its section.
+    pub insn_off: u32,
+    /// The local BTF type ID of the relocation's root type.
+    pub type_id: u32,
+    /// The offset of the access string in the BTF string section.
+    pub access_str_off: u32,
+    /// The kind of the relocation.
+    pub kind: u32,
+}
+
+
+/// The records of a certain type belonging to a single ELF section.
+#[derive(Clone, Debug)]
+pub(crate) struct ExtSec<T> {
+    /// The offset of the section name in the BTF string section.
+    pub sec_name_off: u32,
+    /// The records of the section.
+    pub records: Vec<T>,
+}
+
+
+/// The parsed contents of a `.BTF.ext` section.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BtfExt {
+    /// Function information records, by section.
+    pub func_infos: Vec<ExtSec<FuncInfoRecord>>,
+    /// Line information records, by section.
+    pub line_infos: Vec<ExtSec<LineInfoRecord>>,
+    /// CO-RE relocation records, by section.
+    pub core_relos: Vec<ExtSec<CoreReloRecord>>,
+}
+
+impl BtfExt {
+    /// Parse the `.BTF.ext` section of `elf`, if present.
+    pub fn parse(elf: &Elf) -> Result<Option<Self>> {
+        let Some(section) = elf.section(".BTF.ext") else {
+            return Ok(None)
+        };
+        let data = elf.section_data(section)?;
+        let reader = Reader {
+            data,
+            le: elf.is_le(),
+        };
+
+        if reader.u16(0)? != BTF_EXT_MAGIC {
+            return Err(Error::with_invalid_data("invalid .BTF.ext magic"))
+        }
+        let hdr_len = reader.u32(4)?;
+        if hdr_len < BTF_EXT_HDR_MIN_LEN {
+            return Err(Error::with_invalid_data(format!(
+                "invalid .BTF.ext header length {hdr_len}"
+            )))
+        }
+
+        let func_infos = reader.info(
+            hdr_len,
+            reader.u32(8)?,
+            reader.u32(12)?,
+            FUNC_INFO_REC_SIZE,
+            |rec| {
+                Ok(FuncInfoRecord {
+                    insn_off: reader.u32(rec)?,
+                    type_id: reader.u32(rec + 4)?,
+                })
+            },
+        )?;
+        let line_infos = reader.info(
+            hdr_len,
+            reader.u32(16)?,
+            reader.u32(20)?,
+            LINE_INFO_REC_SIZE,
+            |rec| {
+                Ok(LineInfoRecord {
+                    insn_off: reader.u32(rec)?,
+                    file_name_off: reader.u32(rec + 4)?,
+                    line_off: reader.u32(rec + 8)?,
+                    line_col: reader.u32(rec + 12)?,
+                })
+            },
+        )?;
+        let core_relos = if hdr_len >= BTF_EXT_HDR_CORE_LEN {
+            reader.info(
+                hdr_len,
+                reader.u32(24)?,
+                reader.u32(28)?,
+                CORE_RELO_REC_SIZE,
+                |rec| {
+                    Ok(CoreReloRecord {
+                        insn_off: reader.u32(rec)?,
+                        type_id: reader.u32(rec + 4)?,
+                        access_str_off: reader.u32(rec + 8)?,
+                        kind: reader.u32(rec + 12)?,
+                    })
+                },
+            )?
+        } else {
+            Vec::new()
+        };
+
+        let ext = Self {
+            func_infos,
+            line_infos,
+            core_relos,
+        };
+        Ok(Some(ext))
+    }
+}
+
+
+/// A helper for reading integers of the ELF's endianness from section
+/// data.
+struct Reader<'data> {
+    data: &'data [u8],
+    le: bool,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        offset
+            .checked_add(N)
+            .and_then(|end| self.data.get(offset..end))
+            .map(|bytes| bytes.try_into().unwrap())
+            .ok_or_else(|| {
+                Error::with_invalid_data(format!(".BTF.ext offset {offset:#x} is out of bounds"))
+            })
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.le {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.le {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// Parse one of the per-section info blocks (function, line, or
+    /// CO-RE relocation information), starting at `off` (relative to
+    /// the end of the header) and spanning `len` bytes.
+    ///
+    /// `parse` is invoked with the offset of each record.
+    fn info<T, F>(
+        &self,
+        hdr_len: u32,
+        off: u32,
+        len: u32,
+        min_rec_size: u32,
+        parse: F,
+    ) -> Result<Vec<ExtSec<T>>>
+    where
+        F: Fn(usize) -> Result<T>,
+    {
+        if len == 0 {
+            return Ok(Vec::new())
+        }
+
+        let start = hdr_len as usize + off as usize;
+        let end = start + len as usize;
+        if end > self.data.len() {
+            return Err(Error::with_invalid_data(
+                ".BTF.ext info block is out of bounds",
+            ))
+        }
+
+        let rec_size = self.u32(start)?;
+        if rec_size < min_rec_size {
+            return Err(Error::with_invalid_data(format!(
+                "invalid .BTF.ext record size {rec_size}"
+            )))
+        }
+
+        let mut secs = Vec::new();
+        let mut pos = start + 4;
+        while pos < end {
+            let sec_name_off = self.u32(pos)?;
+            let num_info = self.u32(pos + 4)? as usize;
+            pos += 8;
+
+            let recs_end = pos + num_info * rec_size as usize;
+            if recs_end > end {
+                return Err(Error::with_invalid_data(
+                    ".BTF.ext section records are out of bounds",
+                ))
+            }
+            let recs = (pos..recs_end)
+                .step_by(rec_size as usize)
+                .map(&parse)
+                .collect::<Result<Vec<_>>>()?;
+            let () = secs.push(ExtSec {
+                sec_name_off,
+                records: recs,
+            });
+            pos = recs_end;
+        }
+        Ok(secs)
+    }
+}
@@ -0,0 +1,403 @@
+//! Generation of minimized BTF information for kernels not exposing
+//! BTF information themselves.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::io;
+use std::marker::PhantomData;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::ptr;
+
+use super::ext::BtfExt;
+use super::relo::describe_type;
+use super::relo::CoreRelo;
+use super::relo::CoreReloKind;
+use super::relo::CoreTarget;
+use super::relo::Resolution;
+use super::relo::TargetMatch;
+use super::types::Array;
+use super::types::Composite;
+use super::types::FuncProto;
+use super::types::MemberAttr;
+use super::Btf;
+use super::BtfKind;
+use super::BtfType;
+use super::DropPolicy;
+use super::HasSize as _;
+use super::ReferencesType as _;
+use super::TypeId;
+use crate::elf::Elf;
+use crate::util;
+use crate::util::parse_ret;
+use crate::util::validate_bpf_ret;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+/// The path at which the running kernel exposes its BTF information.
+const VMLINUX_BTF_PATH: &str = "/sys/kernel/btf/vmlinux";
+
+
+/// The set of types (and members thereof) of BTF information that
+/// CO-RE relocations refer to.
+struct Marker<'btf> {
+    btf: &'btf Btf<'btf>,
+    /// The marked types, along with whether pointers were followed when
+    /// marking them.
+    types: BTreeMap<u32, bool>,
+    /// The marked struct and union members, as pairs of the composite
+    /// type and the index of the member in it.
+    members: HashSet<(u32, usize)>,
+    /// The types that were marked including all of their members.
+    complete: HashSet<u32>,
+}
+
+impl<'btf> Marker<'btf> {
+    fn new(btf: &'btf Btf<'btf>) -> Self {
+        Self {
+            btf,
+            types: BTreeMap::new(),
+            members: HashSet::new(),
+            complete: HashSet::new(),
+        }
+    }
+
+    /// Mark the type `id` along with the types it references. Pointees
+    /// are only marked if `follow_ptrs` is set.
+    fn mark_type(&mut self, id: TypeId, follow_ptrs: bool) {
+        let raw = u32::from(id);
+        if raw == 0 {
+            return
+        }
+        match self.types.get(&raw) {
+            Some(true) => return,
+            Some(false) if !follow_ptrs => return,
+            _ => (),
+        }
+        let _prev = self.types.insert(raw, follow_ptrs);
+
+        let btf = self.btf;
+        let Some(ty) = btf.type_by_id::<BtfType<'_>>(id) else {
+            return
+        };
+        match ty.kind() {
+            BtfKind::Ptr if !follow_ptrs => (),
+            BtfKind::Ptr
+            | BtfKind::Typedef
+            | BtfKind::Volatile
+            | BtfKind::Const
+            | BtfKind::Restrict
+            | BtfKind::TypeTag => {
+                if let Some(next) = ty.next_type() {
+                    let () = self.mark_type(next.type_id(), follow_ptrs);
+                }
+            }
+            BtfKind::Array => {
+                // SANITY: We checked the kind.
+                let array = Array::try_from(ty).unwrap();
+                let () = self.mark_type(array.ty(), follow_ptrs);
+                let () = self.mark_type(array.index_ty(), follow_ptrs);
+            }
+            BtfKind::FuncProto => {
+                // SANITY: We checked the kind.
+                let proto = FuncProto::try_from(ty).unwrap();
+                let () = self.mark_type(proto.referenced_type_id(), follow_ptrs);
+                for idx in 0..proto.len() {
+                    if let Some(param) = proto.get(idx) {
+                        let () = self.mark_type(param.ty, follow_ptrs);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Mark the type `id` along with all members of it and any type it
+    /// references.
+    fn mark_complete(&mut self, id: TypeId) {
+        let raw = u32::from(id);
+        if raw == 0 || !self.complete.insert(raw) {
+            return
+        }
+        let () = self.mark_type(id, true);
+
+        let btf = self.btf;
+        let Some(ty) = btf.type_by_id::<BtfType<'_>>(id) else {
+            return
+        };
+        if let Ok(composite) = Composite::try_from(ty) {
+            for idx in 0..composite.len() {
+                let _inserted = self.members.insert((raw, idx));
+                if let Some(member) = composite.get(idx) {
+                    let () = self.mark_complete(member.ty);
+                }
+            }
+        } else if let Ok(array) = Array::try_from(ty) {
+            let () = self.mark_complete(array.ty());
+        } else if let Some(next) = ty.next_type() {
+            let () = self.mark_complete(next.type_id());
+        }
+    }
+
+    /// Mark the types and members `match_` of a relocation of kind
+    /// `kind` refers to.
+    fn mark_match(&mut self, kind: CoreReloKind, match_: &TargetMatch) {
+        if kind.is_field() {
+            let () = self.mark_type(match_.root, false);
+
+            let btf = self.btf;
+            for (composite, idx) in &match_.members {
+                let _inserted = self.members.insert(((*composite).into(), *idx));
+                let member = btf
+                    .type_by_id::<Composite<'_>>(*composite)
+                    .and_then(|composite| composite.get(*idx));
+                if let Some(member) = member {
+                    let () = self.mark_type(member.ty, false);
+                }
+            }
+        } else if kind == CoreReloKind::TypeMatches {
+            let () = self.mark_complete(match_.root);
+        } else if kind.is_type() {
+            let () = self.mark_type(match_.root, true);
+        } else {
+            let () = self.mark_type(match_.root, false);
+        }
+    }
+
+    /// Create new BTF information containing only the marked types and
+    /// members.
+    fn build(self) -> Result<Btf<'static>> {
+        let ids = self
+            .types
+            .keys()
+            .enumerate()
+            .map(|(idx, id)| (*id, idx as i32 + 1))
+            .collect::<HashMap<_, _>>();
+        // Unmarked types can only be referenced by pointers, which we
+        // turn into void pointers.
+        let remap = |id: TypeId| ids.get(&u32::from(id)).copied().unwrap_or(0);
+
+        let ptr = unsafe { libbpf_sys::btf__new_empty() };
+        let ptr = validate_bpf_ret(ptr).context("failed to create BTF information")?;
+        let btf = Btf {
+            ptr,
+            drop_policy: DropPolicy::SelfPtrOnly,
+            _marker: PhantomData,
+        };
+
+        for id in self.types.keys() {
+            // SANITY: We only ever mark existing types.
+            let ty = self.btf.type_by_id::<BtfType<'_>>((*id).into()).unwrap();
+            let () = self
+                .add_type(&btf, ty, &remap)
+                .with_context(|| format!("failed to add `{}`", describe_type(&ty)))?;
+        }
+        Ok(btf)
+    }
+
+    /// Add `ty` to `dst`, with type IDs remapped using `remap`.
+    fn add_type<F>(&self, dst: &Btf<'_>, ty: BtfType<'_>, remap: &F) -> Result<()>
+    where
+        F: Fn(TypeId) -> i32,
+    {
+        fn c_name(name: Option<&OsStr>) -> Result<Option<CString>> {
+            name.map(|name| {
+                CString::new(name.as_bytes())
+                    .map_err(|_| Error::with_invalid_data("BTF name contains NUL byte"))
+            })
+            .transpose()
+        }
+
+        let dst = dst.ptr.as_ptr();
+        let name = c_name(ty.name())?;
+        let name_ptr = name.as_ref().map_or(ptr::null(), |name| name.as_ptr());
+        let next = || {
+            ty.next_type()
+                .map(|next| remap(next.type_id()))
+                .unwrap_or(0)
+        };
+
+        let ret = match ty.kind() {
+            BtfKind::Int | BtfKind::Float | BtfKind::Enum | BtfKind::Enum64 | BtfKind::Fwd => unsafe {
+                libbpf_sys::btf__add_type(dst, self.btf.ptr.as_ptr(), ty.ty)
+            },
+            BtfKind::Ptr => unsafe { libbpf_sys::btf__add_ptr(dst, next()) },
+            BtfKind::Typedef => unsafe { libbpf_sys::btf__add_typedef(dst, name_ptr, next()) },
+            BtfKind::Volatile => unsafe { libbpf_sys::btf__add_volatile(dst, next()) },
+            BtfKind::Const => unsafe { libbpf_sys::btf__add_const(dst, next()) },
+            BtfKind::Restrict => unsafe { libbpf_sys::btf__add_restrict(dst, next()) },
+            BtfKind::TypeTag => unsafe { libbpf_sys::btf__add_type_tag(dst, name_ptr, next()) },
+            BtfKind::Array => {
+                // SANITY: We checked the kind.
+                let array = Array::try_from(ty).unwrap();
+                unsafe {
+                    libbpf_sys::btf__add_array(
+                        dst,
+                        remap(array.index_ty()),
+                        remap(array.ty()),
+                        array.capacity() as u32,
+                    )
+                }
+            }
+            BtfKind::Struct | BtfKind::Union => {
+                // SANITY: We checked the kind.
+                let composite = Composite::try_from(ty).unwrap();
+                let size = composite.size() as u32;
+                let ret = if composite.is_struct {
+                    unsafe { libbpf_sys::btf__add_struct(dst, name_ptr, size) }
+                } else {
+                    unsafe { libbpf_sys::btf__add_union(dst, name_ptr, size) }
+                };
+                let () = parse_ret(ret)?;
+
+                let id = u32::from(ty.type_id());
+                for idx in (0..composite.len()).filter(|idx| self.members.contains(&(id, *idx))) {
+                    // SANITY: The index is in bounds.
+                    let member = composite.get(idx).unwrap();
+                    let (bit_offset, bit_size) = match member.attr {
+                        MemberAttr::Normal { offset } => (offset, 0),
+                        MemberAttr::BitField { size, offset } => (offset, u32::from(size)),
+                    };
+                    let member_name = c_name(member.name)?;
+                    let member_name_ptr = member_name
+                        .as_ref()
+                        .map_or(ptr::null(), |name| name.as_ptr());
+                    let ret = unsafe {
+                        libbpf_sys::btf__add_field(
+                            dst,
+                            member_name_ptr,
+                            remap(member.ty),
+                            bit_offset,
+                            bit_size,
+                        )
+                    };
+                    let () = parse_ret(ret)?;
+                }
+                ret
+            }
+            BtfKind::FuncProto => {
+                // SANITY: We checked the kind.
+                let proto = FuncProto::try_from(ty).unwrap();
+                let ret = unsafe {
+                    libbpf_sys::btf__add_func_proto(dst, remap(proto.referenced_type_id()))
+                };
+                let () = parse_ret(ret)?;
+
+                for idx in 0..proto.len() {
+                    // SANITY: The index is in bounds.
+                    let param = proto.get(idx).unwrap();
+                    let param_name = c_name(param.name)?;
+                    let param_name_ptr = param_name
+                        .as_ref()
+                        .map_or(ptr::null(), |name| name.as_ptr());
+                    let ret = unsafe {
+                        libbpf_sys::btf__add_func_param(dst, param_name_ptr, remap(param.ty))
+                    };
+                    let () = parse_ret(ret)?;
+                }
+                ret
+            }
+            BtfKind::Void | BtfKind::Func | BtfKind::Var | BtfKind::DataSec | BtfKind::DeclTag => {
+                return Err(Error::with_invalid_data(format!(
+                    "unexpected type kind {:?}",
+                    ty.kind()
+                )))
+            }
+        };
+        parse_ret(ret)
+    }
+}
+
+
+impl Btf<'_> {
+    /// Create a minimized copy of this BTF information, containing only
+    /// the types (and struct and union members) that the CO-RE
+    /// relocations of the BPF objects at `objects` refer to.
+    ///
+    /// This is the equivalent of `bpftool gen min_core_btf`. The result
+    /// is typically small enough to ship alongside an application and
+    /// can be used in place of the BTF information of a kernel built
+    /// without `CONFIG_DEBUG_INFO_BTF`, via
+    /// [`ObjectBuilder::btf_custom_path`][crate::ObjectBuilder::btf_custom_path]
+    /// or [`ObjectBuilder::btf_custom_dir`][crate::ObjectBuilder::btf_custom_dir].
+    /// It is only suitable for relocating the provided objects.
+    pub fn min_core<P: AsRef<Path>>(&self, objects: &[P]) -> Result<Btf<'static>> {
+        let target = CoreTarget::new(self);
+        let mut marker = Marker::new(self);
+
+        for path in objects {
+            let path = path.as_ref();
+            let elf = Elf::open(path)?;
+            let Some(ext) = BtfExt::parse(&elf)
+                .with_context(|| format!("failed to parse `.BTF.ext` of `{}`", path.display()))?
+            else {
+                continue
+            };
+            let local = Btf::from_path(path)?;
+
+            for sec in &ext.core_relos {
+                for record in &sec.records {
+                    let relo = CoreRelo::new(&local, record).with_context(|| {
+                        format!("invalid CO-RE relocation in `{}`", path.display())
+                    })?;
+                    // Relocations that fail to match don't keep any types
+                    // alive.
+                    if let Resolution::Matched(matches) = target.resolve(&relo) {
+                        for match_ in &matches {
+                            let () = marker.mark_match(relo.kind, match_);
+                        }
+                    }
+                }
+            }
+        }
+
+        marker.build()
+    }
+}
+
+
+/// Find the BTF file to use for the running kernel in `dir`, in case it
+/// does not expose its own BTF information.
+///
+/// BTF files are expected to be named after the release of the kernel
+/// (as reported by `uname -r`) they describe, optionally with a `.btf`
+/// extension, as is the case for files generated by `cargo libbpf
+/// min-core-btf`.
+///
+/// `None` is returned if the running kernel provides BTF information
+/// itself, in which case nothing needs to be passed to
+/// [`ObjectBuilder::btf_custom_path`][crate::ObjectBuilder::btf_custom_path].
+pub fn find_custom_btf<P: AsRef<Path>>(dir: P) -> Result<Option<PathBuf>> {
+    if Path::new(VMLINUX_BTF_PATH).exists() {
+        return Ok(None)
+    }
+
+    let dir = dir.as_ref();
+    let release = util::kernel_release().ok_or_else(|| {
+        Error::with_io_error(
+            io::ErrorKind::Other,
+            "failed to determine release of running kernel",
+        )
+    })?;
+
+    [format!("{release}.btf"), release.clone()]
+        .into_iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+        .map(Some)
+        .ok_or_else(|| {
+            Error::with_io_error(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no BTF file for kernel `{release}` found in `{}`",
+                    dir.display()
+                ),
+            )
+        })
+}
@@ -12,7 +12,12 @@
 //! [`Btf::type_by_kind`]). If you want to get a type independently of the kind, just make sure `K`
 //! binds to [`BtfType`].
 
+mod compat;
+mod ext;
+mod min;
+mod relo;
 pub mod types;
+mod value;
 
 use std::ffi::CStr;
 use std::ffi::CString;
@@ -36,7 +41,9 @@ use std::os::unix::prelude::OwnedFd;
 use std::path::Path;
 use std::ptr;
 use std::ptr::NonNull;
+use std::slice;
 
+use crate::util;
 use crate::util::parse_ret_i32;
 use crate::util::validate_bpf_ret;
 use crate::AsRawLibbpf;
@@ -46,6 +53,15 @@ use crate::Result;
 
 use self::types::Composite;
 
+pub use self::compat::check_core_compat;
+pub use self::compat::CoreReloFailure;
+pub use self::compat::KernelCompat;
+pub use self::compat::ProgramCompat;
+pub use self::compat::SourceLine;
+pub use self::min::find_custom_btf;
+pub use self::relo::CoreReloKind;
+pub use self::value::BtfValue;
+
 /// The various btf types.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[repr(u32)]
@@ -223,6 +239,19 @@ impl Btf<'static> {
 }
 
 impl<'btf> Btf<'btf> {
+    /// Load split btf information (e.g., of a kernel module) from the
+    /// specified path, on top of `base`.
+    pub(crate) fn from_split_path<P: AsRef<Path>>(path: P, base: &'btf Btf<'_>) -> Result<Self> {
+        let path = util::path_to_cstring(path)?;
+        let ptr = unsafe { libbpf_sys::btf__parse_split(path.as_ptr(), base.ptr.as_ptr()) };
+        let ptr = validate_bpf_ret(ptr).context("failed to parse split BTF information")?;
+        Ok(Btf {
+            ptr,
+            drop_policy: DropPolicy::SelfPtrOnly,
+            _marker: PhantomData,
+        })
+    }
+
     /// Create a new `Btf` instance from the given [`libbpf_sys::bpf_object`].
     pub fn from_bpf_object(obj: &'btf libbpf_sys::bpf_object) -> Result<Option<Self>> {
         Self::from_bpf_object_raw(obj)
@@ -293,7 +322,7 @@ impl<'btf> Btf<'btf> {
     /// Gets a string at a given offset.
     ///
     /// Returns [`None`] when the offset is out of bounds or if the name is empty.
-    fn name_at(&self, offset: u32) -> Option<&OsStr> {
+    pub(crate) fn name_at(&self, offset: u32) -> Option<&OsStr> {
         let name = unsafe {
             // SAFETY:
             // Assuming that btf is a valid pointer, this is always okay to call.
@@ -328,6 +357,22 @@ impl<'btf> Btf<'btf> {
         })
     }
 
+    /// Retrieve the raw BTF data, e.g., for writing it to a file.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut size = 0;
+        let data = unsafe {
+            // SAFETY: the btf pointer is valid.
+            libbpf_sys::btf__raw_data(self.ptr.as_ptr(), &mut size)
+        };
+        let data = validate_bpf_ret(data.cast_mut()).context("failed to retrieve raw BTF data")?;
+        let data = unsafe {
+            // SAFETY: libbpf guarantees that `size` bytes are valid at
+            //         `data`.
+            slice::from_raw_parts(data.as_ptr().cast::<u8>(), size as usize)
+        };
+        Ok(data.to_vec())
+    }
+
     /// Find a btf type by name
     ///
     /// # Panics
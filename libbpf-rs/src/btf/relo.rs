@@ -0,0 +1,1276 @@
+//! Matching of CO-RE relocations against target BTF information.
+//!
+//! The logic follows the one `libbpf` uses for performing CO-RE
+//! relocations when loading an object, but works without ever touching
+//! the object's instructions or the kernel.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::os::unix::ffi::OsStrExt as _;
+
+use super::ext::CoreReloRecord;
+use super::types::Array;
+use super::types::Composite;
+use super::types::Enum;
+use super::types::Enum64;
+use super::types::FuncProto;
+use super::types::Int;
+use super::types::IntEncoding;
+use super::types::MemberAttr;
+use super::Btf;
+use super::BtfKind;
+use super::BtfType;
+use super::HasSize as _;
+use super::ReferencesType as _;
+use super::TypeId;
+use crate::Error;
+use crate::Result;
+
+
+/// The maximum depth up to which types are compared for compatibility.
+const MAX_COMPAT_DEPTH: u32 = 32;
+
+
+/// The kind of a CO-RE relocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CoreReloKind {
+    /// `BPF_CORE_FIELD_BYTE_OFFSET`
+    FieldByteOffset,
+    /// `BPF_CORE_FIELD_BYTE_SIZE`
+    FieldByteSize,
+    /// `BPF_CORE_FIELD_EXISTS`
+    FieldExists,
+    /// `BPF_CORE_FIELD_SIGNED`
+    FieldSigned,
+    /// `BPF_CORE_FIELD_LSHIFT_U64`
+    FieldLShiftU64,
+    /// `BPF_CORE_FIELD_RSHIFT_U64`
+    FieldRShiftU64,
+    /// `BPF_CORE_TYPE_ID_LOCAL`
+    LocalTypeId,
+    /// `BPF_CORE_TYPE_ID_TARGET`
+    TargetTypeId,
+    /// `BPF_CORE_TYPE_EXISTS`
+    TypeExists,
+    /// `BPF_CORE_TYPE_SIZE`
+    TypeSize,
+    /// `BPF_CORE_ENUMVAL_EXISTS`
+    EnumValueExists,
+    /// `BPF_CORE_ENUMVAL_VALUE`
+    EnumValue,
+    /// `BPF_CORE_TYPE_MATCHES`
+    TypeMatches,
+}
+
+impl CoreReloKind {
+    fn from_raw(kind: u32) -> Option<Self> {
+        let kind = match kind {
+            0 => Self::FieldByteOffset,
+            1 => Self::FieldByteSize,
+            2 => Self::FieldExists,
+            3 => Self::FieldSigned,
+            4 => Self::FieldLShiftU64,
+            5 => Self::FieldRShiftU64,
+            6 => Self::LocalTypeId,
+            7 => Self::TargetTypeId,
+            8 => Self::TypeExists,
+            9 => Self::TypeSize,
+            10 => Self::EnumValueExists,
+            11 => Self::EnumValue,
+            12 => Self::TypeMatches,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Whether the relocation refers to a field.
+    pub fn is_field(&self) -> bool {
+        matches!(
+            self,
+            Self::FieldByteOffset
+                | Self::FieldByteSize
+                | Self::FieldExists
+                | Self::FieldSigned
+                | Self::FieldLShiftU64
+                | Self::FieldRShiftU64
+        )
+    }
+
+    /// Whether the relocation refers to a type as a whole.
+    pub fn is_type(&self) -> bool {
+        matches!(
+            self,
+            Self::LocalTypeId
+                | Self::TargetTypeId
+                | Self::TypeExists
+                | Self::TypeSize
+                | Self::TypeMatches
+        )
+    }
+
+    /// Whether the relocation refers to an enum value.
+    pub fn is_enum_value(&self) -> bool {
+        matches!(self, Self::EnumValueExists | Self::EnumValue)
+    }
+
+    /// Whether the relocation checks for the existence of a field,
+    /// type, or enum value.
+    ///
+    /// Such relocations never fail; their result is `0` if the entity
+    /// in question could not be matched.
+    pub fn is_existence_check(&self) -> bool {
+        matches!(
+            self,
+            Self::FieldExists | Self::TypeExists | Self::EnumValueExists | Self::TypeMatches
+        )
+    }
+}
+
+impl Display for CoreReloKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        // The names `libbpf` uses in its log output.
+        let name = match self {
+            Self::FieldByteOffset => "byte_off",
+            Self::FieldByteSize => "byte_sz",
+            Self::FieldExists => "field_exists",
+            Self::FieldSigned => "signed",
+            Self::FieldLShiftU64 => "lshift_u64",
+            Self::FieldRShiftU64 => "rshift_u64",
+            Self::LocalTypeId => "local_type_id",
+            Self::TargetTypeId => "target_type_id",
+            Self::TypeExists => "type_exists",
+            Self::TypeSize => "type_size",
+            Self::EnumValueExists => "enumval_exists",
+            Self::EnumValue => "enumval_value",
+            Self::TypeMatches => "type_matches",
+        };
+        f.write_str(name)
+    }
+}
+
+
+/// Retrieve the "essential" part of a type or enumerator name, i.e.,
+/// the name with any `___<flavor>` suffix removed.
+fn essential_name(name: &[u8]) -> &[u8] {
+    if name.len() >= 5 {
+        for i in (0..=name.len() - 5).rev() {
+            if name[i] != b'_' && &name[i + 1..i + 4] == b"___" && name[i + 4] != b'_' {
+                return &name[..=i]
+            }
+        }
+    }
+    name
+}
+
+/// Produce a human readable description of `ty`, for use in messages.
+pub(crate) fn describe_type(ty: &BtfType<'_>) -> String {
+    let kind = match ty.kind() {
+        BtfKind::Struct => "struct ",
+        BtfKind::Union => "union ",
+        BtfKind::Enum | BtfKind::Enum64 => "enum ",
+        BtfKind::Fwd => "fwd ",
+        _ => "",
+    };
+    match ty.name() {
+        Some(name) => format!("{kind}{}", name.to_string_lossy()),
+        None => format!("{kind}<anon #{}>", ty.type_id()),
+    }
+}
+
+/// Check whether the local field type `local` is compatible with the
+/// target field type `target`.
+fn fields_compat(local: BtfType<'_>, target: BtfType<'_>) -> bool {
+    let local = local.skip_mods_and_typedefs();
+    let target = target.skip_mods_and_typedefs();
+
+    if local.is_composite() && target.is_composite() {
+        return true
+    }
+    if !local.is_core_compat(&target) {
+        return false
+    }
+
+    match local.kind() {
+        BtfKind::Int | BtfKind::Ptr | BtfKind::Fwd | BtfKind::Float => true,
+        BtfKind::Enum | BtfKind::Enum64 => true,
+        BtfKind::Array => match (Array::try_from(local), Array::try_from(target)) {
+            (Ok(local), Ok(target)) => {
+                fields_compat(local.contained_type(), target.contained_type())
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Check whether the local type `local` is compatible with the target
+/// type `target`, recursing up to `depth` levels into referenced types.
+fn types_compat(local: BtfType<'_>, target: BtfType<'_>, depth: u32) -> bool {
+    if depth == 0 {
+        return false
+    }
+
+    let local = local.skip_mods_and_typedefs();
+    let target = target.skip_mods_and_typedefs();
+    if !local.is_core_compat(&target) {
+        return false
+    }
+
+    match local.kind() {
+        BtfKind::Void
+        | BtfKind::Int
+        | BtfKind::Float
+        | BtfKind::Struct
+        | BtfKind::Union
+        | BtfKind::Enum
+        | BtfKind::Enum64
+        | BtfKind::Fwd => true,
+        BtfKind::Ptr => match (local.next_type(), target.next_type()) {
+            (Some(local), Some(target)) => types_compat(local, target, depth - 1),
+            _ => false,
+        },
+        BtfKind::Array => match (Array::try_from(local), Array::try_from(target)) {
+            (Ok(local), Ok(target)) => {
+                types_compat(local.contained_type(), target.contained_type(), depth - 1)
+            }
+            _ => false,
+        },
+        BtfKind::FuncProto => {
+            let local_btf = local.source;
+            let target_btf = target.source;
+            let (Ok(local), Ok(target)) = (FuncProto::try_from(local), FuncProto::try_from(target))
+            else {
+                return false
+            };
+            if local.len() != target.len()
+                || !types_compat(local.referenced_type(), target.referenced_type(), depth - 1)
+            {
+                return false
+            }
+
+            (0..local.len()).all(|idx| {
+                let local = local
+                    .get(idx)
+                    .and_then(|param| local_btf.type_by_id::<BtfType<'_>>(param.ty));
+                let target = target
+                    .get(idx)
+                    .and_then(|param| target_btf.type_by_id::<BtfType<'_>>(param.ty));
+                match (local, target) {
+                    (Some(local), Some(target)) => types_compat(local, target, depth - 1),
+                    _ => false,
+                }
+            })
+        }
+        _ => false,
+    }
+}
+
+
+/// Check whether the local name `local` matches the target name
+/// `target`, ignoring flavor suffixes.
+fn names_match(local: Option<&OsStr>, target: Option<&OsStr>) -> bool {
+    let local = local.map(OsStr::as_bytes).unwrap_or_default();
+    let target = target.map(OsStr::as_bytes).unwrap_or_default();
+    if target.is_empty() {
+        return local.is_empty()
+    }
+    essential_name(local) == essential_name(target)
+}
+
+/// Retrieve the size and enumerator names of the enum `ty`.
+fn enum_names(ty: BtfType<'_>) -> Option<(usize, Vec<Option<&OsStr>>)> {
+    if let Ok(enum_) = Enum::try_from(ty) {
+        let names = (0..enum_.len())
+            .filter_map(|idx| enum_.get(idx))
+            .map(|value| value.name)
+            .collect();
+        Some((enum_.size(), names))
+    } else if let Ok(enum_) = Enum64::try_from(ty) {
+        let names = (0..enum_.len())
+            .filter_map(|idx| enum_.get(idx))
+            .map(|value| value.name)
+            .collect();
+        Some((enum_.size(), names))
+    } else {
+        None
+    }
+}
+
+/// Check whether every member of the local struct or union `local` has
+/// a matching member in the target struct or union `target`.
+fn composites_match(local: BtfType<'_>, target: BtfType<'_>, depth: u32) -> bool {
+    let local_btf = local.source;
+    let target_btf = target.source;
+    let (Ok(local), Ok(target)) = (Composite::try_from(local), Composite::try_from(target)) else {
+        return false
+    };
+    if local.len() > target.len() {
+        return false
+    }
+
+    (0..local.len()).all(|local_idx| {
+        let Some(local) = local.get(local_idx) else {
+            return false
+        };
+        let Some(local_ty) = local_btf.type_by_id::<BtfType<'_>>(local.ty) else {
+            return false
+        };
+        (0..target.len())
+            .filter_map(|target_idx| target.get(target_idx))
+            .filter(|target| names_match(local.name, target.name))
+            .any(|target| {
+                target_btf
+                    .type_by_id::<BtfType<'_>>(target.ty)
+                    .map(|target_ty| types_match(local_ty, target_ty, false, depth - 1))
+                    .unwrap_or(false)
+            })
+    })
+}
+
+/// Check whether the local type `local` matches the target type
+/// `target`, recursing up to `depth` levels into referenced types.
+///
+/// In contrast to [`types_compat`], names, struct and union members, as
+/// well as enumerators have to match, the same way `libbpf`'s
+/// `bpf_core_types_match` requires. `behind_ptr` indicates whether the
+/// types are referenced through a pointer, in which case a forward
+/// declaration suffices.
+fn types_match(local: BtfType<'_>, target: BtfType<'_>, behind_ptr: bool, depth: u32) -> bool {
+    if depth == 0 {
+        return false
+    }
+
+    let local = local.skip_mods_and_typedefs();
+    let target = target.skip_mods_and_typedefs();
+    if !names_match(local.name(), target.name()) {
+        return false
+    }
+
+    match local.kind() {
+        BtfKind::Void => target.kind() == BtfKind::Void,
+        BtfKind::Fwd => {
+            // The kind flag of a forward declaration is set for unions.
+            let is_union = local.kind_flag();
+            match target.kind() {
+                BtfKind::Fwd => is_union == target.kind_flag(),
+                BtfKind::Struct if behind_ptr => !is_union,
+                BtfKind::Union if behind_ptr => is_union,
+                _ => false,
+            }
+        }
+        BtfKind::Enum | BtfKind::Enum64 => {
+            let (Some((local_size, local_names)), Some((target_size, target_names))) =
+                (enum_names(local), enum_names(target))
+            else {
+                return false
+            };
+            local_size == target_size
+                && local_names.len() <= target_names.len()
+                && local_names.iter().all(|local| {
+                    target_names
+                        .iter()
+                        .any(|target| names_match(*local, *target))
+                })
+        }
+        BtfKind::Struct | BtfKind::Union => {
+            if behind_ptr {
+                local.kind() == target.kind()
+                    || (target.kind() == BtfKind::Fwd
+                        && target.kind_flag() == (local.kind() == BtfKind::Union))
+            } else {
+                local.kind() == target.kind() && composites_match(local, target, depth)
+            }
+        }
+        BtfKind::Int => match (Int::try_from(local), Int::try_from(target)) {
+            (Ok(local), Ok(target)) => {
+                local.size() == target.size()
+                    && matches!(local.encoding, IntEncoding::Signed)
+                        == matches!(target.encoding, IntEncoding::Signed)
+            }
+            _ => false,
+        },
+        BtfKind::Ptr => match (target.kind(), local.next_type(), target.next_type()) {
+            (BtfKind::Ptr, Some(local), Some(target)) => {
+                types_match(local, target, true, depth - 1)
+            }
+            _ => false,
+        },
+        BtfKind::Array => match (Array::try_from(local), Array::try_from(target)) {
+            (Ok(local), Ok(target)) => {
+                local.capacity() == target.capacity()
+                    && types_match(
+                        local.contained_type(),
+                        target.contained_type(),
+                        behind_ptr,
+                        depth - 1,
+                    )
+            }
+            _ => false,
+        },
+        BtfKind::FuncProto => {
+            let local_btf = local.source;
+            let target_btf = target.source;
+            let (Ok(local), Ok(target)) = (FuncProto::try_from(local), FuncProto::try_from(target))
+            else {
+                return false
+            };
+            if local.len() != target.len() {
+                return false
+            }
+
+            let params_match = (0..local.len()).all(|idx| {
+                let local = local
+                    .get(idx)
+                    .and_then(|param| local_btf.type_by_id::<BtfType<'_>>(param.ty));
+                let target = target
+                    .get(idx)
+                    .and_then(|param| target_btf.type_by_id::<BtfType<'_>>(param.ty));
+                match (local, target) {
+                    (Some(local), Some(target)) => {
+                        types_match(local, target, behind_ptr, depth - 1)
+                    }
+                    _ => false,
+                }
+            });
+            params_match
+                && types_match(
+                    local.referenced_type(),
+                    target.referenced_type(),
+                    behind_ptr,
+                    depth - 1,
+                )
+        }
+        _ => false,
+    }
+}
+
+
+/// An access step of a field relocation, as described by the local
+/// BTF information.
+#[derive(Clone, Copy, Debug)]
+enum LocalStep<'btf> {
+    /// Access of a struct or union member.
+    Member {
+        /// The member's name, if it is not anonymous.
+        name: Option<&'btf OsStr>,
+        /// The member's type.
+        ty: BtfType<'btf>,
+    },
+    /// Access of an array element.
+    Index(u32),
+}
+
+
+/// A CO-RE relocation, interpreted using the local BTF information of
+/// the object containing it.
+#[derive(Clone, Debug)]
+pub(crate) struct CoreRelo<'btf> {
+    /// The kind of the relocation.
+    pub kind: CoreReloKind,
+    /// The relocation's root type.
+    pub root: BtfType<'btf>,
+    /// The parsed access string.
+    access: Vec<u32>,
+    /// The access steps of a field relocation.
+    steps: Vec<LocalStep<'btf>>,
+    /// The name of the enumerator referred to by an enum value
+    /// relocation.
+    enumerator: Option<&'btf OsStr>,
+}
+
+impl<'btf> CoreRelo<'btf> {
+    /// Interpret the relocation `record` using the object's BTF
+    /// information `local`.
+    pub fn new(local: &'btf Btf<'btf>, record: &CoreReloRecord) -> Result<Self> {
+        let kind = CoreReloKind::from_raw(record.kind).ok_or_else(|| {
+            Error::with_invalid_data(format!("unknown CO-RE relocation kind {}", record.kind))
+        })?;
+        let root = local
+            .type_by_id::<BtfType<'_>>(record.type_id.into())
+            .ok_or_else(|| {
+                Error::with_invalid_data(format!(
+                    "CO-RE relocation refers to invalid type {}",
+                    record.type_id
+                ))
+            })?;
+        let access_str = local
+            .name_at(record.access_str_off)
+            .and_then(OsStr::to_str)
+            .ok_or_else(|| Error::with_invalid_data("CO-RE relocation has invalid access string"))?
+            .to_string();
+        let access = access_str
+            .split(':')
+            .map(str::parse::<u32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| {
+                Error::with_invalid_data(format!("invalid CO-RE access string `{access_str}`"))
+            })?;
+        let invalid_access = || {
+            Error::with_invalid_data(format!(
+                "invalid CO-RE access string `{access_str}` for `{}`",
+                describe_type(&root)
+            ))
+        };
+
+        let mut steps = Vec::new();
+        let mut enumerator = None;
+        if kind.is_field() {
+            let mut ty = root.skip_mods_and_typedefs();
+            for idx in &access[1..] {
+                if let Ok(composite) = Composite::try_from(ty) {
+                    let member = composite.get(*idx as usize).ok_or_else(invalid_access)?;
+                    let member_ty = local
+                        .type_by_id::<BtfType<'_>>(member.ty)
+                        .ok_or_else(invalid_access)?;
+                    let () = steps.push(LocalStep::Member {
+                        name: member.name,
+                        ty: member_ty,
+                    });
+                    ty = member_ty.skip_mods_and_typedefs();
+                } else if let Ok(array) = Array::try_from(ty) {
+                    let () = steps.push(LocalStep::Index(*idx));
+                    ty = array.contained_type().skip_mods_and_typedefs();
+                } else {
+                    return Err(invalid_access())
+                }
+            }
+        } else if kind.is_enum_value() {
+            let ty = root.skip_mods_and_typedefs();
+            let idx = access[0] as usize;
+            let name = if let Ok(enum_) = Enum::try_from(ty) {
+                enum_.get(idx).and_then(|value| value.name)
+            } else if let Ok(enum_) = Enum64::try_from(ty) {
+                enum_.get(idx).and_then(|value| value.name)
+            } else {
+                None
+            };
+            enumerator = Some(name.ok_or_else(invalid_access)?);
+        }
+
+        let slf = Self {
+            kind,
+            root,
+            access,
+            steps,
+            enumerator,
+        };
+        Ok(slf)
+    }
+
+    /// Produce a human readable description of the entity the
+    /// relocation refers to, e.g., `struct task_struct.pid (0:1)`.
+    pub fn describe(&self) -> String {
+        let mut desc = describe_type(&self.root);
+        if self.kind.is_field() {
+            if self.access[0] != 0 {
+                desc += &format!("[{}]", self.access[0]);
+            }
+            for step in &self.steps {
+                match step {
+                    LocalStep::Member {
+                        name: Some(name), ..
+                    } => desc += &format!(".{}", name.to_string_lossy()),
+                    LocalStep::Member { name: None, .. } => (),
+                    LocalStep::Index(idx) => desc += &format!("[{idx}]"),
+                }
+            }
+        } else if let Some(enumerator) = self.enumerator {
+            desc += &format!("::{}", enumerator.to_string_lossy());
+        }
+
+        let access = self
+            .access
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(":");
+        format!("{desc} ({access})")
+    }
+}
+
+
+/// A match of a CO-RE relocation in target BTF information.
+#[derive(Clone, Debug)]
+pub(crate) struct TargetMatch {
+    /// The matched target root type.
+    pub root: TypeId,
+    /// The struct and union members traversed to reach a field, as
+    /// pairs of the composite type and the index of the member in it.
+    pub members: Vec<(TypeId, usize)>,
+    /// The value the relocated instruction would be patched with.
+    pub value: u64,
+}
+
+
+/// The outcome of matching a CO-RE relocation against target BTF
+/// information.
+#[derive(Clone, Debug)]
+pub(crate) enum Resolution {
+    /// The relocation matched one or more candidate types, all of which
+    /// agree on the relocated value.
+    Matched(Vec<TargetMatch>),
+    /// The relocation could not be matched, for the provided reason.
+    Unmatched(String),
+}
+
+
+/// Target BTF information, indexed for looking up CO-RE relocation
+/// candidates.
+pub(crate) struct CoreTarget<'btf> {
+    btf: &'btf Btf<'btf>,
+    /// Named types, by their essential name.
+    candidates: HashMap<&'btf [u8], Vec<BtfType<'btf>>>,
+}
+
+impl<'btf> CoreTarget<'btf> {
+    /// Index the types of `btf`.
+    pub fn new(btf: &'btf Btf<'btf>) -> Self {
+        let mut candidates = HashMap::<_, Vec<_>>::new();
+        for id in 1..btf.len() as u32 {
+            let Some(ty) = btf.type_by_id::<BtfType<'_>>(id.into()) else {
+                continue
+            };
+            if let Some(name) = ty.name() {
+                let () = candidates
+                    .entry(essential_name(name.as_bytes()))
+                    .or_default()
+                    .push(ty);
+            }
+        }
+
+        Self { btf, candidates }
+    }
+
+    /// Match `relo` against the target BTF information.
+    pub fn resolve(&self, relo: &CoreRelo<'_>) -> Resolution {
+        if relo.kind == CoreReloKind::LocalTypeId {
+            return Resolution::Matched(Vec::new())
+        }
+
+        let Some(name) = relo.root.name() else {
+            return Resolution::Unmatched(format!(
+                "root type `{}` is anonymous",
+                describe_type(&relo.root)
+            ))
+        };
+        let candidates = self
+            .candidates
+            .get(essential_name(name.as_bytes()))
+            .into_iter()
+            .flatten()
+            .filter(|candidate| candidate.is_core_compat(&relo.root))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Resolution::Unmatched(format!(
+                "no candidate for `{}` found",
+                describe_type(&relo.root)
+            ))
+        }
+
+        let mut matches = Vec::new();
+        let mut reason = None;
+        for candidate in candidates {
+            let result = if relo.kind.is_field() {
+                self.match_field(relo, *candidate)
+            } else if relo.kind.is_type() {
+                self.match_type(relo, *candidate)
+            } else {
+                self.match_enum_value(relo, *candidate)
+            };
+
+            match result {
+                Ok(match_) => matches.push(match_),
+                Err(err) => reason = Some(err),
+            }
+        }
+
+        if matches.is_empty() {
+            // SANITY: We had at least one candidate, so we either have a
+            //         match or a reason for the mismatch.
+            return Resolution::Unmatched(reason.unwrap())
+        }
+
+        if matches
+            .iter()
+            .any(|match_| match_.value != matches[0].value)
+        {
+            return Resolution::Unmatched(format!(
+                "candidates for `{}` disagree on relocated value",
+                describe_type(&relo.root)
+            ))
+        }
+        Resolution::Matched(matches)
+    }
+
+    /// Determine the size of `ty`.
+    fn size(&self, ty: &BtfType<'_>) -> Result<u64, String> {
+        let size = unsafe {
+            // SAFETY: The btf pointer is valid.
+            libbpf_sys::btf__resolve_size(self.btf.ptr.as_ptr(), ty.type_id().into())
+        };
+        u64::try_from(size)
+            .map_err(|_| format!("failed to determine size of `{}`", describe_type(ty)))
+    }
+
+    /// Find the member called `name` in `composite`, descending into
+    /// anonymous members as necessary.
+    ///
+    /// On success, the traversed members, the bit offset of the member,
+    /// its bitfield size (zero if it is not a bitfield), and its type are
+    /// returned.
+    #[allow(clippy::type_complexity)]
+    fn find_member(
+        &self,
+        composite: &Composite<'btf>,
+        name: &OsStr,
+    ) -> Option<(Vec<(TypeId, usize)>, u64, u8, BtfType<'btf>)> {
+        for idx in 0..composite.len() {
+            let member = composite.get(idx)?;
+            let (offset, bit_size) = match member.attr {
+                MemberAttr::Normal { offset } => (u64::from(offset), 0),
+                MemberAttr::BitField { offset, size } => (u64::from(offset), size),
+            };
+            let member_ty = self.btf.type_by_id::<BtfType<'_>>(member.ty)?;
+
+            match member.name {
+                Some(member_name) if member_name == name => {
+                    return Some((
+                        vec![(composite.type_id(), idx)],
+                        offset,
+                        bit_size,
+                        member_ty,
+                    ))
+                }
+                Some(_) => (),
+                None => {
+                    let Ok(inner) = Composite::try_from(member_ty.skip_mods_and_typedefs()) else {
+                        continue
+                    };
+                    if let Some((mut path, inner_offset, bit_size, ty)) =
+                        self.find_member(&inner, name)
+                    {
+                        let () = path.insert(0, (composite.type_id(), idx));
+                        return Some((path, offset + inner_offset, bit_size, ty))
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Calculate the value of the field relocation of kind `kind` for
+    /// an array element or the root type, located at `bit_offset` and of
+    /// type `ty`.
+    fn calc_elem_relo(
+        &self,
+        kind: CoreReloKind,
+        bit_offset: u64,
+        ty: &BtfType<'_>,
+    ) -> Result<u64, String> {
+        match kind {
+            CoreReloKind::FieldByteOffset => Ok(bit_offset / 8),
+            CoreReloKind::FieldByteSize => self.size(ty),
+            CoreReloKind::FieldExists => Ok(1),
+            CoreReloKind::FieldSigned
+            | CoreReloKind::FieldLShiftU64
+            | CoreReloKind::FieldRShiftU64 => Err(format!(
+                "`{kind}` relocation can't be applied to array access"
+            )),
+            CoreReloKind::LocalTypeId
+            | CoreReloKind::TargetTypeId
+            | CoreReloKind::TypeExists
+            | CoreReloKind::TypeSize
+            | CoreReloKind::EnumValueExists
+            | CoreReloKind::EnumValue
+            | CoreReloKind::TypeMatches => unreachable!(),
+        }
+    }
+
+    /// Calculate the value of the field relocation of kind `kind` for a
+    /// struct or union member located at `bit_offset`, with a bitfield
+    /// size of `bit_size` (zero if it is not a bitfield) and of type
+    /// `ty`, the same way `libbpf`'s `bpf_core_calc_field_relo` does.
+    fn calc_member_relo(
+        &self,
+        kind: CoreReloKind,
+        bit_offset: u64,
+        bit_size: u8,
+        ty: &BtfType<'_>,
+    ) -> Result<u64, String> {
+        let (byte_offset, byte_size, bit_size) = if bit_size > 0 {
+            let bit_size = u64::from(bit_size);
+            let mut byte_size = self.size(ty)?;
+            if byte_size == 0 {
+                return Err(format!("bitfield of `{}` has no size", describe_type(ty)))
+            }
+            let mut byte_offset = bit_offset / 8 / byte_size * byte_size;
+            // Find the smallest load size covering the whole bitfield.
+            while bit_offset + bit_size - byte_offset * 8 > byte_size * 8 {
+                if byte_size >= 8 {
+                    return Err(format!(
+                        "bitfield at bit offset {bit_offset} can't be read with a 64 bit load"
+                    ))
+                }
+                byte_size *= 2;
+                byte_offset = bit_offset / 8 / byte_size * byte_size;
+            }
+            (byte_offset, byte_size, bit_size)
+        } else {
+            let byte_size = self.size(ty)?;
+            (bit_offset / 8, byte_size, byte_size * 8)
+        };
+
+        let value = match kind {
+            CoreReloKind::FieldByteOffset => byte_offset,
+            CoreReloKind::FieldByteSize => byte_size,
+            CoreReloKind::FieldExists => 1,
+            CoreReloKind::FieldSigned => {
+                let ty = ty.skip_mods_and_typedefs();
+                let signed = if ty.is_any_enum() {
+                    ty.kind_flag()
+                } else if let Ok(int) = Int::try_from(ty) {
+                    matches!(int.encoding, IntEncoding::Signed)
+                } else {
+                    false
+                };
+                u64::from(signed)
+            }
+            CoreReloKind::FieldLShiftU64 => {
+                if cfg!(target_endian = "little") {
+                    64 - (bit_offset + bit_size - byte_offset * 8)
+                } else {
+                    (8 - byte_size) * 8 + (bit_offset - byte_offset * 8)
+                }
+            }
+            CoreReloKind::FieldRShiftU64 => 64 - bit_size,
+            CoreReloKind::LocalTypeId
+            | CoreReloKind::TargetTypeId
+            | CoreReloKind::TypeExists
+            | CoreReloKind::TypeSize
+            | CoreReloKind::EnumValueExists
+            | CoreReloKind::EnumValue
+            | CoreReloKind::TypeMatches => unreachable!(),
+        };
+        Ok(value)
+    }
+
+    fn match_field(
+        &self,
+        relo: &CoreRelo<'_>,
+        candidate: BtfType<'btf>,
+    ) -> Result<TargetMatch, String> {
+        let mut ty = candidate.skip_mods_and_typedefs();
+        let mut bit_offset = u64::from(relo.access[0]) * self.size(&ty)? * 8;
+        let mut members = Vec::new();
+        // The bitfield size of the last accessed member, if the last
+        // access step was a member access.
+        let mut member_bit_size = None;
+
+        for step in &relo.steps {
+            match step {
+                // Anonymous members are transparently traversed when
+                // looking up the next named one.
+                LocalStep::Member { name: None, .. } => (),
+                LocalStep::Member {
+                    name: Some(name),
+                    ty: local_ty,
+                } => {
+                    let Ok(composite) = Composite::try_from(ty) else {
+                        return Err(format!("`{}` is not a struct or union", describe_type(&ty)))
+                    };
+                    let (path, offset, bit_size, member_ty) =
+                        self.find_member(&composite, name).ok_or_else(|| {
+                            format!(
+                                "`{}` has no member `{}`",
+                                describe_type(&ty),
+                                name.to_string_lossy()
+                            )
+                        })?;
+                    if !fields_compat(*local_ty, member_ty) {
+                        return Err(format!(
+                            "member `{}` of `{}` has an incompatible type",
+                            name.to_string_lossy(),
+                            describe_type(&ty)
+                        ))
+                    }
+                    let () = members.extend(path);
+                    bit_offset += offset;
+                    member_bit_size = Some(bit_size);
+                    ty = member_ty.skip_mods_and_typedefs();
+                }
+                LocalStep::Index(idx) => {
+                    let Ok(array) = Array::try_from(ty) else {
+                        return Err(format!("`{}` is not an array", describe_type(&ty)))
+                    };
+                    // Flexible arrays have a capacity of zero.
+                    if *idx as usize >= array.capacity() && array.capacity() != 0 {
+                        return Err(format!(
+                            "array index {idx} is out of bounds for `{}`",
+                            describe_type(&ty)
+                        ))
+                    }
+                    let elem = array.contained_type().skip_mods_and_typedefs();
+                    bit_offset += u64::from(*idx) * self.size(&elem)? * 8;
+                    member_bit_size = None;
+                    ty = elem;
+                }
+            }
+        }
+
+        let value = match member_bit_size {
+            Some(bit_size) => self.calc_member_relo(relo.kind, bit_offset, bit_size, &ty)?,
+            None => self.calc_elem_relo(relo.kind, bit_offset, &ty)?,
+        };
+
+        Ok(TargetMatch {
+            root: candidate.type_id(),
+            members,
+            value,
+        })
+    }
+
+    fn match_type(
+        &self,
+        relo: &CoreRelo<'_>,
+        candidate: BtfType<'btf>,
+    ) -> Result<TargetMatch, String> {
+        let is_match = if relo.kind == CoreReloKind::TypeMatches {
+            types_match(relo.root, candidate, false, MAX_COMPAT_DEPTH)
+        } else {
+            types_compat(relo.root, candidate, MAX_COMPAT_DEPTH)
+        };
+        if !is_match {
+            return Err(format!(
+                "`{}` does not match its local definition",
+                describe_type(&candidate)
+            ))
+        }
+
+        let value = match relo.kind {
+            CoreReloKind::TargetTypeId => u32::from(candidate.type_id()).into(),
+            CoreReloKind::TypeSize => self.size(&candidate)?,
+            CoreReloKind::TypeExists | CoreReloKind::TypeMatches => 1,
+            CoreReloKind::FieldByteOffset
+            | CoreReloKind::FieldByteSize
+            | CoreReloKind::FieldExists
+            | CoreReloKind::FieldSigned
+            | CoreReloKind::FieldLShiftU64
+            | CoreReloKind::FieldRShiftU64
+            | CoreReloKind::LocalTypeId
+            | CoreReloKind::EnumValueExists
+            | CoreReloKind::EnumValue => unreachable!(),
+        };
+
+        Ok(TargetMatch {
+            root: candidate.type_id(),
+            members: Vec::new(),
+            value,
+        })
+    }
+
+    fn match_enum_value(
+        &self,
+        relo: &CoreRelo<'_>,
+        candidate: BtfType<'btf>,
+    ) -> Result<TargetMatch, String> {
+        // SANITY: Enum value relocations always have an enumerator.
+        let name = essential_name(relo.enumerator.unwrap().as_bytes());
+        let is_match = |value_name: Option<&OsStr>| {
+            value_name
+                .map(|value_name| essential_name(value_name.as_bytes()) == name)
+                .unwrap_or(false)
+        };
+
+        let ty = candidate.skip_mods_and_typedefs();
+        let value = if let Ok(enum_) = Enum::try_from(ty) {
+            (0..enum_.len())
+                .filter_map(|idx| enum_.get(idx))
+                .find(|value| is_match(value.name))
+                .map(|value| u64::from(value.value as u32))
+        } else if let Ok(enum_) = Enum64::try_from(ty) {
+            (0..enum_.len())
+                .filter_map(|idx| enum_.get(idx))
+                .find(|value| is_match(value.name))
+                .map(|value| value.value)
+        } else {
+            None
+        };
+
+        let value = value.ok_or_else(|| {
+            format!(
+                "`{}` has no value `{}`",
+                describe_type(&ty),
+                String::from_utf8_lossy(name)
+            )
+        })?;
+        let value = match relo.kind {
+            CoreReloKind::EnumValue => value,
+            _ => 1,
+        };
+
+        Ok(TargetMatch {
+            root: candidate.type_id(),
+            members: Vec::new(),
+            value,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CString;
+    use std::marker::PhantomData;
+    use std::ptr::NonNull;
+
+    use crate::btf::DropPolicy;
+
+
+    /// Create BTF information by invoking `add_types` on an empty
+    /// `struct btf`.
+    fn new_btf<F>(add_types: F) -> Btf<'static>
+    where
+        F: FnOnce(*mut libbpf_sys::btf),
+    {
+        let ptr = NonNull::new(unsafe { libbpf_sys::btf__new_empty() }).unwrap();
+        let () = add_types(ptr.as_ptr());
+        Btf {
+            ptr,
+            drop_policy: DropPolicy::SelfPtrOnly,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Add a struct or union member to the most recently added type.
+    fn add_field(btf: *mut libbpf_sys::btf, name: &str, ty: i32, bit_off: u32, bit_sz: u32) {
+        let name = CString::new(name).unwrap();
+        let rc = unsafe { libbpf_sys::btf__add_field(btf, name.as_ptr(), ty, bit_off, bit_sz) };
+        assert_eq!(rc, 0);
+    }
+
+    /// Add `int` and `unsigned int` types, with IDs 1 and 2,
+    /// respectively.
+    fn add_ints(btf: *mut libbpf_sys::btf) {
+        let int = CString::new("int").unwrap();
+        let uint = CString::new("unsigned int").unwrap();
+        let signed = libbpf_sys::BTF_INT_SIGNED as _;
+        assert_eq!(
+            unsafe { libbpf_sys::btf__add_int(btf, int.as_ptr(), 4, signed) },
+            1
+        );
+        assert_eq!(
+            unsafe { libbpf_sys::btf__add_int(btf, uint.as_ptr(), 4, 0) },
+            2
+        );
+    }
+
+    /// Add an enum called `e` with the provided enumerators.
+    fn add_enum(btf: *mut libbpf_sys::btf, enumerators: &[&str]) -> i32 {
+        let name = CString::new("e").unwrap();
+        let id = unsafe { libbpf_sys::btf__add_enum(btf, name.as_ptr(), 4) };
+        for (value, enumerator) in enumerators.iter().enumerate() {
+            let enumerator = CString::new(*enumerator).unwrap();
+            let rc =
+                unsafe { libbpf_sys::btf__add_enum_value(btf, enumerator.as_ptr(), value as _) };
+            assert_eq!(rc, 0);
+        }
+        id
+    }
+
+    /// Add a struct called `s` of size `size`.
+    fn add_struct(btf: *mut libbpf_sys::btf, size: u32) -> i32 {
+        let name = CString::new("s").unwrap();
+        unsafe { libbpf_sys::btf__add_struct(btf, name.as_ptr(), size) }
+    }
+
+    /// Resolve a relocation of kind `kind` with access string `access`
+    /// for the local type with ID `type_id`.
+    fn resolve_relo<L, T>(
+        add_local: L,
+        add_target: T,
+        type_id: u32,
+        kind: CoreReloKind,
+        access: &str,
+    ) -> Result<u64, String>
+    where
+        L: FnOnce(*mut libbpf_sys::btf),
+        T: FnOnce(*mut libbpf_sys::btf),
+    {
+        let access = CString::new(access).unwrap();
+        let mut access_str_off = 0;
+        let local = new_btf(|btf| {
+            let () = add_local(btf);
+            access_str_off = unsafe { libbpf_sys::btf__add_str(btf, access.as_ptr()) } as u32;
+        });
+        let target = new_btf(add_target);
+
+        // SANITY: All relocation kinds have a raw representation.
+        let kind = (0..)
+            .find(|raw| CoreReloKind::from_raw(*raw) == Some(kind))
+            .unwrap();
+        let record = CoreReloRecord {
+            insn_off: 0,
+            type_id,
+            access_str_off,
+            kind,
+        };
+        let relo = CoreRelo::new(&local, &record).unwrap();
+        match CoreTarget::new(&target).resolve(&relo) {
+            Resolution::Matched(matches) => Ok(matches[0].value),
+            Resolution::Unmatched(reason) => Err(reason),
+        }
+    }
+
+    /// Resolve a field relocation of kind `kind` with access string
+    /// `access` for `struct s { int x; int sb:3; unsigned int ub:5; }`,
+    /// against a target in which all fields are located at different
+    /// offsets.
+    fn resolve_bitfield_relo(kind: CoreReloKind, access: &str) -> Result<u64, String> {
+        let add_local = |btf| {
+            let () = add_ints(btf);
+            assert_eq!(add_struct(btf, 8), 3);
+            let () = add_field(btf, "x", 1, 0, 0);
+            let () = add_field(btf, "sb", 1, 32, 3);
+            let () = add_field(btf, "ub", 2, 35, 5);
+        };
+        let add_target = |btf| {
+            let () = add_ints(btf);
+            assert_eq!(add_struct(btf, 16), 3);
+            let () = add_field(btf, "pad", 1, 0, 0);
+            let () = add_field(btf, "pad2", 1, 32, 0);
+            let () = add_field(btf, "x", 1, 64, 0);
+            let () = add_field(btf, "sb", 1, 100, 3);
+            let () = add_field(btf, "ub", 2, 120, 5);
+        };
+        resolve_relo(add_local, add_target, 3, kind, access)
+    }
+
+    /// Check that we calculate byte offsets of regular and bitfield
+    /// members correctly.
+    #[test]
+    fn field_byte_offset() {
+        let kind = CoreReloKind::FieldByteOffset;
+        assert_eq!(resolve_bitfield_relo(kind, "0:0"), Ok(8));
+        assert_eq!(resolve_bitfield_relo(kind, "0:1"), Ok(12));
+        assert_eq!(resolve_bitfield_relo(kind, "0:2"), Ok(12));
+    }
+
+    /// Check that we calculate byte sizes of regular and bitfield
+    /// members correctly.
+    #[test]
+    fn field_byte_size() {
+        let kind = CoreReloKind::FieldByteSize;
+        assert_eq!(resolve_bitfield_relo(kind, "0"), Ok(16));
+        assert_eq!(resolve_bitfield_relo(kind, "0:0"), Ok(4));
+        assert_eq!(resolve_bitfield_relo(kind, "0:1"), Ok(4));
+        assert_eq!(resolve_bitfield_relo(kind, "0:2"), Ok(4));
+    }
+
+    /// Check that existing members are reported as such.
+    #[test]
+    fn field_exists() {
+        let kind = CoreReloKind::FieldExists;
+        assert_eq!(resolve_bitfield_relo(kind, "0:0"), Ok(1));
+        assert_eq!(resolve_bitfield_relo(kind, "0:1"), Ok(1));
+        assert_eq!(resolve_bitfield_relo(kind, "0:2"), Ok(1));
+    }
+
+    /// Check that we report the signedness of members correctly.
+    #[test]
+    fn field_signed() {
+        let kind = CoreReloKind::FieldSigned;
+        assert_eq!(resolve_bitfield_relo(kind, "0:0"), Ok(1));
+        assert_eq!(resolve_bitfield_relo(kind, "0:1"), Ok(1));
+        assert_eq!(resolve_bitfield_relo(kind, "0:2"), Ok(0));
+        assert!(resolve_bitfield_relo(kind, "0").is_err());
+    }
+
+    /// Check that we calculate left shifts for extracting members from
+    /// a 64 bit load correctly.
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn field_lshift_u64() {
+        let kind = CoreReloKind::FieldLShiftU64;
+        assert_eq!(resolve_bitfield_relo(kind, "0:0"), Ok(32));
+        assert_eq!(resolve_bitfield_relo(kind, "0:1"), Ok(57));
+        assert_eq!(resolve_bitfield_relo(kind, "0:2"), Ok(35));
+        assert!(resolve_bitfield_relo(kind, "0").is_err());
+    }
+
+    /// Check that we calculate right shifts for extracting members from
+    /// a 64 bit load correctly.
+    #[test]
+    fn field_rshift_u64() {
+        let kind = CoreReloKind::FieldRShiftU64;
+        assert_eq!(resolve_bitfield_relo(kind, "0:0"), Ok(32));
+        assert_eq!(resolve_bitfield_relo(kind, "0:1"), Ok(61));
+        assert_eq!(resolve_bitfield_relo(kind, "0:2"), Ok(59));
+        assert!(resolve_bitfield_relo(kind, "0").is_err());
+    }
+
+
+    /// Check that we strip flavor suffixes from names correctly.
+    #[test]
+    fn essential_names() {
+        assert_eq!(essential_name(b"task_struct"), b"task_struct");
+        assert_eq!(essential_name(b"task_struct___pre_5_14"), b"task_struct");
+        assert_eq!(essential_name(b"a___b___c"), b"a___b");
+        assert_eq!(essential_name(b"__x___y"), b"__x");
+        assert_eq!(essential_name(b"x____y"), b"x____y");
+        assert_eq!(essential_name(b"x___"), b"x___");
+    }
+
+    /// Check that `type_matches` relocations require struct members to
+    /// match by name and type, while `type_exists` ones don't.
+    #[test]
+    fn type_matches_struct() {
+        let add_local = |btf| {
+            let () = add_ints(btf);
+            assert_eq!(add_struct(btf, 4), 3);
+            let () = add_field(btf, "x", 1, 0, 0);
+        };
+        let add_target = |members: &'static [(&'static str, i32)]| {
+            move |btf| {
+                let () = add_ints(btf);
+                assert_eq!(add_struct(btf, 8), 3);
+                for (idx, (name, ty)) in members.iter().enumerate() {
+                    let () = add_field(btf, name, *ty, idx as u32 * 32, 0);
+                }
+            }
+        };
+        let resolve = |kind, members| resolve_relo(add_local, add_target(members), 3, kind, "0");
+
+        let matches = CoreReloKind::TypeMatches;
+        let exists = CoreReloKind::TypeExists;
+        assert_eq!(resolve(matches, &[("y", 1), ("x", 1)]), Ok(1));
+        assert!(resolve(matches, &[("y", 1), ("z", 1)]).is_err());
+        assert_eq!(resolve(exists, &[("y", 1), ("z", 1)]), Ok(1));
+        assert!(resolve(matches, &[("x", 2)]).is_err());
+        assert_eq!(resolve(exists, &[("x", 2)]), Ok(1));
+    }
+
+    /// Check that `type_matches` relocations require all local
+    /// enumerators to be present in the target, while `type_exists`
+    /// ones don't.
+    #[test]
+    fn type_matches_enum() {
+        let add_local = |btf| assert_eq!(add_enum(btf, &["A", "B"]), 1);
+        let resolve = |kind, enumerators: &'static [&'static str]| {
+            let add_target = move |btf| assert_eq!(add_enum(btf, enumerators), 1);
+            resolve_relo(add_local, add_target, 1, kind, "0")
+        };
+
+        let matches = CoreReloKind::TypeMatches;
+        let exists = CoreReloKind::TypeExists;
+        assert_eq!(resolve(matches, &["C", "B", "A"]), Ok(1));
+        assert!(resolve(matches, &["A", "C"]).is_err());
+        assert!(resolve(matches, &["A"]).is_err());
+        assert_eq!(resolve(exists, &["A", "C"]), Ok(1));
+    }
+}
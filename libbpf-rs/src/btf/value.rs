@@ -0,0 +1,342 @@
+use std::io;
+
+use super::types;
+use super::Btf;
+use super::BtfKind;
+use super::BtfType;
+use super::ReferencesType as _;
+use super::TypeId;
+use crate::Error;
+use crate::Result;
+
+
+/// A value decoded from raw bytes according to its BTF type
+/// information.
+///
+/// See [`Btf::decode_value`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum BtfValue {
+    /// A boolean.
+    Bool(bool),
+    /// A character.
+    Char(u8),
+    /// A signed integer.
+    Int(i128),
+    /// An unsigned integer.
+    Uint(u128),
+    /// A floating point number.
+    Float(f64),
+    /// A pointer.
+    Ptr(u64),
+    /// An enum value, along with the name of the matching variant, if
+    /// any.
+    Enum {
+        /// The name of the variant matching the value.
+        name: Option<String>,
+        /// The numeric value.
+        value: i128,
+    },
+    /// An array.
+    Array(Vec<BtfValue>),
+    /// A struct, as a list of named members. Anonymous members have an
+    /// empty name.
+    Struct(Vec<(String, BtfValue)>),
+    /// A union, with all members decoded.
+    Union(Vec<(String, BtfValue)>),
+}
+
+impl BtfValue {
+    /// Retrieve the member with the given name of a struct or union.
+    pub fn field(&self, name: &str) -> Option<&BtfValue> {
+        match self {
+            Self::Struct(members) | Self::Union(members) => members
+                .iter()
+                .find(|(member, _)| member == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+
+/// Extract `bits` bits starting at bit `bit_offset` from `data`.
+fn read_bits(data: &[u8], bit_offset: usize, bits: usize) -> Result<u128> {
+    if cfg!(target_endian = "big") {
+        return Err(Error::with_io_error(
+            io::ErrorKind::Unsupported,
+            "decoding of bitfields is only supported on little endian systems",
+        ))
+    }
+
+    let start = bit_offset / 8;
+    let end = (bit_offset + bits + 7) / 8;
+    let bytes = data
+        .get(start..end)
+        .ok_or_else(|| Error::with_invalid_data("bitfield exceeds data"))?;
+    if bytes.len() > 16 {
+        return Err(Error::with_invalid_data("bitfield is too large"))
+    }
+    let mut buf = [0; 16];
+    let () = buf[..bytes.len()].copy_from_slice(bytes);
+    let value = u128::from_le_bytes(buf) >> (bit_offset % 8);
+    let mask = if bits >= 128 {
+        u128::MAX
+    } else {
+        (1 << bits) - 1
+    };
+    Ok(value & mask)
+}
+
+/// Read an unsigned integer of `data.len()` bytes.
+fn read_uint(data: &[u8]) -> Result<u128> {
+    let mut buf = [0; 16];
+    match data.len() {
+        len @ (1 | 2 | 4 | 8 | 16) => {
+            if cfg!(target_endian = "big") {
+                let () = buf[16 - len..].copy_from_slice(data);
+                Ok(u128::from_be_bytes(buf))
+            } else {
+                let () = buf[..len].copy_from_slice(data);
+                Ok(u128::from_le_bytes(buf))
+            }
+        }
+        len => Err(Error::with_invalid_data(format!(
+            "unsupported integer size {len}"
+        ))),
+    }
+}
+
+/// Sign extend the lower `bits` bits of `value`.
+fn sign_extend(value: u128, bits: usize) -> i128 {
+    let shift = 128 - bits;
+    ((value << shift) as i128) >> shift
+}
+
+
+impl Btf<'_> {
+    /// Retrieve the size of the type with the given ID, in bytes.
+    pub fn type_size(&self, ty: TypeId) -> Result<usize> {
+        let ret = unsafe { libbpf_sys::btf__resolve_size(self.ptr.as_ptr(), ty.into()) };
+        if ret < 0 {
+            return Err(Error::from_raw_os_error(-ret as i32))
+        }
+        Ok(ret as usize)
+    }
+
+    /// Decode `data` as a value of the type with the given ID.
+    ///
+    /// `data` has to contain at least [`type_size`][Self::type_size]
+    /// bytes.
+    pub fn decode_value(&self, ty: TypeId, data: &[u8]) -> Result<BtfValue> {
+        let ty = self
+            .type_by_id::<BtfType<'_>>(ty)
+            .ok_or_else(|| Error::with_invalid_data(format!("type ID {ty} does not exist")))?;
+        self.decode_type(ty, data)
+    }
+
+    fn decode_type(&self, ty: BtfType<'_>, data: &[u8]) -> Result<BtfValue> {
+        let ty = ty.skip_mods_and_typedefs();
+        let size = self.type_size(ty.type_id())?;
+        let data = data.get(..size).ok_or_else(|| {
+            Error::with_invalid_data(format!(
+                "{} bytes of data are insufficient for type ID {} of size {size}",
+                data.len(),
+                ty.type_id()
+            ))
+        })?;
+
+        let value = match ty.kind() {
+            BtfKind::Int => {
+                let int = types::Int::try_from(ty).unwrap();
+                let bits = usize::from(int.bits);
+                let raw = if int.offset == 0 && bits == 8 * data.len() {
+                    read_uint(data)?
+                } else {
+                    read_bits(data, int.offset.into(), bits)?
+                };
+                match int.encoding {
+                    types::IntEncoding::Bool => BtfValue::Bool(raw != 0),
+                    types::IntEncoding::Char => BtfValue::Char(raw as u8),
+                    types::IntEncoding::Signed => BtfValue::Int(sign_extend(raw, bits)),
+                    types::IntEncoding::None => BtfValue::Uint(raw),
+                }
+            }
+            BtfKind::Float => match data.len() {
+                4 => BtfValue::Float(f32::from_ne_bytes(data.try_into().unwrap()).into()),
+                8 => BtfValue::Float(f64::from_ne_bytes(data.try_into().unwrap())),
+                len => {
+                    return Err(Error::with_io_error(
+                        io::ErrorKind::Unsupported,
+                        format!("floating point numbers of size {len} are not supported"),
+                    ))
+                }
+            },
+            BtfKind::Ptr => BtfValue::Ptr(read_uint(data)? as u64),
+            BtfKind::Enum => {
+                let enum_ = types::Enum::try_from(ty).unwrap();
+                let value = sign_extend(read_uint(data)?, 8 * data.len());
+                let name = enum_
+                    .iter()
+                    .find(|member| i128::from(member.value) == value)
+                    .and_then(|member| member.name)
+                    .map(|name| name.to_string_lossy().into_owned());
+                BtfValue::Enum { name, value }
+            }
+            BtfKind::Enum64 => {
+                let enum_ = types::Enum64::try_from(ty).unwrap();
+                let raw = read_uint(data)? as u64;
+                let name = enum_
+                    .iter()
+                    .find(|member| member.value == raw)
+                    .and_then(|member| member.name)
+                    .map(|name| name.to_string_lossy().into_owned());
+                BtfValue::Enum {
+                    name,
+                    value: raw.into(),
+                }
+            }
+            BtfKind::Array => {
+                let array = types::Array::try_from(ty).unwrap();
+                let elem = array.contained_type();
+                let elem_size = self.type_size(elem.type_id())?;
+                let values = (0..array.capacity())
+                    .map(|i| self.decode_type(elem, &data[i * elem_size..]))
+                    .collect::<Result<_>>()?;
+                BtfValue::Array(values)
+            }
+            BtfKind::Struct | BtfKind::Union => {
+                let composite = types::Composite::try_from(ty).unwrap();
+                let mut members = Vec::with_capacity(composite.len());
+                for member in composite.iter() {
+                    let name = member
+                        .name
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let member_ty = self.type_by_id::<BtfType<'_>>(member.ty).ok_or_else(|| {
+                        Error::with_invalid_data(format!("type ID {} does not exist", member.ty))
+                    })?;
+                    let value = match member.attr {
+                        types::MemberAttr::Normal { offset } => {
+                            let offset = offset as usize / 8;
+                            self.decode_type(member_ty, &data[offset..])?
+                        }
+                        types::MemberAttr::BitField { size, offset } => {
+                            let bits = usize::from(size);
+                            let raw = read_bits(data, offset as usize, bits)?;
+                            let skipped = member_ty.skip_mods_and_typedefs();
+                            match types::Int::try_from(skipped) {
+                                Ok(int) if matches!(int.encoding, types::IntEncoding::Signed) => {
+                                    BtfValue::Int(sign_extend(raw, bits))
+                                }
+                                Ok(int) if matches!(int.encoding, types::IntEncoding::Bool) => {
+                                    BtfValue::Bool(raw != 0)
+                                }
+                                _ if skipped.is_any_enum() => BtfValue::Enum {
+                                    name: None,
+                                    value: raw as i128,
+                                },
+                                _ => BtfValue::Uint(raw),
+                            }
+                        }
+                    };
+                    let () = members.push((name, value));
+                }
+                if composite.is_struct {
+                    BtfValue::Struct(members)
+                } else {
+                    BtfValue::Union(members)
+                }
+            }
+            BtfKind::Var => {
+                let var = types::Var::try_from(ty).unwrap();
+                self.decode_type(var.referenced_type(), data)?
+            }
+            kind => {
+                return Err(Error::with_io_error(
+                    io::ErrorKind::Unsupported,
+                    format!("values of BTF kind {kind:?} cannot be decoded"),
+                ))
+            }
+        };
+        Ok(value)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CString;
+    use std::marker::PhantomData;
+    use std::ptr::NonNull;
+
+    use super::super::DropPolicy;
+
+
+    /// Check that we can decode a struct containing integers, an
+    /// array, and bitfields.
+    #[test]
+    fn decode_struct() {
+        let ptr = NonNull::new(unsafe { libbpf_sys::btf__new_empty() }).unwrap();
+        let btf = Btf {
+            ptr,
+            drop_policy: DropPolicy::SelfPtrOnly,
+            _marker: PhantomData,
+        };
+        let raw = btf.ptr.as_ptr();
+        let name = |name: &str| CString::new(name).unwrap();
+
+        let u32_id = unsafe { libbpf_sys::btf__add_int(raw, name("u32").as_ptr(), 4, 0) };
+        let s16_id = unsafe {
+            libbpf_sys::btf__add_int(
+                raw,
+                name("s16").as_ptr(),
+                2,
+                libbpf_sys::BTF_INT_SIGNED as _,
+            )
+        };
+        let bool_id = unsafe {
+            libbpf_sys::btf__add_int(raw, name("bool").as_ptr(), 1, libbpf_sys::BTF_INT_BOOL as _)
+        };
+        let array_id = unsafe { libbpf_sys::btf__add_array(raw, u32_id, s16_id, 2) };
+        let struct_id = unsafe { libbpf_sys::btf__add_struct(raw, name("rec").as_ptr(), 12) };
+        assert!(struct_id > 0);
+        for (member, ty, bit_offset, bit_size) in [
+            ("a", u32_id, 0, 0),
+            ("b", array_id, 32, 0),
+            ("c", s16_id, 64, 3),
+            ("d", bool_id, 67, 1),
+        ] {
+            let ret = unsafe {
+                libbpf_sys::btf__add_field(raw, name(member).as_ptr(), ty, bit_offset, bit_size)
+            };
+            assert_eq!(ret, 0);
+        }
+
+        assert_eq!(btf.type_size(TypeId::from(struct_id as u32)).unwrap(), 12);
+
+        let mut data = Vec::new();
+        let () = data.extend(42u32.to_ne_bytes());
+        let () = data.extend((-1i16).to_ne_bytes());
+        let () = data.extend(7i16.to_ne_bytes());
+        // c = -3 (0b101), d = true
+        let () = data.extend([0b1101, 0, 0, 0]);
+
+        let value = btf
+            .decode_value(TypeId::from(struct_id as u32), &data)
+            .unwrap();
+        assert_eq!(value.field("a"), Some(&BtfValue::Uint(42)));
+        assert_eq!(
+            value.field("b"),
+            Some(&BtfValue::Array(vec![BtfValue::Int(-1), BtfValue::Int(7)]))
+        );
+        assert_eq!(value.field("c"), Some(&BtfValue::Int(-3)));
+        assert_eq!(value.field("d"), Some(&BtfValue::Bool(true)));
+        assert_eq!(value.field("e"), None);
+
+        let _err = btf
+            .decode_value(TypeId::from(struct_id as u32), &data[..8])
+            .unwrap_err();
+    }
+}
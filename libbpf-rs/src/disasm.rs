@@ -0,0 +1,862 @@
+//! Disassembly of BPF instructions.
+//!
+//! The [`Disassembler`] renders instructions in the syntax used by the
+//! kernel's verifier log and `bpftool prog dump xlated`, e.g.:
+//! ```text
+//! handle__tp:
+//! ; int handle__tp(void *ctx) @ tp.bpf.c:12
+//!    0: (b7) r1 = 0
+//!    1: (18) r2 = map[events]
+//!    3: (85) call bpf_ringbuf_output#130
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::os::unix::io::AsFd as _;
+use std::os::unix::io::AsRawFd as _;
+use std::os::unix::io::BorrowedFd;
+use std::path::Path;
+
+use libbpf_sys::bpf_func_info;
+use libbpf_sys::bpf_insn;
+
+use crate::btf::BtfType;
+use crate::btf::TypeId;
+use crate::query::LineInfo;
+use crate::query::ProgramInfo;
+use crate::Btf;
+use crate::MapHandle;
+use crate::MapInfo;
+use crate::Program;
+use crate::Result;
+
+
+/// `BPF_ST | BPF_NOSPEC`, which is not part of the generated bindings.
+const BPF_NOSPEC: u32 = 0xc0;
+
+/// The names of all BPF helpers, indexed by their `bpf_func_id`.
+const HELPERS: [&str; libbpf_sys::__BPF_FUNC_MAX_ID as usize] = [
+    "bpf_unspec",
+    "bpf_map_lookup_elem",
+    "bpf_map_update_elem",
+    "bpf_map_delete_elem",
+    "bpf_probe_read",
+    "bpf_ktime_get_ns",
+    "bpf_trace_printk",
+    "bpf_get_prandom_u32",
+    "bpf_get_smp_processor_id",
+    "bpf_skb_store_bytes",
+    "bpf_l3_csum_replace",
+    "bpf_l4_csum_replace",
+    "bpf_tail_call",
+    "bpf_clone_redirect",
+    "bpf_get_current_pid_tgid",
+    "bpf_get_current_uid_gid",
+    "bpf_get_current_comm",
+    "bpf_get_cgroup_classid",
+    "bpf_skb_vlan_push",
+    "bpf_skb_vlan_pop",
+    "bpf_skb_get_tunnel_key",
+    "bpf_skb_set_tunnel_key",
+    "bpf_perf_event_read",
+    "bpf_redirect",
+    "bpf_get_route_realm",
+    "bpf_perf_event_output",
+    "bpf_skb_load_bytes",
+    "bpf_get_stackid",
+    "bpf_csum_diff",
+    "bpf_skb_get_tunnel_opt",
+    "bpf_skb_set_tunnel_opt",
+    "bpf_skb_change_proto",
+    "bpf_skb_change_type",
+    "bpf_skb_under_cgroup",
+    "bpf_get_hash_recalc",
+    "bpf_get_current_task",
+    "bpf_probe_write_user",
+    "bpf_current_task_under_cgroup",
+    "bpf_skb_change_tail",
+    "bpf_skb_pull_data",
+    "bpf_csum_update",
+    "bpf_set_hash_invalid",
+    "bpf_get_numa_node_id",
+    "bpf_skb_change_head",
+    "bpf_xdp_adjust_head",
+    "bpf_probe_read_str",
+    "bpf_get_socket_cookie",
+    "bpf_get_socket_uid",
+    "bpf_set_hash",
+    "bpf_setsockopt",
+    "bpf_skb_adjust_room",
+    "bpf_redirect_map",
+    "bpf_sk_redirect_map",
+    "bpf_sock_map_update",
+    "bpf_xdp_adjust_meta",
+    "bpf_perf_event_read_value",
+    "bpf_perf_prog_read_value",
+    "bpf_getsockopt",
+    "bpf_override_return",
+    "bpf_sock_ops_cb_flags_set",
+    "bpf_msg_redirect_map",
+    "bpf_msg_apply_bytes",
+    "bpf_msg_cork_bytes",
+    "bpf_msg_pull_data",
+    "bpf_bind",
+    "bpf_xdp_adjust_tail",
+    "bpf_skb_get_xfrm_state",
+    "bpf_get_stack",
+    "bpf_skb_load_bytes_relative",
+    "bpf_fib_lookup",
+    "bpf_sock_hash_update",
+    "bpf_msg_redirect_hash",
+    "bpf_sk_redirect_hash",
+    "bpf_lwt_push_encap",
+    "bpf_lwt_seg6_store_bytes",
+    "bpf_lwt_seg6_adjust_srh",
+    "bpf_lwt_seg6_action",
+    "bpf_rc_repeat",
+    "bpf_rc_keydown",
+    "bpf_skb_cgroup_id",
+    "bpf_get_current_cgroup_id",
+    "bpf_get_local_storage",
+    "bpf_sk_select_reuseport",
+    "bpf_skb_ancestor_cgroup_id",
+    "bpf_sk_lookup_tcp",
+    "bpf_sk_lookup_udp",
+    "bpf_sk_release",
+    "bpf_map_push_elem",
+    "bpf_map_pop_elem",
+    "bpf_map_peek_elem",
+    "bpf_msg_push_data",
+    "bpf_msg_pop_data",
+    "bpf_rc_pointer_rel",
+    "bpf_spin_lock",
+    "bpf_spin_unlock",
+    "bpf_sk_fullsock",
+    "bpf_tcp_sock",
+    "bpf_skb_ecn_set_ce",
+    "bpf_get_listener_sock",
+    "bpf_skc_lookup_tcp",
+    "bpf_tcp_check_syncookie",
+    "bpf_sysctl_get_name",
+    "bpf_sysctl_get_current_value",
+    "bpf_sysctl_get_new_value",
+    "bpf_sysctl_set_new_value",
+    "bpf_strtol",
+    "bpf_strtoul",
+    "bpf_sk_storage_get",
+    "bpf_sk_storage_delete",
+    "bpf_send_signal",
+    "bpf_tcp_gen_syncookie",
+    "bpf_skb_output",
+    "bpf_probe_read_user",
+    "bpf_probe_read_kernel",
+    "bpf_probe_read_user_str",
+    "bpf_probe_read_kernel_str",
+    "bpf_tcp_send_ack",
+    "bpf_send_signal_thread",
+    "bpf_jiffies64",
+    "bpf_read_branch_records",
+    "bpf_get_ns_current_pid_tgid",
+    "bpf_xdp_output",
+    "bpf_get_netns_cookie",
+    "bpf_get_current_ancestor_cgroup_id",
+    "bpf_sk_assign",
+    "bpf_ktime_get_boot_ns",
+    "bpf_seq_printf",
+    "bpf_seq_write",
+    "bpf_sk_cgroup_id",
+    "bpf_sk_ancestor_cgroup_id",
+    "bpf_ringbuf_output",
+    "bpf_ringbuf_reserve",
+    "bpf_ringbuf_submit",
+    "bpf_ringbuf_discard",
+    "bpf_ringbuf_query",
+    "bpf_csum_level",
+    "bpf_skc_to_tcp6_sock",
+    "bpf_skc_to_tcp_sock",
+    "bpf_skc_to_tcp_timewait_sock",
+    "bpf_skc_to_tcp_request_sock",
+    "bpf_skc_to_udp6_sock",
+    "bpf_get_task_stack",
+    "bpf_load_hdr_opt",
+    "bpf_store_hdr_opt",
+    "bpf_reserve_hdr_opt",
+    "bpf_inode_storage_get",
+    "bpf_inode_storage_delete",
+    "bpf_d_path",
+    "bpf_copy_from_user",
+    "bpf_snprintf_btf",
+    "bpf_seq_printf_btf",
+    "bpf_skb_cgroup_classid",
+    "bpf_redirect_neigh",
+    "bpf_per_cpu_ptr",
+    "bpf_this_cpu_ptr",
+    "bpf_redirect_peer",
+    "bpf_task_storage_get",
+    "bpf_task_storage_delete",
+    "bpf_get_current_task_btf",
+    "bpf_bprm_opts_set",
+    "bpf_ktime_get_coarse_ns",
+    "bpf_ima_inode_hash",
+    "bpf_sock_from_file",
+    "bpf_check_mtu",
+    "bpf_for_each_map_elem",
+    "bpf_snprintf",
+    "bpf_sys_bpf",
+    "bpf_btf_find_by_name_kind",
+    "bpf_sys_close",
+    "bpf_timer_init",
+    "bpf_timer_set_callback",
+    "bpf_timer_start",
+    "bpf_timer_cancel",
+    "bpf_get_func_ip",
+    "bpf_get_attach_cookie",
+    "bpf_task_pt_regs",
+    "bpf_get_branch_snapshot",
+    "bpf_trace_vprintk",
+    "bpf_skc_to_unix_sock",
+    "bpf_kallsyms_lookup_name",
+    "bpf_find_vma",
+    "bpf_loop",
+    "bpf_strncmp",
+    "bpf_get_func_arg",
+    "bpf_get_func_ret",
+    "bpf_get_func_arg_cnt",
+    "bpf_get_retval",
+    "bpf_set_retval",
+    "bpf_xdp_get_buff_len",
+    "bpf_xdp_load_bytes",
+    "bpf_xdp_store_bytes",
+    "bpf_copy_from_user_task",
+    "bpf_skb_set_tstamp",
+    "bpf_ima_file_hash",
+    "bpf_kptr_xchg",
+    "bpf_map_lookup_percpu_elem",
+    "bpf_skc_to_mptcp_sock",
+    "bpf_dynptr_from_mem",
+    "bpf_ringbuf_reserve_dynptr",
+    "bpf_ringbuf_submit_dynptr",
+    "bpf_ringbuf_discard_dynptr",
+    "bpf_dynptr_read",
+    "bpf_dynptr_write",
+    "bpf_dynptr_data",
+    "bpf_tcp_raw_gen_syncookie_ipv4",
+    "bpf_tcp_raw_gen_syncookie_ipv6",
+    "bpf_tcp_raw_check_syncookie_ipv4",
+    "bpf_tcp_raw_check_syncookie_ipv6",
+    "bpf_ktime_get_tai_ns",
+    "bpf_user_ringbuf_drain",
+    "bpf_cgrp_storage_get",
+    "bpf_cgrp_storage_delete",
+];
+
+
+/// How map references embedded in `BPF_LD_IMM64` instructions are to be
+/// interpreted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MapRefKind {
+    /// Map references are file descriptors, as is the case for the
+    /// instructions of a loaded [`Program`].
+    Fd,
+    /// Map references are map IDs, as is the case for the translated
+    /// instructions reported by the kernel.
+    #[default]
+    Id,
+}
+
+
+/// A disassembler for BPF instructions.
+///
+/// Map references, helper and kfunc calls, as well as calls to
+/// subprograms are resolved to names where possible. If line
+/// information is available, instructions are annotated with the source
+/// code lines they originate from.
+#[derive(Debug, Default)]
+pub struct Disassembler<'btf> {
+    map_refs: MapRefKind,
+    map_names: HashMap<u32, String>,
+    btf: Option<Btf<'btf>>,
+    kernel_btf: Option<Btf<'btf>>,
+    func_info: Vec<bpf_func_info>,
+    line_info: Vec<LineInfo>,
+}
+
+impl<'btf> Disassembler<'btf> {
+    /// Create a new `Disassembler` without any information to resolve
+    /// names with.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a `Disassembler` for the instructions of a loaded
+    /// [`Program`], as returned by [`Program::insns`].
+    ///
+    /// Note that instructions of a program that failed to load or was
+    /// never loaded carry no map references that could be resolved.
+    pub fn for_program(prog: &Program<'_>) -> Disassembler<'static> {
+        let mut disasm = Disassembler::new().map_refs(MapRefKind::Fd);
+        for_each_map_ref(prog.insns(), |fd| {
+            // SAFETY: The file descriptor is owned by the object that
+            //         `prog` belongs to and remains open for the
+            //         duration of this call.
+            let fd = unsafe { BorrowedFd::borrow_raw(fd as _) };
+            if let Some(name) = MapInfo::new(fd).ok().and_then(map_name) {
+                let _prev = disasm.map_names.insert(fd.as_raw_fd() as u32, name);
+            }
+        });
+
+        if has_kfunc_calls(prog.insns()) {
+            disasm.kernel_btf = Btf::from_vmlinux().ok();
+        }
+        disasm
+    }
+
+    /// Create a `Disassembler` for the translated instructions of a
+    /// program as reported by the kernel, as returned by
+    /// [`ProgramInfo::xlated_insns`].
+    ///
+    /// BTF, function and line information are only available if they
+    /// were requested when retrieving `info`.
+    pub fn for_program_info(info: &ProgramInfo) -> Result<Disassembler<'static>> {
+        let insns = info.xlated_insns();
+        let mut disasm = Disassembler::new()
+            .map_refs(MapRefKind::Id)
+            .func_info(info.func_info.clone())
+            .line_info(info.line_info.clone());
+
+        for id in &info.map_ids {
+            // Maps may go away underneath us at any point, in which case
+            // they just won't be resolved.
+            if let Some(name) = MapHandle::from_map_id(*id)
+                .and_then(|map| MapInfo::new(map.as_fd()))
+                .ok()
+                .and_then(map_name)
+            {
+                let _prev = disasm.map_names.insert(*id, name);
+            }
+        }
+
+        if info.btf_id != 0 {
+            disasm.btf = Some(Btf::from_prog_id(info.id)?);
+        }
+        if has_kfunc_calls(&insns) {
+            disasm.kernel_btf = Btf::from_vmlinux().ok();
+        }
+        Ok(disasm)
+    }
+
+    /// Set how map references are to be interpreted.
+    pub fn map_refs(mut self, kind: MapRefKind) -> Self {
+        self.map_refs = kind;
+        self
+    }
+
+    /// Set the name of the map with the given file descriptor or ID,
+    /// depending on the configured [`MapRefKind`].
+    pub fn map_name(mut self, reference: u32, name: impl Into<String>) -> Self {
+        let _prev = self.map_names.insert(reference, name.into());
+        self
+    }
+
+    /// Set the BTF of the program, used for resolving function and line
+    /// information.
+    pub fn btf(mut self, btf: Btf<'btf>) -> Self {
+        self.btf = Some(btf);
+        self
+    }
+
+    /// Set the kernel BTF, used for resolving the names of kfuncs and
+    /// kernel symbols.
+    pub fn kernel_btf(mut self, btf: Btf<'btf>) -> Self {
+        self.kernel_btf = Some(btf);
+        self
+    }
+
+    /// Set the function information of the program.
+    pub fn func_info(mut self, func_info: Vec<bpf_func_info>) -> Self {
+        self.func_info = func_info;
+        self
+    }
+
+    /// Set the line information of the program.
+    pub fn line_info(mut self, line_info: Vec<LineInfo>) -> Self {
+        self.line_info = line_info;
+        self
+    }
+
+    /// Disassemble the provided instructions.
+    ///
+    /// Every instruction is rendered on a line of its own, prefixed with
+    /// its index and opcode. `BPF_LD_IMM64` instructions span two slots,
+    /// the second of which is not rendered.
+    pub fn disassemble(&self, insns: &[bpf_insn]) -> String {
+        let mut out = String::new();
+        let mut idx = 0;
+        while idx < insns.len() {
+            if let Some(name) = self.func_name(idx) {
+                let _result = writeln!(out, "{name}:");
+            }
+            if let Some(line) = self.source_line(idx) {
+                let _result = writeln!(out, "; {line}");
+            }
+            let insn = &insns[idx];
+            let _result = writeln!(
+                out,
+                "{idx:4}: ({:02x}) {}",
+                insn.code,
+                self.insn(insns, idx)
+            );
+            idx += if is_ld_imm64(insn) { 2 } else { 1 };
+        }
+        out
+    }
+
+    /// Render the instruction at `idx` (without index and opcode).
+    ///
+    /// `None` is returned if `idx` is out of bounds.
+    pub fn disassemble_insn(&self, insns: &[bpf_insn], idx: usize) -> Option<String> {
+        if idx >= insns.len() {
+            return None
+        }
+        Some(self.insn(insns, idx))
+    }
+
+    fn func_name(&self, idx: usize) -> Option<String> {
+        let btf = self.btf.as_ref()?;
+        let info = self
+            .func_info
+            .iter()
+            .find(|info| info.insn_off as usize == idx)?;
+        let ty = btf.type_by_id::<BtfType<'_>>(TypeId::from(info.type_id))?;
+        Some(ty.name()?.to_string_lossy().into_owned())
+    }
+
+    fn source_line(&self, idx: usize) -> Option<String> {
+        let btf = self.btf.as_ref()?;
+        let info = self
+            .line_info
+            .iter()
+            .find(|info| info.insn_off as usize == idx)?;
+        let line = btf.name_at(info.line_off)?.to_string_lossy();
+        let line = line.trim();
+        match btf.name_at(info.file_name_off) {
+            Some(file) => {
+                let file = Path::new(file);
+                let file = file
+                    .file_name()
+                    .unwrap_or(file.as_os_str())
+                    .to_string_lossy();
+                Some(format!("{line} @ {file}:{}", info.line_num))
+            }
+            None => Some(line.to_string()),
+        }
+    }
+
+    fn map(&self, imm: i32) -> String {
+        let imm = imm as u32;
+        match (self.map_names.get(&imm), self.map_refs) {
+            (Some(name), _) => format!("map[{name}]"),
+            (None, MapRefKind::Fd) => format!("map[fd:{imm}]"),
+            (None, MapRefKind::Id) => format!("map[id:{imm}]"),
+        }
+    }
+
+    fn kernel_name(&self, btf_id: i32) -> Option<String> {
+        let btf = self.kernel_btf.as_ref()?;
+        let ty = btf.type_by_id::<BtfType<'_>>(TypeId::from(btf_id as u32))?;
+        Some(ty.name()?.to_string_lossy().into_owned())
+    }
+
+    fn subprog(&self, idx: usize, imm: i32) -> String {
+        let target = (idx as i64 + i64::from(imm) + 1) as usize;
+        match self.func_name(target) {
+            Some(name) => format!("pc{imm:+}#{name}"),
+            None => format!("pc{imm:+}"),
+        }
+    }
+
+    fn insn(&self, insns: &[bpf_insn], idx: usize) -> String {
+        let insn = &insns[idx];
+        let code = u32::from(insn.code);
+        let (dst, src, off, imm) = (insn.dst_reg(), insn.src_reg(), insn.off, insn.imm);
+
+        match class(code) {
+            class @ (libbpf_sys::BPF_ALU | libbpf_sys::BPF_ALU64) => {
+                let is64 = class == libbpf_sys::BPF_ALU64;
+                let r = if is64 { 'r' } else { 'w' };
+                match op(code) {
+                    libbpf_sys::BPF_END => {
+                        if is64 {
+                            format!("r{dst} = bswap{imm} r{dst}")
+                        } else if source(code) == libbpf_sys::BPF_TO_BE {
+                            format!("r{dst} = be{imm} r{dst}")
+                        } else {
+                            format!("r{dst} = le{imm} r{dst}")
+                        }
+                    }
+                    libbpf_sys::BPF_NEG => format!("{r}{dst} = -{r}{dst}"),
+                    libbpf_sys::BPF_MOV if source(code) == libbpf_sys::BPF_X && off != 0 => {
+                        format!("{r}{dst} = (s{off}){r}{src}")
+                    }
+                    op => {
+                        let op = alu_op(op, off);
+                        if source(code) == libbpf_sys::BPF_X {
+                            format!("{r}{dst} {op} {r}{src}")
+                        } else {
+                            format!("{r}{dst} {op} {imm}")
+                        }
+                    }
+                }
+            }
+            libbpf_sys::BPF_STX => {
+                let size = size_name(code);
+                let mem = format!("({size} *)(r{dst} {off:+})");
+                match mode(code) {
+                    libbpf_sys::BPF_MEM => format!("*{mem} = r{src}"),
+                    libbpf_sys::BPF_ATOMIC => {
+                        let bits = if size_bits(code) == libbpf_sys::BPF_DW {
+                            "64"
+                        } else {
+                            ""
+                        };
+                        let imm = imm as u32;
+                        match imm {
+                            libbpf_sys::BPF_XCHG => {
+                                format!("r{src} = atomic{bits}_xchg({mem}, r{src})")
+                            }
+                            libbpf_sys::BPF_CMPXCHG => {
+                                format!("r0 = atomic{bits}_cmpxchg({mem}, r0, r{src})")
+                            }
+                            _ if imm & libbpf_sys::BPF_FETCH != 0 => {
+                                let name = match imm & !libbpf_sys::BPF_FETCH {
+                                    libbpf_sys::BPF_ADD => "add",
+                                    libbpf_sys::BPF_AND => "and",
+                                    libbpf_sys::BPF_OR => "or",
+                                    libbpf_sys::BPF_XOR => "xor",
+                                    _ => return format!("BUG_{:02x}", insn.code),
+                                };
+                                format!("r{src} = atomic{bits}_fetch_{name}({mem}, r{src})")
+                            }
+                            libbpf_sys::BPF_ADD
+                            | libbpf_sys::BPF_AND
+                            | libbpf_sys::BPF_OR
+                            | libbpf_sys::BPF_XOR => {
+                                format!("lock *{mem} {} r{src}", alu_op(imm, 0))
+                            }
+                            _ => format!("BUG_{:02x}", insn.code),
+                        }
+                    }
+                    _ => format!("BUG_{:02x}", insn.code),
+                }
+            }
+            libbpf_sys::BPF_ST => match mode(code) {
+                libbpf_sys::BPF_MEM => {
+                    format!("*({} *)(r{dst} {off:+}) = {imm}", size_name(code))
+                }
+                BPF_NOSPEC => "nospec".to_string(),
+                _ => format!("BUG_st_{:02x}", insn.code),
+            },
+            libbpf_sys::BPF_LDX => match mode(code) {
+                libbpf_sys::BPF_MEM => {
+                    format!("r{dst} = *({} *)(r{src} {off:+})", size_name(code))
+                }
+                libbpf_sys::BPF_MEMSX => {
+                    let size = size_name(code).replacen('u', "s", 1);
+                    format!("r{dst} = *({size} *)(r{src} {off:+})")
+                }
+                _ => format!("BUG_ldx_{:02x}", insn.code),
+            },
+            libbpf_sys::BPF_LD => match mode(code) {
+                libbpf_sys::BPF_ABS => format!("r0 = *({} *)skb[{imm}]", size_name(code)),
+                libbpf_sys::BPF_IND => {
+                    format!("r0 = *({} *)skb[r{src} + {imm}]", size_name(code))
+                }
+                libbpf_sys::BPF_IMM if size_bits(code) == libbpf_sys::BPF_DW => {
+                    let Some(next) = insns.get(idx + 1) else {
+                        return format!("BUG_ld_{:02x}", insn.code)
+                    };
+                    format!("r{dst} = {}", self.ld_imm64(idx, insn, next))
+                }
+                _ => format!("BUG_ld_{:02x}", insn.code),
+            },
+            class @ (libbpf_sys::BPF_JMP | libbpf_sys::BPF_JMP32) => {
+                let is32 = class == libbpf_sys::BPF_JMP32;
+                let r = if is32 { 'w' } else { 'r' };
+                match op(code) {
+                    libbpf_sys::BPF_CALL if !is32 => format!("call {}", self.call(idx, insn)),
+                    libbpf_sys::BPF_JA if is32 => format!("gotol pc{imm:+}"),
+                    libbpf_sys::BPF_JA => format!("goto pc{off:+}"),
+                    libbpf_sys::BPF_EXIT if !is32 => "exit".to_string(),
+                    op => {
+                        let Some(op) = jmp_op(op) else {
+                            return format!("BUG_jmp_{:02x}", insn.code)
+                        };
+                        if source(code) == libbpf_sys::BPF_X {
+                            format!("if {r}{dst} {op} {r}{src} goto pc{off:+}")
+                        } else {
+                            format!("if {r}{dst} {op} {:#x} goto pc{off:+}", imm as u32)
+                        }
+                    }
+                }
+            }
+            _ => format!("BUG_{:02x}", insn.code),
+        }
+    }
+
+    fn ld_imm64(&self, idx: usize, insn: &bpf_insn, next: &bpf_insn) -> String {
+        let imm = insn.imm;
+        let off = next.imm as u32;
+        match u32::from(insn.src_reg()) {
+            libbpf_sys::BPF_PSEUDO_MAP_FD => self.map(imm),
+            libbpf_sys::BPF_PSEUDO_MAP_VALUE => format!("{}[0]+{off}", self.map(imm)),
+            libbpf_sys::BPF_PSEUDO_MAP_IDX => format!("map[idx:{imm}]"),
+            libbpf_sys::BPF_PSEUDO_MAP_IDX_VALUE => format!("map[idx:{imm}][0]+{off}"),
+            libbpf_sys::BPF_PSEUDO_BTF_ID => match self.kernel_name(imm) {
+                Some(name) => format!("{name}#{imm}"),
+                None => format!("ksym#{imm}"),
+            },
+            libbpf_sys::BPF_PSEUDO_FUNC => format!("subprog[{}]", self.subprog(idx, imm)),
+            _ => {
+                let value = u64::from(imm as u32) | (u64::from(next.imm as u32) << 32);
+                format!("{value:#x}")
+            }
+        }
+    }
+
+    fn call(&self, idx: usize, insn: &bpf_insn) -> String {
+        let imm = insn.imm;
+        match u32::from(insn.src_reg()) {
+            libbpf_sys::BPF_PSEUDO_CALL => self.subprog(idx, imm),
+            libbpf_sys::BPF_PSEUDO_KFUNC_CALL => {
+                // A non-zero offset refers to a kfunc in module BTF, which
+                // we do not have at hand.
+                let name = (insn.off == 0).then(|| self.kernel_name(imm)).flatten();
+                let name = name.as_deref().unwrap_or("kernel-function");
+                format!("{name}#{imm}")
+            }
+            _ => {
+                let name = usize::try_from(imm)
+                    .ok()
+                    .and_then(|imm| HELPERS.get(imm))
+                    .unwrap_or(&"unknown");
+                format!("{name}#{imm}")
+            }
+        }
+    }
+}
+
+
+fn class(code: u32) -> u32 {
+    code & 0x07
+}
+
+fn size_bits(code: u32) -> u32 {
+    code & 0x18
+}
+
+fn mode(code: u32) -> u32 {
+    code & 0xe0
+}
+
+fn op(code: u32) -> u32 {
+    code & 0xf0
+}
+
+fn source(code: u32) -> u32 {
+    code & 0x08
+}
+
+fn is_ld_imm64(insn: &bpf_insn) -> bool {
+    u32::from(insn.code) == libbpf_sys::BPF_LD | libbpf_sys::BPF_IMM | libbpf_sys::BPF_DW
+}
+
+fn size_name(code: u32) -> &'static str {
+    match size_bits(code) {
+        libbpf_sys::BPF_W => "u32",
+        libbpf_sys::BPF_H => "u16",
+        libbpf_sys::BPF_B => "u8",
+        _ => "u64",
+    }
+}
+
+fn alu_op(op: u32, off: i16) -> &'static str {
+    match (op, off) {
+        (libbpf_sys::BPF_ADD, _) => "+=",
+        (libbpf_sys::BPF_SUB, _) => "-=",
+        (libbpf_sys::BPF_MUL, _) => "*=",
+        (libbpf_sys::BPF_DIV, 1) => "s/=",
+        (libbpf_sys::BPF_DIV, _) => "/=",
+        (libbpf_sys::BPF_OR, _) => "|=",
+        (libbpf_sys::BPF_AND, _) => "&=",
+        (libbpf_sys::BPF_LSH, _) => "<<=",
+        (libbpf_sys::BPF_RSH, _) => ">>=",
+        (libbpf_sys::BPF_MOD, 1) => "s%=",
+        (libbpf_sys::BPF_MOD, _) => "%=",
+        (libbpf_sys::BPF_XOR, _) => "^=",
+        (libbpf_sys::BPF_MOV, _) => "=",
+        (libbpf_sys::BPF_ARSH, _) => "s>>=",
+        _ => "?=",
+    }
+}
+
+fn jmp_op(op: u32) -> Option<&'static str> {
+    let op = match op {
+        libbpf_sys::BPF_JEQ => "==",
+        libbpf_sys::BPF_JGT => ">",
+        libbpf_sys::BPF_JGE => ">=",
+        libbpf_sys::BPF_JSET => "&",
+        libbpf_sys::BPF_JNE => "!=",
+        libbpf_sys::BPF_JSGT => "s>",
+        libbpf_sys::BPF_JSGE => "s>=",
+        libbpf_sys::BPF_JLT => "<",
+        libbpf_sys::BPF_JLE => "<=",
+        libbpf_sys::BPF_JSLT => "s<",
+        libbpf_sys::BPF_JSLE => "s<=",
+        _ => return None,
+    };
+    Some(op)
+}
+
+fn map_name(info: MapInfo) -> Option<String> {
+    info.name().ok().map(str::to_string)
+}
+
+/// Invoke `f` for every map reference in `insns`.
+fn for_each_map_ref(insns: &[bpf_insn], mut f: impl FnMut(i32)) {
+    for insn in insns.iter().filter(|insn| is_ld_imm64(insn)) {
+        if matches!(
+            u32::from(insn.src_reg()),
+            libbpf_sys::BPF_PSEUDO_MAP_FD | libbpf_sys::BPF_PSEUDO_MAP_VALUE
+        ) {
+            f(insn.imm)
+        }
+    }
+}
+
+fn has_kfunc_calls(insns: &[bpf_insn]) -> bool {
+    insns.iter().any(|insn| {
+        u32::from(insn.code) == libbpf_sys::BPF_JMP | libbpf_sys::BPF_CALL
+            && u32::from(insn.src_reg()) == libbpf_sys::BPF_PSEUDO_KFUNC_CALL
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn insn(code: u32, dst: u8, src: u8, off: i16, imm: i32) -> bpf_insn {
+        bpf_insn {
+            code: code as u8,
+            _bitfield_align_1: [],
+            _bitfield_1: bpf_insn::new_bitfield_1(dst, src),
+            off,
+            imm,
+        }
+    }
+
+    /// Check that we render a set of common instructions the way the
+    /// verifier does.
+    #[test]
+    fn disassemble_common() {
+        use libbpf_sys::*;
+
+        let insns = [
+            insn(BPF_ALU64 | BPF_MOV | BPF_K, 1, 0, 0, 0),
+            insn(BPF_ALU | BPF_ADD | BPF_X, 2, 3, 0, 0),
+            insn(BPF_STX | BPF_MEM | BPF_W, 10, 1, -4, 0),
+            insn(BPF_LDX | BPF_MEM | BPF_DW, 0, 1, 8, 0),
+            insn(BPF_ST | BPF_MEM | BPF_H, 10, 0, -8, 42),
+            insn(BPF_JMP | BPF_JEQ | BPF_K, 0, 0, 2, 0),
+            insn(BPF_JMP32 | BPF_JSGT | BPF_X, 1, 2, -3, 0),
+            insn(BPF_ALU | BPF_END | BPF_TO_BE, 1, 0, 0, 16),
+            insn(BPF_STX | BPF_ATOMIC | BPF_DW, 1, 2, 0, BPF_ADD as _),
+            insn(
+                BPF_STX | BPF_ATOMIC | BPF_W,
+                1,
+                2,
+                0,
+                (BPF_ADD | BPF_FETCH) as _,
+            ),
+            insn(BPF_STX | BPF_ATOMIC | BPF_DW, 1, 2, 0, BPF_CMPXCHG as _),
+            insn(BPF_JMP | BPF_CALL, 0, 0, 0, BPF_FUNC_map_lookup_elem as _),
+            insn(BPF_JMP | BPF_JA, 0, 0, -5, 0),
+            insn(BPF_JMP | BPF_EXIT, 0, 0, 0, 0),
+        ];
+        let expected = r#"   0: (b7) r1 = 0
+   1: (0c) w2 += w3
+   2: (63) *(u32 *)(r10 -4) = r1
+   3: (79) r0 = *(u64 *)(r1 +8)
+   4: (6a) *(u16 *)(r10 -8) = 42
+   5: (15) if r0 == 0x0 goto pc+2
+   6: (6e) if w1 s> w2 goto pc-3
+   7: (dc) r1 = be16 r1
+   8: (db) lock *(u64 *)(r1 +0) += r2
+   9: (c3) r2 = atomic_fetch_add((u32 *)(r1 +0), r2)
+  10: (db) r0 = atomic64_cmpxchg((u64 *)(r1 +0), r0, r2)
+  11: (85) call bpf_map_lookup_elem#1
+  12: (05) goto pc-5
+  13: (95) exit
+"#;
+        assert_eq!(Disassembler::new().disassemble(&insns), expected);
+    }
+
+    /// Check that `BPF_LD_IMM64` instructions referencing maps and
+    /// subprograms are resolved properly.
+    #[test]
+    fn disassemble_ld_imm64() {
+        use libbpf_sys::*;
+
+        let ld = BPF_LD | BPF_IMM | BPF_DW;
+        let insns = [
+            insn(ld, 1, BPF_PSEUDO_MAP_FD as _, 0, 7),
+            insn(0, 0, 0, 0, 0),
+            insn(ld, 2, BPF_PSEUDO_MAP_VALUE as _, 0, 8),
+            insn(0, 0, 0, 0, 16),
+            insn(ld, 3, 0, 0, 0x5678),
+            insn(0, 0, 0, 0, 0x1234),
+            insn(ld, 4, BPF_PSEUDO_FUNC as _, 0, 2),
+            insn(0, 0, 0, 0, 0),
+            insn(BPF_JMP | BPF_CALL, 0, BPF_PSEUDO_CALL as _, 0, -9),
+            insn(BPF_JMP | BPF_CALL, 0, BPF_PSEUDO_KFUNC_CALL as _, 0, 1337),
+        ];
+        let disasm = Disassembler::new().map_name(7, "events");
+        let expected = r#"   0: (18) r1 = map[events]
+   2: (18) r2 = map[id:8][0]+16
+   4: (18) r3 = 0x123400005678
+   6: (18) r4 = subprog[pc+2]
+   8: (85) call pc-9
+   9: (85) call kernel-function#1337
+"#;
+        assert_eq!(disasm.disassemble(&insns), expected);
+
+        let disasm = Disassembler::new().map_refs(MapRefKind::Fd);
+        assert_eq!(
+            disasm.disassemble_insn(&insns, 0).as_deref(),
+            Some("r1 = map[fd:7]")
+        );
+    }
+
+    /// Check that we render unknown helpers and truncated instructions
+    /// without panicking.
+    #[test]
+    fn disassemble_invalid() {
+        use libbpf_sys::*;
+
+        let insns = [
+            insn(BPF_JMP | BPF_CALL, 0, 0, 0, 100_000),
+            insn(BPF_LD | BPF_IMM | BPF_DW, 1, 0, 0, 0),
+        ];
+        let disasm = Disassembler::new();
+        assert_eq!(
+            disasm.disassemble_insn(&insns, 0).as_deref(),
+            Some("call unknown#100000")
+        );
+        assert_eq!(
+            disasm.disassemble_insn(&insns, 1).as_deref(),
+            Some("BUG_ld_18")
+        );
+        assert_eq!(disasm.disassemble_insn(&insns, 2), None);
+    }
+}
@@ -0,0 +1,548 @@
+//! A minimal reader for ELF files, sufficient for inspecting sections,
+//! notes, and symbols.
+
+use std::fs;
+use std::path::Path;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+/// `SHT_SYMTAB`
+const SHT_SYMTAB: u32 = 2;
+/// `SHT_NOTE`
+pub(crate) const SHT_NOTE: u32 = 7;
+/// `SHT_DYNSYM`
+const SHT_DYNSYM: u32 = 11;
+/// `SHT_GNU_verdef`
+const SHT_GNU_VERDEF: u32 = 0x6ffffffd;
+/// `SHT_GNU_versym`
+const SHT_GNU_VERSYM: u32 = 0x6fffffff;
+/// `PT_LOAD`
+const PT_LOAD: u32 = 1;
+/// `STT_FUNC`
+const STT_FUNC: u8 = 2;
+/// `STT_GNU_IFUNC`
+const STT_GNU_IFUNC: u8 = 10;
+/// `SHN_UNDEF`
+const SHN_UNDEF: u16 = 0;
+/// The bit of a `.gnu.version` entry marking a version as hidden,
+/// i.e., as not being the default one.
+const VERSYM_HIDDEN: u16 = 0x8000;
+
+
+/// Add `delta` to the file offset `off`, failing on overflow.
+fn offset_add(off: u64, delta: u64) -> Result<u64> {
+    off.checked_add(delta).ok_or_else(|| {
+        Error::with_invalid_data(format!("ELF offset {off:#x}+{delta:#x} overflows"))
+    })
+}
+
+/// Calculate the file offset of entry `idx` of a table starting at
+/// `base` with entries of `entsize` bytes, failing on overflow.
+fn entry_offset(base: u64, idx: u64, entsize: u64) -> Result<u64> {
+    let off = idx.checked_mul(entsize).ok_or_else(|| {
+        Error::with_invalid_data(format!("ELF table entry {idx} of size {entsize} overflows"))
+    })?;
+    offset_add(base, off)
+}
+
+
+/// A section of an ELF file.
+#[derive(Clone, Debug)]
+pub(crate) struct Section {
+    pub name: String,
+    pub ty: u32,
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+}
+
+
+/// A loadable segment of an ELF file.
+#[derive(Clone, Debug)]
+struct Segment {
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+}
+
+
+/// A note contained in an ELF note section.
+#[derive(Debug)]
+pub(crate) struct Note<'elf> {
+    pub name: &'elf [u8],
+    pub ty: u32,
+    pub desc: &'elf [u8],
+}
+
+
+/// A defined symbol contained in an ELF symbol table.
+#[derive(Clone, Debug)]
+pub(crate) struct ElfSymbol {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+    /// The symbol's version along with whether it is the default one.
+    pub version: Option<(String, bool)>,
+}
+
+
+/// An ELF file, read into memory.
+#[derive(Debug)]
+pub(crate) struct Elf {
+    data: Vec<u8>,
+    is64: bool,
+    le: bool,
+    sections: Vec<Section>,
+    segments: Vec<Segment>,
+}
+
+impl Elf {
+    /// Read and parse the ELF file at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let data = fs::read(path)
+            .with_context(|| format!("failed to read ELF file `{}`", path.display()))?;
+        Self::parse(data).with_context(|| format!("failed to parse ELF file `{}`", path.display()))
+    }
+
+    /// Parse the provided ELF data.
+    pub fn parse(data: Vec<u8>) -> Result<Self> {
+        if data.get(..4) != Some(b"\x7fELF") {
+            return Err(Error::with_invalid_data("invalid ELF magic"))
+        }
+        let is64 = match data.get(4) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => return Err(Error::with_invalid_data("invalid ELF class")),
+        };
+        let le = match data.get(5) {
+            Some(1) => true,
+            Some(2) => false,
+            _ => return Err(Error::with_invalid_data("invalid ELF data encoding")),
+        };
+
+        let mut elf = Self {
+            data,
+            is64,
+            le,
+            sections: Vec::new(),
+            segments: Vec::new(),
+        };
+        elf.sections = elf.parse_sections()?;
+        elf.segments = elf.parse_segments()?;
+        Ok(elf)
+    }
+
+    fn bytes(&self, offset: u64, len: u64) -> Result<&[u8]> {
+        let start = usize::try_from(offset).ok();
+        let end = offset
+            .checked_add(len)
+            .and_then(|end| usize::try_from(end).ok());
+        match (start, end) {
+            (Some(start), Some(end)) if end <= self.data.len() => Ok(&self.data[start..end]),
+            _ => Err(Error::with_invalid_data(format!(
+                "ELF data range {offset:#x}+{len:#x} is out of bounds"
+            ))),
+        }
+    }
+
+    pub fn u8(&self, offset: u64) -> Result<u8> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    pub fn u16(&self, offset: u64) -> Result<u16> {
+        let bytes = self.bytes(offset, 2)?.try_into().unwrap();
+        Ok(if self.le {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    pub fn u32(&self, offset: u64) -> Result<u32> {
+        let bytes = self.bytes(offset, 4)?.try_into().unwrap();
+        Ok(if self.le {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    pub fn u64(&self, offset: u64) -> Result<u64> {
+        let bytes = self.bytes(offset, 8)?.try_into().unwrap();
+        Ok(if self.le {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+
+    /// The size of an address, in bytes.
+    pub fn addr_size(&self) -> u64 {
+        if self.is64 {
+            8
+        } else {
+            4
+        }
+    }
+
+    /// Whether the ELF file is little endian.
+    pub fn is_le(&self) -> bool {
+        self.le
+    }
+
+    fn parse_sections(&self) -> Result<Vec<Section>> {
+        let (shoff, shentsize, shnum, shstrndx) = if self.is64 {
+            (
+                self.u64(0x28)?,
+                self.u16(0x3a)?,
+                self.u16(0x3c)?,
+                self.u16(0x3e)?,
+            )
+        } else {
+            (
+                self.u32(0x20)?.into(),
+                self.u16(0x2e)?,
+                self.u16(0x30)?,
+                self.u16(0x32)?,
+            )
+        };
+
+        let sections = (0..u64::from(shnum))
+            .map(|idx| self.parse_section(entry_offset(shoff, idx, shentsize.into())?))
+            .collect::<Result<Vec<_>>>()?;
+
+        let strtab = sections
+            .get(usize::from(shstrndx))
+            .map(|(_name_off, strtab)| strtab.clone());
+        let sections = sections
+            .into_iter()
+            .map(|(name_off, mut section)| {
+                if let Some(strtab) = &strtab {
+                    section.name = self.str_at(strtab, name_off.into()).unwrap_or_default();
+                }
+                section
+            })
+            .collect();
+        Ok(sections)
+    }
+
+    /// Parse the section header at `off`, returning the offset of the
+    /// section's name along with the section itself.
+    fn parse_section(&self, off: u64) -> Result<(u32, Section)> {
+        // Make sure that the whole header is in bounds, so that offsets
+        // into it can't overflow.
+        let _hdr = self.bytes(off, if self.is64 { 64 } else { 40 })?;
+        let section = if self.is64 {
+            Section {
+                name: String::new(),
+                ty: self.u32(off + 4)?,
+                addr: self.u64(off + 16)?,
+                offset: self.u64(off + 24)?,
+                size: self.u64(off + 32)?,
+                link: self.u32(off + 40)?,
+            }
+        } else {
+            Section {
+                name: String::new(),
+                ty: self.u32(off + 4)?,
+                addr: self.u32(off + 12)?.into(),
+                offset: self.u32(off + 16)?.into(),
+                size: self.u32(off + 20)?.into(),
+                link: self.u32(off + 24)?,
+            }
+        };
+        Ok((self.u32(off)?, section))
+    }
+
+    fn parse_segments(&self) -> Result<Vec<Segment>> {
+        let (phoff, phentsize, phnum) = if self.is64 {
+            (self.u64(0x20)?, self.u16(0x36)?, self.u16(0x38)?)
+        } else {
+            (self.u32(0x1c)?.into(), self.u16(0x2a)?, self.u16(0x2c)?)
+        };
+
+        let mut segments = Vec::new();
+        for idx in 0..u64::from(phnum) {
+            let off = entry_offset(phoff, idx, phentsize.into())?;
+            // Make sure that the whole header is in bounds, so that
+            // offsets into it can't overflow.
+            let _hdr = self.bytes(off, if self.is64 { 56 } else { 32 })?;
+            if self.u32(off)? != PT_LOAD {
+                continue
+            }
+            let segment = if self.is64 {
+                Segment {
+                    offset: self.u64(off + 8)?,
+                    vaddr: self.u64(off + 16)?,
+                    filesz: self.u64(off + 32)?,
+                }
+            } else {
+                Segment {
+                    offset: self.u32(off + 4)?.into(),
+                    vaddr: self.u32(off + 8)?.into(),
+                    filesz: self.u32(off + 16)?.into(),
+                }
+            };
+            let () = segments.push(segment);
+        }
+        Ok(segments)
+    }
+
+    /// Retrieve the NUL terminated string at offset `off` in the string
+    /// table section `strtab`.
+    pub fn str_at(&self, strtab: &Section, off: u64) -> Option<String> {
+        let data = self.section_data(strtab).ok()?;
+        let data = data.get(usize::try_from(off).ok()?..)?;
+        let len = data.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&data[..len]).into_owned())
+    }
+
+    /// Retrieve all sections.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Find the section with the given name.
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// Retrieve the contents of a section.
+    pub fn section_data(&self, section: &Section) -> Result<&[u8]> {
+        self.bytes(section.offset, section.size)
+    }
+
+    /// Translate a (link time) virtual address into an offset into the
+    /// file, based on the file's loadable segments.
+    pub fn file_offset(&self, addr: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|segment| addr >= segment.vaddr && addr - segment.vaddr < segment.filesz)
+            .and_then(|segment| (addr - segment.vaddr).checked_add(segment.offset))
+    }
+
+    /// Parse the defined function symbols contained in the symbol table
+    /// sections (`.symtab` and `.dynsym`).
+    pub fn func_symbols(&self) -> Result<Vec<ElfSymbol>> {
+        let mut symbols = Vec::new();
+        for section in self
+            .sections
+            .iter()
+            .filter(|section| matches!(section.ty, SHT_SYMTAB | SHT_DYNSYM))
+        {
+            let versions = if section.ty == SHT_DYNSYM {
+                self.symbol_versions()?
+            } else {
+                Vec::new()
+            };
+            let () = symbols.extend(self.parse_symbols(section, &versions)?);
+        }
+        Ok(symbols)
+    }
+
+    fn parse_symbols(
+        &self,
+        section: &Section,
+        versions: &[Option<(String, bool)>],
+    ) -> Result<Vec<ElfSymbol>> {
+        let strtab = self
+            .sections
+            .get(section.link as usize)
+            .ok_or_else(|| Error::with_invalid_data("symbol table has invalid string table"))?;
+        let entsize = if self.is64 { 24 } else { 16 };
+
+        let mut symbols = Vec::new();
+        for idx in 0..section.size / entsize {
+            let off = entry_offset(section.offset, idx, entsize)?;
+            let _sym = self.bytes(off, entsize)?;
+            let (name, info, shndx, addr, size) = if self.is64 {
+                (
+                    self.u32(off)?,
+                    self.u8(off + 4)?,
+                    self.u16(off + 6)?,
+                    self.u64(off + 8)?,
+                    self.u64(off + 16)?,
+                )
+            } else {
+                (
+                    self.u32(off)?,
+                    self.u8(off + 12)?,
+                    self.u16(off + 14)?,
+                    self.u32(off + 4)?.into(),
+                    self.u32(off + 8)?.into(),
+                )
+            };
+
+            let ty = info & 0xf;
+            if !matches!(ty, STT_FUNC | STT_GNU_IFUNC) || shndx == SHN_UNDEF || addr == 0 {
+                continue
+            }
+            let Some(name) = self.str_at(strtab, name.into()) else {
+                continue
+            };
+
+            let () = symbols.push(ElfSymbol {
+                name,
+                addr,
+                size,
+                version: versions.get(idx as usize).cloned().flatten(),
+            });
+        }
+        Ok(symbols)
+    }
+
+    /// Parse the `.gnu.version` and `.gnu.version_d` sections, returning
+    /// the version of each `.dynsym` entry.
+    fn symbol_versions(&self) -> Result<Vec<Option<(String, bool)>>> {
+        let Some(versym) = self
+            .sections
+            .iter()
+            .find(|section| section.ty == SHT_GNU_VERSYM)
+        else {
+            return Ok(Vec::new())
+        };
+        let Some(verdef) = self
+            .sections
+            .iter()
+            .find(|section| section.ty == SHT_GNU_VERDEF)
+        else {
+            return Ok(Vec::new())
+        };
+        let strtab = self
+            .sections
+            .get(verdef.link as usize)
+            .ok_or_else(|| Error::with_invalid_data("version section has invalid string table"))?;
+
+        // Map version indices to names.
+        let mut names = Vec::new();
+        let mut off = verdef.offset;
+        loop {
+            // Make sure that the whole `Elf_Verdef` is in bounds, so
+            // that offsets into it can't overflow.
+            let _verdef = self.bytes(off, 20)?;
+            let ndx = self.u16(off + 4)?;
+            let aux = self.u32(off + 12)?;
+            let next = self.u32(off + 16)?;
+            let name = self.u32(offset_add(off, aux.into())?)?;
+            if let Some(name) = self.str_at(strtab, name.into()) {
+                let () = names.push((ndx, name));
+            }
+            if next == 0 {
+                break
+            }
+            off = offset_add(off, next.into())?;
+        }
+
+        (0..versym.size / 2)
+            .map(|idx| {
+                let ver = self.u16(entry_offset(versym.offset, idx, 2)?)?;
+                let ndx = ver & !VERSYM_HIDDEN;
+                // Indices 0 and 1 denote local and unversioned global
+                // symbols, respectively.
+                if ndx <= 1 {
+                    return Ok(None)
+                }
+                let version = names
+                    .iter()
+                    .find(|(idx, _name)| *idx == ndx)
+                    .map(|(_idx, name)| (name.clone(), ver & VERSYM_HIDDEN == 0));
+                Ok(version)
+            })
+            .collect()
+    }
+
+    /// Parse the notes contained in a note section.
+    pub fn notes(&self, section: &Section) -> Result<Vec<Note<'_>>> {
+        fn align4(x: u64) -> u64 {
+            (x + 3) & !3
+        }
+
+        let mut notes = Vec::new();
+        let end = offset_add(section.offset, section.size)?;
+        let mut off = section.offset;
+        while end.saturating_sub(off) >= 12 {
+            let namesz = u64::from(self.u32(off)?);
+            let descsz = u64::from(self.u32(off + 4)?);
+            let ty = self.u32(off + 8)?;
+            let name_off = off + 12;
+            let desc_off = offset_add(name_off, align4(namesz))?;
+            let name = self.bytes(name_off, namesz)?;
+            // The name includes the terminating NUL byte.
+            let name = name.strip_suffix(&[0]).unwrap_or(name);
+            let desc = self.bytes(desc_off, descsz)?;
+            let () = notes.push(Note { name, ty, desc });
+            off = offset_add(desc_off, align4(descsz))?;
+        }
+        Ok(notes)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env::current_exe;
+    use std::mem::size_of;
+
+
+    /// Check that we can parse the sections of the running executable.
+    #[test]
+    fn parse_own_sections() {
+        let elf = Elf::open(&current_exe().unwrap()).unwrap();
+        assert!(elf.section(".text").is_some());
+        assert!(elf.section(".symtab").is_some() || elf.section(".dynsym").is_some());
+        assert_eq!(elf.addr_size(), size_of::<usize>() as u64);
+    }
+
+    /// Check that we can parse the function symbols of the running
+    /// executable and map their addresses to file offsets.
+    #[test]
+    fn parse_own_symbols() {
+        let elf = Elf::open(&current_exe().unwrap()).unwrap();
+        let symbols = elf.func_symbols().unwrap();
+        let main = symbols.iter().find(|sym| sym.name == "main").unwrap();
+        assert!(elf.file_offset(main.addr).is_some());
+    }
+
+    /// Check that we reject data that is not an ELF file.
+    #[test]
+    fn parse_invalid() {
+        let _err = Elf::parse(b"not an ELF file".to_vec()).unwrap_err();
+        let _err = Elf::parse(b"\x7fELF\x02\x01".to_vec()).unwrap_err();
+    }
+
+    /// Check that we reject section header tables whose offsets
+    /// overflow, instead of panicking.
+    #[test]
+    fn parse_overflowing_offsets() {
+        let mut data = vec![0; 64];
+        let () = data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        // `e_shoff`
+        let () = data[0x28..0x30].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        // `e_shentsize`
+        let () = data[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+        // `e_shnum`
+        let () = data[0x3c..0x3e].copy_from_slice(&2u16.to_le_bytes());
+        let _err = Elf::parse(data).unwrap_err();
+    }
+
+    /// Check that we reject program header tables whose offsets
+    /// overflow, instead of panicking.
+    #[test]
+    fn parse_overflowing_segment_offsets() {
+        let mut data = vec![0; 64];
+        let () = data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        // `e_phoff`
+        let () = data[0x20..0x28].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        // `e_phentsize`
+        let () = data[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+        // `e_phnum`
+        let () = data[0x38..0x3a].copy_from_slice(&2u16.to_le_bytes());
+        let _err = Elf::parse(data).unwrap_err();
+    }
+}
@@ -11,6 +11,8 @@ use std::mem::transmute;
 use std::ops::Deref;
 use std::result;
 
+use crate::VerifierLog;
+
 /// A result type using our [`Error`] by default.
 pub type Result<T, E = Error> = result::Result<T, E>;
 
@@ -110,6 +112,10 @@ enum ErrorImpl {
         context: &'static str,
         source: Box<ErrorImpl>,
     },
+    VerifierLog {
+        log: Box<VerifierLog>,
+        source: Box<ErrorImpl>,
+    },
 }
 
 impl ErrorImpl {
@@ -130,9 +136,19 @@ impl ErrorImpl {
                 io::ErrorKind::OutOfMemory => ErrorKind::OutOfMemory,
                 _ => ErrorKind::Other,
             },
+            Self::ContextOwned { source, .. }
+            | Self::ContextStatic { source, .. }
+            | Self::VerifierLog { source, .. } => source.deref().kind(),
+        }
+    }
+
+    fn verifier_log(&self) -> Option<&VerifierLog> {
+        match self {
+            Self::Io(..) => None,
             Self::ContextOwned { source, .. } | Self::ContextStatic { source, .. } => {
-                source.deref().kind()
+                source.verifier_log()
             }
+            Self::VerifierLog { log, .. } => Some(log),
         }
     }
 
@@ -166,6 +182,10 @@ impl Debug for ErrorImpl {
                     dbg = f.debug_tuple(stringify!(ContextStatic));
                     dbg.field(context)
                 }
+                Self::VerifierLog { log, .. } => {
+                    dbg = f.debug_tuple(stringify!(VerifierLog));
+                    dbg.field(log)
+                }
             }
             .finish()
         } else {
@@ -173,6 +193,7 @@ impl Debug for ErrorImpl {
                 Self::Io(error) => write!(f, "Error: {error}")?,
                 Self::ContextOwned { context, .. } => write!(f, "Error: {context}")?,
                 Self::ContextStatic { context, .. } => write!(f, "Error: {context}")?,
+                Self::VerifierLog { log, .. } => write!(f, "Error: {log}")?,
             };
 
             if let Some(source) = self.source() {
@@ -195,6 +216,7 @@ impl Display for ErrorImpl {
             Self::Io(error) => Display::fmt(error, f)?,
             Self::ContextOwned { context, .. } => Display::fmt(context, f)?,
             Self::ContextStatic { context, .. } => Display::fmt(context, f)?,
+            Self::VerifierLog { log, .. } => Display::fmt(log, f)?,
         };
 
         if f.alternate() {
@@ -212,7 +234,9 @@ impl error::Error for ErrorImpl {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Self::Io(error) => error.source(),
-            Self::ContextOwned { source, .. } | Self::ContextStatic { source, .. } => Some(source),
+            Self::ContextOwned { source, .. }
+            | Self::ContextStatic { source, .. }
+            | Self::VerifierLog { source, .. } => Some(source),
         }
     }
 }
@@ -361,6 +385,27 @@ impl Error {
         self.error.kind()
     }
 
+    /// Retrieve the log of the BPF verifier, if this error was caused
+    /// by the kernel rejecting a BPF program.
+    ///
+    /// The log is searched for in the entire error chain, meaning that
+    /// additional context layered on top of the error does not hide it.
+    #[inline]
+    pub fn verifier_log(&self) -> Option<&VerifierLog> {
+        self.error.verifier_log()
+    }
+
+    /// Layer the provided verifier log on top of this `Error`, creating
+    /// a new one in the process.
+    pub(crate) fn with_verifier_log(self, log: VerifierLog) -> Self {
+        Self {
+            error: Box::new(ErrorImpl::VerifierLog {
+                log: Box::new(log),
+                source: self.error,
+            }),
+        }
+    }
+
     /// Layer the provided context on top of this `Error`, creating a
     /// new one in the process.
     fn layer_context(self, context: Cow<'static, Str>) -> Self {
@@ -533,6 +578,7 @@ impl<T> IntoError<T> for Option<T> {
 mod tests {
     use super::*;
 
+    use std::ffi::OsStr;
     use std::mem::size_of;
 
     /// Check various features of our `Str` wrapper type.
@@ -610,4 +656,29 @@ Caused by:
         assert_eq!(format!("{err:?}"), expected);
         assert_ne!(format!("{err:#?}"), "");
     }
+
+    /// Check that a verifier log attached to an error can be retrieved
+    /// and is reported as expected.
+    #[test]
+    fn error_verifier_log() {
+        let err = Error::from_raw_os_error(libc::EACCES);
+        assert!(err.verifier_log().is_none());
+
+        let log = "0: (b7) r0 = 0\n1: (95) exit\nR0 !read_ok\n";
+        let log = VerifierLog::new(OsStr::new("prog"), log.to_string());
+        let err = err.with_verifier_log(log);
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(
+            format!("{err}"),
+            "BPF program `prog` failed verification at instruction 1: R0 !read_ok"
+        );
+
+        let err = err.context("failed to load object");
+        let log = err.verifier_log().unwrap();
+        assert_eq!(log.program(), "prog");
+        assert_eq!(log.failed_insn().unwrap().insn, "exit");
+        assert!(format!("{err:#}").starts_with(
+            "failed to load object: BPF program `prog` failed verification at instruction 1"
+        ));
+    }
 }
@@ -0,0 +1,223 @@
+//! Executor agnostic sources of owned ring and perf buffer samples.
+//!
+//! The types in this module never block. Instead, they expose file
+//! descriptors that become readable once data is available, which can
+//! be registered with the event loop of choice.
+
+use std::collections::VecDeque;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::BorrowedFd;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+
+use crate::error::IntoError as _;
+use crate::MapCore;
+use crate::PerfBuffer;
+use crate::PerfBufferBuilder;
+use crate::Result;
+use crate::RingBuffer;
+use crate::RingBufferBuilder;
+
+
+/// A source of owned samples from a single `ringbuf` map.
+///
+/// In contrast to [`RingBuffer`], a `RingBufferSource` does not invoke
+/// user provided callbacks, but copies samples out of the ring, so that
+/// they can be handed out at a later point. Readiness is signaled by
+/// the file descriptor exposed via [`AsFd`].
+#[derive(Debug)]
+pub struct RingBufferSource {
+    rb: RingBuffer<'static>,
+    samples: Receiver<Vec<u8>>,
+}
+
+impl RingBufferSource {
+    /// Create a new `RingBufferSource` for the provided `ringbuf` map.
+    pub fn new(map: &dyn MapCore) -> Result<Self> {
+        let (sender, samples) = channel();
+        let mut builder = RingBufferBuilder::new();
+        let _builder = builder.add(map, move |data: &[u8]| {
+            // The receiver lives as long as the ring buffer, so sending
+            // can't fail.
+            let _result = sender.send(data.to_vec());
+            0
+        })?;
+        let rb = builder.build()?;
+
+        Ok(Self { rb, samples })
+    }
+
+    /// Retrieve the next sample, consuming from the ring if no
+    /// previously consumed one is pending.
+    ///
+    /// `None` is returned if no sample is available at this point.
+    pub fn try_next(&mut self) -> Result<Option<Vec<u8>>> {
+        if let Ok(sample) = self.samples.try_recv() {
+            return Ok(Some(sample))
+        }
+
+        let () = self.rb.consume()?;
+        Ok(self.samples.try_recv().ok())
+    }
+
+    /// Consume and return all currently available samples.
+    ///
+    /// This method is meant to be used on shutdown, to retrieve samples
+    /// that have not been handed out yet.
+    pub fn drain(&mut self) -> Result<Vec<Vec<u8>>> {
+        let () = self.rb.consume()?;
+        Ok(self.samples.try_iter().collect())
+    }
+}
+
+impl AsFd for RingBufferSource {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.rb.as_fd()
+    }
+}
+
+
+/// An event retrieved from a perf buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PerfEvent {
+    /// A sample was submitted.
+    Sample {
+        /// The CPU on which the sample was submitted.
+        cpu: i32,
+        /// The sample's data.
+        data: Vec<u8>,
+    },
+    /// Samples got lost.
+    Lost {
+        /// The CPU on which samples got lost.
+        cpu: i32,
+        /// The number of lost samples.
+        count: u64,
+    },
+}
+
+
+/// A source of owned events from the per-CPU buffers of a
+/// `PerfEventArray` map.
+///
+/// Each of the buffers can be consumed individually. Readiness of a
+/// single buffer is signaled by the file descriptor reported by
+/// [`PerfBufferSource::buffer_fd`], while the one exposed via [`AsFd`]
+/// covers all buffers.
+#[derive(Debug)]
+pub struct PerfBufferSource {
+    pb: PerfBuffer<'static>,
+    events: Receiver<PerfEvent>,
+    /// Consumed events not yet handed out, per buffer.
+    pending: Vec<VecDeque<PerfEvent>>,
+}
+
+impl PerfBufferSource {
+    /// Create a new `PerfBufferSource` for the provided
+    /// `PerfEventArray` map, using buffers of `pages` pages each.
+    pub fn new<M>(map: &M, pages: usize) -> Result<Self>
+    where
+        M: MapCore,
+    {
+        let (sample_sender, events) = channel();
+        let lost_sender = sample_sender.clone();
+        // The receiver lives as long as the perf buffer, so sending
+        // can't fail.
+        let pb = PerfBufferBuilder::new(map)
+            .pages(pages)
+            .sample_cb(move |cpu, data: &[u8]| {
+                let _result = sample_sender.send(PerfEvent::Sample {
+                    cpu,
+                    data: data.to_vec(),
+                });
+            })
+            .lost_cb(move |cpu, count| {
+                let _result = lost_sender.send(PerfEvent::Lost { cpu, count });
+            })
+            .build()?;
+        let pending = (0..pb.buffer_cnt()).map(|_| VecDeque::new()).collect();
+
+        Ok(Self {
+            pb,
+            events,
+            pending,
+        })
+    }
+
+    /// Retrieve the number of per-CPU buffers.
+    pub fn buffer_cnt(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Retrieve the file descriptor of the buffer with index `buf_idx`.
+    /// It becomes readable once data is available in this buffer.
+    pub fn buffer_fd(&self, buf_idx: usize) -> Result<BorrowedFd<'_>> {
+        let fd = self.pb.buffer_fd(buf_idx)?;
+        // SAFETY: The file descriptor is owned by the perf buffer and
+        //         stays valid for as long as it is alive.
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        Ok(fd)
+    }
+
+    /// Retrieve the next event of the buffer with index `buf_idx`,
+    /// consuming from it if no previously consumed event is pending.
+    ///
+    /// `None` is returned if no event is available at this point.
+    pub fn try_next(&mut self, buf_idx: usize) -> Result<Option<PerfEvent>> {
+        let pending = self
+            .pending
+            .get_mut(buf_idx)
+            .ok_or_invalid_data(|| format!("perf buffer index {buf_idx} is out of bounds"))?;
+
+        if let Some(event) = pending.pop_front() {
+            return Ok(Some(event))
+        }
+
+        // Only the buffer at `buf_idx` is consumed, so all events that
+        // got reported belong to it.
+        let () = self.pb.consume_buffer(buf_idx)?;
+        let () = pending.extend(self.events.try_iter());
+        Ok(pending.pop_front())
+    }
+
+    /// Consume and return all currently available events of the buffer
+    /// with index `buf_idx`.
+    ///
+    /// This method is meant to be used on shutdown, to retrieve events
+    /// that have not been handed out yet.
+    pub fn drain_buffer(&mut self, buf_idx: usize) -> Result<Vec<PerfEvent>> {
+        let pending = self
+            .pending
+            .get_mut(buf_idx)
+            .ok_or_invalid_data(|| format!("perf buffer index {buf_idx} is out of bounds"))?;
+
+        let () = self.pb.consume_buffer(buf_idx)?;
+        let () = pending.extend(self.events.try_iter());
+        Ok(pending.drain(..).collect())
+    }
+}
+
+impl AsFd for PerfBufferSource {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.pb.as_fd()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Check that `RingBufferSource` and `PerfBufferSource` are `Send`.
+    #[test]
+    fn sources_are_send() {
+        fn test<T>()
+        where
+            T: Send,
+        {
+        }
+
+        test::<RingBufferSource>();
+        test::<PerfBufferSource>();
+    }
+}
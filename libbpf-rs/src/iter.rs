@@ -1,19 +1,36 @@
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
 use std::io;
+use std::io::Read as _;
+use std::marker::PhantomData;
+use std::mem::size_of;
 use std::os::fd::AsFd;
 use std::os::fd::AsRawFd;
 use std::os::fd::FromRawFd;
 use std::os::fd::OwnedFd;
+use std::ptr;
 
+use crate::btf::BtfType;
+use crate::btf::BtfValue;
+use crate::btf::TypeId;
+use crate::Btf;
 use crate::Error;
 use crate::Link;
 use crate::Result;
 
+
+/// The number of bytes to read from an iterator at a time.
+const READ_CHUNK_SIZE: usize = 4096;
+
 /// Represents a bpf iterator for reading kernel data structures. This requires
 /// Linux 5.8.
 ///
 /// This implements [`std::io::Read`] for reading bytes from the iterator.
 /// Methods require working with raw bytes. You may find libraries such as
-/// [`plain`](https://crates.io/crates/plain) helpful.
+/// [`plain`](https://crates.io/crates/plain) helpful. For iterators
+/// emitting fixed-size binary records, consider using [`IterRecords`]
+/// instead.
 #[derive(Debug)]
 pub struct Iter {
     fd: OwnedFd,
@@ -43,3 +60,243 @@ impl io::Read for Iter {
         Ok(bytes_read as usize)
     }
 }
+
+
+/// A decoder of the fixed-size records read by [`IterRecords`].
+pub trait RecordDecoder {
+    /// The type of a decoded record.
+    type Record;
+
+    /// The size of a record, in bytes.
+    fn record_size(&self) -> usize;
+
+    /// Decode a record from exactly [`record_size`][Self::record_size]
+    /// bytes.
+    fn decode(&self, data: &[u8]) -> Result<Self::Record>;
+}
+
+
+/// A [`RecordDecoder`] interpreting records as plain-old-data `T`, as
+/// emitted by a `bpf_seq_write(seq, &value, sizeof(value))`.
+pub struct PodDecoder<T> {
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: Copy> PodDecoder<T> {
+    /// Create a new `PodDecoder`.
+    ///
+    /// # Safety
+    /// `T` has to be valid for any bit pattern of `size_of::<T>()` bytes
+    /// and its layout has to match that of the records emitted by the
+    /// BPF program, i.e., it should be `#[repr(C)]` and not contain
+    /// references, `bool`s, or `enum`s.
+    pub unsafe fn new() -> Self {
+        Self {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for PodDecoder<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("PodDecoder").finish()
+    }
+}
+
+impl<T: Copy> RecordDecoder for PodDecoder<T> {
+    type Record = T;
+
+    fn record_size(&self) -> usize {
+        size_of::<T>()
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T> {
+        assert_eq!(data.len(), size_of::<T>());
+        // SAFETY: The data is large enough and, per the contract of
+        //         `PodDecoder::new`, any bit pattern is a valid `T`.
+        let record = unsafe { ptr::read_unaligned(data.as_ptr().cast::<T>()) };
+        Ok(record)
+    }
+}
+
+
+/// A [`RecordDecoder`] decoding records according to a BTF type, e.g.,
+/// one of the types contained in the BPF object the program stems from.
+#[derive(Debug)]
+pub struct BtfDecoder<'btf> {
+    btf: &'btf Btf<'btf>,
+    ty: TypeId,
+    size: usize,
+}
+
+impl<'btf> BtfDecoder<'btf> {
+    /// Create a new `BtfDecoder` for records of the type with the given
+    /// ID.
+    pub fn new(btf: &'btf Btf<'btf>, ty: TypeId) -> Result<Self> {
+        let size = btf.type_size(ty)?;
+        Ok(Self { btf, ty, size })
+    }
+
+    /// Create a new `BtfDecoder` for records of the type with the given
+    /// name, e.g., the name of a `struct`.
+    pub fn with_type_name(btf: &'btf Btf<'btf>, name: &str) -> Result<Self> {
+        let ty = btf
+            .type_by_name::<BtfType<'_>>(name)
+            .ok_or_else(|| {
+                Error::with_io_error(
+                    io::ErrorKind::NotFound,
+                    format!("BTF type `{name}` does not exist"),
+                )
+            })?
+            .type_id();
+        Self::new(btf, ty)
+    }
+}
+
+impl RecordDecoder for BtfDecoder<'_> {
+    type Record = BtfValue;
+
+    fn record_size(&self) -> usize {
+        self.size
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<BtfValue> {
+        self.btf.decode_value(self.ty, data)
+    }
+}
+
+
+/// A reader of fixed-size records from a BPF iterator.
+///
+/// Records are reassembled independently of how the kernel splits the
+/// iterator's output across `read` calls. Because every iterator only
+/// produces a single pass over the kernel objects, [`snapshot`][Self::snapshot]
+/// creates a new iterator for each invocation.
+///
+/// ```no_run
+/// # use libbpf_rs::IterRecords;
+/// # use libbpf_rs::Link;
+/// # use libbpf_rs::PodDecoder;
+/// # fn dump(link: &Link) -> libbpf_rs::Result<()> {
+/// #[repr(C)]
+/// #[derive(Clone, Copy, Debug)]
+/// struct IndexPidPair {
+///     i: u32,
+///     pid: i32,
+/// }
+///
+/// // SAFETY: `IndexPidPair` is valid for any bit pattern.
+/// let decoder = unsafe { PodDecoder::<IndexPidPair>::new() };
+/// let mut records = IterRecords::new(link, decoder)?;
+/// for record in records.snapshot()? {
+///     println!("{record:?}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct IterRecords<'link, D> {
+    link: &'link Link,
+    iter: Iter,
+    decoder: D,
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl<'link, D> IterRecords<'link, D>
+where
+    D: RecordDecoder,
+{
+    /// Create a new `IterRecords` reading records from an iterator
+    /// created from `link`, using `decoder` for decoding them.
+    pub fn new(link: &'link Link, decoder: D) -> Result<Self> {
+        if decoder.record_size() == 0 {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                "iterator records cannot be empty",
+            ))
+        }
+
+        Ok(Self {
+            link,
+            iter: Iter::new(link)?,
+            decoder,
+            buf: Vec::new(),
+            pos: 0,
+            eof: false,
+        })
+    }
+
+    /// Discard any remaining records and create a new iterator, starting
+    /// a new pass over the kernel objects.
+    pub fn restart(&mut self) -> Result<()> {
+        self.iter = Iter::new(self.link)?;
+        let () = self.buf.clear();
+        self.pos = 0;
+        self.eof = false;
+        Ok(())
+    }
+
+    /// Read all records of a new pass over the kernel objects.
+    pub fn snapshot(&mut self) -> Result<Vec<D::Record>> {
+        let () = self.restart()?;
+        self.collect()
+    }
+
+    /// Read the next record, if any.
+    pub fn next_record(&mut self) -> Result<Option<D::Record>> {
+        let size = self.decoder.record_size();
+        loop {
+            if self.buf.len() - self.pos >= size {
+                let data = &self.buf[self.pos..self.pos + size];
+                self.pos += size;
+                return self.decoder.decode(data).map(Some)
+            }
+
+            if self.eof {
+                let remaining = self.buf.len() - self.pos;
+                if remaining > 0 {
+                    let () = self.buf.clear();
+                    self.pos = 0;
+                    return Err(Error::with_invalid_data(format!(
+                        "iterator output ended with truncated record of {remaining} bytes (expected {size})"
+                    )))
+                }
+                return Ok(None)
+            }
+
+            // Move a partial record to the front before reading more.
+            let () = self.buf.copy_within(self.pos.., 0);
+            let () = self.buf.truncate(self.buf.len() - self.pos);
+            self.pos = 0;
+
+            let len = self.buf.len();
+            let () = self.buf.resize(len + READ_CHUNK_SIZE.max(size), 0);
+            let result = self.iter.read(&mut self.buf[len..]);
+            let () = self
+                .buf
+                .truncate(len + result.as_ref().copied().unwrap_or(0));
+            match result {
+                Ok(0) => self.eof = true,
+                Ok(_) => (),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => {
+                    self.eof = true;
+                    return Err(Error::from(err))
+                }
+            }
+        }
+    }
+}
+
+impl<D> Iterator for IterRecords<'_, D>
+where
+    D: RecordDecoder,
+{
+    type Item = Result<D::Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
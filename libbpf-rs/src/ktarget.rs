@@ -0,0 +1,439 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::btf::types;
+use crate::btf::Btf;
+use crate::btf::TypeId;
+use crate::util;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+/// The directory containing the BTF information of the kernel and its
+/// modules.
+const SYSFS_BTF: &str = "/sys/kernel/btf";
+/// Candidate mount points of tracefs.
+const TRACEFS: [&str; 2] = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+/// The list of functions that can not be kprobed.
+const KPROBE_BLACKLIST: &str = "/sys/kernel/debug/kprobes/blacklist";
+/// The maximum number of suggestions reported for an unknown function.
+const MAX_SUGGESTIONS: usize = 5;
+
+
+/// The ways of attaching a program to a kernel function.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KernelProbeKind {
+    /// A kprobe or kretprobe.
+    Kprobe,
+    /// An fentry program.
+    Fentry,
+    /// An fexit program.
+    Fexit,
+}
+
+
+/// Information about a kernel function, as reported by
+/// [`KernelTargets`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KernelFunc {
+    /// The name of the function.
+    pub name: String,
+    /// The module containing the function, if it is not part of the
+    /// kernel image itself.
+    pub module: Option<String>,
+    /// Whether the function has a symbol in `/proc/kallsyms`.
+    ///
+    /// Functions that got inlined into all of their callers do not.
+    pub in_kallsyms: bool,
+    /// Whether the function is listed in `available_filter_functions`,
+    /// meaning that it can be traced by ftrace.
+    ///
+    /// This is `None` if the list could not be read (which generally
+    /// requires elevated privileges).
+    pub traceable: Option<bool>,
+    /// Whether the function is described by kernel or module BTF.
+    pub has_btf: bool,
+    /// Whether the function is on the kprobe blacklist.
+    pub kprobe_blacklisted: bool,
+}
+
+impl KernelFunc {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+
+/// A catalog of the kernel functions available as attach targets.
+///
+/// The catalog is assembled from `/proc/kallsyms`, the ftrace list of
+/// `available_filter_functions`, the kprobe blacklist, as well as the
+/// BTF information of the kernel and its modules.
+#[derive(Clone, Debug, Default)]
+pub struct KernelTargets {
+    funcs: BTreeMap<String, KernelFunc>,
+}
+
+impl KernelTargets {
+    /// Load the catalog for the running kernel.
+    ///
+    /// Only `/proc/kallsyms` is required to be readable; all other
+    /// sources are consulted on a best effort basis.
+    pub fn load() -> Result<Self> {
+        let kallsyms =
+            fs::read_to_string("/proc/kallsyms").context("failed to read `/proc/kallsyms`")?;
+        let filter_funcs = TRACEFS.iter().find_map(|dir| {
+            fs::read_to_string(Path::new(dir).join("available_filter_functions")).ok()
+        });
+        let blacklist = fs::read_to_string(KPROBE_BLACKLIST).ok();
+        let btf_funcs = btf_funcs();
+
+        Ok(Self::from_sources(
+            &kallsyms,
+            filter_funcs.as_deref(),
+            blacklist.as_deref(),
+            btf_funcs,
+        ))
+    }
+
+    fn from_sources(
+        kallsyms: &str,
+        filter_funcs: Option<&str>,
+        blacklist: Option<&str>,
+        btf_funcs: Vec<(String, Option<String>)>,
+    ) -> Self {
+        let mut funcs = BTreeMap::<String, KernelFunc>::new();
+
+        // Lines are of the form `<addr> <type> <name>[ [<module>]]`.
+        for line in kallsyms.lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(_addr), Some(ty), Some(name)) = (fields.next(), fields.next(), fields.next())
+            else {
+                continue
+            };
+            if !matches!(ty, "t" | "T") {
+                continue
+            }
+            let module = fields.next().map(strip_module);
+            let func = funcs
+                .entry(name.to_string())
+                .or_insert_with(|| KernelFunc::new(name));
+            func.in_kallsyms = true;
+            if func.module.is_none() {
+                func.module = module;
+            }
+        }
+
+        for (name, module) in btf_funcs {
+            let func = funcs
+                .entry(name.clone())
+                .or_insert_with(|| KernelFunc::new(&name));
+            func.has_btf = true;
+            if func.module.is_none() {
+                func.module = module;
+            }
+        }
+
+        if let Some(filter_funcs) = filter_funcs {
+            let () = funcs
+                .values_mut()
+                .for_each(|func| func.traceable = Some(false));
+            // Lines are of the form `<name>[ [<module>]]`.
+            for name in filter_funcs
+                .lines()
+                .filter_map(|line| line.split_whitespace().next())
+            {
+                if let Some(func) = funcs.get_mut(name) {
+                    func.traceable = Some(true);
+                }
+            }
+        }
+
+        // Lines are of the form `<start>-<end>\t<name>`.
+        if let Some(blacklist) = blacklist {
+            for name in blacklist
+                .lines()
+                .filter_map(|line| line.split_whitespace().nth(1))
+            {
+                if let Some(func) = funcs.get_mut(name) {
+                    func.kprobe_blacklisted = true;
+                }
+            }
+        }
+
+        Self { funcs }
+    }
+
+    /// Look up the function with the given name.
+    pub fn func(&self, name: &str) -> Option<&KernelFunc> {
+        self.funcs.get(name)
+    }
+
+    /// Retrieve all functions whose name matches the glob `pattern`,
+    /// e.g., `tcp_*`, sorted by name.
+    pub fn matching<'slf>(
+        &'slf self,
+        pattern: &'slf str,
+    ) -> impl Iterator<Item = &'slf KernelFunc> + 'slf {
+        self.funcs
+            .values()
+            .filter(move |func| util::glob_match(pattern, &func.name))
+    }
+
+    /// Check whether a program can be attached to the function `name` in
+    /// the provided way.
+    pub fn can_attach(&self, name: &str, kind: KernelProbeKind) -> bool {
+        self.check(name, kind).is_ok()
+    }
+
+    /// Check whether a program can be attached to the function `name` in
+    /// the provided way, reporting the reason if it can not.
+    pub fn check(&self, name: &str, kind: KernelProbeKind) -> Result<()> {
+        let Some(func) = self.funcs.get(name) else {
+            let mut msg =
+                format!("kernel function `{name}` does not exist; it may have been renamed");
+            let clones = self.clones(name);
+            if !clones.is_empty() {
+                msg += &format!(
+                    "; it is only available as compiler generated clone: {}",
+                    clones.join(", ")
+                );
+            } else {
+                let suggestions = self.suggest(name);
+                if !suggestions.is_empty() {
+                    msg += &format!("; did you mean: {}?", suggestions.join(", "));
+                }
+            }
+            return Err(Error::with_io_error(io::ErrorKind::NotFound, msg))
+        };
+
+        let problem = match kind {
+            KernelProbeKind::Kprobe if !func.in_kallsyms => {
+                Some("has no symbol in `/proc/kallsyms`; it may have been inlined")
+            }
+            KernelProbeKind::Kprobe if func.kprobe_blacklisted => {
+                Some("is on the kprobe blacklist")
+            }
+            KernelProbeKind::Fentry | KernelProbeKind::Fexit if !func.has_btf => {
+                Some("is not described by BTF")
+            }
+            KernelProbeKind::Fentry | KernelProbeKind::Fexit if func.traceable == Some(false) => {
+                Some("can not be traced by ftrace; it may be marked `notrace`")
+            }
+            _ => None,
+        };
+
+        match problem {
+            Some(problem) => Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!("kernel function `{name}` {problem}"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Find the compiler generated clones (e.g., `name.isra.0` or
+    /// `name.constprop.0`) of the function `name`.
+    fn clones(&self, name: &str) -> Vec<&str> {
+        let prefix = format!("{name}.");
+        self.funcs
+            .range(prefix.clone()..)
+            .map(|(name, _func)| name.as_str())
+            .take_while(|name| name.starts_with(&prefix))
+            .collect()
+    }
+
+    /// Suggest the names of existing functions that are close to `name`,
+    /// ordered by similarity.
+    pub fn suggest(&self, name: &str) -> Vec<&str> {
+        let max_distance = (name.len() / 4).max(2);
+        let mut candidates = self
+            .funcs
+            .keys()
+            .filter_map(|candidate| {
+                let distance = edit_distance(name, candidate);
+                (distance <= max_distance).then_some((distance, candidate.as_str()))
+            })
+            .collect::<Vec<_>>();
+        let () = candidates.sort();
+        candidates
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_distance, candidate)| candidate)
+            .collect()
+    }
+}
+
+
+/// Strip the brackets surrounding a module name in `/proc/kallsyms`.
+fn strip_module(module: &str) -> String {
+    module
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string()
+}
+
+/// Collect the names of all functions described by kernel and module
+/// BTF, along with the containing module.
+fn btf_funcs() -> Vec<(String, Option<String>)> {
+    fn funcs<'btf>(btf: &'btf Btf<'_>, start: usize) -> impl Iterator<Item = String> + 'btf {
+        (start..btf.len())
+            .filter_map(|id| btf.type_by_id::<types::Func<'_>>(TypeId::from(id as u32)))
+            .filter_map(|func| func.name().map(|name| name.to_string_lossy().into_owned()))
+    }
+
+    let Ok(vmlinux) = Btf::from_vmlinux() else {
+        return Vec::new()
+    };
+    let mut result = funcs(&vmlinux, 1)
+        .map(|name| (name, None))
+        .collect::<Vec<_>>();
+
+    let Ok(dir) = fs::read_dir(SYSFS_BTF) else {
+        return result
+    };
+    for entry in dir.flatten() {
+        let module = entry.file_name().to_string_lossy().into_owned();
+        if module == "vmlinux" {
+            continue
+        }
+        if let Ok(btf) = Btf::from_split_path(entry.path(), &vmlinux) {
+            let () =
+                result.extend(funcs(&btf, vmlinux.len()).map(|name| (name, Some(module.clone()))));
+        }
+    }
+    result
+}
+
+/// Compute the Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.bytes().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                prev.min(row[j]).min(cur) + 1
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// Augment the error `err` of a failed attachment to the kernel function
+/// `name` with the reason reported by [`KernelTargets::check`], if any.
+pub(crate) fn explain_attach_error(err: Error, name: &str, kind: KernelProbeKind) -> Error {
+    // The catalog is deliberately built anew for every error: this is a
+    // cold path and a cached catalog would miss functions of modules
+    // loaded in the meantime.
+    match KernelTargets::load().map(|targets| targets.check(name, kind)) {
+        Ok(Err(reason)) => err.context(reason.to_string()),
+        Ok(Ok(())) | Err(_) => err,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn targets() -> KernelTargets {
+        let kallsyms = r#"0000000000000000 T do_sys_open
+0000000000000000 t do_sys_openat2
+0000000000000000 t tcp_v4_rcv
+0000000000000000 t tcp_v6_rcv
+0000000000000000 t ext4_file_open.isra.0
+0000000000000000 t nf_hook_slow [nf_tables]
+0000000000000000 D some_data
+"#;
+        let filter_funcs = r#"do_sys_openat2
+tcp_v4_rcv
+tcp_v6_rcv
+nf_hook_slow [nf_tables]
+"#;
+        let blacklist = "0x0-0x10\tdo_sys_open\n";
+        let btf_funcs = vec![
+            ("do_sys_open".to_string(), None),
+            ("do_sys_openat2".to_string(), None),
+            ("tcp_v4_rcv".to_string(), None),
+            ("inlined_helper".to_string(), None),
+            ("nf_hook_slow".to_string(), Some("nf_tables".to_string())),
+        ];
+        KernelTargets::from_sources(kallsyms, Some(filter_funcs), Some(blacklist), btf_funcs)
+    }
+
+    /// Check that we assemble function information from all sources.
+    #[test]
+    fn catalog_assembly() {
+        let targets = targets();
+        assert_eq!(targets.func("some_data"), None);
+
+        let func = targets.func("nf_hook_slow").unwrap();
+        assert_eq!(func.module.as_deref(), Some("nf_tables"));
+        assert!(func.in_kallsyms);
+        assert!(func.has_btf);
+        assert_eq!(func.traceable, Some(true));
+
+        let func = targets.func("do_sys_open").unwrap();
+        assert!(func.kprobe_blacklisted);
+        assert_eq!(func.traceable, Some(false));
+
+        let names = targets
+            .matching("tcp_v*_rcv")
+            .map(|func| func.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["tcp_v4_rcv", "tcp_v6_rcv"]);
+    }
+
+    /// Check that we correctly determine attachability.
+    #[test]
+    fn attachability() {
+        let targets = targets();
+        assert!(targets.can_attach("do_sys_openat2", KernelProbeKind::Kprobe));
+        assert!(targets.can_attach("do_sys_openat2", KernelProbeKind::Fentry));
+        assert!(targets.can_attach("tcp_v4_rcv", KernelProbeKind::Fexit));
+        // Not described by BTF.
+        assert!(targets.can_attach("tcp_v6_rcv", KernelProbeKind::Kprobe));
+        assert!(!targets.can_attach("tcp_v6_rcv", KernelProbeKind::Fentry));
+        // Blacklisted and not traceable.
+        assert!(!targets.can_attach("do_sys_open", KernelProbeKind::Kprobe));
+        assert!(!targets.can_attach("do_sys_open", KernelProbeKind::Fentry));
+        // Inlined.
+        assert!(!targets.can_attach("inlined_helper", KernelProbeKind::Kprobe));
+
+        let err = targets
+            .check("ext4_file_open", KernelProbeKind::Kprobe)
+            .unwrap_err();
+        assert!(err.to_string().contains("ext4_file_open.isra.0"), "{err}");
+
+        let err = targets
+            .check("tcp_v5_rcv", KernelProbeKind::Kprobe)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("did you mean: tcp_v4_rcv, tcp_v6_rcv?"),
+            "{err}"
+        );
+    }
+
+    /// Check that we compute edit distances correctly.
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("tcp_v4_rcv", "tcp_v6_rcv"), 1);
+    }
+}
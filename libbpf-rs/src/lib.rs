@@ -78,36 +78,78 @@
 )]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+mod asm;
+#[cfg(feature = "tokio")]
+mod async_buf;
 pub mod btf;
+mod disasm;
+mod elf;
 mod error;
+mod event_source;
 mod iter;
+mod ktarget;
+mod light_skeleton;
 mod link;
 mod linker;
 mod map;
 mod object;
 mod perf_buffer;
 mod print;
+pub mod probe;
 mod program;
 pub mod query;
 mod ringbuf;
+mod ringbuf_reader;
 mod skeleton;
+mod socket;
+mod stats;
+mod sym;
 mod tc;
+mod token;
+mod usdt;
 mod user_ringbuf;
 mod util;
+mod verifier;
 mod xdp;
 
 pub use libbpf_sys;
 
+pub use crate::asm::AluOp;
+pub use crate::asm::Assembler;
+pub use crate::asm::JmpOp;
+pub use crate::asm::MemSize;
+pub use crate::asm::Reg;
+#[cfg(feature = "tokio")]
+pub use crate::async_buf::AsyncPerfBuffer;
+#[cfg(feature = "tokio")]
+pub use crate::async_buf::AsyncRingBuffer;
+#[cfg(feature = "tokio")]
+pub use crate::async_buf::PerfBufferStream;
+#[cfg(feature = "tokio")]
+pub use crate::async_buf::RingBufferStream;
 pub use crate::btf::Btf;
 pub use crate::btf::HasSize;
 pub use crate::btf::ReferencesType;
+pub use crate::disasm::Disassembler;
+pub use crate::disasm::MapRefKind;
 pub use crate::error::Error;
 pub use crate::error::ErrorExt;
 pub use crate::error::ErrorKind;
 pub use crate::error::Result;
+pub use crate::event_source::PerfBufferSource;
+pub use crate::event_source::PerfEvent;
+pub use crate::event_source::RingBufferSource;
+pub use crate::iter::BtfDecoder;
 pub use crate::iter::Iter;
+pub use crate::iter::IterRecords;
+pub use crate::iter::PodDecoder;
+pub use crate::iter::RecordDecoder;
+pub use crate::ktarget::KernelFunc;
+pub use crate::ktarget::KernelProbeKind;
+pub use crate::ktarget::KernelTargets;
 pub use crate::link::Link;
 pub use crate::linker::Linker;
+pub use crate::linker::LinkerFileOpts;
 pub use crate::map::Map;
 pub use crate::map::MapCore;
 pub use crate::map::MapFlags;
@@ -132,21 +174,42 @@ pub use crate::print::get_print;
 pub use crate::print::set_print;
 pub use crate::print::PrintCallback;
 pub use crate::print::PrintLevel;
+pub use crate::program::CgroupIterOrder;
 pub use crate::program::Input as ProgramInput;
+pub use crate::program::IterOpts;
+pub use crate::program::NetfilterHook;
+pub use crate::program::NetfilterOpts;
+pub use crate::program::NetfilterProtocolFamily;
 pub use crate::program::OpenProgram;
 pub use crate::program::OpenProgramImpl;
 pub use crate::program::OpenProgramMut;
 pub use crate::program::Output as ProgramOutput;
 pub use crate::program::Program;
 pub use crate::program::ProgramAttachType;
+pub use crate::program::ProgramHandle;
 pub use crate::program::ProgramImpl;
+pub use crate::program::ProgramLoadOpts;
 pub use crate::program::ProgramMut;
 pub use crate::program::ProgramType;
+pub use crate::program::RawTracepointContext;
+pub use crate::program::SkbContext;
 pub use crate::program::TracepointOpts;
 pub use crate::program::UprobeOpts;
 pub use crate::program::UsdtOpts;
+pub use crate::program::XdpContext;
 pub use crate::ringbuf::RingBuffer;
 pub use crate::ringbuf::RingBufferBuilder;
+pub use crate::ringbuf_reader::RingBufferBatch;
+pub use crate::ringbuf_reader::RingBufferReader;
+pub use crate::ringbuf_reader::RingBufferSamples;
+pub use crate::socket::SocketArray;
+pub use crate::socket::SocketAttachment;
+pub use crate::stats::ProgramStats;
+pub use crate::stats::ProgramStatsSampler;
+pub use crate::stats::StatsGuard;
+pub use crate::stats::StatsType;
+pub use crate::sym::Symbol;
+pub use crate::sym::SymbolResolver;
 pub use crate::tc::TcAttachPoint;
 pub use crate::tc::TcHook;
 pub use crate::tc::TcHookBuilder;
@@ -157,10 +220,21 @@ pub use crate::tc::TC_H_INGRESS;
 pub use crate::tc::TC_H_MIN_EGRESS;
 pub use crate::tc::TC_H_MIN_INGRESS;
 pub use crate::tc::TC_INGRESS;
+pub use crate::token::BpfToken;
+pub use crate::token::BpfTokenInfo;
+pub use crate::usdt::UsdtArg;
+pub use crate::usdt::UsdtInspector;
+pub use crate::usdt::UsdtProbe;
 pub use crate::user_ringbuf::UserRingBuffer;
 pub use crate::user_ringbuf::UserRingBufferSample;
 pub use crate::util::num_possible_cpus;
+pub use crate::verifier::VerifierInsn;
+pub use crate::verifier::VerifierLog;
 pub use crate::xdp::Xdp;
+pub use crate::xdp::XdpActions;
+pub use crate::xdp::XdpComponent;
+pub use crate::xdp::XdpComponentOpts;
+pub use crate::xdp::XdpDispatcher;
 pub use crate::xdp::XdpFlags;
 
 
@@ -183,3 +257,14 @@ pub mod skel {
     pub use super::skeleton::Skel;
     pub use super::skeleton::SkelBuilder;
 }
+
+/// Runtime support for light skeletons, i.e., skeletons loading their
+/// BPF object by means of a loader program.
+pub mod light_skel {
+    pub use super::light_skeleton::LightAttach;
+    pub use super::light_skeleton::LightMapSpec;
+    pub use super::light_skeleton::LightProgSpec;
+    pub use super::light_skeleton::LightSkel;
+    pub use super::light_skeleton::LightSkelSpec;
+    pub use super::light_skeleton::OpenLightSkel;
+}
@@ -0,0 +1,550 @@
+//! A minimal runtime for "light" skeletons.
+//!
+//! A light skeleton does not carry the BPF object file itself. Instead,
+//! `libbpf` records all steps necessary for loading the object in a
+//! loader program and accompanying data blob at skeleton generation
+//! time (`bpf_object__gen_loader`). At runtime, the loader program gets
+//! loaded and run once and creates all maps and programs on the object's
+//! behalf. No ELF parsing or relocation happens in user space.
+
+use std::ffi::c_int;
+use std::ffi::OsStr;
+use std::io;
+use std::mem::size_of;
+use std::mem::size_of_val;
+use std::os::fd::AsFd as _;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::ptr;
+use std::ptr::NonNull;
+use std::slice;
+
+use crate::object::c_buf_to_string;
+use crate::util;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Link;
+use crate::MapCore as _;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapType;
+use crate::ProgramHandle;
+use crate::ProgramLoadOpts;
+use crate::ProgramType;
+use crate::Result;
+use crate::VerifierLog;
+
+
+/// The name of the map holding the loader program's data blob.
+const LOADER_MAP_NAME: &str = "__loader.map";
+/// The name of the loader program.
+const LOADER_PROG_NAME: &str = "__loader.prog";
+/// The license the loader program is loaded with.
+const LOADER_LICENSE: &str = "Dual BSD/GPL";
+/// The size of the buffer used for capturing the log of the programs
+/// loaded by the loader program, if requested.
+const LOG_BUF_SIZE: usize = 16 * 1024 * 1024;
+
+
+/// The context header shared with the loader program; `struct
+/// bpf_loader_ctx`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct LoaderCtx {
+    sz: u32,
+    flags: u32,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+}
+
+/// The per-map part of the loader program's context; `struct
+/// bpf_map_desc`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct MapDesc {
+    /// Output: the file descriptor of the created map.
+    map_fd: c_int,
+    /// Input: the number of entries to create the map with, if not
+    /// zero.
+    max_entries: u32,
+    /// Input: the address of the map's initial contents.
+    initial_value: u64,
+}
+
+/// The per-program part of the loader program's context; `struct
+/// bpf_prog_desc`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct ProgDesc {
+    /// Output: the file descriptor of the loaded program.
+    prog_fd: c_int,
+}
+
+
+/// How a program of a light skeleton gets attached by
+/// [`LightSkel::attach`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightAttach {
+    /// The program does not support automatic attachment.
+    None,
+    /// Attach to the raw tracepoint with the given name.
+    RawTracepoint(&'static str),
+    /// Attach a tracing or LSM program to the target it was loaded
+    /// for (`fentry`, `fexit`, `tp_btf`, `lsm`, ...).
+    Tracing,
+    /// Create a BPF iterator link.
+    Iter,
+}
+
+
+/// The description of a map of a light skeleton.
+#[derive(Clone, Copy, Debug)]
+pub struct LightMapSpec {
+    /// The name of the map.
+    pub name: &'static str,
+    /// For maps backing global data sections: the initial contents.
+    pub initial_value: Option<&'static [u8]>,
+    /// Whether the map's contents are read-only from user space once
+    /// loaded.
+    pub read_only: bool,
+}
+
+
+/// The description of a program of a light skeleton.
+#[derive(Clone, Copy, Debug)]
+pub struct LightProgSpec {
+    /// The name of the program.
+    pub name: &'static str,
+    /// How the program gets attached.
+    pub attach: LightAttach,
+}
+
+
+/// The description of a light skeleton, as generated by
+/// `libbpf-cargo`.
+#[derive(Clone, Copy, Debug)]
+pub struct LightSkelSpec {
+    /// The loader program's data blob.
+    pub data: &'static [u8],
+    /// The loader program's instructions, in raw form.
+    pub insns: &'static [u8],
+    /// The object's maps, in the order `libbpf` knows them.
+    pub maps: &'static [LightMapSpec],
+    /// The object's programs, in the order `libbpf` knows them.
+    pub progs: &'static [LightProgSpec],
+}
+
+
+/// A memory mapping, unmapped on drop.
+#[derive(Debug)]
+struct Mmap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(len: usize, prot: c_int, flags: c_int, fd: c_int) -> Result<Self> {
+        let ptr = unsafe { libc::mmap(ptr::null_mut(), len, prot, flags, fd, 0) };
+        if ptr == libc::MAP_FAILED {
+            return Err(Error::from(io::Error::last_os_error()))
+        }
+        // SANITY: `mmap` never returns NULL on success for a NULL hint.
+        let ptr = NonNull::new(ptr.cast()).unwrap();
+        Ok(Self { ptr, len })
+    }
+
+    /// Create an anonymous mapping of `len` bytes, initialized with
+    /// `data`.
+    fn anonymous(len: usize, data: &[u8]) -> Result<Self> {
+        let mmap = Self::new(
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+            -1,
+        )?;
+        // SAFETY: The mapping is writable and at least `data.len()` bytes
+        //         large.
+        let () = unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), mmap.ptr.as_ptr(), data.len().min(len))
+        };
+        Ok(mmap)
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: The mapping is valid for `len` bytes for as long as we
+        //         exist.
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: The mapping is valid for `len` bytes for as long as we
+        //         exist.
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        let _ret = unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
+
+// SAFETY: `Mmap` merely owns the mapping; synchronization of accesses to
+//         the mapped memory is up to users, as is the case for the
+//         global data of regular skeletons.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+
+/// Round `len` up to the next multiple of the system's page size.
+fn mmap_size(len: usize) -> usize {
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    (len + page_size - 1) / page_size * page_size
+}
+
+
+/// A light skeleton that has not yet been loaded.
+///
+/// Global data can be adjusted and maps resized before
+/// [loading][OpenLightSkel::load] it.
+#[derive(Debug)]
+pub struct OpenLightSkel {
+    spec: &'static LightSkelSpec,
+    max_entries: Vec<u32>,
+    /// The initial contents of each map backing a global data section.
+    data: Vec<Option<Mmap>>,
+    log_level: u32,
+}
+
+impl OpenLightSkel {
+    /// "Open" the light skeleton described by `spec`.
+    pub fn open(spec: &'static LightSkelSpec) -> Result<Self> {
+        let data = spec
+            .maps
+            .iter()
+            .map(|map| {
+                map.initial_value
+                    .map(|value| Mmap::anonymous(mmap_size(value.len()), value))
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let slf = Self {
+            spec,
+            max_entries: vec![0; spec.maps.len()],
+            data,
+            log_level: 0,
+        };
+        Ok(slf)
+    }
+
+    /// Set the number of entries of the map at index `map`.
+    pub fn set_max_entries(&mut self, map: usize, max_entries: u32) -> Result<()> {
+        let entry = self.max_entries.get_mut(map).ok_or_else(|| {
+            Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!("invalid map index {map}"),
+            )
+        })?;
+        *entry = max_entries;
+        Ok(())
+    }
+
+    /// Set the verifier log level used when loading the skeleton's
+    /// programs.
+    ///
+    /// With a non-zero level, the log is attached to the error reported
+    /// in case loading fails.
+    pub fn set_log_level(&mut self, log_level: u32) {
+        self.log_level = log_level;
+    }
+
+    /// Retrieve the initial contents of the global data section backed
+    /// by the map at index `map`.
+    pub fn map_data(&self, map: usize) -> Option<&[u8]> {
+        self.data.get(map)?.as_ref().map(Mmap::as_slice)
+    }
+
+    /// Retrieve the initial contents of the global data section backed
+    /// by the map at index `map`, for modification.
+    pub fn map_data_mut(&mut self, map: usize) -> Option<&mut [u8]> {
+        self.data.get_mut(map)?.as_mut().map(Mmap::as_mut_slice)
+    }
+
+    /// Run the loader program with the context `ctx`.
+    fn run_loader(&self, ctx: &mut [u64], log: &mut [u8]) -> Result<()> {
+        let spec = self.spec;
+        let create_opts = libbpf_sys::bpf_map_create_opts {
+            sz: size_of::<libbpf_sys::bpf_map_create_opts>() as _,
+            ..Default::default()
+        };
+        let map = MapHandle::create(
+            MapType::Array,
+            Some(LOADER_MAP_NAME),
+            size_of::<u32>() as _,
+            spec.data.len() as _,
+            1,
+            &create_opts,
+        )
+        .context("failed to create loader map")?;
+        let () = map
+            .update(&0u32.to_ne_bytes(), spec.data, MapFlags::ANY)
+            .context("failed to update loader map")?;
+
+        let insns = spec
+            .insns
+            .chunks_exact(size_of::<libbpf_sys::bpf_insn>())
+            // SAFETY: Each chunk is exactly as large as an instruction.
+            .map(|insn| unsafe { ptr::read_unaligned(insn.as_ptr().cast()) })
+            .collect::<Vec<libbpf_sys::bpf_insn>>();
+        let fd_array = [map.as_fd().as_raw_fd()];
+        let opts = ProgramLoadOpts {
+            prog_flags: libbpf_sys::BPF_F_SLEEPABLE,
+            ..Default::default()
+        };
+        let prog = ProgramHandle::load_with(
+            ProgramType::Syscall,
+            Some(LOADER_PROG_NAME),
+            LOADER_LICENSE,
+            &insns,
+            &opts,
+            |load_opts| load_opts.fd_array = fd_array.as_ptr(),
+        )
+        .context("failed to load loader program")?;
+
+        let mut run_opts = libbpf_sys::bpf_test_run_opts {
+            sz: size_of::<libbpf_sys::bpf_test_run_opts>() as _,
+            // The kernel copies the context back after the run, which is
+            // how we learn about the created maps and programs.
+            ctx_in: ctx.as_ptr().cast(),
+            ctx_size_in: size_of_val(ctx) as _,
+            ..Default::default()
+        };
+        let ret =
+            unsafe { libbpf_sys::bpf_prog_test_run_opts(prog.as_fd().as_raw_fd(), &mut run_opts) };
+        let () = util::parse_ret(ret).context("failed to execute loader program")?;
+
+        let retval = run_opts.retval as i32;
+        if retval < 0 {
+            let err = Error::from_raw_os_error(-retval);
+            let log = c_buf_to_string(log);
+            let err = if log.is_empty() {
+                err
+            } else {
+                // The log belongs to whichever program the loader failed
+                // to load; its name is not known to us.
+                err.with_verifier_log(VerifierLog::new(OsStr::new(""), log))
+            };
+            return Err(err).context("loader program failed")
+        }
+        Ok(())
+    }
+
+    /// Load the skeleton's maps and programs by means of its loader
+    /// program.
+    pub fn load(self) -> Result<LightSkel> {
+        let spec = self.spec;
+        let ctx_size = size_of::<LoaderCtx>()
+            + spec.maps.len() * size_of::<MapDesc>()
+            + spec.progs.len() * size_of::<ProgDesc>();
+        // Use a buffer of `u64`s to get the context's alignment right.
+        let mut ctx = vec![0u64; (ctx_size + size_of::<u64>() - 1) / size_of::<u64>()];
+        let mut log = vec![0u8; if self.log_level != 0 { LOG_BUF_SIZE } else { 0 }];
+
+        let base = ctx.as_mut_ptr().cast::<u8>();
+        let maps = unsafe { base.add(size_of::<LoaderCtx>()) }.cast::<MapDesc>();
+        let progs = unsafe { maps.add(spec.maps.len()) }.cast::<ProgDesc>();
+
+        let header = LoaderCtx {
+            sz: ctx_size as _,
+            flags: 0,
+            log_level: self.log_level,
+            log_size: log.len() as _,
+            log_buf: log.as_mut_ptr() as u64,
+        };
+        // SAFETY: All writes are within the bounds of `ctx`.
+        let () = unsafe { ptr::write_unaligned(base.cast::<LoaderCtx>(), header) };
+        for (idx, data) in self.data.iter().enumerate() {
+            let desc = MapDesc {
+                map_fd: 0,
+                max_entries: self.max_entries[idx],
+                initial_value: data
+                    .as_ref()
+                    .map(|data| data.ptr.as_ptr() as u64)
+                    .unwrap_or(0),
+            };
+            let () = unsafe { ptr::write_unaligned(maps.add(idx), desc) };
+        }
+
+        let () = self.run_loader(&mut ctx, &mut log)?;
+
+        // Take ownership of all file descriptors first, so that they are
+        // closed in case anything fails below.
+        let map_fds = (0..spec.maps.len())
+            .map(|idx| unsafe { ptr::read_unaligned(maps.add(idx)) }.map_fd)
+            .map(|fd| (fd > 0).then(|| unsafe { OwnedFd::from_raw_fd(fd) }))
+            .collect::<Vec<_>>();
+        let prog_fds = (0..spec.progs.len())
+            .map(|idx| unsafe { ptr::read_unaligned(progs.add(idx)) }.prog_fd)
+            .map(|fd| (fd > 0).then(|| unsafe { OwnedFd::from_raw_fd(fd) }))
+            .collect::<Vec<_>>();
+
+        let mut data = Vec::with_capacity(spec.maps.len());
+        for ((map, fd), initial) in spec.maps.iter().zip(&map_fds).zip(&self.data) {
+            let mmap = match (fd, initial) {
+                (Some(fd), Some(initial)) => {
+                    let prot = if map.read_only {
+                        libc::PROT_READ
+                    } else {
+                        libc::PROT_READ | libc::PROT_WRITE
+                    };
+                    let mmap = Mmap::new(initial.len, prot, libc::MAP_SHARED, fd.as_raw_fd())
+                        .with_context(|| format!("failed to mmap map `{}`", map.name))?;
+                    Some(mmap)
+                }
+                _ => None,
+            };
+            let () = data.push(mmap);
+        }
+
+        let maps = spec
+            .maps
+            .iter()
+            .zip(map_fds)
+            .map(|(map, fd)| {
+                let fd = fd.ok_or_else(|| {
+                    Error::with_invalid_data(format!("map `{}` was not created", map.name))
+                })?;
+                MapHandle::from_fd(fd)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let progs = spec
+            .progs
+            .iter()
+            .zip(prog_fds)
+            .map(|(prog, fd)| {
+                let fd = fd.ok_or_else(|| {
+                    Error::with_invalid_data(format!("program `{}` was not loaded", prog.name))
+                })?;
+                ProgramHandle::from_fd(fd)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let slf = LightSkel {
+            spec,
+            maps,
+            progs,
+            data,
+            links: (0..spec.progs.len()).map(|_| None).collect(),
+        };
+        Ok(slf)
+    }
+}
+
+
+/// A loaded light skeleton.
+#[derive(Debug)]
+pub struct LightSkel {
+    spec: &'static LightSkelSpec,
+    maps: Vec<MapHandle>,
+    progs: Vec<ProgramHandle>,
+    /// The memory mapped contents of each map backing a global data
+    /// section.
+    data: Vec<Option<Mmap>>,
+    links: Vec<Option<Link>>,
+}
+
+impl LightSkel {
+    /// Retrieve the map at index `map`.
+    pub fn map(&self, map: usize) -> Option<&MapHandle> {
+        self.maps.get(map)
+    }
+
+    /// Retrieve the program at index `prog`.
+    pub fn prog(&self, prog: usize) -> Option<&ProgramHandle> {
+        self.progs.get(prog)
+    }
+
+    /// Retrieve the program at index `prog`, mutably.
+    pub fn prog_mut(&mut self, prog: usize) -> Option<&mut ProgramHandle> {
+        self.progs.get_mut(prog)
+    }
+
+    /// Retrieve the contents of the global data section backed by the
+    /// map at index `map`.
+    pub fn map_data(&self, map: usize) -> Option<&[u8]> {
+        self.data.get(map)?.as_ref().map(Mmap::as_slice)
+    }
+
+    /// Retrieve the contents of the global data section backed by the
+    /// map at index `map`, for modification.
+    ///
+    /// `None` is returned for read-only sections.
+    pub fn map_data_mut(&mut self, map: usize) -> Option<&mut [u8]> {
+        if self.spec.maps.get(map)?.read_only {
+            return None
+        }
+        self.data.get_mut(map)?.as_mut().map(Mmap::as_mut_slice)
+    }
+
+    /// Attach the program at index `prog`, as per its
+    /// [`LightAttach`] setting.
+    ///
+    /// Programs not supporting automatic attachment are skipped.
+    pub fn attach_prog(&mut self, prog: usize) -> Result<()> {
+        let spec = self.spec.progs.get(prog).ok_or_else(|| {
+            Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!("invalid program index {prog}"),
+            )
+        })?;
+        let handle = &self.progs[prog];
+        let link = match spec.attach {
+            LightAttach::None => return Ok(()),
+            LightAttach::RawTracepoint(tp_name) => handle.attach_raw_tracepoint(tp_name)?,
+            LightAttach::Tracing => {
+                let fd = unsafe {
+                    libbpf_sys::bpf_raw_tracepoint_open(ptr::null(), handle.as_fd().as_raw_fd())
+                };
+                let fd = util::parse_ret_i32(fd)?;
+                // SAFETY: `bpf_raw_tracepoint_open` returns a valid file
+                //         descriptor on success, which we now own.
+                let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+                Link::from_fd(fd)?
+            }
+            LightAttach::Iter => handle.attach_iter()?,
+        };
+        self.links[prog] = Some(link);
+        Ok(())
+    }
+
+    /// Attach all programs supporting automatic attachment.
+    pub fn attach(&mut self) -> Result<()> {
+        for prog in 0..self.progs.len() {
+            let () = self.attach_prog(prog).with_context(|| {
+                format!("failed to attach program `{}`", self.spec.progs[prog].name)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Retrieve the link of the program at index `prog`, if it is
+    /// attached.
+    pub fn link(&self, prog: usize) -> Option<&Link> {
+        self.links.get(prog)?.as_ref()
+    }
+
+    /// Detach the program at index `prog`, if it is attached.
+    pub fn detach_prog(&mut self, prog: usize) {
+        if let Some(link) = self.links.get_mut(prog) {
+            *link = None;
+        }
+    }
+}
+
+// Make sure our layouts match the ones the loader program expects.
+const _: () = assert!(size_of::<LoaderCtx>() == 24);
+const _: () = assert!(size_of::<MapDesc>() == 16);
+const _: () = assert!(size_of::<ProgDesc>() == 4);
@@ -1,17 +1,54 @@
+use std::ffi::c_char;
+use std::ffi::c_int;
 use std::fmt::Debug;
+use std::io;
+use std::mem::size_of;
 use std::os::unix::io::AsFd;
 use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::IntoRawFd as _;
+use std::os::unix::io::OwnedFd;
 use std::path::Path;
 use std::path::PathBuf;
+use std::ptr;
 use std::ptr::NonNull;
 
 use crate::util;
 use crate::util::validate_bpf_ret;
 use crate::AsRawLibbpf;
+use crate::Error;
 use crate::ErrorExt as _;
 use crate::Program;
 use crate::Result;
 
+/// The layout of libbpf's (internal) `struct bpf_link`.
+///
+/// libbpf does not provide a way to wrap an existing link file
+/// descriptor, so we set up the object ourselves, the same way
+/// `bpf_link__open` does. The definition has to be kept in sync with
+/// the one of the libbpf version we link against.
+#[repr(C)]
+struct RawLink {
+    detach: Option<unsafe extern "C" fn(*mut libbpf_sys::bpf_link) -> c_int>,
+    dealloc: Option<unsafe extern "C" fn(*mut libbpf_sys::bpf_link)>,
+    pin_path: *mut c_char,
+    fd: c_int,
+    disconnected: bool,
+}
+
+/// The equivalent of libbpf's `bpf_link__detach_fd`.
+unsafe extern "C" fn detach_fd(link: *mut libbpf_sys::bpf_link) -> c_int {
+    // SAFETY: The function is only ever installed in `RawLink` objects.
+    let link = unsafe { &*link.cast::<RawLink>() };
+    if unsafe { libc::close(link.fd) } < 0 {
+        -io::Error::last_os_error()
+            .raw_os_error()
+            .unwrap_or(libc::EINVAL)
+    } else {
+        0
+    }
+}
+
+
 /// Represents an attached [`Program`].
 ///
 /// This struct is used to model ownership. The underlying program will be detached
@@ -41,6 +78,29 @@ impl Link {
         Ok(slf)
     }
 
+    /// Create a [`Link`] from a file descriptor referencing a BPF link,
+    /// taking ownership of it.
+    pub(crate) fn from_fd(fd: OwnedFd) -> Result<Self> {
+        // libbpf releases the object using `free`, so we have to allocate
+        // it using the C allocator.
+        let ptr = unsafe { libc::calloc(1, size_of::<RawLink>()) }.cast::<RawLink>();
+        let ptr = NonNull::new(ptr).ok_or_else(|| {
+            Error::with_io_error(io::ErrorKind::OutOfMemory, "failed to allocate link")
+        })?;
+        let () = unsafe {
+            ptr.as_ptr().write(RawLink {
+                detach: Some(detach_fd),
+                dealloc: None,
+                pin_path: ptr::null_mut(),
+                fd: fd.into_raw_fd(),
+                disconnected: false,
+            })
+        };
+        // SAFETY: We just initialized the object the way libbpf would.
+        let slf = unsafe { Self::new(ptr.cast()) };
+        Ok(slf)
+    }
+
     /// Takes ownership from pointer.
     ///
     /// # Safety
@@ -137,3 +197,28 @@ impl Drop for Link {
         let _ = unsafe { libbpf_sys::bpf_link__destroy(self.ptr.as_ptr()) };
     }
 }
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd as _;
+
+
+    /// Check that a [`Link`] created from a file descriptor is
+    /// understood by libbpf.
+    #[test]
+    fn link_from_fd() {
+        let fd = OwnedFd::from(File::open("/dev/null").unwrap());
+        let raw_fd = fd.as_raw_fd();
+        let mut link = Link::from_fd(fd).unwrap();
+        assert_eq!(link.as_fd().as_raw_fd(), raw_fd);
+        assert_eq!(link.pin_path(), None);
+
+        // Pinning a non-BPF file descriptor has to fail gracefully.
+        assert!(link.pin("/sys/fs/bpf/libbpf-rs-link-from-fd").is_err());
+        assert_eq!(link.pin_path(), None);
+    }
+}
@@ -1,16 +1,83 @@
+use std::fs::File;
+use std::io;
+use std::io::Read as _;
+use std::io::Seek as _;
+use std::io::SeekFrom;
+use std::io::Write as _;
+use std::mem::size_of;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
 use std::path::Path;
+use std::path::PathBuf;
 use std::ptr::null_mut;
 use std::ptr::NonNull;
 
 use crate::util::path_to_cstring;
+use crate::util::str_to_cstring;
 use crate::util::validate_bpf_ret;
 use crate::AsRawLibbpf;
 use crate::Error;
 use crate::ErrorExt as _;
 use crate::Result;
 
+
+/// Options to optionally be provided when adding a file or buffer to a
+/// [`Linker`].
+#[derive(Clone, Debug, Default)]
+pub struct LinkerFileOpts {
+    /// A name identifying the input in error messages.
+    ///
+    /// Defaults to the path of the file. Buffers are anonymous otherwise.
+    pub name: Option<String>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl From<&LinkerFileOpts> for libbpf_sys::bpf_linker_file_opts {
+    fn from(opts: &LinkerFileOpts) -> Self {
+        let LinkerFileOpts {
+            name: _,
+            _non_exhaustive,
+        } = opts;
+
+        #[allow(clippy::needless_update)]
+        libbpf_sys::bpf_linker_file_opts {
+            sz: size_of::<Self>() as _,
+            // bpf_linker_file_opts might have padding fields on some platform
+            ..Default::default()
+        }
+    }
+}
+
+
+/// Create an anonymous, memory backed file with the given name.
+fn memfd(name: &str) -> Result<File> {
+    let name = str_to_cstring(name)?;
+    // SAFETY: `name` is a valid C string.
+    let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(Error::from(io::Error::last_os_error())).context("failed to create memory file");
+    }
+    // SAFETY: `fd` is a valid file descriptor we exclusively own.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    Ok(File::from(fd))
+}
+
+/// Retrieve a path under which `file` can be opened by `libbpf`.
+fn fd_path(file: &File) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()))
+}
+
+
 /// A type used for linking multiple BPF object files into a single one.
 ///
+/// Inputs can be provided as files or as in-memory buffers. The output
+/// is either written to a file or, for a linker created with
+/// [`Linker::in_memory`], kept in memory to be retrieved with
+/// [`Linker::link_to_memory`]. The latter can be fed directly into
+/// [`ObjectBuilder::open_memory`][crate::ObjectBuilder::open_memory].
+///
 /// Please refer to
 /// <https://lwn.net/ml/bpf/20210310040431.916483-6-andrii@kernel.org/> for
 /// additional details.
@@ -18,6 +85,8 @@ use crate::Result;
 pub struct Linker {
     /// The `libbpf` linker object.
     linker: NonNull<libbpf_sys::bpf_linker>,
+    /// The memory file backing the output, if we link in memory.
+    output: Option<File>,
 }
 
 impl Linker {
@@ -26,12 +95,28 @@ impl Linker {
     where
         P: AsRef<Path>,
     {
-        let output = path_to_cstring(output)?;
+        Self::with_output(output.as_ref(), None)
+    }
+
+    /// Instantiate a `Linker` object that links into an in-memory buffer,
+    /// to be retrieved using [`Linker::link_to_memory`].
+    ///
+    /// This functionality relies on `/proc` being mounted.
+    pub fn in_memory() -> Result<Self> {
+        let output = memfd("libbpf-rs-linker")?;
+        Self::with_output(&fd_path(&output), Some(output))
+    }
+
+    fn with_output(path: &Path, output: Option<File>) -> Result<Self> {
+        let path = path_to_cstring(path)?;
         let opts = null_mut();
-        // SAFETY: `output` is a valid pointer and `opts` is accepted as NULL.
-        let ptr = unsafe { libbpf_sys::bpf_linker__new(output.as_ptr(), opts) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach iterator")?;
-        let slf = Self { linker: ptr };
+        // SAFETY: `path` is a valid pointer and `opts` is accepted as NULL.
+        let ptr = unsafe { libbpf_sys::bpf_linker__new(path.as_ptr(), opts) };
+        let ptr = validate_bpf_ret(ptr).context("failed to create linker")?;
+        let slf = Self {
+            linker: ptr,
+            output,
+        };
         Ok(slf)
     }
 
@@ -40,11 +125,56 @@ impl Linker {
     where
         P: AsRef<Path>,
     {
-        let file = path_to_cstring(file)?;
-        let opts = null_mut();
-        // SAFETY: `linker` and `file` are a valid pointers.
+        self.add_file_with_opts(file, &LinkerFileOpts::default())
+    }
+
+    /// Add a file to the set of files to link, using the provided options.
+    pub fn add_file_with_opts<P>(&mut self, file: P, opts: &LinkerFileOpts) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let file = file.as_ref();
+        let result = self.add_path(file, opts);
+        match &opts.name {
+            Some(name) => result.with_context(|| format!("failed to add `{name}` to linker")),
+            None => result.with_context(|| format!("failed to add `{}` to linker", file.display())),
+        }
+    }
+
+    /// Add the BPF object file contained in `buf` to the set of files to
+    /// link.
+    ///
+    /// This functionality relies on `/proc` being mounted.
+    pub fn add_buf(&mut self, buf: &[u8]) -> Result<()> {
+        self.add_buf_with_opts(buf, &LinkerFileOpts::default())
+    }
+
+    /// Add the BPF object file contained in `buf` to the set of files to
+    /// link, using the provided options.
+    ///
+    /// This functionality relies on `/proc` being mounted.
+    pub fn add_buf_with_opts(&mut self, buf: &[u8], opts: &LinkerFileOpts) -> Result<()> {
+        let result = memfd("libbpf-rs-linker-input").and_then(|mut file| {
+            let () = file
+                .write_all(buf)
+                .map_err(Error::from)
+                .context("failed to write BPF object to memory file")?;
+            // `libbpf` opens the file on its own and is done with it
+            // once the call returns.
+            self.add_path(&fd_path(&file), opts)
+        });
+        match &opts.name {
+            Some(name) => result.with_context(|| format!("failed to add `{name}` to linker")),
+            None => result.context("failed to add buffer to linker"),
+        }
+    }
+
+    fn add_path(&mut self, path: &Path, opts: &LinkerFileOpts) -> Result<()> {
+        let path = path_to_cstring(path)?;
+        let opts = libbpf_sys::bpf_linker_file_opts::from(opts);
+        // SAFETY: `linker`, `path`, and `opts` are valid pointers.
         let err =
-            unsafe { libbpf_sys::bpf_linker__add_file(self.linker.as_ptr(), file.as_ptr(), opts) };
+            unsafe { libbpf_sys::bpf_linker__add_file(self.linker.as_ptr(), path.as_ptr(), &opts) };
         if err != 0 {
             Err(Error::from_raw_os_error(err)).context("bpf_linker__add_file failed")
         } else {
@@ -62,6 +192,28 @@ impl Linker {
         }
         Ok(())
     }
+
+    /// Link all BPF object files [added](Self::add_file) to this object into
+    /// a single one, returning its contents.
+    ///
+    /// The linker has to have been created using [`Linker::in_memory`].
+    pub fn link_to_memory(&self) -> Result<Vec<u8>> {
+        let mut output = self.output.as_ref().ok_or_else(|| {
+            Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                "linker does not link into memory",
+            )
+        })?;
+        let () = self.link()?;
+
+        let mut buf = Vec::new();
+        let _cnt = output
+            .seek(SeekFrom::Start(0))
+            .and_then(|_pos| output.read_to_end(&mut buf))
+            .map_err(Error::from)
+            .context("failed to read linked BPF object")?;
+        Ok(buf)
+    }
 }
 
 impl AsRawLibbpf for Linker {
@@ -31,6 +31,7 @@ use crate::util;
 use crate::util::parse_ret_i32;
 use crate::util::validate_bpf_ret;
 use crate::AsRawLibbpf;
+use crate::BpfToken;
 use crate::Error;
 use crate::ErrorExt as _;
 use crate::Link;
@@ -951,6 +952,27 @@ impl MapHandle {
         })
     }
 
+    /// Create a bpf map whose data is not managed by libbpf, using the
+    /// permissions granted by `token`.
+    ///
+    /// See [`MapHandle::create`].
+    pub fn create_with_token<T: AsRef<OsStr>>(
+        map_type: MapType,
+        name: Option<T>,
+        key_size: u32,
+        value_size: u32,
+        max_entries: u32,
+        opts: &libbpf_sys::bpf_map_create_opts,
+        token: &BpfToken,
+    ) -> Result<Self> {
+        let opts = libbpf_sys::bpf_map_create_opts {
+            map_flags: opts.map_flags | libbpf_sys::BPF_F_TOKEN_FD,
+            token_fd: token.as_fd().as_raw_fd() as _,
+            ..*opts
+        };
+        Self::create(map_type, name, key_size, value_size, max_entries, &opts)
+    }
+
     /// Open a previously pinned map from its path.
     ///
     /// # Panics
@@ -990,7 +1012,7 @@ impl MapHandle {
         .and_then(Self::from_fd)
     }
 
-    fn from_fd(fd: OwnedFd) -> Result<Self> {
+    pub(crate) fn from_fd(fd: OwnedFd) -> Result<Self> {
         let info = MapInfo::new(fd.as_fd())?;
         Ok(Self {
             fd,
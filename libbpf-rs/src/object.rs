@@ -1,19 +1,29 @@
 use core::ffi::c_void;
+use std::any::type_name;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::ffi::OsStr;
+use std::io;
 use std::mem;
+use std::mem::align_of;
+use std::mem::size_of;
 use std::os::unix::ffi::OsStrExt as _;
 use std::path::Path;
 use std::ptr;
 use std::ptr::addr_of;
 use std::ptr::NonNull;
 
+use crate::btf;
+use crate::btf::types::DataSec;
+use crate::btf::types::Var;
 use crate::map::map_fd;
+use crate::print::print;
 use crate::set_print;
 use crate::util;
 use crate::util::validate_bpf_ret;
+use crate::BpfToken;
 use crate::Btf;
+use crate::Error;
 use crate::ErrorExt as _;
 use crate::Map;
 use crate::MapMut;
@@ -25,8 +35,24 @@ use crate::PrintLevel;
 use crate::Program;
 use crate::ProgramMut;
 use crate::Result;
+use crate::VerifierLog;
 
 
+/// The size of the buffer used for capturing a program's verifier log.
+///
+/// This is the same size that libbpf uses by default. The buffer is
+/// only written to by the kernel on failure (or when a log level is
+/// set), so most of it will never be backed by physical memory.
+pub(crate) const VERIFIER_LOG_SIZE: usize = u32::MAX as usize >> 8;
+
+
+/// Convert a NUL terminated C string contained in a buffer into a
+/// `String`, replacing invalid UTF-8 sequences.
+pub(crate) fn c_buf_to_string(buf: &[u8]) -> String {
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
 /// An iterator over the maps in a BPF object.
 #[derive(Debug)]
 pub struct MapIter<'obj> {
@@ -81,6 +107,89 @@ impl Iterator for ProgIter<'_> {
 }
 
 
+/// Find the global variable `name` in one of the data sections (`.bss`,
+/// `.data`, `.rodata`, and their custom `.data.*` and `.rodata.*`
+/// counterparts) of `obj`.
+///
+/// On success, the map backing the section is returned along with a
+/// pointer to the variable in the section's memory. The variable is
+/// checked to be of the same size as `T` and to be suitably aligned for
+/// it.
+fn find_global<T>(
+    obj: &libbpf_sys::bpf_object,
+    name: &str,
+) -> Result<(NonNull<libbpf_sys::bpf_map>, NonNull<T>)> {
+    let btf = Btf::from_bpf_object(obj)?.ok_or_else(|| {
+        Error::with_io_error(io::ErrorKind::NotFound, "BPF object does not contain BTF")
+    })?;
+
+    for map in MapIter::new(obj) {
+        // SAFETY: `map` is a valid map pointer provided by libbpf.
+        if !unsafe { libbpf_sys::bpf_map__is_internal(map.as_ptr()) } {
+            continue
+        }
+
+        let type_id = unsafe { libbpf_sys::bpf_map__btf_value_type_id(map.as_ptr()) };
+        let Some(datasec) = btf.type_by_id::<DataSec<'_>>(type_id.into()) else {
+            continue
+        };
+
+        let found = (0..datasec.len())
+            .filter_map(|idx| datasec.get(idx))
+            .find(|info| {
+                btf.type_by_id::<Var<'_>>(info.ty)
+                    .and_then(|var| var.name())
+                    .map(|var_name| var_name == name)
+                    .unwrap_or(false)
+            });
+        let Some(info) = found else { continue };
+
+        if info.size != size_of::<T>() {
+            return Err(Error::with_invalid_data(format!(
+                "global variable `{name}` is of size {} but `{}` has size {}",
+                info.size,
+                type_name::<T>(),
+                size_of::<T>()
+            )))
+        }
+
+        let mut size = 0;
+        // SAFETY: `map` is a valid map pointer provided by libbpf.
+        let data = unsafe { libbpf_sys::bpf_map__initial_value(map.as_ptr(), &mut size) };
+        let data = NonNull::new(data.cast::<u8>()).ok_or_else(|| {
+            Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                format!("data section of global variable `{name}` is not memory mapped"),
+            )
+        })?;
+
+        let offset = info.offset as usize;
+        if offset + info.size > size as usize {
+            return Err(Error::with_invalid_data(format!(
+                "global variable `{name}` lies outside of its data section"
+            )))
+        }
+
+        // SAFETY: We checked that the offset is within the bounds of the
+        //         section.
+        let ptr = unsafe { data.as_ptr().add(offset) }.cast::<T>();
+        if ptr as usize % align_of::<T>() != 0 {
+            return Err(Error::with_invalid_data(format!(
+                "global variable `{name}` is not sufficiently aligned for `{}`",
+                type_name::<T>()
+            )))
+        }
+        // SAFETY: `data` is non-null and the offset keeps it in bounds.
+        let ptr = unsafe { NonNull::new_unchecked(ptr) };
+        return Ok((map, ptr))
+    }
+
+    Err(Error::with_io_error(
+        io::ErrorKind::NotFound,
+        format!("global variable `{name}` not found"),
+    ))
+}
+
 /// A trait implemented for types that are thin wrappers around `libbpf` types.
 ///
 /// The trait provides access to the underlying `libbpf` (or `libbpf-sys`)
@@ -106,6 +215,12 @@ pub trait AsRawLibbpf {
 pub struct ObjectBuilder {
     name: Option<CString>,
     pin_root_path: Option<CString>,
+    kconfig: Option<CString>,
+    btf_custom_path: Option<CString>,
+    bpf_token_path: Option<CString>,
+    /// The size of the kernel log buffer to provide to each opened
+    /// object.
+    kernel_log_size: Option<usize>,
 
     opts: libbpf_sys::bpf_object_open_opts,
 }
@@ -127,6 +242,10 @@ impl Default for ObjectBuilder {
         Self {
             name: None,
             pin_root_path: None,
+            kconfig: None,
+            btf_custom_path: None,
+            bpf_token_path: None,
+            kernel_log_size: None,
             opts,
         }
     }
@@ -158,6 +277,182 @@ impl ObjectBuilder {
         self
     }
 
+    /// Provide values for `extern` Kconfig variables (`CONFIG_*`) used by
+    /// the object, overriding those that libbpf would otherwise read from
+    /// the running kernel's configuration.
+    ///
+    /// Values are provided the same way they appear in a kernel
+    /// configuration file, e.g., `y`, `m`, `42`, or `"string"`. An empty
+    /// `config` clears any previously provided values.
+    ///
+    /// ```no_run
+    /// # use libbpf_rs::ObjectBuilder;
+    /// let mut builder = ObjectBuilder::default();
+    /// let _builder = builder
+    ///     .kconfig([("CONFIG_BPF_SYSCALL", "y"), ("CONFIG_HZ", "250")])
+    ///     .unwrap();
+    /// ```
+    pub fn kconfig<I, K, V>(&mut self, config: I) -> Result<&mut Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut kconfig = String::new();
+        for (name, value) in config {
+            let (name, value) = (name.as_ref(), value.as_ref());
+            let valid_name = name.starts_with("CONFIG_")
+                && name.len() > "CONFIG_".len()
+                && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_');
+            if !valid_name {
+                return Err(Error::with_io_error(
+                    io::ErrorKind::InvalidInput,
+                    format!("`{name}` is not a valid Kconfig variable name"),
+                ))
+            }
+            if value.is_empty() || value.contains(['\n', '\0']) {
+                return Err(Error::with_io_error(
+                    io::ErrorKind::InvalidInput,
+                    format!("`{value}` is not a valid value for Kconfig variable `{name}`"),
+                ))
+            }
+            let () = kconfig.push_str(&format!("{name}={value}\n"));
+        }
+
+        self.kconfig = if kconfig.is_empty() {
+            None
+        } else {
+            Some(util::str_to_cstring(&kconfig)?)
+        };
+        self.opts.kconfig = self.kconfig.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+        Ok(self)
+    }
+
+    /// Use the BTF information at `path` instead of the running kernel's
+    /// (`/sys/kernel/btf/vmlinux`) for performing CO-RE relocations.
+    ///
+    /// This is useful on kernels that don't expose their BTF
+    /// information.
+    pub fn btf_custom_path<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::NotFound,
+                format!("BTF file `{}` does not exist", path.display()),
+            ))
+        }
+
+        self.btf_custom_path = Some(util::path_to_cstring(path)?);
+        self.opts.btf_custom_path = self
+            .btf_custom_path
+            .as_ref()
+            .map_or(ptr::null(), |p| p.as_ptr());
+        Ok(self)
+    }
+
+    /// Use the BTF file for the running kernel from `dir` for performing
+    /// CO-RE relocations, but only if the kernel does not expose BTF
+    /// information itself.
+    ///
+    /// See [`btf::find_custom_btf`][crate::btf::find_custom_btf] for how
+    /// the file is looked up. Files of the expected layout can be
+    /// generated using `cargo libbpf min-core-btf`.
+    pub fn btf_custom_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<&mut Self> {
+        match btf::find_custom_btf(dir)? {
+            Some(path) => self.btf_custom_path(path),
+            None => Ok(self),
+        }
+    }
+
+    /// Set the log level used for loading BTF information and programs
+    /// of the object into the kernel.
+    ///
+    /// The level is a combination of `1` (basic verifier log), `2`
+    /// (verbose verifier log), and `4` (verifier statistics). Programs
+    /// inherit it, unless overwritten via
+    /// [`OpenProgramMut::set_log_level`].
+    pub fn kernel_log_level(&mut self, log_level: u32) -> Result<&mut Self> {
+        if log_level & !0b111 != 0 {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!("invalid kernel log level {log_level}"),
+            ))
+        }
+        self.opts.kernel_log_level = log_level;
+        Ok(self)
+    }
+
+    /// Have the kernel's log of loading the object's BTF information and
+    /// any program that doesn't have a log buffer of its own written
+    /// into a buffer of `size` bytes.
+    ///
+    /// Every object opened by the builder gets a buffer of its own. The
+    /// log can be retrieved via [`OpenObject::kernel_log`] and
+    /// [`Object::kernel_log`]. Usually, the kernel only provides a log
+    /// for programs failing to load, unless a log level was set via
+    /// [`ObjectBuilder::kernel_log_level`].
+    ///
+    /// The buffer is only provided to objects opened via
+    /// [`ObjectBuilder::open_file`] and [`ObjectBuilder::open_memory`],
+    /// not to users of the raw options retrieved via
+    /// [`AsRawLibbpf::as_libbpf_object`].
+    pub fn kernel_log_buf(&mut self, size: usize) -> Result<&mut Self> {
+        // The kernel rejects log buffers smaller than this.
+        const MIN_LOG_SIZE: usize = 128;
+
+        if !(MIN_LOG_SIZE..=u32::MAX as usize).contains(&size) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "kernel log buffer size {size} is not in range [{MIN_LOG_SIZE}, {}]",
+                    u32::MAX
+                ),
+            ))
+        }
+
+        self.kernel_log_size = Some(size);
+        Ok(self)
+    }
+
+    /// Set the path of a BPF file system instance to create a BPF token
+    /// from, for loading the object with the privileges delegated to it.
+    ///
+    /// By default, libbpf uses the path provided in the
+    /// `LIBBPF_BPF_TOKEN_PATH` environment variable or tries
+    /// `/sys/fs/bpf`, if present. An empty path disables the use of a
+    /// token altogether.
+    pub fn bpf_token_path<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self> {
+        let path = path.as_ref();
+        if !path.as_os_str().is_empty() && !path.is_dir() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::NotFound,
+                format!("BPF file system `{}` does not exist", path.display()),
+            ))
+        }
+        if path.as_os_str().len() >= libc::PATH_MAX as usize {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!("BPF token path `{}` is too long", path.display()),
+            ))
+        }
+
+        self.bpf_token_path = Some(util::path_to_cstring(path)?);
+        self.opts.bpf_token_path = self
+            .bpf_token_path
+            .as_ref()
+            .map_or(ptr::null(), |p| p.as_ptr());
+        Ok(self)
+    }
+
+    /// Use the BPF file system instance `token` was created from for
+    /// loading the object, with the permissions delegated to it.
+    ///
+    /// libbpf creates a token of its own when the object gets loaded, so
+    /// `token` has to be kept alive until then.
+    pub fn bpf_token(&mut self, token: &BpfToken) -> Result<&mut Self> {
+        self.bpf_token_path(token.bpffs_path())
+    }
+
     /// Option to print debug output to stderr.
     ///
     /// Note: This function uses [`set_print`] internally and will overwrite any callbacks
@@ -171,34 +466,55 @@ impl ObjectBuilder {
         self
     }
 
+    /// Open an object using `open`, which is provided with the options
+    /// to use, handing the kernel log buffer (if any) to the result.
+    fn open_with<F>(&mut self, open: F) -> Result<OpenObject>
+    where
+        F: FnOnce(
+            *const libbpf_sys::bpf_object_open_opts,
+        ) -> Result<NonNull<libbpf_sys::bpf_object>>,
+    {
+        let mut kernel_log = self.kernel_log_size.map(|size| vec![0; size]);
+        if let Some(buf) = &mut kernel_log {
+            self.opts.kernel_log_buf = buf.as_mut_ptr().cast();
+            self.opts.kernel_log_size = buf.len() as _;
+        }
+        let result = open(self.as_libbpf_object().as_ptr());
+        // The buffer belongs to the opened object from here on.
+        self.opts.kernel_log_buf = ptr::null_mut();
+        self.opts.kernel_log_size = 0;
+
+        let ptr = result?;
+        let mut obj = unsafe { OpenObject::from_ptr(ptr) };
+        obj.kernel_log = kernel_log;
+        Ok(obj)
+    }
+
     /// Open an object using the provided path on the file system.
     pub fn open_file<P: AsRef<Path>>(&mut self, path: P) -> Result<OpenObject> {
         let path = path.as_ref();
         let path_c = util::path_to_cstring(path)?;
         let path_ptr = path_c.as_ptr();
-        let opts_ptr = self.as_libbpf_object().as_ptr();
-
-        let ptr = unsafe { libbpf_sys::bpf_object__open_file(path_ptr, opts_ptr) };
-        let ptr = validate_bpf_ret(ptr)
-            .with_context(|| format!("failed to open object from `{}`", path.display()))?;
 
-        let obj = unsafe { OpenObject::from_ptr(ptr) };
-        Ok(obj)
+        self.open_with(|opts_ptr| {
+            let ptr = unsafe { libbpf_sys::bpf_object__open_file(path_ptr, opts_ptr) };
+            validate_bpf_ret(ptr)
+                .with_context(|| format!("failed to open object from `{}`", path.display()))
+        })
     }
 
     /// Open an object from memory.
     pub fn open_memory(&mut self, mem: &[u8]) -> Result<OpenObject> {
-        let opts_ptr = self.as_libbpf_object().as_ptr();
-        let ptr = unsafe {
-            libbpf_sys::bpf_object__open_mem(
-                mem.as_ptr() as *const c_void,
-                mem.len() as libbpf_sys::size_t,
-                opts_ptr,
-            )
-        };
-        let ptr = validate_bpf_ret(ptr).context("failed to open object from memory")?;
-        let obj = unsafe { OpenObject::from_ptr(ptr) };
-        Ok(obj)
+        self.open_with(|opts_ptr| {
+            let ptr = unsafe {
+                libbpf_sys::bpf_object__open_mem(
+                    mem.as_ptr() as *const c_void,
+                    mem.len() as libbpf_sys::size_t,
+                    opts_ptr,
+                )
+            };
+            validate_bpf_ret(ptr).context("failed to open object from memory")
+        })
     }
 }
 
@@ -217,9 +533,14 @@ impl AsRawLibbpf for ObjectBuilder {
 ///
 /// Use this object to access [`OpenMap`]s and [`OpenProgram`]s.
 #[derive(Debug)]
-#[repr(transparent)]
+// `OpenObject` and `Object` have to share the same layout, as skeletons
+// convert between them in place.
+#[repr(C)]
 pub struct OpenObject {
     ptr: NonNull<libbpf_sys::bpf_object>,
+    /// The buffer for the kernel's log, as requested via
+    /// [`ObjectBuilder::kernel_log_buf`].
+    kernel_log: Option<Vec<u8>>,
 }
 
 impl OpenObject {
@@ -234,13 +555,19 @@ impl OpenObject {
     ///
     /// It is not safe to manipulate `ptr` after this operation.
     pub unsafe fn from_ptr(ptr: NonNull<libbpf_sys::bpf_object>) -> Self {
-        Self { ptr }
+        Self {
+            ptr,
+            kernel_log: None,
+        }
     }
 
     /// Takes underlying `libbpf_sys::bpf_object` pointer.
+    ///
+    /// If the object was opened with a kernel log buffer, the buffer is
+    /// leaked, as libbpf may still write to it when loading the object.
     pub fn take_ptr(mut self) -> NonNull<libbpf_sys::bpf_object> {
         let ptr = {
-            let Self { ptr } = &mut self;
+            let Self { ptr, .. } = &mut self;
             *ptr
         };
         // avoid double free of self.ptr
@@ -248,6 +575,15 @@ impl OpenObject {
         ptr
     }
 
+    /// Retrieve the kernel's log, if a buffer for it was requested via
+    /// [`ObjectBuilder::kernel_log_buf`].
+    ///
+    /// The log only contains data once loading was attempted, e.g., after
+    /// [`OpenObject::load`] failed.
+    pub fn kernel_log(&self) -> Option<String> {
+        self.kernel_log.as_deref().map(c_buf_to_string)
+    }
+
     /// Retrieve the object's name.
     pub fn name(&self) -> Option<&OsStr> {
         // SAFETY: We ensured `ptr` is valid during construction.
@@ -285,12 +621,127 @@ impl OpenObject {
             .map(|mut ptr| unsafe { OpenProgramMut::new_mut(ptr.as_mut()) })
     }
 
+    /// Retrieve a mutable reference to the global variable `name`, as
+    /// stored in one of the object's data sections.
+    ///
+    /// The variable is looked up in the object's BTF. Data sections
+    /// include `.bss`, `.data`, and `.rodata`, as well as custom
+    /// sections such as `.data.foo`. Writes through the returned
+    /// reference determine the variable's initial value once the
+    /// object gets loaded.
+    ///
+    /// # Safety
+    /// `T` has to match the layout of the variable's type and has to be
+    /// valid for any bit pattern. The size and alignment of `T` are
+    /// checked.
+    pub unsafe fn global_mut<T>(&mut self, name: &str) -> Result<&mut T> {
+        let (_map, mut ptr) = find_global::<T>(unsafe { self.ptr.as_ref() }, name)?;
+        Ok(unsafe { ptr.as_mut() })
+    }
+
     /// Load the maps and programs contained in this BPF object into the system.
-    pub fn load(self) -> Result<Object> {
+    ///
+    /// If the kernel rejects one of the programs, the returned error
+    /// carries the verifier's log, which can be retrieved via
+    /// [`Error::verifier_log`][crate::Error::verifier_log].
+    pub fn load(mut self) -> Result<Object> {
+        // Capture the verifier log of programs ourselves, so that we can
+        // report it as part of the error. Programs for which a buffer has
+        // already been set up by other means are left alone. If the
+        // object has a kernel log buffer, libbpf writes the log of all
+        // of them there, where we pick it up from.
+        //
+        // libbpf only requests a log for a program without a log level
+        // once loading it has failed, and it stops at the first such
+        // failure. Hence, all of these programs share a single buffer,
+        // which is only ever written to for the program that failed.
+        // Programs with a log level get a buffer of their own, as the
+        // kernel provides a log for their successful load, too.
+        //
+        // Note that libbpf won't touch any of these buffers after the
+        // load, successful or not, but it also refuses to have them
+        // reset (`bpf_program__set_log_buf` fails with `EBUSY`), so
+        // the pointers it retains have to be considered dangling
+        // afterwards.
+        let mut shared_log = Vec::new();
+        let mut own_logs = Vec::new();
+        let mut progs = Vec::new();
+        let inject = self.kernel_log.is_none();
+        for mut prog in self
+            .progs_mut()
+            .filter(|prog| inject && prog.autoload() && !prog.has_log_buf())
+        {
+            let buf = if prog.log_level() == 0 {
+                if shared_log.is_empty() {
+                    shared_log = vec![0; VERIFIER_LOG_SIZE];
+                }
+                let () = progs.push((prog.as_libbpf_object(), None));
+                &mut shared_log
+            } else {
+                let () = own_logs.push(vec![0; VERIFIER_LOG_SIZE]);
+                let () = progs.push((prog.as_libbpf_object(), Some(own_logs.len() - 1)));
+                // SANITY: We just pushed a buffer.
+                own_logs.last_mut().unwrap()
+            };
+            // SAFETY: The buffer outlives the `bpf_object__load` call
+            //         below and is not otherwise accessed until it
+            //         returned.
+            let () = unsafe { prog.set_log_buf(buf) }?;
+        }
+
         let ret = unsafe { libbpf_sys::bpf_object__load(self.ptr.as_ptr()) };
+
+        for (prog, log_idx) in progs {
+            let prog = unsafe { OpenProgram::new(prog.as_ref()) };
+            // SAFETY: `bpf_program__fd` is always safe to call.
+            let fd = unsafe { libbpf_sys::bpf_program__fd(prog.as_libbpf_object().as_ptr()) };
+            let buf = match log_idx {
+                Some(idx) => &own_logs[idx],
+                // The shared buffer only ever contains the log of the
+                // program that failed to load.
+                None if fd < 0 => &shared_log,
+                None => continue,
+            };
+            let log = c_buf_to_string(buf);
+            if log.is_empty() {
+                continue
+            }
+
+            let name = prog.name().to_string_lossy();
+            let msg =
+                format!("prog '{name}': -- BEGIN PROG LOAD LOG --\n{log}-- END PROG LOAD LOG --\n");
+            if fd >= 0 {
+                // Only a user requested log level will have the kernel
+                // provide a log for a successfully loaded program.
+                // Mirror how libbpf itself would have reported it.
+                let () = print(PrintLevel::Debug, msg);
+            } else if ret < 0 {
+                let () = print(PrintLevel::Warn, msg);
+                let log = VerifierLog::new(prog.name(), log);
+                let err = Error::from_raw_os_error(-ret).with_verifier_log(log);
+                return Err(err)
+            }
+        }
+        if let Some(buf) = self.kernel_log.as_deref().filter(|_| ret < 0) {
+            // libbpf stops at the first program failing to load, leaving
+            // the kernel log buffer with its log.
+            let failed = self.progs().find(|prog| {
+                // SAFETY: `bpf_program__fd` is always safe to call.
+                let fd = unsafe { libbpf_sys::bpf_program__fd(prog.as_libbpf_object().as_ptr()) };
+                prog.autoload() && fd < 0
+            });
+            let log = c_buf_to_string(buf);
+            if let Some(prog) = failed.filter(|prog| !prog.has_log_buf() && !log.is_empty()) {
+                let log = VerifierLog::new(prog.name(), log);
+                let err = Error::from_raw_os_error(-ret).with_verifier_log(log);
+                return Err(err)
+            }
+        }
         let () = util::parse_ret(ret)?;
 
-        let obj = unsafe { Object::from_ptr(self.take_ptr()) };
+        let kernel_log = self.kernel_log.take();
+        let mut obj = unsafe { Object::from_ptr(self.take_ptr()) };
+        obj.kernel_log = kernel_log;
 
         Ok(obj)
     }
@@ -324,9 +775,11 @@ impl Drop for OpenObject {
 /// Note that this is an explanation of the motivation -- Rust's lifetime system should already be
 /// enforcing this invariant.
 #[derive(Debug)]
-#[repr(transparent)]
+#[repr(C)]
 pub struct Object {
     ptr: NonNull<libbpf_sys::bpf_object>,
+    /// The buffer holding the kernel's log of loading the object.
+    kernel_log: Option<Vec<u8>>,
 }
 
 impl Object {
@@ -339,7 +792,16 @@ impl Object {
     ///
     /// It is not safe to manipulate `ptr` after this operation.
     pub unsafe fn from_ptr(ptr: NonNull<libbpf_sys::bpf_object>) -> Self {
-        Self { ptr }
+        Self {
+            ptr,
+            kernel_log: None,
+        }
+    }
+
+    /// Retrieve the kernel's log of loading the object, if a buffer for
+    /// it was requested via [`ObjectBuilder::kernel_log_buf`].
+    pub fn kernel_log(&self) -> Option<String> {
+        self.kernel_log.as_deref().map(c_buf_to_string)
     }
 
     /// Retrieve the object's name.
@@ -361,6 +823,43 @@ impl Object {
         Btf::from_bpf_object(unsafe { &*self.ptr.as_ptr() })
     }
 
+    /// Retrieve a reference to the global variable `name`, as stored in
+    /// one of the object's memory mapped data sections.
+    ///
+    /// See [`OpenObject::global_mut`] for details. Note that the value
+    /// may be modified concurrently by BPF programs.
+    ///
+    /// # Safety
+    /// `T` has to match the layout of the variable's type and has to be
+    /// valid for any bit pattern. The size and alignment of `T` are
+    /// checked.
+    pub unsafe fn global<T>(&self, name: &str) -> Result<&T> {
+        let (_map, ptr) = find_global::<T>(unsafe { self.ptr.as_ref() }, name)?;
+        Ok(unsafe { ptr.as_ref() })
+    }
+
+    /// Retrieve a mutable reference to the global variable `name`, as
+    /// stored in one of the object's memory mapped data sections.
+    ///
+    /// Variables in read-only sections such as `.rodata` cannot be
+    /// modified after the object has been loaded.
+    ///
+    /// # Safety
+    /// `T` has to match the layout of the variable's type and has to be
+    /// valid for any bit pattern. The size and alignment of `T` are
+    /// checked.
+    pub unsafe fn global_mut<T>(&mut self, name: &str) -> Result<&mut T> {
+        let (map, mut ptr) = find_global::<T>(unsafe { self.ptr.as_ref() }, name)?;
+        let flags = unsafe { libbpf_sys::bpf_map__map_flags(map.as_ptr()) };
+        if flags & libbpf_sys::BPF_F_RDONLY_PROG != 0 {
+            return Err(Error::with_io_error(
+                io::ErrorKind::PermissionDenied,
+                format!("global variable `{name}` is read-only"),
+            ))
+        }
+        Ok(unsafe { ptr.as_mut() })
+    }
+
     /// Retrieve an iterator over all BPF maps in the object.
     pub fn maps(&self) -> impl Iterator<Item = Map<'_>> {
         MapIter::new(unsafe { self.ptr.as_ref() })
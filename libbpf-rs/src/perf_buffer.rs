@@ -2,7 +2,9 @@ use core::ffi::c_void;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
+use std::os::unix::prelude::AsFd;
 use std::os::unix::prelude::AsRawFd;
+use std::os::unix::prelude::BorrowedFd;
 use std::ptr;
 use std::ptr::NonNull;
 use std::slice;
@@ -250,6 +252,17 @@ impl AsRawLibbpf for PerfBuffer<'_> {
     }
 }
 
+impl AsFd for PerfBuffer<'_> {
+    /// Retrieve the epoll file descriptor, which becomes readable once
+    /// data is available in any of the per-CPU buffers.
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        let fd = self.epoll_fd();
+        // SAFETY: The epoll file descriptor is owned by the perf buffer
+        //         and stays valid for as long as it is alive.
+        unsafe { BorrowedFd::borrow_raw(fd) }
+    }
+}
+
 // SAFETY: `perf_buffer` objects can safely be polled from any thread.
 unsafe impl Send for PerfBuffer<'_> {}
 
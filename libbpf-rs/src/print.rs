@@ -75,6 +75,16 @@ extern "C" fn outer_print_cb(
     0 // return value is ignored by libbpf
 }
 
+/// Report a message through the currently installed print callback,
+/// mirroring how libbpf emits its own output.
+pub(crate) fn print(level: PrintLevel, msg: String) {
+    if let Some((min_level, func)) = { *PRINT_CB.lock().unwrap() } {
+        if level <= min_level {
+            func(level, msg);
+        }
+    }
+}
+
 /// Set a callback to receive log messages from libbpf, instead of printing them to stderr.
 ///
 /// # Arguments
@@ -0,0 +1,520 @@
+//! Probe the running kernel for supported BPF features.
+//!
+//! For example, to check whether the kernel supports BPF ring buffers:
+//! ```no_run
+//! use libbpf_rs::probe::FeatureReport;
+//!
+//! let report = FeatureReport::probe().unwrap();
+//! println!("ring buffers supported: {}", report.ringbuf);
+//! ```
+//!
+//! With the `serde` feature enabled, a [`FeatureReport`] can be
+//! serialized, e.g., to compare the capabilities of different systems.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::ffi::CStr;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::os::raw::c_char;
+use std::os::raw::c_int;
+use std::ptr;
+use std::ptr::NonNull;
+use std::str::FromStr;
+
+use crate::btf::types::DeclTag;
+use crate::btf::types::Enum;
+use crate::btf::types::Func;
+use crate::btf::ReferencesType as _;
+use crate::util;
+use crate::util::validate_bpf_ret;
+use crate::Btf;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+/// A function mapping a numeric BPF type to its name, as provided by
+/// libbpf.
+type TypeStrFn = unsafe extern "C" fn(u32) -> *const c_char;
+
+/// A function adding a BTF type of a certain kind to a BTF object,
+/// given the type ID of a previously added `int` type. It returns the
+/// ID of the added type or a negative error.
+type AddBtfKindFn = fn(*mut libbpf_sys::btf, c_int) -> c_int;
+
+/// Convert a string literal into a C string pointer.
+macro_rules! c_str {
+    ($s:literal) => {
+        concat!($s, "\0").as_ptr().cast::<c_char>()
+    };
+}
+
+/// The BTF kinds we probe for, along with functions adding a type of
+/// the respective kind to a BTF object.
+const BTF_KINDS: &[(&str, AddBtfKindFn)] = &[
+    ("int", |_btf, int| int),
+    ("ptr", |btf, int| unsafe {
+        libbpf_sys::btf__add_ptr(btf, int)
+    }),
+    ("array", |btf, int| unsafe {
+        libbpf_sys::btf__add_array(btf, int, int, 1)
+    }),
+    ("struct", |btf, int| unsafe {
+        let id = libbpf_sys::btf__add_struct(btf, c_str!("s"), 4);
+        let ret = libbpf_sys::btf__add_field(btf, c_str!("a"), int, 0, 0);
+        if ret < 0 {
+            ret
+        } else {
+            id
+        }
+    }),
+    ("union", |btf, int| unsafe {
+        let id = libbpf_sys::btf__add_union(btf, c_str!("u"), 4);
+        let ret = libbpf_sys::btf__add_field(btf, c_str!("a"), int, 0, 0);
+        if ret < 0 {
+            ret
+        } else {
+            id
+        }
+    }),
+    ("enum", |btf, _int| unsafe {
+        let id = libbpf_sys::btf__add_enum(btf, c_str!("e"), 4);
+        let ret = libbpf_sys::btf__add_enum_value(btf, c_str!("A"), 0);
+        if ret < 0 {
+            ret
+        } else {
+            id
+        }
+    }),
+    ("fwd", |btf, _int| unsafe {
+        libbpf_sys::btf__add_fwd(btf, c_str!("f"), libbpf_sys::BTF_FWD_STRUCT)
+    }),
+    ("typedef", |btf, int| unsafe {
+        libbpf_sys::btf__add_typedef(btf, c_str!("t"), int)
+    }),
+    ("volatile", |btf, int| unsafe {
+        libbpf_sys::btf__add_volatile(btf, int)
+    }),
+    ("const", |btf, int| unsafe {
+        libbpf_sys::btf__add_const(btf, int)
+    }),
+    ("restrict", |btf, int| unsafe {
+        let ptr = libbpf_sys::btf__add_ptr(btf, int);
+        libbpf_sys::btf__add_restrict(btf, ptr)
+    }),
+    ("func_proto", |btf, int| unsafe {
+        libbpf_sys::btf__add_func_proto(btf, int)
+    }),
+    ("func", |btf, int| unsafe {
+        let proto = libbpf_sys::btf__add_func_proto(btf, int);
+        libbpf_sys::btf__add_func(btf, c_str!("f"), libbpf_sys::BTF_FUNC_GLOBAL, proto)
+    }),
+    ("var", |btf, int| unsafe {
+        let linkage = libbpf_sys::BTF_VAR_GLOBAL_ALLOCATED as _;
+        libbpf_sys::btf__add_var(btf, c_str!("v"), linkage, int)
+    }),
+    ("datasec", |btf, int| unsafe {
+        let linkage = libbpf_sys::BTF_VAR_GLOBAL_ALLOCATED as _;
+        let var = libbpf_sys::btf__add_var(btf, c_str!("v"), linkage, int);
+        let id = libbpf_sys::btf__add_datasec(btf, c_str!(".data"), 4);
+        let ret = libbpf_sys::btf__add_datasec_var_info(btf, var, 0, 4);
+        if ret < 0 {
+            ret
+        } else {
+            id
+        }
+    }),
+    ("float", |btf, _int| unsafe {
+        libbpf_sys::btf__add_float(btf, c_str!("float"), 4)
+    }),
+    ("decl_tag", |btf, int| unsafe {
+        let linkage = libbpf_sys::BTF_VAR_GLOBAL_ALLOCATED as _;
+        let var = libbpf_sys::btf__add_var(btf, c_str!("v"), linkage, int);
+        libbpf_sys::btf__add_decl_tag(btf, c_str!("tag"), var, -1)
+    }),
+    ("type_tag", |btf, int| unsafe {
+        let tag = libbpf_sys::btf__add_type_tag(btf, c_str!("tag"), int);
+        libbpf_sys::btf__add_ptr(btf, tag)
+    }),
+    ("enum64", |btf, _int| unsafe {
+        let id = libbpf_sys::btf__add_enum64(btf, c_str!("e"), 8, false);
+        let ret = libbpf_sys::btf__add_enum64_value(btf, c_str!("A"), 1 << 40);
+        if ret < 0 {
+            ret
+        } else {
+            id
+        }
+    }),
+];
+
+
+/// Convert the result of a libbpf probe into a `bool`.
+fn probe_result(ret: c_int) -> Result<bool> {
+    match ret {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(Error::from_raw_os_error(-ret)),
+    }
+}
+
+/// Retrieve the name libbpf uses for the numeric BPF type `ty`, if any.
+fn type_str(f: TypeStrFn, ty: u32) -> Option<String> {
+    let ptr = unsafe { f(ty) };
+    if ptr.is_null() {
+        return None
+    }
+    // SAFETY: libbpf returns a pointer to a static NUL terminated string.
+    let name = unsafe { CStr::from_ptr(ptr) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Retrieve all types known to libbpf, starting at `first`, along with
+/// their names.
+fn known_types(f: TypeStrFn, first: u32) -> Vec<(u32, String)> {
+    (first..)
+        .map_while(|ty| type_str(f, ty).map(|name| (ty, name)))
+        .collect()
+}
+
+/// Read the numeric value of a sysctl, returning `None` if it is not
+/// available.
+fn read_sysctl<T: FromStr>(name: &str) -> Option<T> {
+    let path = format!("/proc/sys/{name}");
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Retrieve the names of the enumerators of the enum `enum_name` from
+/// `btf`, stripped of `prefix`.
+///
+/// Enumerators not starting with `prefix` (such as the usual
+/// `__MAX_*` sentinels) and `UNSPEC` ones are ignored. Names are
+/// converted to the ones libbpf uses, if it knows the enumerator.
+fn btf_enum_names(
+    btf: &Btf<'_>,
+    enum_name: &str,
+    prefix: &str,
+    f: TypeStrFn,
+) -> Option<BTreeSet<String>> {
+    let ty = btf.type_by_name::<Enum<'_>>(enum_name)?;
+    let names = (0..ty.len())
+        .filter_map(|idx| ty.get(idx))
+        .filter_map(|member| {
+            let name = member.name?.to_str()?.strip_prefix(prefix)?;
+            if name == "UNSPEC" {
+                return None
+            }
+            let name = type_str(f, member.value as u32).unwrap_or_else(|| name.to_lowercase());
+            Some(name)
+        })
+        .collect();
+    Some(names)
+}
+
+/// Retrieve the names of the BPF helpers known to the kernel, indexed
+/// by their ID, along with the number of helpers.
+fn helper_names(btf: Option<&Btf<'_>>) -> (BTreeMap<u32, String>, u32) {
+    let mut names = BTreeMap::new();
+    let mut max_id = libbpf_sys::__BPF_FUNC_MAX_ID;
+
+    if let Some(ty) = btf.and_then(|btf| btf.type_by_name::<Enum<'_>>("bpf_func_id")) {
+        for member in (0..ty.len()).filter_map(|idx| ty.get(idx)) {
+            let Some(name) = member.name.and_then(OsStr::to_str) else {
+                continue
+            };
+
+            if name == "__BPF_FUNC_MAX_ID" {
+                max_id = member.value as u32;
+            } else if let Some(name) = name.strip_prefix("BPF_FUNC_") {
+                let _prev = names.insert(member.value as u32, format!("bpf_{name}"));
+            }
+        }
+    }
+    (names, max_id)
+}
+
+
+/// A BTF object under construction, used for probing BTF kinds.
+struct BtfBuilder {
+    ptr: NonNull<libbpf_sys::btf>,
+}
+
+impl BtfBuilder {
+    fn new() -> Result<Self> {
+        let ptr = unsafe { libbpf_sys::btf__new_empty() };
+        let ptr = validate_bpf_ret(ptr).context("failed to create BTF object")?;
+        Ok(Self { ptr })
+    }
+
+    /// Try loading the BTF data into the kernel.
+    fn load(&self) -> Result<bool> {
+        let mut size = 0;
+        let data = unsafe { libbpf_sys::btf__raw_data(self.ptr.as_ptr(), &mut size) };
+        if data.is_null() {
+            return Err(Error::with_invalid_data("failed to retrieve raw BTF data"))
+        }
+
+        let fd = unsafe { libbpf_sys::bpf_btf_load(data, size as _, ptr::null_mut()) };
+        if fd >= 0 {
+            // SAFETY: `bpf_btf_load` returned a valid file descriptor.
+            let _fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            return Ok(true)
+        }
+
+        let err = -fd;
+        if err == libc::EINVAL {
+            Ok(false)
+        } else {
+            Err(Error::from_raw_os_error(err))
+        }
+    }
+}
+
+impl Drop for BtfBuilder {
+    fn drop(&mut self) {
+        let () = unsafe { libbpf_sys::btf__free(self.ptr.as_ptr()) };
+    }
+}
+
+
+/// The state of the kernel's BPF JIT compiler, as configured via
+/// `sysctl`.
+///
+/// A value is `None` if the corresponding setting is not available.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct JitStatus {
+    /// The value of `net.core.bpf_jit_enable`: `0` if the JIT is
+    /// disabled, `1` if it is enabled, and `2` if it is enabled with
+    /// debug output.
+    pub enable: Option<u32>,
+    /// The value of `net.core.bpf_jit_harden`.
+    pub harden: Option<u32>,
+    /// The value of `net.core.bpf_jit_kallsyms`.
+    pub kallsyms: Option<u32>,
+    /// The value of `net.core.bpf_jit_limit`, in bytes.
+    pub limit: Option<u64>,
+}
+
+impl JitStatus {
+    /// Retrieve the JIT status of the running kernel.
+    pub fn probe() -> Self {
+        Self {
+            enable: read_sysctl("net/core/bpf_jit_enable"),
+            harden: read_sysctl("net/core/bpf_jit_harden"),
+            kallsyms: read_sysctl("net/core/bpf_jit_kallsyms"),
+            limit: read_sysctl("net/core/bpf_jit_limit"),
+        }
+    }
+
+    /// Check whether the JIT compiler is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enable.unwrap_or(0) != 0
+    }
+}
+
+
+/// A report of the BPF features supported by the running kernel.
+///
+/// Types are identified by the names libbpf uses for them, e.g.,
+/// `socket_filter` or `ringbuf`, which in turn match the kernel's
+/// definitions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FeatureReport {
+    /// The release of the running kernel, as reported by `uname`.
+    pub kernel_release: Option<String>,
+    /// The state of the JIT compiler.
+    pub jit: JitStatus,
+    /// The value of `kernel.unprivileged_bpf_disabled`, if available.
+    pub unprivileged_bpf_disabled: Option<u32>,
+    /// Program types known to libbpf and whether the kernel supports
+    /// them.
+    pub program_types: BTreeMap<String, bool>,
+    /// Map types known to libbpf and whether the kernel supports them.
+    pub map_types: BTreeMap<String, bool>,
+    /// The attach types known to the kernel.
+    ///
+    /// This information is derived from the kernel's BTF and is `None`
+    /// if that is unavailable.
+    pub attach_types: Option<BTreeSet<String>>,
+    /// The link types known to the kernel.
+    ///
+    /// This information is derived from the kernel's BTF and is `None`
+    /// if that is unavailable.
+    pub link_types: Option<BTreeSet<String>>,
+    /// The helpers available to each supported program type.
+    ///
+    /// Helpers are named as in the kernel's BTF (e.g.,
+    /// `bpf_map_lookup_elem`), falling back to their numeric ID if
+    /// that is unavailable. Program types for which helper support
+    /// cannot be probed generically (e.g., `tracing` or `lsm`, which
+    /// require an attach target) are not included.
+    pub helpers: BTreeMap<String, BTreeSet<String>>,
+    /// BTF kinds and whether the kernel supports them.
+    pub btf_kinds: BTreeMap<String, bool>,
+    /// The kfuncs declared in the kernel's BTF.
+    ///
+    /// Only functions tagged as kfuncs are reported, which requires the
+    /// kernel to have been built with a sufficiently recent `pahole`.
+    /// The member is `None` if the kernel's BTF is unavailable.
+    pub kfuncs: Option<BTreeSet<String>>,
+    /// Whether BPF ring buffers are supported.
+    pub ringbuf: bool,
+    /// Whether the `bpf_loop` helper is supported.
+    pub bpf_loop: bool,
+}
+
+impl FeatureReport {
+    /// Probe the running kernel for all supported features.
+    ///
+    /// Probing loads a large number of small programs into the kernel
+    /// and may take a while. Make sure the process has the required set
+    /// of CAP_* permissions (or runs as root).
+    pub fn probe() -> Result<Self> {
+        let vmlinux = Btf::from_vmlinux().ok();
+
+        let mut program_types = BTreeMap::new();
+        let mut helpers = BTreeMap::new();
+        let mut bpf_loop = false;
+        let (helper_names, max_helper_id) = helper_names(vmlinux.as_ref());
+
+        for (ty, name) in known_types(libbpf_sys::libbpf_bpf_prog_type_str, 1) {
+            let ret = unsafe { libbpf_sys::libbpf_probe_bpf_prog_type(ty, ptr::null()) };
+            let supported = probe_result(ret)
+                .with_context(|| format!("failed to probe program type `{name}`"))?;
+            let _prev = program_types.insert(name.clone(), supported);
+            if !supported {
+                continue
+            }
+
+            let mut available = BTreeSet::new();
+            let mut probeable = true;
+            for id in 1..max_helper_id {
+                let ret = unsafe { libbpf_sys::libbpf_probe_bpf_helper(ty, id, ptr::null()) };
+                if ret == -libc::EOPNOTSUPP {
+                    probeable = false;
+                    break
+                }
+                let supported = probe_result(ret).with_context(|| {
+                    format!("failed to probe helper {id} for program type `{name}`")
+                })?;
+                if supported {
+                    bpf_loop |= id == libbpf_sys::BPF_FUNC_loop;
+                    let helper = helper_names
+                        .get(&id)
+                        .cloned()
+                        .unwrap_or_else(|| id.to_string());
+                    let _inserted = available.insert(helper);
+                }
+            }
+
+            if probeable {
+                let _prev = helpers.insert(name, available);
+            }
+        }
+
+        let map_types = known_types(libbpf_sys::libbpf_bpf_map_type_str, 1)
+            .into_iter()
+            .map(|(ty, name)| {
+                let ret = unsafe { libbpf_sys::libbpf_probe_bpf_map_type(ty, ptr::null()) };
+                let supported = probe_result(ret)
+                    .with_context(|| format!("failed to probe map type `{name}`"))?;
+                Ok((name, supported))
+            })
+            .collect::<Result<BTreeMap<_, _>>>()?;
+        let ringbuf = map_types.get("ringbuf").copied().unwrap_or(false);
+
+        let btf_kinds = BTF_KINDS
+            .iter()
+            .map(|(name, add)| {
+                let btf = BtfBuilder::new()?;
+                let int = unsafe {
+                    libbpf_sys::btf__add_int(
+                        btf.ptr.as_ptr(),
+                        c_str!("int"),
+                        4,
+                        libbpf_sys::BTF_INT_SIGNED as _,
+                    )
+                };
+                let ret = add(btf.ptr.as_ptr(), int);
+                if ret < 0 {
+                    return Err(Error::from_raw_os_error(-ret))
+                        .with_context(|| format!("failed to construct BTF of kind `{name}`"))
+                }
+                let supported = btf
+                    .load()
+                    .with_context(|| format!("failed to probe BTF kind `{name}`"))?;
+                Ok((name.to_string(), supported))
+            })
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        let attach_types = vmlinux.as_ref().and_then(|btf| {
+            btf_enum_names(
+                btf,
+                "bpf_attach_type",
+                "BPF_",
+                libbpf_sys::libbpf_bpf_attach_type_str,
+            )
+        });
+        let link_types = vmlinux.as_ref().and_then(|btf| {
+            btf_enum_names(
+                btf,
+                "bpf_link_type",
+                "BPF_LINK_TYPE_",
+                libbpf_sys::libbpf_bpf_link_type_str,
+            )
+        });
+        let kfuncs = vmlinux.as_ref().map(|btf| {
+            btf.type_by_kind::<DeclTag<'_>>()
+                .filter(|tag| tag.name() == Some(OsStr::new("bpf_kfunc")))
+                .filter_map(|tag| Func::try_from(tag.referenced_type()).ok())
+                .filter_map(|func| func.name().map(|name| name.to_string_lossy().into_owned()))
+                .collect()
+        });
+
+        let report = Self {
+            kernel_release: util::kernel_release(),
+            jit: JitStatus::probe(),
+            unprivileged_bpf_disabled: read_sysctl("kernel/unprivileged_bpf_disabled"),
+            program_types,
+            map_types,
+            attach_types,
+            link_types,
+            helpers,
+            btf_kinds,
+            kfuncs,
+            ringbuf,
+            bpf_loop,
+        };
+        Ok(report)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that we can retrieve libbpf's names of BPF types.
+    #[test]
+    fn type_names() {
+        let progs = known_types(libbpf_sys::libbpf_bpf_prog_type_str, 1);
+        assert_eq!(
+            progs.first(),
+            Some(&(
+                libbpf_sys::BPF_PROG_TYPE_SOCKET_FILTER,
+                "socket_filter".to_string()
+            ))
+        );
+
+        let maps = known_types(libbpf_sys::libbpf_bpf_map_type_str, 1);
+        assert!(maps.iter().any(|(_ty, name)| name == "ringbuf"));
+
+        let name = type_str(libbpf_sys::libbpf_bpf_attach_type_str, u32::MAX);
+        assert_eq!(name, None);
+    }
+}
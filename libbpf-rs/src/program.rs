@@ -5,6 +5,8 @@
 use std::ffi::c_void;
 use std::ffi::CStr;
 use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fs::remove_file;
 use std::marker::PhantomData;
 use std::mem;
 use std::mem::size_of;
@@ -21,9 +23,19 @@ use std::path::Path;
 use std::ptr;
 use std::ptr::NonNull;
 use std::slice;
+use std::time::Duration;
 
 use libbpf_sys::bpf_func_id;
 
+use crate::ktarget;
+use crate::ktarget::KernelProbeKind;
+use crate::object::c_buf_to_string;
+use crate::object::VERIFIER_LOG_SIZE;
+use crate::query::ProgInfoQueryOptions;
+use crate::query::ProgramInfo;
+use crate::query::Tag;
+use crate::socket::SocketAttachKind;
+use crate::socket::SocketAttachment;
 use crate::util;
 use crate::util::validate_bpf_ret;
 use crate::util::BpfObjectType;
@@ -33,6 +45,7 @@ use crate::ErrorExt as _;
 use crate::Link;
 use crate::Mut;
 use crate::Result;
+use crate::VerifierLog;
 
 /// Options to optionally be provided when attaching to a uprobe.
 #[derive(Clone, Debug, Default)]
@@ -50,7 +63,8 @@ pub struct UprobeOpts {
     /// argument to should be 0. To trace an offset within a function, specify
     /// `func_name` and use `func_offset` argument to specify offset within the
     /// function. Shared library functions must specify the shared library
-    /// binary_path.
+    /// binary_path. Mangled names of Rust or C++ functions can be
+    /// looked up by means of [`SymbolResolver`][crate::SymbolResolver].
     pub func_name: String,
     #[doc(hidden)]
     pub _non_exhaustive: (),
@@ -107,6 +121,209 @@ impl From<TracepointOpts> for libbpf_sys::bpf_tracepoint_opts {
     }
 }
 
+/// Protocol family of a netfilter hook. Maps to `NFPROTO_*` in kernel uapi.
+///
+/// Only families supported by BPF netfilter links are represented.
+#[non_exhaustive]
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NetfilterProtocolFamily {
+    /// IPv4 (`NFPROTO_IPV4`).
+    #[default]
+    Ipv4 = 2,
+    /// IPv6 (`NFPROTO_IPV6`).
+    Ipv6 = 10,
+    /// See [`MapType::Unknown`][crate::MapType::Unknown]
+    Unknown = u32::MAX,
+}
+
+impl From<u32> for NetfilterProtocolFamily {
+    fn from(value: u32) -> Self {
+        use NetfilterProtocolFamily::*;
+
+        match value {
+            x if x == Ipv4 as u32 => Ipv4,
+            x if x == Ipv6 as u32 => Ipv6,
+            _ => Unknown,
+        }
+    }
+}
+
+/// A netfilter hook point. Maps to `enum nf_inet_hooks` in kernel uapi.
+#[non_exhaustive]
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NetfilterHook {
+    /// Packets after they entered the stack, before routing
+    /// (`NF_INET_PRE_ROUTING`).
+    #[default]
+    PreRouting = 0,
+    /// Packets destined to the local host (`NF_INET_LOCAL_IN`).
+    LocalIn,
+    /// Packets being forwarded to another host (`NF_INET_FORWARD`).
+    Forward,
+    /// Locally generated packets (`NF_INET_LOCAL_OUT`).
+    LocalOut,
+    /// Packets about to leave the host, after routing
+    /// (`NF_INET_POST_ROUTING`).
+    PostRouting,
+    /// See [`MapType::Unknown`][crate::MapType::Unknown]
+    Unknown = u32::MAX,
+}
+
+impl From<u32> for NetfilterHook {
+    fn from(value: u32) -> Self {
+        use NetfilterHook::*;
+
+        match value {
+            x if x == PreRouting as u32 => PreRouting,
+            x if x == LocalIn as u32 => LocalIn,
+            x if x == Forward as u32 => Forward,
+            x if x == LocalOut as u32 => LocalOut,
+            x if x == PostRouting as u32 => PostRouting,
+            _ => Unknown,
+        }
+    }
+}
+
+/// Options to be provided when attaching a program to a netfilter hook.
+#[derive(Clone, Debug, Default)]
+pub struct NetfilterOpts {
+    /// The protocol family to hook into.
+    pub protocol_family: NetfilterProtocolFamily,
+    /// The hook point within the protocol family.
+    pub hook: NetfilterHook,
+    /// Priority of the program relative to other hooks at the same point.
+    ///
+    /// Lower values run first. The kernel rejects `i32::MIN` and
+    /// `i32::MAX`, as they are reserved for internal use.
+    pub priority: i32,
+    /// Whether to request IP defragmentation before the program gets
+    /// invoked (`BPF_F_NETFILTER_IP_DEFRAG`).
+    pub ip_defrag: bool,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl From<NetfilterOpts> for libbpf_sys::bpf_netfilter_opts {
+    fn from(opts: NetfilterOpts) -> Self {
+        let NetfilterOpts {
+            protocol_family,
+            hook,
+            priority,
+            ip_defrag,
+            _non_exhaustive,
+        } = opts;
+
+        #[allow(clippy::needless_update)]
+        libbpf_sys::bpf_netfilter_opts {
+            sz: size_of::<Self>() as _,
+            pf: protocol_family as u32,
+            hooknum: hook as u32,
+            priority,
+            flags: if ip_defrag {
+                libbpf_sys::BPF_F_NETFILTER_IP_DEFRAG
+            } else {
+                0
+            },
+            // bpf_netfilter_opts might have padding fields on some platform
+            ..Default::default()
+        }
+    }
+}
+
+/// The order in which a cgroup iterator visits cgroups. Maps to
+/// `enum bpf_cgroup_iter_order` in kernel uapi.
+#[non_exhaustive]
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CgroupIterOrder {
+    /// Only visit the provided cgroup itself.
+    #[default]
+    SelfOnly = 1,
+    /// Visit the cgroup and its descendants in pre-order.
+    DescendantsPre,
+    /// Visit the cgroup and its descendants in post-order.
+    DescendantsPost,
+    /// Visit the cgroup and its ancestors, up to the root.
+    AncestorsUp,
+    /// See [`MapType::Unknown`][crate::MapType::Unknown]
+    Unknown = u32::MAX,
+}
+
+impl From<u32> for CgroupIterOrder {
+    fn from(value: u32) -> Self {
+        use CgroupIterOrder::*;
+
+        match value {
+            x if x == SelfOnly as u32 => SelfOnly,
+            x if x == DescendantsPre as u32 => DescendantsPre,
+            x if x == DescendantsPost as u32 => DescendantsPost,
+            x if x == AncestorsUp as u32 => AncestorsUp,
+            _ => Unknown,
+        }
+    }
+}
+
+/// Options to be provided when attaching a program to a BPF iterator,
+/// scoping the objects it iterates over. Maps to `union
+/// bpf_iter_link_info` in kernel uapi.
+///
+/// Iterators that don't require any scoping can be attached using
+/// [`ProgramMut::attach`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug)]
+pub enum IterOpts<'fd> {
+    /// Iterate over the elements of a map, for `iter/bpf_map_elem` and
+    /// `iter/bpf_sk_storage_map` programs.
+    Map(BorrowedFd<'fd>),
+    /// Iterate over cgroups, starting at the cgroup referred to by the
+    /// provided file descriptor (as opened from the cgroup file system).
+    Cgroup {
+        /// A file descriptor referring to the cgroup to start at.
+        fd: BorrowedFd<'fd>,
+        /// The order in which to visit cgroups.
+        order: CgroupIterOrder,
+    },
+    /// Iterate over cgroups, starting at the cgroup with the provided
+    /// ID.
+    CgroupId {
+        /// The ID of the cgroup to start at.
+        id: u64,
+        /// The order in which to visit cgroups.
+        order: CgroupIterOrder,
+    },
+    /// Only visit the thread with the provided ID, for `iter/task`,
+    /// `iter/task_file`, and `iter/task_vma` programs.
+    Tid(u32),
+    /// Only visit threads of the process with the provided PID.
+    Pid(u32),
+    /// Only visit threads of the process referred to by the provided
+    /// pidfd.
+    PidFd(BorrowedFd<'fd>),
+}
+
+impl From<IterOpts<'_>> for libbpf_sys::bpf_iter_link_info {
+    fn from(opts: IterOpts<'_>) -> Self {
+        let mut linkinfo = Self::default();
+        match opts {
+            IterOpts::Map(fd) => linkinfo.map.map_fd = fd.as_raw_fd() as _,
+            IterOpts::Cgroup { fd, order } => {
+                linkinfo.cgroup.cgroup_fd = fd.as_raw_fd() as _;
+                linkinfo.cgroup.order = order as _;
+            }
+            IterOpts::CgroupId { id, order } => {
+                linkinfo.cgroup.cgroup_id = id;
+                linkinfo.cgroup.order = order as _;
+            }
+            IterOpts::Tid(tid) => linkinfo.task.tid = tid,
+            IterOpts::Pid(pid) => linkinfo.task.pid = pid,
+            IterOpts::PidFd(fd) => linkinfo.task.pid_fd = fd.as_raw_fd() as _,
+        }
+        linkinfo
+    }
+}
+
 
 /// An immutable parsed but not yet loaded BPF program.
 pub type OpenProgram<'obj> = OpenProgramImpl<'obj>;
@@ -167,6 +384,24 @@ impl<'obj> OpenProgram<'obj> {
         unsafe { libbpf_sys::bpf_program__insn_cnt(self.ptr.as_ptr()) as usize }
     }
 
+    /// Return `true` if the bpf program is set to autoload, `false` otherwise.
+    pub fn autoload(&self) -> bool {
+        unsafe { libbpf_sys::bpf_program__autoload(self.ptr.as_ptr()) }
+    }
+
+    /// Return the bpf program's log level.
+    pub fn log_level(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_program__log_level(self.ptr.as_ptr()) }
+    }
+
+    /// Check whether a buffer for the kernel's verifier log has been
+    /// set for the program.
+    pub(crate) fn has_log_buf(&self) -> bool {
+        let mut size = 0;
+        let buf = unsafe { libbpf_sys::bpf_program__log_buf(self.ptr.as_ptr(), &mut size) };
+        !buf.is_null()
+    }
+
     /// Gives read-only access to BPF program's underlying BPF instructions.
     ///
     /// Keep in mind, libbpf can modify and append/delete BPF program's
@@ -226,6 +461,23 @@ impl<'obj> OpenProgramMut<'obj> {
         debug_assert!(util::parse_ret(rc).is_ok(), "{rc}");
     }
 
+    /// Set the buffer that the kernel's verifier log is written into
+    /// when the program is loaded.
+    ///
+    /// # Safety
+    /// The buffer has to stay valid for as long as libbpf may access it,
+    /// i.e., until the object containing the program has been loaded.
+    pub(crate) unsafe fn set_log_buf(&mut self, buf: &mut [u8]) -> Result<()> {
+        let rc = unsafe {
+            libbpf_sys::bpf_program__set_log_buf(
+                self.ptr.as_ptr(),
+                buf.as_mut_ptr().cast(),
+                buf.len() as _,
+            )
+        };
+        util::parse_ret(rc)
+    }
+
     /// Set whether a bpf program should be automatically loaded by default
     /// when the bpf object is loaded.
     pub fn set_autoload(&mut self, autoload: bool) {
@@ -290,7 +542,7 @@ impl<T> AsRawLibbpf for OpenProgramImpl<'_, T> {
 /// Type of a [`Program`]. Maps to `enum bpf_prog_type` in kernel uapi.
 #[non_exhaustive]
 #[repr(u32)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 // TODO: Document variants.
 #[allow(missing_docs)]
 pub enum ProgramType {
@@ -326,6 +578,7 @@ pub enum ProgramType {
     Lsm,
     SkLookup,
     Syscall,
+    Netfilter,
     /// See [`MapType::Unknown`][crate::MapType::Unknown]
     Unknown = u32::MAX,
 }
@@ -397,6 +650,7 @@ impl From<u32> for ProgramType {
             x if x == Lsm as u32 => Lsm,
             x if x == SkLookup as u32 => SkLookup,
             x if x == Syscall as u32 => Syscall,
+            x if x == Netfilter as u32 => Netfilter,
             _ => Unknown,
         }
     }
@@ -405,7 +659,7 @@ impl From<u32> for ProgramType {
 /// Attach type of a [`Program`]. Maps to `enum bpf_attach_type` in kernel uapi.
 #[non_exhaustive]
 #[repr(u32)]
-#[derive(Clone, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 // TODO: Document variants.
 #[allow(missing_docs)]
 pub enum ProgramAttachType {
@@ -451,6 +705,7 @@ pub enum ProgramAttachType {
     SkReuseportSelect,
     SkReuseportSelectOrMigrate,
     PerfEvent,
+    Netfilter = libbpf_sys::BPF_NETFILTER,
     /// See [`MapType::Unknown`][crate::MapType::Unknown]
     Unknown = u32::MAX,
 }
@@ -502,6 +757,7 @@ impl From<u32> for ProgramAttachType {
             x if x == SkReuseportSelect as u32 => SkReuseportSelect,
             x if x == SkReuseportSelectOrMigrate as u32 => SkReuseportSelectOrMigrate,
             x if x == PerfEvent as u32 => PerfEvent,
+            x if x == Netfilter as u32 => Netfilter,
             _ => Unknown,
         }
     }
@@ -527,6 +783,22 @@ pub struct Input<'dat> {
     pub cpu: u32,
     /// The 'flags' value passed to the kernel.
     pub flags: u32,
+    /// The number of times to run the program.
+    ///
+    /// A value of zero is treated the same as one by the kernel.
+    pub repeat: u32,
+    /// The number of frames to process in a single batch when running
+    /// in [`xdp_live_frames`][Self::xdp_live_frames] mode.
+    ///
+    /// A value of zero lets the kernel pick a default.
+    pub batch_size: u32,
+    /// Whether to run an XDP program on "live" frames, i.e., to
+    /// actually perform the redirects and transmits it requests
+    /// (`BPF_F_TEST_XDP_LIVE_FRAMES`).
+    ///
+    /// Neither output context nor output data may be provided in this
+    /// mode.
+    pub xdp_live_frames: bool,
     /// The struct is non-exhaustive and open to extension.
     #[doc(hidden)]
     pub _non_exhaustive: (),
@@ -544,11 +816,226 @@ pub struct Output<'dat> {
     pub context: Option<&'dat mut [u8]>,
     /// Output data filled by the program.
     pub data: Option<&'dat mut [u8]>,
+    /// The average duration of a single program run, as measured by the
+    /// kernel.
+    pub duration: Duration,
     /// The struct is non-exhaustive and open to extension.
     #[doc(hidden)]
     pub _non_exhaustive: (),
 }
 
+
+/// Reinterpret the plain-old-data `value` as a mutable byte slice.
+///
+/// # Safety
+/// `T` must not contain any padding bytes and be valid for any bit
+/// pattern.
+unsafe fn pod_bytes_mut<T>(value: &mut T) -> &mut [u8] {
+    // SAFETY: The pointer is derived from a reference and the caller
+    //         guarantees that all bytes are initialized.
+    unsafe { slice::from_raw_parts_mut((value as *mut T).cast(), size_of::<T>()) }
+}
+
+
+/// A `struct __sk_buff` context, for use with [`ProgramMut::test_run`]
+/// on socket filter, TC and similar programs.
+///
+/// Only fields that the kernel allows to be provided as input are
+/// exposed. After a run, the same fields reflect the values written
+/// back by the kernel, if the object was used as output context.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct SkbContext {
+    _reserved0: [u32; 2],
+    /// The `mark` of the packet.
+    pub mark: u32,
+    _reserved1: [u32; 5],
+    /// The `priority` of the packet.
+    pub priority: u32,
+    /// The interface index the packet was received on.
+    pub ingress_ifindex: u32,
+    /// The interface index of the device the packet is associated with.
+    pub ifindex: u32,
+    _reserved2: u32,
+    /// The control buffer, freely usable by programs.
+    pub cb: [u32; 5],
+    _reserved3: [u32; 19],
+    _flow_keys: u64,
+    /// The timestamp of the packet.
+    pub tstamp: u64,
+    /// The length of the packet on the wire.
+    pub wire_len: u32,
+    /// The number of GSO segments.
+    pub gso_segs: u32,
+    _sk: u64,
+    /// The GSO segment size.
+    pub gso_size: u32,
+    _reserved4: [u8; 4],
+    /// The hardware timestamp of the packet.
+    pub hwtstamp: u64,
+}
+
+impl SkbContext {
+    /// Retrieve the context as a byte slice, suitable for
+    /// [`Input::context_in`] or [`Input::context_out`].
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: `SkbContext` is `repr(C)`, has all padding made
+        //         explicit, and only contains integers.
+        unsafe { pod_bytes_mut(self) }
+    }
+}
+
+
+/// A `struct xdp_md` context, for use with [`ProgramMut::test_run`] on XDP
+/// programs.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct XdpContext {
+    /// The length of the metadata preceding the packet in
+    /// [`Input::data_in`].
+    pub data: u32,
+    /// The length of [`Input::data_in`], including metadata.
+    pub data_end: u32,
+    _data_meta: u32,
+    /// The interface index the packet was received on.
+    ///
+    /// The interface has to exist.
+    pub ingress_ifindex: u32,
+    /// The receive queue index of the packet.
+    pub rx_queue_index: u32,
+    _egress_ifindex: u32,
+}
+
+impl XdpContext {
+    /// Create a context for packet data of the given length, without
+    /// any metadata.
+    pub fn new(data_len: usize) -> Self {
+        Self {
+            data_end: data_len as _,
+            ..Default::default()
+        }
+    }
+
+    /// Retrieve the context as a byte slice, suitable for
+    /// [`Input::context_in`] or [`Input::context_out`].
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: `XdpContext` is `repr(C)` and only contains `u32`s.
+        unsafe { pod_bytes_mut(self) }
+    }
+}
+
+
+/// The arguments of a raw tracepoint program, for use with
+/// [`ProgramMut::test_run`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RawTracepointContext {
+    args: [u64; Self::MAX_ARGS],
+    len: usize,
+}
+
+impl RawTracepointContext {
+    /// The maximum number of arguments a raw tracepoint program can
+    /// receive.
+    pub const MAX_ARGS: usize = 12;
+
+    /// Create a context from the provided arguments.
+    pub fn new(args: &[u64]) -> Result<Self> {
+        if args.len() > Self::MAX_ARGS {
+            return Err(Error::with_invalid_data(format!(
+                "raw tracepoint programs accept at most {} arguments, got {}",
+                Self::MAX_ARGS,
+                args.len()
+            )))
+        }
+
+        let mut ctx = Self {
+            len: args.len(),
+            ..Default::default()
+        };
+        ctx.args[..args.len()].copy_from_slice(args);
+        Ok(ctx)
+    }
+
+    /// Retrieve the arguments.
+    pub fn args(&self) -> &[u64] {
+        &self.args[..self.len]
+    }
+
+    /// Retrieve the context as a byte slice, suitable for
+    /// [`Input::context_in`].
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        let args = &mut self.args[..self.len];
+        // SAFETY: `u64` has no padding and is valid for any bit pattern.
+        unsafe { slice::from_raw_parts_mut(args.as_mut_ptr().cast(), size_of_val(args)) }
+    }
+}
+
+/// Test run the program referenced by `fd` with the given input.
+fn test_run<'dat>(fd: BorrowedFd<'_>, input: Input<'dat>) -> Result<Output<'dat>> {
+    unsafe fn slice_from_array<'t, T>(items: *mut T, num_items: usize) -> Option<&'t mut [T]> {
+        if items.is_null() {
+            None
+        } else {
+            Some(unsafe { slice::from_raw_parts_mut(items, num_items) })
+        }
+    }
+
+    let Input {
+        context_in,
+        mut context_out,
+        data_in,
+        mut data_out,
+        cpu,
+        mut flags,
+        repeat,
+        batch_size,
+        xdp_live_frames,
+        _non_exhaustive: (),
+    } = input;
+
+    if xdp_live_frames {
+        flags |= libbpf_sys::BPF_F_TEST_XDP_LIVE_FRAMES;
+    }
+
+    let mut opts = unsafe { mem::zeroed::<libbpf_sys::bpf_test_run_opts>() };
+    opts.sz = size_of_val(&opts) as _;
+    opts.ctx_in = context_in
+        .as_ref()
+        .map(|data| data.as_ptr().cast())
+        .unwrap_or_else(ptr::null);
+    opts.ctx_size_in = context_in.map(|data| data.len() as _).unwrap_or(0);
+    opts.ctx_out = context_out
+        .as_mut()
+        .map(|data| data.as_mut_ptr().cast())
+        .unwrap_or_else(ptr::null_mut);
+    opts.ctx_size_out = context_out.map(|data| data.len() as _).unwrap_or(0);
+    opts.data_in = data_in
+        .map(|data| data.as_ptr().cast())
+        .unwrap_or_else(ptr::null);
+    opts.data_size_in = data_in.map(|data| data.len() as _).unwrap_or(0);
+    opts.data_out = data_out
+        .as_mut()
+        .map(|data| data.as_mut_ptr().cast())
+        .unwrap_or_else(ptr::null_mut);
+    opts.data_size_out = data_out.map(|data| data.len() as _).unwrap_or(0);
+    opts.cpu = cpu;
+    opts.flags = flags;
+    opts.repeat = repeat as _;
+    opts.batch_size = batch_size;
+
+    let rc = unsafe { libbpf_sys::bpf_prog_test_run_opts(fd.as_raw_fd(), &mut opts) };
+    let () = util::parse_ret(rc)?;
+    let output = Output {
+        return_value: opts.retval,
+        context: unsafe { slice_from_array(opts.ctx_out.cast(), opts.ctx_size_out as _) },
+        data: unsafe { slice_from_array(opts.data_out.cast(), opts.data_size_out as _) },
+        duration: Duration::from_nanos(opts.duration.into()),
+        _non_exhaustive: (),
+    };
+    Ok(output)
+}
+
+
 /// An immutable loaded BPF program.
 pub type Program<'obj> = ProgramImpl<'obj>;
 /// A mutable loaded BPF program.
@@ -843,13 +1330,20 @@ impl<'obj> ProgramMut<'obj> {
 
     /// Attach this program to a [kernel
     /// probe](https://www.kernel.org/doc/html/latest/trace/kprobetrace.html).
+    ///
+    /// If attachment fails, the error is augmented with the reason
+    /// reported by [`KernelTargets::check`][crate::KernelTargets::check],
+    /// if any.
     pub fn attach_kprobe<T: AsRef<str>>(&mut self, retprobe: bool, func_name: T) -> Result<Link> {
-        let func_name = util::str_to_cstring(func_name.as_ref())?;
+        let name = func_name.as_ref();
+        let func_name = util::str_to_cstring(name)?;
         let func_name_ptr = func_name.as_ptr();
         let ptr = unsafe {
             libbpf_sys::bpf_program__attach_kprobe(self.ptr.as_ptr(), retprobe, func_name_ptr)
         };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach kprobe")?;
+        let ptr = validate_bpf_ret(ptr)
+            .map_err(|err| ktarget::explain_attach_error(err, name, KernelProbeKind::Kprobe))
+            .context("failed to attach kprobe")?;
         // SAFETY: the pointer came from libbpf and has been checked for errors.
         let link = unsafe { Link::new(ptr) };
         Ok(link)
@@ -961,9 +1455,29 @@ impl<'obj> ProgramMut<'obj> {
     }
 
     /// Attach to a [fentry/fexit kernel probe](https://lwn.net/Articles/801479/)
+    ///
+    /// If attachment fails, the error is augmented with the reason
+    /// reported by [`KernelTargets::check`][crate::KernelTargets::check]
+    /// for the target named by the program's section, if any.
     pub fn attach_trace(&mut self) -> Result<Link> {
         let ptr = unsafe { libbpf_sys::bpf_program__attach_trace(self.ptr.as_ptr()) };
-        let ptr = validate_bpf_ret(ptr).context("failed to attach fentry/fexit kernel probe")?;
+        let ptr = validate_bpf_ret(ptr)
+            .map_err(|err| {
+                let kind = match self.attach_type() {
+                    ProgramAttachType::TraceFexit => KernelProbeKind::Fexit,
+                    _ => KernelProbeKind::Fentry,
+                };
+                // Sections are of the form `fentry/<func>`, `fexit.s/<func>`,
+                // and similar.
+                let section = self.section().to_string_lossy();
+                match section.split_once('/') {
+                    Some((_prefix, name)) if !name.is_empty() => {
+                        ktarget::explain_attach_error(err, name, kind)
+                    }
+                    _ => err,
+                }
+            })
+            .context("failed to attach fentry/fexit kernel probe")?;
         // SAFETY: the pointer came from libbpf and has been checked for errors.
         let link = unsafe { Link::new(ptr) };
         Ok(link)
@@ -1000,6 +1514,20 @@ impl<'obj> ProgramMut<'obj> {
         Ok(link)
     }
 
+    /// Attach this program to a
+    /// [netfilter](https://www.netfilter.org/) hook. The entry point of the
+    /// program must be defined with `SEC("netfilter")`.
+    pub fn attach_netfilter(&mut self, opts: NetfilterOpts) -> Result<Link> {
+        let opts = libbpf_sys::bpf_netfilter_opts::from(opts);
+        let ptr = unsafe {
+            libbpf_sys::bpf_program__attach_netfilter(self.ptr.as_ptr(), &opts as *const _)
+        };
+        let ptr = validate_bpf_ret(ptr).context("failed to attach netfilter program")?;
+        // SAFETY: the pointer came from libbpf and has been checked for errors.
+        let link = unsafe { Link::new(ptr) };
+        Ok(link)
+    }
+
     fn attach_usdt_impl(
         &mut self,
         pid: i32,
@@ -1079,8 +1607,15 @@ impl<'obj> ProgramMut<'obj> {
     /// [BPF Iterator](https://www.kernel.org/doc/html/latest/bpf/bpf_iterators.html).
     /// The entry point of the program must be defined with `SEC("iter")` or `SEC("iter.s")`.
     pub fn attach_iter(&mut self, map_fd: BorrowedFd<'_>) -> Result<Link> {
-        let mut linkinfo = libbpf_sys::bpf_iter_link_info::default();
-        linkinfo.map.map_fd = map_fd.as_raw_fd() as _;
+        self.attach_iter_with_opts(IterOpts::Map(map_fd))
+    }
+
+    /// Attach this program to a
+    /// [BPF Iterator](https://www.kernel.org/doc/html/latest/bpf/bpf_iterators.html),
+    /// scoped as described by `opts`.
+    /// The entry point of the program must be defined with `SEC("iter")` or `SEC("iter.s")`.
+    pub fn attach_iter_with_opts(&mut self, opts: IterOpts<'_>) -> Result<Link> {
+        let mut linkinfo = libbpf_sys::bpf_iter_link_info::from(opts);
         let attach_opt = libbpf_sys::bpf_iter_attach_opts {
             link_info: &mut linkinfo as *mut libbpf_sys::bpf_iter_link_info,
             link_info_len: size_of::<libbpf_sys::bpf_iter_link_info>() as _,
@@ -1104,59 +1639,39 @@ impl<'obj> ProgramMut<'obj> {
     ///
     /// This function uses the
     /// [BPF_PROG_RUN](https://www.kernel.org/doc/html/latest/bpf/bpf_prog_run.html)
-    /// facility.
+    /// facility. Typed input contexts for common program types are
+    /// available in the form of [`SkbContext`], [`XdpContext`], and
+    /// [`RawTracepointContext`].
     pub fn test_run<'dat>(&mut self, input: Input<'dat>) -> Result<Output<'dat>> {
-        unsafe fn slice_from_array<'t, T>(items: *mut T, num_items: usize) -> Option<&'t mut [T]> {
-            if items.is_null() {
-                None
-            } else {
-                Some(unsafe { slice::from_raw_parts_mut(items, num_items) })
-            }
-        }
+        test_run(self.as_fd(), input)
+    }
+}
 
-        let Input {
-            context_in,
-            mut context_out,
-            data_in,
-            mut data_out,
-            cpu,
-            flags,
-            _non_exhaustive: (),
-        } = input;
-
-        let mut opts = unsafe { mem::zeroed::<libbpf_sys::bpf_test_run_opts>() };
-        opts.sz = size_of_val(&opts) as _;
-        opts.ctx_in = context_in
-            .as_ref()
-            .map(|data| data.as_ptr().cast())
-            .unwrap_or_else(ptr::null);
-        opts.ctx_size_in = context_in.map(|data| data.len() as _).unwrap_or(0);
-        opts.ctx_out = context_out
-            .as_mut()
-            .map(|data| data.as_mut_ptr().cast())
-            .unwrap_or_else(ptr::null_mut);
-        opts.ctx_size_out = context_out.map(|data| data.len() as _).unwrap_or(0);
-        opts.data_in = data_in
-            .map(|data| data.as_ptr().cast())
-            .unwrap_or_else(ptr::null);
-        opts.data_size_in = data_in.map(|data| data.len() as _).unwrap_or(0);
-        opts.data_out = data_out
-            .as_mut()
-            .map(|data| data.as_mut_ptr().cast())
-            .unwrap_or_else(ptr::null_mut);
-        opts.data_size_out = data_out.map(|data| data.len() as _).unwrap_or(0);
-        opts.cpu = cpu;
-        opts.flags = flags;
-
-        let rc = unsafe { libbpf_sys::bpf_prog_test_run_opts(self.as_fd().as_raw_fd(), &mut opts) };
-        let () = util::parse_ret(rc)?;
-        let output = Output {
-            return_value: opts.retval,
-            context: unsafe { slice_from_array(opts.ctx_out.cast(), opts.ctx_size_out as _) },
-            data: unsafe { slice_from_array(opts.data_out.cast(), opts.data_size_out as _) },
-            _non_exhaustive: (),
-        };
-        Ok(output)
+impl<T> ProgramImpl<'_, T> {
+    /// Attach this program to the socket `sock` as a socket filter
+    /// (`SO_ATTACH_BPF`).
+    ///
+    /// The program is detached once the returned [`SocketAttachment`] is
+    /// dropped.
+    pub fn attach_socket<'sock, S: AsFd>(&self, sock: &'sock S) -> Result<SocketAttachment<'sock>> {
+        SocketAttachment::new(self.as_fd(), sock.as_fd(), SocketAttachKind::Filter)
+            .context("failed to attach socket filter")
+    }
+
+    /// Attach this program to the reuseport group that `sock` is a member
+    /// of (`SO_ATTACH_REUSEPORT_EBPF`), for selecting the socket
+    /// receiving a packet or connection.
+    ///
+    /// The socket has to have `SO_REUSEPORT` set. Use a
+    /// [`SocketArray`][crate::SocketArray] for providing the sockets to
+    /// select from. The program is detached once the returned
+    /// [`SocketAttachment`] is dropped.
+    pub fn attach_reuseport<'sock, S: AsFd>(
+        &self,
+        sock: &'sock S,
+    ) -> Result<SocketAttachment<'sock>> {
+        SocketAttachment::new(self.as_fd(), sock.as_fd(), SocketAttachKind::Reuseport)
+            .context("failed to attach reuseport program")
     }
 }
 
@@ -1186,12 +1701,297 @@ impl<T> AsRawLibbpf for ProgramImpl<'_, T> {
     }
 }
 
+
+/// Options to optionally be provided when loading a program via
+/// [`ProgramHandle::load`].
+#[derive(Clone, Debug, Default)]
+pub struct ProgramLoadOpts {
+    /// The attach type the program is expected to be attached with.
+    pub expected_attach_type: Option<ProgramAttachType>,
+    /// The `BPF_F_*` flags to load the program with.
+    pub prog_flags: u32,
+    /// The verifier log level.
+    ///
+    /// If zero, the verifier log is only captured if loading fails.
+    pub log_level: u32,
+    /// The size of the buffer for capturing the verifier log. A value of
+    /// zero selects a reasonable default.
+    pub log_size: usize,
+    /// The struct is non-exhaustive and open to extension.
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+
+/// A handle to a loaded BPF program that is not owned by an
+/// [`Object`][crate::Object].
+///
+/// A `ProgramHandle` can be used to interact with programs loaded by
+/// other processes, e.g., after retrieving them via their ID or a pinned
+/// path:
+/// ```no_run
+/// # use libbpf_rs::ProgramHandle;
+/// let prog = ProgramHandle::from_pinned_path("/sys/fs/bpf/my_prog").unwrap();
+/// println!("{:?}: {:?}", prog.name(), prog.prog_type());
+/// ```
+#[derive(Debug)]
+pub struct ProgramHandle {
+    fd: OwnedFd,
+    id: u32,
+    name: OsString,
+    ty: ProgramType,
+    tag: Tag,
+}
+
+impl ProgramHandle {
+    /// Load a program from raw instructions, e.g., as created by an
+    /// [`Assembler`][crate::Assembler].
+    ///
+    /// If the program is rejected, the verifier log is attached to the
+    /// returned error and can be retrieved via
+    /// [`Error::verifier_log`].
+    pub fn load<T: AsRef<OsStr>>(
+        prog_type: ProgramType,
+        name: Option<T>,
+        license: &str,
+        insns: &[libbpf_sys::bpf_insn],
+        opts: &ProgramLoadOpts,
+    ) -> Result<Self> {
+        Self::load_with(prog_type, name, license, insns, opts, |_load_opts| ())
+    }
+
+    /// Load a program from raw instructions, allowing for adjustment of
+    /// the low-level load options (e.g., to provide BTF information)
+    /// by means of `setup`.
+    pub(crate) fn load_with<T: AsRef<OsStr>>(
+        prog_type: ProgramType,
+        name: Option<T>,
+        license: &str,
+        insns: &[libbpf_sys::bpf_insn],
+        opts: &ProgramLoadOpts,
+        setup: impl FnOnce(&mut libbpf_sys::bpf_prog_load_opts),
+    ) -> Result<Self> {
+        let name = name.map(|name| util::str_to_cstring(&name.as_ref().to_string_lossy()));
+        let name = name.transpose()?;
+        let name_ptr = name
+            .as_ref()
+            .map(|name| name.as_ptr())
+            .unwrap_or_else(ptr::null);
+        let license = util::str_to_cstring(license)?;
+        let log_size = if opts.log_size == 0 {
+            VERIFIER_LOG_SIZE
+        } else {
+            opts.log_size
+        };
+        let log_size = u32::try_from(log_size).map_err(|_| {
+            Error::with_invalid_data(format!("verifier log size {log_size} is too large"))
+        })?;
+        let mut log = vec![0u8; log_size as usize];
+
+        let mut load_opts = libbpf_sys::bpf_prog_load_opts {
+            sz: size_of::<libbpf_sys::bpf_prog_load_opts>() as _,
+            expected_attach_type: opts.expected_attach_type.map(|ty| ty as u32).unwrap_or(0),
+            prog_flags: opts.prog_flags,
+            log_level: opts.log_level,
+            log_size,
+            log_buf: log.as_mut_ptr().cast(),
+            ..Default::default()
+        };
+        let () = setup(&mut load_opts);
+
+        let fd = unsafe {
+            libbpf_sys::bpf_prog_load(
+                prog_type as u32,
+                name_ptr,
+                license.as_ptr(),
+                insns.as_ptr(),
+                insns.len() as _,
+                &mut load_opts,
+            )
+        };
+        if fd < 0 {
+            let err = Error::from_raw_os_error(-fd);
+            let log = c_buf_to_string(&log);
+            if log.is_empty() {
+                return Err(err)
+            }
+            let name = name.map(|name| name.to_string_lossy().into_owned());
+            let log = VerifierLog::new(OsStr::new(name.as_deref().unwrap_or("")), log);
+            return Err(err.with_verifier_log(log))
+        }
+
+        // SAFETY: `bpf_prog_load` returns a valid file descriptor on
+        //         success, which we now own.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Self::from_fd(fd)
+    }
+
+    /// Open a loaded program from its program ID.
+    pub fn from_prog_id(id: u32) -> Result<Self> {
+        Program::fd_from_id(id).and_then(Self::from_fd)
+    }
+
+    /// Open a program pinned in bpffs.
+    pub fn from_pinned_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Program::fd_from_pinned_path(path).and_then(Self::from_fd)
+    }
+
+    /// Create a `ProgramHandle` from a file descriptor referencing a
+    /// BPF program.
+    pub fn from_fd(fd: OwnedFd) -> Result<Self> {
+        let info = ProgramInfo::load_from_fd(fd.as_fd(), &ProgInfoQueryOptions::default())?;
+        Ok(Self {
+            fd,
+            id: info.id,
+            name: OsStr::from_bytes(info.name.to_bytes()).to_os_string(),
+            ty: info.ty,
+            tag: info.tag,
+        })
+    }
+
+    /// Retrieve the ID of the program.
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Retrieve the name of the program.
+    ///
+    /// Note that the kernel truncates program names to
+    /// `BPF_OBJ_NAME_LEN - 1` bytes.
+    #[inline]
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// Retrieve the type of the program.
+    #[inline]
+    pub fn prog_type(&self) -> ProgramType {
+        self.ty
+    }
+
+    /// Retrieve the tag of the program, i.e., the hash over its
+    /// instructions.
+    #[inline]
+    pub fn tag(&self) -> &Tag {
+        &self.tag
+    }
+
+    /// Retrieve information about the program from the kernel,
+    /// including the parts requested by `opts`.
+    pub fn info(&self, opts: &ProgInfoQueryOptions) -> Result<ProgramInfo> {
+        ProgramInfo::load_from_fd(self.fd.as_fd(), opts)
+    }
+
+    /// [Pin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
+    /// this program to bpffs.
+    pub fn pin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path_c = util::path_to_cstring(path)?;
+        let path_ptr = path_c.as_ptr();
+
+        let ret = unsafe { libbpf_sys::bpf_obj_pin(self.fd.as_raw_fd(), path_ptr) };
+        util::parse_ret(ret)
+    }
+
+    /// [Unpin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
+    /// this program from bpffs.
+    pub fn unpin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        remove_file(path).context("failed to remove pin program")
+    }
+
+    /// Create a BPF link attaching the program to the given raw target.
+    fn link_create(&self, target: i32, attach_type: ProgramAttachType) -> Result<Link> {
+        let fd = unsafe {
+            libbpf_sys::bpf_link_create(
+                self.fd.as_raw_fd(),
+                target,
+                attach_type as u32,
+                ptr::null(),
+            )
+        };
+        let fd = util::parse_ret_i32(fd).context("failed to create link")?;
+        // SAFETY: `bpf_link_create` returns a valid file descriptor on
+        //         success, which we now own.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Link::from_fd(fd)
+    }
+
+    /// Attach the program to `target` via a BPF link with the given
+    /// attach type.
+    ///
+    /// `target` is the file descriptor of the object to attach to, e.g.,
+    /// a cgroup for cgroup attach types or a network namespace for netns
+    /// attach types.
+    ///
+    /// The program gets detached once the returned [`Link`] is dropped,
+    /// unless it was pinned in the meantime.
+    pub fn attach(&self, target: BorrowedFd<'_>, attach_type: ProgramAttachType) -> Result<Link> {
+        self.link_create(target.as_raw_fd(), attach_type)
+    }
+
+    /// Attach an XDP program to the network interface with the given
+    /// index via a BPF link.
+    pub fn attach_xdp(&self, ifindex: i32) -> Result<Link> {
+        self.link_create(ifindex, ProgramAttachType::Xdp)
+    }
+
+    /// Attach a BPF iterator program via a BPF link.
+    ///
+    /// The resulting link can be used to create an [`Iter`][crate::Iter].
+    pub fn attach_iter(&self) -> Result<Link> {
+        self.link_create(0, ProgramAttachType::TraceIter)
+    }
+
+    /// Attach the program to the raw tracepoint with the given name.
+    ///
+    /// Similar to [`attach`][Self::attach], the program stays attached
+    /// for as long as the returned [`Link`] exists.
+    pub fn attach_raw_tracepoint<T: AsRef<str>>(&self, tp_name: T) -> Result<Link> {
+        let tp_name = util::str_to_cstring(tp_name.as_ref())?;
+        let fd =
+            unsafe { libbpf_sys::bpf_raw_tracepoint_open(tp_name.as_ptr(), self.fd.as_raw_fd()) };
+        let fd = util::parse_ret_i32(fd).context("failed to attach raw tracepoint")?;
+        // SAFETY: `bpf_raw_tracepoint_open` returns a valid file descriptor
+        //         on success, which we now own.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Link::from_fd(fd)
+    }
+
+    /// Test run the program with the given input data.
+    ///
+    /// Please refer to [`ProgramMut::test_run`] for details.
+    pub fn test_run<'dat>(&mut self, input: Input<'dat>) -> Result<Output<'dat>> {
+        test_run(self.fd.as_fd(), input)
+    }
+}
+
+impl<T> TryFrom<&ProgramImpl<'_, T>> for ProgramHandle {
+    type Error = Error;
+
+    fn try_from(other: &ProgramImpl<'_, T>) -> Result<Self> {
+        let fd = other
+            .as_fd()
+            .try_clone_to_owned()
+            .context("failed to duplicate program file descriptor")?;
+        Self::from_fd(fd)
+    }
+}
+
+impl AsFd for ProgramHandle {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use std::mem::discriminant;
 
+    use crate::ErrorKind;
+
     #[test]
     fn program_type() {
         use ProgramType::*;
@@ -1229,6 +2029,7 @@ mod tests {
             Lsm,
             SkLookup,
             Syscall,
+            Netfilter,
             Unknown,
         ] {
             // check if discriminants match after a roundtrip conversion
@@ -1283,6 +2084,7 @@ mod tests {
             SkReuseportSelect,
             SkReuseportSelectOrMigrate,
             PerfEvent,
+            Netfilter,
             Unknown,
         ] {
             // check if discriminants match after a roundtrip conversion
@@ -1291,5 +2093,102 @@ mod tests {
                 discriminant(&ProgramAttachType::from(t as u32))
             );
         }
+
+        assert_eq!(Netfilter as u32, libbpf_sys::BPF_NETFILTER);
+    }
+
+    #[test]
+    fn netfilter_protocol_family() {
+        use NetfilterProtocolFamily::*;
+
+        for t in [Ipv4, Ipv6, Unknown] {
+            // check if discriminants match after a roundtrip conversion
+            assert_eq!(t, NetfilterProtocolFamily::from(t as u32));
+        }
+
+        assert_eq!(Ipv4 as u32, 2);
+        assert_eq!(Ipv6 as u32, 10);
+    }
+
+    #[test]
+    fn netfilter_hook() {
+        use NetfilterHook::*;
+
+        for t in [PreRouting, LocalIn, Forward, LocalOut, PostRouting, Unknown] {
+            // check if discriminants match after a roundtrip conversion
+            assert_eq!(t, NetfilterHook::from(t as u32));
+        }
+    }
+
+    /// Check that `NetfilterOpts` get converted to their libbpf
+    /// counterpart as expected.
+    #[test]
+    fn netfilter_opts_conversion() {
+        let opts = NetfilterOpts {
+            protocol_family: NetfilterProtocolFamily::Ipv6,
+            hook: NetfilterHook::LocalOut,
+            priority: -128,
+            ip_defrag: true,
+            ..Default::default()
+        };
+        let opts = libbpf_sys::bpf_netfilter_opts::from(opts);
+        assert_eq!(opts.sz, size_of::<libbpf_sys::bpf_netfilter_opts>() as _);
+        assert_eq!(opts.pf, 10);
+        assert_eq!(opts.hooknum, 3);
+        assert_eq!(opts.priority, -128);
+        assert_eq!(opts.flags, libbpf_sys::BPF_F_NETFILTER_IP_DEFRAG);
+    }
+
+    /// Check that the layout of our context types matches that of the
+    /// corresponding kernel types.
+    #[test]
+    fn test_run_context_layout() {
+        fn offset<T, F>(base: &T, field: &F) -> usize {
+            field as *const F as usize - base as *const T as usize
+        }
+
+        let ctx = SkbContext::default();
+        assert_eq!(size_of::<SkbContext>(), 192);
+        assert_eq!(offset(&ctx, &ctx.mark), 8);
+        assert_eq!(offset(&ctx, &ctx.priority), 32);
+        assert_eq!(offset(&ctx, &ctx.ingress_ifindex), 36);
+        assert_eq!(offset(&ctx, &ctx.ifindex), 40);
+        assert_eq!(offset(&ctx, &ctx.cb), 48);
+        assert_eq!(offset(&ctx, &ctx.tstamp), 152);
+        assert_eq!(offset(&ctx, &ctx.wire_len), 160);
+        assert_eq!(offset(&ctx, &ctx.gso_segs), 164);
+        assert_eq!(offset(&ctx, &ctx.gso_size), 176);
+        assert_eq!(offset(&ctx, &ctx.hwtstamp), 184);
+
+        let mut ctx = XdpContext::new(64);
+        assert_eq!(size_of::<XdpContext>(), size_of::<libbpf_sys::xdp_md>());
+        assert_eq!(offset(&ctx, &ctx.ingress_ifindex), 12);
+        assert_eq!(ctx.as_bytes_mut()[4..8], 64u32.to_ne_bytes());
+    }
+
+    /// Check that raw tracepoint contexts are bounded in the number of
+    /// arguments.
+    #[test]
+    fn raw_tracepoint_context() {
+        let mut ctx = RawTracepointContext::new(&[1, 2, 3]).unwrap();
+        assert_eq!(ctx.args(), &[1, 2, 3]);
+        assert_eq!(ctx.as_bytes_mut().len(), 24);
+
+        let args = [0; RawTracepointContext::MAX_ARGS + 1];
+        let _err = RawTracepointContext::new(&args).unwrap_err();
+    }
+
+    /// Check that we reject verifier log sizes not representable by
+    /// the kernel.
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn program_load_log_size_overflow() {
+        let opts = ProgramLoadOpts {
+            log_size: u32::MAX as usize + 1,
+            ..Default::default()
+        };
+        let err = ProgramHandle::load(ProgramType::SocketFilter, None::<&str>, "GPL", &[], &opts)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
     }
 }
@@ -13,6 +13,7 @@
 use std::ffi::c_void;
 use std::ffi::CString;
 use std::io;
+use std::mem::size_of;
 use std::mem::size_of_val;
 use std::os::fd::AsFd;
 use std::os::fd::AsRawFd;
@@ -24,7 +25,10 @@ use std::ptr;
 use std::time::Duration;
 
 use crate::util;
+use crate::CgroupIterOrder;
 use crate::MapType;
+use crate::NetfilterHook;
+use crate::NetfilterProtocolFamily;
 use crate::ProgramAttachType;
 use crate::ProgramType;
 use crate::Result;
@@ -269,7 +273,7 @@ impl ProgInfoQueryOptions {
 }
 
 impl ProgramInfo {
-    fn load_from_fd(fd: BorrowedFd<'_>, opts: &ProgInfoQueryOptions) -> Result<Self> {
+    pub(crate) fn load_from_fd(fd: BorrowedFd<'_>, opts: &ProgInfoQueryOptions) -> Result<Self> {
         let mut item = libbpf_sys::bpf_prog_info::default();
 
         let mut xlated_prog_insns: Vec<u8> = Vec::new();
@@ -368,7 +372,7 @@ impl ProgramInfo {
         };
         util::parse_ret(ret)?;
 
-        return Ok(ProgramInfo {
+        Ok(ProgramInfo {
             name: name.to_owned(),
             ty,
             tag: Tag(item.tag),
@@ -395,7 +399,22 @@ impl ProgramInfo {
             run_time_ns: item.run_time_ns,
             run_cnt: item.run_cnt,
             recursion_misses: item.recursion_misses,
-        });
+        })
+    }
+
+    /// Retrieve the translated instructions of the program.
+    ///
+    /// The result is empty unless the instructions were requested via
+    /// [`ProgInfoQueryOptions::include_xlated_prog_insns`].
+    pub fn xlated_insns(&self) -> Vec<libbpf_sys::bpf_insn> {
+        self.xlated_prog_insns
+            .chunks_exact(size_of::<libbpf_sys::bpf_insn>())
+            .map(|chunk| {
+                // SAFETY: `chunk` is exactly as large as a `bpf_insn`,
+                //         which is valid for any bit pattern.
+                unsafe { ptr::read_unaligned(chunk.as_ptr().cast()) }
+            })
+            .collect()
     }
 }
 
@@ -428,11 +447,8 @@ impl Iterator for ProgInfoIter {
 
         let prog = ProgramInfo::load_from_fd(fd.as_fd(), &self.opts);
 
-        match prog {
-            Ok(p) => Some(p),
-            // TODO: We should consider bubbling up errors properly.
-            Err(_err) => None,
-        }
+        // TODO: We should consider bubbling up errors properly.
+        prog.ok()
     }
 }
 
@@ -578,11 +594,8 @@ impl Iterator for BtfInfoIter {
 
         let info = BtfInfo::load_from_fd(fd.as_fd());
 
-        match info {
-            Ok(i) => Some(i),
-            // TODO: We should consider bubbling up errors properly.
-            Err(_err) => None,
-        }
+        // TODO: We should consider bubbling up errors properly.
+        info.ok()
     }
 }
 
@@ -616,6 +629,92 @@ pub struct NetNsLinkInfo {
     pub attach_type: ProgramAttachType,
 }
 
+/// Information about a BPF netfilter link.
+#[derive(Debug, Clone)]
+pub struct NetfilterLinkInfo {
+    /// The protocol family the program is attached to.
+    pub protocol_family: NetfilterProtocolFamily,
+    /// The hook point the program is attached to.
+    pub hook: NetfilterHook,
+    /// The priority of the program at the hook point.
+    pub priority: i32,
+    /// Whether IP defragmentation was requested.
+    pub ip_defrag: bool,
+}
+
+/// Target specific parameters of a BPF iterator link.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IterLinkParams {
+    /// The iterator is not scoped.
+    None,
+    /// The iterator visits the elements of a map.
+    Map {
+        /// The ID of the map.
+        map_id: u32,
+    },
+    /// The iterator visits cgroups.
+    Cgroup {
+        /// The ID of the cgroup the iteration starts at.
+        cgroup_id: u64,
+        /// The order in which cgroups are visited.
+        order: CgroupIterOrder,
+    },
+    /// The iterator visits a single thread or the threads of a single
+    /// process.
+    Task {
+        /// The ID of the visited thread, if scoped to a thread.
+        tid: u32,
+        /// The PID of the visited process, if scoped to a process.
+        pid: u32,
+    },
+}
+
+/// Information about a BPF iterator link.
+#[derive(Debug, Clone)]
+pub struct IterLinkInfo {
+    /// The name of the iterator target, e.g., `task` or `bpf_map_elem`.
+    pub target_name: String,
+    /// The parameters the iterator was attached with.
+    pub params: IterLinkParams,
+}
+
+impl IterLinkInfo {
+    fn from_uapi(iter: &libbpf_sys::bpf_link_info__bindgen_ty_1__bindgen_ty_4) -> Self {
+        let target_name = util::c_ptr_to_string(iter.target_name as *const c_char)
+            .unwrap_or_else(|_| "?".to_string());
+        let params = match target_name.as_str() {
+            "bpf_map_elem" | "bpf_sk_storage_map" => IterLinkParams::Map {
+                map_id: unsafe { iter.__bindgen_anon_1.map.map_id },
+            },
+            "cgroup" => {
+                let cgroup = unsafe { iter.__bindgen_anon_2.cgroup };
+                IterLinkParams::Cgroup {
+                    cgroup_id: cgroup.cgroup_id,
+                    order: CgroupIterOrder::from(cgroup.order),
+                }
+            }
+            "task" | "task_file" | "task_vma" => {
+                let task = unsafe { iter.__bindgen_anon_2.task };
+                if task.tid == 0 && task.pid == 0 {
+                    IterLinkParams::None
+                } else {
+                    IterLinkParams::Task {
+                        tid: task.tid,
+                        pid: task.pid,
+                    }
+                }
+            }
+            _ => IterLinkParams::None,
+        };
+
+        Self {
+            target_name,
+            params,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 // TODO: Document variants.
 #[allow(missing_docs)]
@@ -623,8 +722,9 @@ pub enum LinkTypeInfo {
     RawTracepoint(RawTracepointLinkInfo),
     Tracing(TracingLinkInfo),
     Cgroup(CgroupLinkInfo),
-    Iter,
+    Iter(IterLinkInfo),
     NetNs(NetNsLinkInfo),
+    Netfilter(NetfilterLinkInfo),
     Unknown,
 }
 
@@ -677,13 +777,41 @@ impl LinkInfo {
                     s.__bindgen_anon_1.cgroup.attach_type
                 }),
             }),
-            libbpf_sys::BPF_LINK_TYPE_ITER => LinkTypeInfo::Iter,
+            libbpf_sys::BPF_LINK_TYPE_ITER => {
+                let mut buf = [0; 256];
+                s.__bindgen_anon_1.iter.target_name = buf.as_mut_ptr() as u64;
+                s.__bindgen_anon_1.iter.target_name_len = buf.len() as u32;
+                let item_ptr: *mut libbpf_sys::bpf_link_info = &mut s;
+                let mut len = size_of_val(&s) as u32;
+
+                let ret = unsafe {
+                    libbpf_sys::bpf_obj_get_info_by_fd(
+                        fd.as_raw_fd(),
+                        item_ptr as *mut c_void,
+                        &mut len,
+                    )
+                };
+                if ret != 0 {
+                    return None;
+                }
+
+                LinkTypeInfo::Iter(IterLinkInfo::from_uapi(unsafe { &s.__bindgen_anon_1.iter }))
+            }
             libbpf_sys::BPF_LINK_TYPE_NETNS => LinkTypeInfo::NetNs(NetNsLinkInfo {
                 ino: unsafe { s.__bindgen_anon_1.netns.netns_ino },
                 attach_type: ProgramAttachType::from(unsafe {
                     s.__bindgen_anon_1.netns.attach_type
                 }),
             }),
+            libbpf_sys::BPF_LINK_TYPE_NETFILTER => {
+                let netfilter = unsafe { s.__bindgen_anon_1.netfilter };
+                LinkTypeInfo::Netfilter(NetfilterLinkInfo {
+                    protocol_family: NetfilterProtocolFamily::from(netfilter.pf),
+                    hook: NetfilterHook::from(netfilter.hooknum),
+                    priority: netfilter.priority,
+                    ip_defrag: netfilter.flags & libbpf_sys::BPF_F_NETFILTER_IP_DEFRAG != 0,
+                })
+            }
             _ => LinkTypeInfo::Unknown,
         };
 
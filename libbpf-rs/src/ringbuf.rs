@@ -5,6 +5,7 @@ use std::fmt::Result as FmtResult;
 use std::ops::Deref as _;
 use std::ops::DerefMut as _;
 use std::os::raw::c_ulong;
+use std::os::unix::prelude::AsFd;
 use std::os::unix::prelude::AsRawFd;
 use std::os::unix::prelude::BorrowedFd;
 use std::ptr::null_mut;
@@ -222,6 +223,17 @@ impl AsRawLibbpf for RingBuffer<'_> {
     }
 }
 
+impl AsFd for RingBuffer<'_> {
+    /// Retrieve the epoll file descriptor, which becomes readable once
+    /// data is available in any of the rings.
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        let fd = self.epoll_fd();
+        // SAFETY: The epoll file descriptor is owned by the ring buffer
+        //         and stays valid for as long as it is alive.
+        unsafe { BorrowedFd::borrow_raw(fd) }
+    }
+}
+
 // SAFETY: `ring_buffer` objects can safely be polled from any thread.
 unsafe impl Send for RingBuffer<'_> {}
 
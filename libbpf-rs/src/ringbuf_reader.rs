@@ -0,0 +1,268 @@
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::io;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd as _;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::OwnedFd;
+use std::ptr;
+use std::ptr::NonNull;
+use std::slice;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use crate::Error;
+use crate::MapCore;
+use crate::MapType;
+use crate::Result;
+
+const BUSY_BIT: u32 = libbpf_sys::BPF_RINGBUF_BUSY_BIT as _;
+const DISCARD_BIT: u32 = libbpf_sys::BPF_RINGBUF_DISCARD_BIT as _;
+const HDR_SZ: u64 = libbpf_sys::BPF_RINGBUF_HDR_SZ as _;
+
+
+/// Map `len` bytes of `fd` at `offset`.
+fn mmap(fd: BorrowedFd<'_>, len: usize, prot: i32, offset: usize) -> Result<NonNull<c_void>> {
+    // SAFETY: We let the kernel pick the address, so no existing
+    //         mapping can be affected.
+    let ptr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            len,
+            prot,
+            libc::MAP_SHARED,
+            fd.as_raw_fd(),
+            offset as libc::off_t,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(Error::from(io::Error::last_os_error()))
+    }
+    // SANITY: `mmap` never returns NULL on success for a NULL hint.
+    Ok(NonNull::new(ptr).unwrap())
+}
+
+
+/// A zero-copy reader of a single `ringbuf` map.
+///
+/// In contrast to [`RingBuffer`][crate::RingBuffer], a
+/// `RingBufferReader` does not rely on callbacks. Instead, it maps the
+/// ring into the process' address space and hands out samples in place,
+/// by means of [`RingBufferBatch`] objects. Records are only released to
+/// the producer once a batch is explicitly
+/// [committed][RingBufferBatch::commit].
+///
+/// The file descriptor exposed via [`AsFd`] becomes readable once data
+/// is available and can be used to wait for new samples.
+///
+/// A ring must not have any consumers other than a single reader.
+#[derive(Debug)]
+pub struct RingBufferReader {
+    /// A duplicate of the map's file descriptor.
+    fd: OwnedFd,
+    /// The writable page containing the consumer position.
+    consumer: NonNull<c_void>,
+    /// The read-only page containing the producer position, followed by
+    /// the data area, which is mapped twice in a row.
+    producer: NonNull<c_void>,
+    /// The length of the mapping at `producer`.
+    producer_len: usize,
+    page_size: usize,
+    mask: u64,
+}
+
+impl RingBufferReader {
+    /// Create a new `RingBufferReader` for the provided `ringbuf` map.
+    pub fn new(map: &dyn MapCore) -> Result<Self> {
+        if map.map_type() != MapType::RingBuf {
+            return Err(Error::with_invalid_data("Must use a RingBuf map"))
+        }
+
+        let fd = map.as_fd().try_clone_to_owned()?;
+        let size = map.info()?.info.max_entries as usize;
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+
+        let consumer = mmap(fd.as_fd(), page_size, libc::PROT_READ | libc::PROT_WRITE, 0)?;
+        let producer_len = page_size + 2 * size;
+        let producer = match mmap(fd.as_fd(), producer_len, libc::PROT_READ, page_size) {
+            Ok(producer) => producer,
+            Err(err) => {
+                let _ret = unsafe { libc::munmap(consumer.as_ptr(), page_size) };
+                return Err(err)
+            }
+        };
+
+        Ok(Self {
+            fd,
+            consumer,
+            producer,
+            producer_len,
+            page_size,
+            mask: size as u64 - 1,
+        })
+    }
+
+    fn consumer_pos(&self) -> &AtomicU64 {
+        // SAFETY: The consumer position is the first member of the
+        //         page-aligned consumer page, which stays mapped for as
+        //         long as we exist.
+        unsafe { &*self.consumer.as_ptr().cast::<AtomicU64>() }
+    }
+
+    fn producer_pos(&self) -> &AtomicU64 {
+        // SAFETY: The producer position is the first member of the
+        //         page-aligned producer page, which stays mapped for as
+        //         long as we exist.
+        unsafe { &*self.producer.as_ptr().cast::<AtomicU64>() }
+    }
+
+    /// Retrieve a pointer to the record at position `pos`.
+    fn record(&self, pos: u64) -> *const u8 {
+        let offset = self.page_size + (pos & self.mask) as usize;
+        // SAFETY: The offset is within the data area, which directly
+        //         follows the producer page.
+        unsafe { self.producer.as_ptr().cast::<u8>().add(offset) }
+    }
+
+    /// Start a new batch of samples, beginning at the current consumer
+    /// position.
+    pub fn batch(&mut self) -> RingBufferBatch<'_> {
+        let pos = self.consumer_pos().load(Ordering::Acquire);
+        RingBufferBatch {
+            reader: self,
+            pos: Cell::new(pos),
+        }
+    }
+
+    /// Check whether data is available to be read.
+    pub fn is_empty(&self) -> bool {
+        let consumer_pos = self.consumer_pos().load(Ordering::Acquire);
+        let producer_pos = self.producer_pos().load(Ordering::Acquire);
+        consumer_pos >= producer_pos
+    }
+}
+
+impl AsFd for RingBufferReader {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl Drop for RingBufferReader {
+    fn drop(&mut self) {
+        let _ret = unsafe { libc::munmap(self.producer.as_ptr(), self.producer_len) };
+        let _ret = unsafe { libc::munmap(self.consumer.as_ptr(), self.page_size) };
+    }
+}
+
+// SAFETY: The mappings are only ever accessed through the reader, which
+//         can be used from any thread.
+unsafe impl Send for RingBufferReader {}
+
+
+/// A batch of samples read from a [`RingBufferReader`].
+///
+/// Samples are read in place via [`RingBufferBatch::iter`]. All samples
+/// handed out so far are released at once by
+/// [committing][RingBufferBatch::commit] the batch. If a batch is
+/// dropped without being committed, its samples will be read again by
+/// the next batch.
+#[derive(Debug)]
+pub struct RingBufferBatch<'rb> {
+    reader: &'rb mut RingBufferReader,
+    /// The position just past the last record handed out.
+    pos: Cell<u64>,
+}
+
+impl RingBufferBatch<'_> {
+    /// Create an iterator over the samples available in the ring.
+    ///
+    /// Iteration ends once no more committed samples are available.
+    /// Iteration continues where a previous iterator left off.
+    pub fn iter(&self) -> RingBufferSamples<'_> {
+        RingBufferSamples { batch: self }
+    }
+
+    /// Release all samples handed out so far to the producer, by
+    /// advancing the consumer position past them.
+    pub fn commit(self) {
+        let () = self
+            .reader
+            .consumer_pos()
+            .store(self.pos.get(), Ordering::Release);
+    }
+}
+
+impl<'batch> IntoIterator for &'batch RingBufferBatch<'_> {
+    type Item = &'batch [u8];
+    type IntoIter = RingBufferSamples<'batch>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+
+/// An iterator over the samples of a [`RingBufferBatch`].
+#[derive(Debug)]
+pub struct RingBufferSamples<'batch> {
+    batch: &'batch RingBufferBatch<'batch>,
+}
+
+impl<'batch> Iterator for RingBufferSamples<'batch> {
+    type Item = &'batch [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = &*self.batch.reader;
+
+        loop {
+            let pos = self.batch.pos.get();
+            if pos >= reader.producer_pos().load(Ordering::Acquire) {
+                return None
+            }
+
+            let record = reader.record(pos);
+            // SAFETY: Each record starts with an eight byte aligned
+            //         header, the first member of which is its length.
+            let len = unsafe { &*record.cast::<AtomicU32>() }.load(Ordering::Acquire);
+            if len & BUSY_BIT != 0 {
+                // The record has been reserved but not yet been
+                // committed by the producer.
+                return None
+            }
+
+            let size = u64::from(len & !(BUSY_BIT | DISCARD_BIT));
+            let () = self.batch.pos.set(pos + (size + HDR_SZ + 7) / 8 * 8);
+
+            if len & DISCARD_BIT == 0 {
+                // SAFETY: The data area is mapped twice in a row, so the
+                //         record is contiguous in memory. It is not
+                //         reused by the producer before we advance the
+                //         consumer position past it, which requires
+                //         consuming the batch we borrow from.
+                let data =
+                    unsafe { slice::from_raw_parts(record.add(HDR_SZ as usize), size as usize) };
+                return Some(data)
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Check that `RingBufferReader` is `Send`.
+    #[test]
+    fn ringbuffer_reader_is_send() {
+        fn test<T>()
+        where
+            T: Send,
+        {
+        }
+
+        test::<RingBufferReader>();
+    }
+}
@@ -16,8 +16,10 @@ use libbpf_sys::bpf_map;
 use libbpf_sys::bpf_map_skeleton;
 use libbpf_sys::bpf_object;
 use libbpf_sys::bpf_object_skeleton;
+use libbpf_sys::bpf_object_subskeleton;
 use libbpf_sys::bpf_prog_skeleton;
 use libbpf_sys::bpf_program;
+use libbpf_sys::bpf_var_skeleton;
 
 use crate::error::IntoError as _;
 use crate::util;
@@ -42,6 +44,14 @@ struct ProgSkelConfig {
     link: Box<*mut bpf_link>,
 }
 
+#[derive(Debug)]
+struct VarSkelConfig {
+    name: String,
+    /// The index of the map containing the variable.
+    map: usize,
+    addr: Box<*mut c_void>,
+}
+
 #[allow(missing_docs)]
 #[derive(Debug)]
 pub struct ObjectSkeletonConfigBuilder<'dat> {
@@ -318,6 +328,197 @@ impl Drop for ObjectSkeletonConfig<'_> {
     }
 }
 
+/// A builder for an [`ObjectSubskeletonConfig`], describing the maps,
+/// programs, and global variables a library object contributed to a
+/// larger, linked object.
+#[derive(Debug, Default)]
+pub struct ObjectSubskeletonConfigBuilder {
+    maps: Vec<MapSkelConfig>,
+    progs: Vec<ProgSkelConfig>,
+    vars: Vec<VarSkelConfig>,
+}
+
+impl ObjectSubskeletonConfigBuilder {
+    /// Construct a new instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a map to the config
+    ///
+    /// `name` is the name of the map in the library object, which for
+    /// global data sections is the section name, e.g., `.bss`. Set
+    /// `mmaped` to `true` if the map is mmap'able to userspace
+    pub fn map<T: AsRef<str>>(&mut self, name: T, mmaped: bool) -> &mut Self {
+        let m = if mmaped {
+            Some(Box::new(ptr::null_mut()))
+        } else {
+            None
+        };
+
+        self.maps.push(MapSkelConfig {
+            name: name.as_ref().to_string(),
+            p: Box::new(ptr::null_mut()),
+            mmaped: m,
+        });
+
+        self
+    }
+
+    /// Adds a prog to the config
+    pub fn prog<T: AsRef<str>>(&mut self, name: T) -> &mut Self {
+        self.progs.push(ProgSkelConfig {
+            name: name.as_ref().to_string(),
+            p: Box::new(ptr::null_mut()),
+            link: Box::new(ptr::null_mut()),
+        });
+
+        self
+    }
+
+    /// Adds a global variable to the config
+    ///
+    /// `map` is the index of the global data section map containing the
+    /// variable, as determined by the order in which maps were passed to
+    /// [`ObjectSubskeletonConfigBuilder::map`].
+    pub fn var<T: AsRef<str>>(&mut self, map: usize, name: T) -> &mut Self {
+        self.vars.push(VarSkelConfig {
+            name: name.as_ref().to_string(),
+            map,
+            addr: Box::new(ptr::null_mut()),
+        });
+
+        self
+    }
+
+    /// Bind the configured maps, programs, and variables to the ones of
+    /// `object`, which has to be at least opened.
+    pub fn build(mut self, object: NonNull<bpf_object>) -> Result<ObjectSubskeletonConfig> {
+        // Holds `CString`s alive so pointers to them stay valid
+        let mut string_pool = Vec::new();
+
+        let mut maps = self
+            .maps
+            .iter_mut()
+            .map(|map| {
+                Ok(bpf_map_skeleton {
+                    name: str_to_cstring_and_pool(&map.name, &mut string_pool)?,
+                    map: &mut *map.p,
+                    mmaped: if let Some(ref mut mmaped) = map.mmaped {
+                        &mut **mmaped
+                    } else {
+                        ptr::null_mut()
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut progs = self
+            .progs
+            .iter_mut()
+            .map(|prog| {
+                Ok(bpf_prog_skeleton {
+                    name: str_to_cstring_and_pool(&prog.name, &mut string_pool)?,
+                    prog: &mut *prog.p,
+                    link: &mut *prog.link,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut vars = Vec::with_capacity(self.vars.len());
+        for var in self.vars.iter_mut() {
+            let map = self.maps.get_mut(var.map).ok_or_invalid_data(|| {
+                format!("variable `{}` refers to invalid map {}", var.name, var.map)
+            })?;
+            let () = vars.push(bpf_var_skeleton {
+                name: str_to_cstring_and_pool(&var.name, &mut string_pool)?,
+                map: &mut *map.p,
+                addr: &mut *var.addr,
+            });
+        }
+
+        let mut s = bpf_object_subskeleton {
+            sz: size_of::<bpf_object_subskeleton>() as c_ulong,
+            obj: object.as_ptr(),
+            map_cnt: maps.len() as i32,
+            map_skel_sz: size_of::<bpf_map_skeleton>() as i32,
+            maps: maps.as_mut_ptr(),
+            prog_cnt: progs.len() as i32,
+            prog_skel_sz: size_of::<bpf_prog_skeleton>() as i32,
+            progs: progs.as_mut_ptr(),
+            var_cnt: vars.len() as i32,
+            var_skel_sz: size_of::<bpf_var_skeleton>() as i32,
+            vars: vars.as_mut_ptr(),
+        };
+
+        // SAFETY: All pointers in `s` are valid for the duration of the
+        //         call and `libbpf` does not retain any of them; it
+        //         merely stores the looked up objects in our boxes.
+        let ret = unsafe { libbpf_sys::bpf_object__open_subskeleton(&mut s) };
+        if ret != 0 {
+            return Err(Error::from_raw_os_error(-ret));
+        }
+
+        Ok(ObjectSubskeletonConfig {
+            maps: self.maps,
+            progs: self.progs,
+            vars: self.vars,
+        })
+    }
+}
+
+/// The maps, programs, and global variables of a library object, as
+/// bound to a larger, linked object by an
+/// [`ObjectSubskeletonConfigBuilder`].
+///
+/// Contrary to [`ObjectSkeletonConfig`], this type does not need to be
+/// kept alive: all pointers it hands out refer to memory managed by the
+/// object.
+#[derive(Debug)]
+pub struct ObjectSubskeletonConfig {
+    maps: Vec<MapSkelConfig>,
+    progs: Vec<ProgSkelConfig>,
+    vars: Vec<VarSkelConfig>,
+}
+
+impl ObjectSubskeletonConfig {
+    /// Returns the map pointer for a map at the specified `index`.
+    ///
+    /// The index is determined by the order in which the map was passed to
+    /// `ObjectSubskeletonConfigBuilder::map`. Index starts at 0.
+    pub fn map_ptr(&self, index: usize) -> Result<*mut bpf_map> {
+        let map = self
+            .maps
+            .get(index)
+            .ok_or_invalid_data(|| format!("Invalid map index: {index}"))?;
+        Ok(*map.p)
+    }
+
+    /// Returns the program pointer for a prog at the specified `index`.
+    ///
+    /// The index is determined by the order in which the prog was passed to
+    /// `ObjectSubskeletonConfigBuilder::prog`. Index starts at 0.
+    pub fn prog_ptr(&self, index: usize) -> Result<*mut bpf_program> {
+        let prog = self
+            .progs
+            .get(index)
+            .ok_or_invalid_data(|| format!("Invalid prog index: {index}"))?;
+        Ok(*prog.p)
+    }
+
+    /// Returns the address of a global variable at the specified `index`.
+    ///
+    /// The index is determined by the order in which the variable was
+    /// passed to `ObjectSubskeletonConfigBuilder::var`. Index starts at 0.
+    pub fn var_ptr(&self, index: usize) -> Result<*mut c_void> {
+        let var = self
+            .vars
+            .get(index)
+            .ok_or_invalid_data(|| format!("Invalid var index: {index}"))?;
+        Ok(*var.addr)
+    }
+}
+
 /// A trait for skeleton builder.
 pub trait SkelBuilder<'obj> {
     /// Define that when BPF object is opened, the returned type should implement the [`OpenSkel`]
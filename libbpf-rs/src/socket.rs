@@ -0,0 +1,197 @@
+use std::ffi::c_int;
+use std::ffi::c_void;
+use std::io;
+use std::mem::size_of;
+use std::mem::size_of_val;
+use std::mem::ManuallyDrop;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::BorrowedFd;
+
+use crate::Error;
+use crate::ErrorKind;
+use crate::MapCore;
+use crate::MapFlags;
+use crate::MapType;
+use crate::Result;
+
+
+/// `SO_DETACH_REUSEPORT_BPF`, which is not provided by `libc` for all
+/// targets.
+const SO_DETACH_REUSEPORT_BPF: c_int = 68;
+
+
+fn setsockopt<T>(sock: BorrowedFd<'_>, opt: c_int, value: &T) -> Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            sock.as_raw_fd(),
+            libc::SOL_SOCKET,
+            opt,
+            value as *const T as *const c_void,
+            size_of_val(value) as _,
+        )
+    };
+    if ret < 0 {
+        return Err(Error::from(io::Error::last_os_error()))
+    }
+    Ok(())
+}
+
+
+/// The ways of attaching a program to a socket.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum SocketAttachKind {
+    /// A socket filter (`SO_ATTACH_BPF`).
+    Filter,
+    /// A reuseport group selector (`SO_ATTACH_REUSEPORT_EBPF`).
+    Reuseport,
+}
+
+impl SocketAttachKind {
+    fn attach_opt(self) -> c_int {
+        match self {
+            Self::Filter => libc::SO_ATTACH_BPF,
+            Self::Reuseport => libc::SO_ATTACH_REUSEPORT_EBPF,
+        }
+    }
+
+    fn detach_opt(self) -> c_int {
+        match self {
+            Self::Filter => libc::SO_DETACH_BPF,
+            Self::Reuseport => SO_DETACH_REUSEPORT_BPF,
+        }
+    }
+}
+
+
+/// A program attached to a socket, detaching it when dropped.
+///
+/// Created by [`Program::attach_socket`][crate::Program::attach_socket]
+/// and [`Program::attach_reuseport`][crate::Program::attach_reuseport].
+#[derive(Debug)]
+#[must_use = "the program is detached when the attachment is dropped"]
+pub struct SocketAttachment<'sock> {
+    sock: BorrowedFd<'sock>,
+    kind: SocketAttachKind,
+}
+
+impl<'sock> SocketAttachment<'sock> {
+    pub(crate) fn new(
+        prog: BorrowedFd<'_>,
+        sock: BorrowedFd<'sock>,
+        kind: SocketAttachKind,
+    ) -> Result<Self> {
+        let prog_fd = prog.as_raw_fd();
+        let () = setsockopt(sock, kind.attach_opt(), &prog_fd)?;
+        Ok(Self { sock, kind })
+    }
+
+    /// Detach the program from the socket, reporting any error.
+    pub fn detach(self) -> Result<()> {
+        let slf = ManuallyDrop::new(self);
+        slf.detach_impl()
+    }
+
+    fn detach_impl(&self) -> Result<()> {
+        // The option value is ignored by the kernel.
+        setsockopt(self.sock, self.kind.detach_opt(), &0 as &c_int)
+    }
+}
+
+impl Drop for SocketAttachment<'_> {
+    fn drop(&mut self) {
+        let _result = self.detach_impl();
+    }
+}
+
+
+/// An array of sockets, i.e., a map of type [`MapType::Sockmap`] or
+/// [`MapType::ReuseportSockarray`], as used for steering packets and
+/// connections to sockets.
+///
+/// ```no_run
+/// # use std::net::UdpSocket;
+/// # use libbpf_rs::MapCore;
+/// # use libbpf_rs::SocketArray;
+/// # fn steer(map: &dyn MapCore, sock: &UdpSocket) -> libbpf_rs::Result<()> {
+/// let array = SocketArray::new(map)?;
+/// let () = array.insert(0, sock)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SocketArray<'map> {
+    map: &'map dyn MapCore,
+}
+
+impl<'map> SocketArray<'map> {
+    /// Wrap the provided map, checking that its type and layout is
+    /// suitable.
+    pub fn new(map: &'map dyn MapCore) -> Result<Self> {
+        if !matches!(
+            map.map_type(),
+            MapType::Sockmap | MapType::ReuseportSockarray
+        ) {
+            return Err(Error::with_invalid_data(format!(
+                "map `{}` of type `{:?}` is not a socket array",
+                map.name().to_string_lossy(),
+                map.map_type(),
+            )))
+        }
+        if map.key_size() as usize != size_of::<u32>() {
+            return Err(Error::with_invalid_data(format!(
+                "socket array `{}` has unsupported key size {}",
+                map.name().to_string_lossy(),
+                map.key_size(),
+            )))
+        }
+        if !matches!(map.value_size() as usize, 4 | 8) {
+            return Err(Error::with_invalid_data(format!(
+                "socket array `{}` has unsupported value size {}",
+                map.name().to_string_lossy(),
+                map.value_size(),
+            )))
+        }
+        Ok(Self { map })
+    }
+
+    /// Store the socket `sock` at `index`, replacing any socket stored
+    /// there before.
+    ///
+    /// Sockets stored in a [`MapType::ReuseportSockarray`] have to be
+    /// bound members of a reuseport group.
+    pub fn insert<S: AsFd>(&self, index: u32, sock: &S) -> Result<()> {
+        let fd = sock.as_fd().as_raw_fd();
+        let value = if self.map.value_size() == 8 {
+            u64::from(fd as u32).to_ne_bytes().to_vec()
+        } else {
+            (fd as u32).to_ne_bytes().to_vec()
+        };
+        self.map.update(&index.to_ne_bytes(), &value, MapFlags::ANY)
+    }
+
+    /// Remove the socket stored at `index`.
+    pub fn remove(&self, index: u32) -> Result<()> {
+        self.map.delete(&index.to_ne_bytes())
+    }
+
+    /// Retrieve the cookie (see `SO_COOKIE`) of the socket stored at
+    /// `index`, if any.
+    ///
+    /// Cookies can only be retrieved for maps with 8 byte values.
+    pub fn cookie(&self, index: u32) -> Result<Option<u64>> {
+        if self.map.value_size() != 8 {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                "socket cookies require 8 byte map values",
+            ))
+        }
+        let value = match self.map.lookup(&index.to_ne_bytes(), MapFlags::ANY) {
+            Ok(value) => value,
+            // Empty slots are reported as non-existent.
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        Ok(value.map(|value| u64::from_ne_bytes(value.as_slice().try_into().unwrap())))
+    }
+}
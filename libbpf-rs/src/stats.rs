@@ -0,0 +1,252 @@
+use std::ffi::c_void;
+use std::ffi::CString;
+use std::mem::size_of_val;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::util;
+use crate::Program;
+use crate::Result;
+
+
+/// The type of statistics to enable via [`StatsGuard`].
+#[non_exhaustive]
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StatsType {
+    /// Collect the run time and run count of programs
+    /// (`BPF_STATS_RUN_TIME`).
+    #[default]
+    RunTime = libbpf_sys::BPF_STATS_RUN_TIME,
+}
+
+
+/// A guard keeping the collection of BPF program statistics enabled for
+/// as long as it is alive.
+///
+/// Statistics collection comes with a small run time overhead for every
+/// program invocation and is disabled by default. It stays enabled for
+/// as long as at least one guard (from any process) exists.
+#[derive(Debug)]
+pub struct StatsGuard {
+    _fd: OwnedFd,
+}
+
+impl StatsGuard {
+    /// Enable collection of statistics of the provided type.
+    pub fn new(ty: StatsType) -> Result<Self> {
+        let fd = unsafe { libbpf_sys::bpf_enable_stats(ty as _) };
+        let fd = util::parse_ret_i32(fd)?;
+        // SAFETY: `bpf_enable_stats` returns a valid file descriptor on
+        //         success, which we now own.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Ok(Self { _fd: fd })
+    }
+}
+
+
+/// The raw counters of a program, as reported by the kernel.
+#[derive(Clone, Copy, Debug, Default)]
+struct Counters {
+    run_time_ns: u64,
+    run_cnt: u64,
+    recursion_misses: u64,
+}
+
+impl Counters {
+    fn load(fd: BorrowedFd<'_>) -> Result<(CString, Self)> {
+        let mut info = libbpf_sys::bpf_prog_info::default();
+        let mut len = size_of_val(&info) as u32;
+        let ret = unsafe {
+            libbpf_sys::bpf_obj_get_info_by_fd(
+                fd.as_raw_fd(),
+                &mut info as *mut libbpf_sys::bpf_prog_info as *mut c_void,
+                &mut len,
+            )
+        };
+        let () = util::parse_ret(ret)?;
+
+        // SANITY: The kernel guarantees NUL termination.
+        let name = util::c_char_slice_to_cstr(&info.name).unwrap().to_owned();
+        let counters = Self {
+            run_time_ns: info.run_time_ns,
+            run_cnt: info.run_cnt,
+            recursion_misses: info.recursion_misses,
+        };
+        Ok((name, counters))
+    }
+}
+
+
+/// Statistics of a single program over a sampling interval, as
+/// reported by [`ProgramStatsSampler::sample`].
+#[derive(Clone, Debug)]
+pub struct ProgramStats {
+    /// The ID of the program.
+    pub id: u32,
+    /// The name of the program.
+    pub name: CString,
+    /// The length of the sampling interval.
+    pub interval: Duration,
+    /// The number of times the program ran during the interval.
+    pub run_cnt: u64,
+    /// The total time spent running the program during the interval.
+    pub run_time: Duration,
+    /// The number of times the program got skipped during the interval,
+    /// because of recursion or concurrent execution prevention.
+    pub recursion_misses: u64,
+}
+
+impl ProgramStats {
+    /// The number of program invocations per second.
+    pub fn rate(&self) -> f64 {
+        let secs = self.interval.as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            self.run_cnt as f64 / secs
+        }
+    }
+
+    /// The average time spent per program invocation, in nanoseconds.
+    pub fn avg_run_time_ns(&self) -> f64 {
+        if self.run_cnt == 0 {
+            0.0
+        } else {
+            self.run_time.as_nanos() as f64 / self.run_cnt as f64
+        }
+    }
+}
+
+
+#[derive(Debug)]
+struct Sampled {
+    id: u32,
+    fd: OwnedFd,
+    /// The counters as of the last sample.
+    last: Counters,
+    /// The time at which `last` was retrieved.
+    last_time: Instant,
+}
+
+
+/// A sampler of the run time statistics of a set of programs.
+///
+/// The kernel only maintains run time statistics while collection is
+/// enabled, e.g., by means of a [`StatsGuard`].
+#[derive(Debug)]
+pub struct ProgramStatsSampler {
+    programs: Vec<Sampled>,
+}
+
+impl ProgramStatsSampler {
+    /// Create a new sampler without any programs.
+    pub fn new() -> Self {
+        Self {
+            programs: Vec::new(),
+        }
+    }
+
+    /// Add the program referenced by the given file descriptor.
+    ///
+    /// The program's statistics are reported relative to the time of
+    /// the call.
+    pub fn add_program(&mut self, fd: BorrowedFd<'_>) -> Result<()> {
+        let id = Program::id_from_fd(fd)?;
+        let fd = fd.try_clone_to_owned()?;
+        let (_name, last) = Counters::load(fd.as_fd())?;
+        let () = self.programs.push(Sampled {
+            id,
+            fd,
+            last,
+            last_time: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Add the program with the given ID.
+    pub fn add_program_id(&mut self, id: u32) -> Result<()> {
+        let fd = Program::fd_from_id(id)?;
+        self.add_program(fd.as_fd())
+    }
+
+    /// Sample the statistics of all programs, reporting the changes
+    /// since the last sample (or since the program was added).
+    pub fn sample(&mut self) -> Result<Vec<ProgramStats>> {
+        let samples = self
+            .programs
+            .iter()
+            .map(|prog| {
+                let (name, counters) = Counters::load(prog.fd.as_fd())?;
+                Ok((name, counters, Instant::now()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Only advance the baselines once all programs have been
+        // sampled, so that a failure does not lose the interval's data.
+        let stats = self
+            .programs
+            .iter_mut()
+            .zip(samples)
+            .map(|(prog, (name, counters, time))| {
+                let stats = ProgramStats {
+                    id: prog.id,
+                    name,
+                    interval: time.duration_since(prog.last_time),
+                    run_cnt: counters.run_cnt.saturating_sub(prog.last.run_cnt),
+                    run_time: Duration::from_nanos(
+                        counters.run_time_ns.saturating_sub(prog.last.run_time_ns),
+                    ),
+                    recursion_misses: counters
+                        .recursion_misses
+                        .saturating_sub(prog.last.recursion_misses),
+                };
+                prog.last = counters;
+                prog.last_time = time;
+                stats
+            })
+            .collect();
+        Ok(stats)
+    }
+}
+
+impl Default for ProgramStatsSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that derived statistics are computed correctly.
+    #[test]
+    fn program_stats() {
+        let stats = ProgramStats {
+            id: 1,
+            name: CString::new("prog").unwrap(),
+            interval: Duration::from_millis(500),
+            run_cnt: 100,
+            run_time: Duration::from_micros(10),
+            recursion_misses: 0,
+        };
+        assert_eq!(stats.rate(), 200.0);
+        assert_eq!(stats.avg_run_time_ns(), 100.0);
+
+        let stats = ProgramStats {
+            interval: Duration::ZERO,
+            run_cnt: 0,
+            ..stats
+        };
+        assert_eq!(stats.rate(), 0.0);
+        assert_eq!(stats.avg_run_time_ns(), 0.0);
+    }
+}
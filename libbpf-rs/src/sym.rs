@@ -0,0 +1,476 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use cpp_demangle::DemangleOptions;
+
+use crate::elf::Elf;
+use crate::elf::SHT_NOTE;
+use crate::usdt::mapped_executables;
+use crate::util;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+
+/// The note type used for build IDs.
+const NT_GNU_BUILD_ID: u32 = 3;
+/// The directory in which separate debug information files are searched.
+const DEBUG_DIR: &str = "/usr/lib/debug";
+
+
+/// A function symbol contained in an ELF file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    /// The raw (possibly mangled) name of the symbol, without version.
+    pub name: String,
+    /// The demangled name of the symbol, if it is a Rust or C++
+    /// symbol.
+    ///
+    /// Rust names are reported without the trailing hash, C++ names
+    /// include the parameter list.
+    pub demangled: Option<String>,
+    /// The version of the symbol, e.g., `GLIBC_2.2.5`, if any.
+    pub version: Option<String>,
+    /// Whether `version` is the default version of the symbol, i.e.,
+    /// the one that the static linker binds to (`name@@VERSION`).
+    pub default_version: bool,
+    /// The (link time) address of the symbol.
+    pub address: u64,
+    /// The size of the symbol, in bytes.
+    pub size: u64,
+    /// The offset of the symbol in the file, as expected by
+    /// [`ProgramMut::attach_uprobe`][crate::ProgramMut::attach_uprobe].
+    pub file_offset: u64,
+}
+
+impl Symbol {
+    /// Check whether the symbol is referred to by `name`, which is
+    /// compared against the raw and the demangled name.
+    ///
+    /// A C++ name matches with and without the parameter list.
+    fn has_name(&self, name: &str) -> bool {
+        if self.name == name {
+            return true
+        }
+        match &self.demangled {
+            Some(demangled) => {
+                demangled == name
+                    || demangled
+                        .strip_prefix(name)
+                        .is_some_and(|rest| rest.starts_with('('))
+            }
+            None => false,
+        }
+    }
+}
+
+
+/// A resolver of function symbols in ELF files, for the purpose of
+/// attaching uprobes.
+///
+/// Symbols are collected from the `.symtab` and `.dynsym` sections. If
+/// the file has been stripped of its `.symtab`, a separate debug
+/// information file is consulted, as referenced by build ID or by a
+/// `.gnu_debuglink` section.
+///
+/// ```no_run
+/// # use libbpf_rs::ProgramMut;
+/// # use libbpf_rs::SymbolResolver;
+/// # fn attach(prog: &mut ProgramMut<'_>, pid: u32) -> libbpf_rs::Result<()> {
+/// let resolver = SymbolResolver::for_library(pid, "libfoo")?;
+/// let offset = resolver.func_offset("foo::Bar::baz")?;
+/// let _link = prog.attach_uprobe(false, pid as i32, resolver.path(), offset)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SymbolResolver {
+    path: PathBuf,
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolResolver {
+    /// Collect the function symbols of the ELF file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let elf = Elf::open(path)?;
+        let mut symbols = collect_symbols(&elf, &elf);
+
+        if elf.section(".symtab").is_none() {
+            if let Some(debug) = find_debug_file(&elf, path) {
+                // Addresses are shared between the two files, but file
+                // offsets always have to be relative to the original one.
+                let () = symbols.extend(collect_symbols(&debug, &elf));
+            }
+        }
+
+        // Symbols generally appear in both `.symtab` and `.dynsym`, with
+        // only the latter carrying version information.
+        let () = symbols.sort_by(|a, b| {
+            (&a.name, a.address, a.version.is_none()).cmp(&(
+                &b.name,
+                b.address,
+                b.version.is_none(),
+            ))
+        });
+        let () = symbols.dedup_by(|a, b| a.name == b.name && a.address == b.address);
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            symbols,
+        })
+    }
+
+    /// Collect the function symbols of the library `lib` as mapped by
+    /// the process with the given PID.
+    ///
+    /// See [`SymbolResolver::find_library`] for the supported forms of
+    /// `lib`.
+    pub fn for_library(pid: u32, lib: &str) -> Result<Self> {
+        let path = Self::find_library(pid, lib)?;
+        Self::new(path)
+    }
+
+    /// Find the path of the library `lib` as mapped by the process with
+    /// the given PID, by means of `/proc/<pid>/maps`.
+    ///
+    /// `lib` may be a full path, a file name (`libc.so.6`), or a file
+    /// name lacking the version suffix or the `lib` prefix (`libc.so`,
+    /// `libc`, or `c`).
+    pub fn find_library(pid: u32, lib: &str) -> Result<PathBuf> {
+        let maps = format!("/proc/{pid}/maps");
+        let maps = fs::read_to_string(&maps).with_context(|| format!("failed to read `{maps}`"))?;
+        let prefixed = format!("lib{lib}");
+
+        let path = mapped_executables(&maps)
+            .find(|path| {
+                if *path == lib {
+                    return true
+                }
+                let Some(name) = Path::new(path).file_name().and_then(|name| name.to_str()) else {
+                    return false
+                };
+                [lib, prefixed.as_str()].iter().any(|lib| {
+                    name == *lib
+                        || name
+                            .strip_prefix(lib)
+                            .is_some_and(|rest| rest.starts_with('.'))
+                })
+            })
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                Error::with_io_error(
+                    io::ErrorKind::NotFound,
+                    format!("library `{lib}` is not mapped by process {pid}"),
+                )
+            });
+        path
+    }
+
+    /// Retrieve the path of the ELF file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Retrieve all symbols, sorted by name.
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Find the symbol referred to by `name`.
+    ///
+    /// `name` is compared against raw as well as demangled names and may
+    /// carry a version, as in `name@VERSION` or `name@@VERSION` (which
+    /// only matches the default version). If a name without version
+    /// refers to multiple versions of a symbol, the default one is
+    /// picked.
+    ///
+    /// An error is reported if no symbol or multiple distinct symbols
+    /// match.
+    pub fn find(&self, name: &str) -> Result<&Symbol> {
+        let (name, version) = split_version(name);
+        let mut candidates = self
+            .symbols
+            .iter()
+            .filter(|sym| sym.has_name(name))
+            .filter(|sym| match &version {
+                Some((version, default)) => {
+                    sym.version.as_deref() == Some(*version) && (!default || sym.default_version)
+                }
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        if version.is_none() && candidates.iter().any(|sym| sym.default_version) {
+            let () = candidates.retain(|sym| sym.version.is_none() || sym.default_version);
+        }
+        let () = candidates.sort_by_key(|sym| sym.file_offset);
+        let () = candidates.dedup_by_key(|sym| sym.file_offset);
+
+        match candidates.as_slice() {
+            [sym] => Ok(sym),
+            [] => Err(Error::with_io_error(
+                io::ErrorKind::NotFound,
+                format!("symbol `{name}` not found in `{}`", self.path.display()),
+            )),
+            syms => {
+                let names = syms
+                    .iter()
+                    .map(|sym| sym.demangled.as_deref().unwrap_or(&sym.name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Err(Error::with_io_error(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "symbol `{name}` is ambiguous in `{}`: {names}",
+                        self.path.display()
+                    ),
+                ))
+            }
+        }
+    }
+
+    /// Retrieve all symbols whose raw or demangled name matches the glob
+    /// `pattern`, e.g., `mycrate::*`.
+    pub fn matching<'slf>(
+        &'slf self,
+        pattern: &'slf str,
+    ) -> impl Iterator<Item = &'slf Symbol> + 'slf {
+        self.symbols.iter().filter(move |sym| {
+            util::glob_match(pattern, &sym.name)
+                || sym
+                    .demangled
+                    .as_deref()
+                    .is_some_and(|demangled| util::glob_match(pattern, demangled))
+        })
+    }
+
+    /// Find the file offset of the function referred to by `name`, as
+    /// expected by [`ProgramMut::attach_uprobe`][crate::ProgramMut::attach_uprobe].
+    ///
+    /// See [`SymbolResolver::find`] for the supported forms of `name`.
+    pub fn func_offset(&self, name: &str) -> Result<usize> {
+        let sym = self.find(name)?;
+        usize::try_from(sym.file_offset).map_err(Error::with_invalid_data)
+    }
+}
+
+
+/// Split a possibly versioned symbol name into the name and the version
+/// along with whether it is the default version.
+fn split_version(name: &str) -> (&str, Option<(&str, bool)>) {
+    match name.split_once('@') {
+        Some((name, version)) => match version.strip_prefix('@') {
+            Some(version) => (name, Some((version, true))),
+            None => (name, Some((version, false))),
+        },
+        None => (name, None),
+    }
+}
+
+/// Demangle a Rust or C++ symbol name.
+fn demangle(name: &str) -> Option<String> {
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        return Some(format!("{demangled:#}"))
+    }
+    let symbol = cpp_demangle::Symbol::new(name).ok()?;
+    symbol.demangle(&DemangleOptions::default()).ok()
+}
+
+/// Collect the function symbols of `elf`, translating addresses into
+/// offsets into `file`.
+fn collect_symbols(elf: &Elf, file: &Elf) -> Vec<Symbol> {
+    let Ok(symbols) = elf.func_symbols() else {
+        return Vec::new()
+    };
+
+    symbols
+        .into_iter()
+        .filter_map(|sym| {
+            let file_offset = file.file_offset(sym.addr)?;
+            // Names in `.symtab` may carry the version themselves.
+            let (name, version) = match (sym.version, split_version(&sym.name)) {
+                (Some(version), _) => (sym.name.clone(), Some(version)),
+                (None, (name, Some((version, default)))) => {
+                    (name.to_string(), Some((version.to_string(), default)))
+                }
+                (None, (_name, None)) => (sym.name.clone(), None),
+            };
+            let (version, default_version) = match version {
+                Some((version, default)) => (Some(version), default),
+                None => (None, false),
+            };
+
+            Some(Symbol {
+                demangled: demangle(&name),
+                name,
+                version,
+                default_version,
+                address: sym.addr,
+                size: sym.size,
+                file_offset,
+            })
+        })
+        .collect()
+}
+
+/// Compute the CRC32 checksum used by `.gnu_debuglink` sections.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Find the separate debug information file belonging to `elf`, which
+/// got read from `path`.
+fn find_debug_file(elf: &Elf, path: &Path) -> Option<Elf> {
+    let build_id = elf
+        .sections()
+        .iter()
+        .filter(|section| section.ty == SHT_NOTE)
+        .filter_map(|section| elf.notes(section).ok())
+        .flatten()
+        .find(|note| note.name == b"GNU" && note.ty == NT_GNU_BUILD_ID)
+        .map(|note| {
+            note.desc
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        });
+
+    if let Some(build_id) = build_id.filter(|build_id| build_id.len() > 2) {
+        let (dir, file) = build_id.split_at(2);
+        let debug = Path::new(DEBUG_DIR).join(format!(".build-id/{dir}/{file}.debug"));
+        if let Ok(elf) = Elf::open(&debug) {
+            return Some(elf)
+        }
+    }
+
+    let section = elf.section(".gnu_debuglink")?;
+    let data = elf.section_data(section).ok()?;
+    let len = data.iter().position(|b| *b == 0)?;
+    let name = String::from_utf8_lossy(&data[..len]).into_owned();
+    // The CRC follows the name, aligned to four bytes.
+    let crc_off = (len + 4) & !3;
+    let crc = data.get(crc_off..crc_off + 4)?;
+    let crc = if elf.is_le() {
+        u32::from_le_bytes(crc.try_into().unwrap())
+    } else {
+        u32::from_be_bytes(crc.try_into().unwrap())
+    };
+
+    let path = path.canonicalize().ok()?;
+    let dir = path.parent()?;
+    let candidates = [
+        dir.join(&name),
+        dir.join(".debug").join(&name),
+        Path::new(DEBUG_DIR)
+            .join(dir.strip_prefix("/").ok()?)
+            .join(&name),
+    ];
+    candidates.iter().find_map(|candidate| {
+        if *candidate == path {
+            return None
+        }
+        let data = fs::read(candidate).ok()?;
+        if crc32(&data) != crc {
+            return None
+        }
+        Elf::parse(data).ok()
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env::current_exe;
+    use std::process;
+
+
+    /// Check that we can split versioned symbol names.
+    #[test]
+    fn version_splitting() {
+        assert_eq!(split_version("malloc"), ("malloc", None));
+        assert_eq!(
+            split_version("memcpy@GLIBC_2.2.5"),
+            ("memcpy", Some(("GLIBC_2.2.5", false)))
+        );
+        assert_eq!(
+            split_version("memcpy@@GLIBC_2.14"),
+            ("memcpy", Some(("GLIBC_2.14", true)))
+        );
+    }
+
+    /// Check that we demangle Rust and C++ symbol names.
+    #[test]
+    fn demangling() {
+        assert_eq!(
+            demangle("_ZN4core3fmt5write17h0123456789abcdefE").as_deref(),
+            Some("core::fmt::write")
+        );
+        assert_eq!(demangle("_ZN2ns3fooEi").as_deref(), Some("ns::foo(int)"));
+        assert_eq!(demangle("main"), None);
+    }
+
+    /// Check that we compute CRC32 checksums as expected.
+    #[test]
+    fn crc32_checksum() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    /// Check that we can resolve a Rust function of the running test
+    /// binary by its demangled name.
+    #[test]
+    fn resolve_own_symbol() {
+        let exe = current_exe().unwrap();
+        let resolver = SymbolResolver::new(&exe).unwrap();
+        let sym = resolver
+            .find("libbpf_rs::sym::tests::resolve_own_symbol")
+            .unwrap();
+        assert!(sym.name.starts_with("_R") || sym.name.starts_with("_ZN"));
+        assert_eq!(
+            resolver
+                .func_offset("libbpf_rs::sym::tests::resolve_own_symbol")
+                .unwrap(),
+            sym.file_offset as usize
+        );
+        assert!(resolver.matching("libbpf_rs::sym::tests::*").count() > 0);
+
+        let err = resolver
+            .find("libbpf_rs::sym::tests::does_not_exist")
+            .unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::NotFound);
+    }
+
+    /// Check that we can find the C library mapped by the current process
+    /// and resolve versioned symbols in it.
+    #[test]
+    fn resolve_libc_symbol() {
+        let pid = process::id();
+        let path = SymbolResolver::find_library(pid, "c").unwrap();
+        assert_eq!(SymbolResolver::find_library(pid, "libc").unwrap(), path);
+
+        let resolver = SymbolResolver::for_library(pid, "libc").unwrap();
+        let malloc = resolver.find("malloc").unwrap();
+        let version = malloc.version.as_deref().unwrap();
+        assert!(version.starts_with("GLIBC_"), "{version}");
+        let versioned = resolver.find(&format!("malloc@@{version}")).unwrap();
+        assert_eq!(versioned.file_offset, malloc.file_offset);
+
+        let _err = SymbolResolver::find_library(pid, "does-not-exist").unwrap_err();
+    }
+}
@@ -0,0 +1,219 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::mem::size_of;
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::util;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapType;
+use crate::ProgramAttachType;
+use crate::ProgramType;
+use crate::Result;
+
+
+/// A BPF token, granting the BPF permissions delegated to a BPF file
+/// system instance (via its `delegate_cmds`, `delegate_maps`,
+/// `delegate_progs`, and `delegate_attachs` mount options) to
+/// processes in the user namespace owning it.
+///
+/// A token can be used via [`ObjectBuilder::bpf_token`][crate::ObjectBuilder::bpf_token]
+/// and [`MapHandle::create_with_token`][crate::MapHandle::create_with_token].
+/// This requires Linux 6.9.
+#[derive(Debug)]
+pub struct BpfToken {
+    fd: OwnedFd,
+    /// The BPF file system instance the token was created from.
+    bpffs: OwnedFd,
+}
+
+impl BpfToken {
+    /// Create a token from a file descriptor referring to the root of a
+    /// BPF file system instance, e.g., as returned by `fsmount`.
+    pub fn from_bpffs(bpffs: BorrowedFd<'_>) -> Result<Self> {
+        let mut opts = libbpf_sys::bpf_token_create_opts {
+            sz: size_of::<libbpf_sys::bpf_token_create_opts>() as _,
+            ..Default::default()
+        };
+        let fd = unsafe { libbpf_sys::bpf_token_create(bpffs.as_raw_fd(), &mut opts) };
+        let fd = util::parse_ret_i32(fd).context("failed to create BPF token")?;
+
+        Ok(Self {
+            // SAFETY: A file descriptor coming from `bpf_token_create`
+            //         is always suitable for ownership.
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            bpffs: bpffs.try_clone_to_owned()?,
+        })
+    }
+
+    /// Create a token from the BPF file system mounted at `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bpffs = File::options()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY)
+            .open(path)
+            .with_context(|| format!("failed to open BPF file system `{}`", path.display()))?;
+        Self::from_bpffs(bpffs.as_fd())
+    }
+
+    /// Retrieve a path referring to the BPF file system instance the
+    /// token was created from, which is valid for as long as the token
+    /// is alive.
+    pub(crate) fn bpffs_path(&self) -> PathBuf {
+        PathBuf::from(format!("/proc/self/fd/{}", self.bpffs.as_raw_fd()))
+    }
+
+    /// Retrieve a description of the permissions granted by the token.
+    pub fn info(&self) -> Result<BpfTokenInfo> {
+        let path = format!("/proc/self/fdinfo/{}", self.fd.as_raw_fd());
+        let fdinfo =
+            fs::read_to_string(&path).with_context(|| format!("failed to read `{path}`"))?;
+        BpfTokenInfo::parse(&fdinfo)
+    }
+}
+
+impl AsFd for BpfToken {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+
+/// A description of the permissions granted by a [`BpfToken`].
+///
+/// Each permission set is a bit mask, with bit `n` granting the
+/// command, map type, program type, or attach type with the numeric
+/// value `n`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BpfTokenInfo {
+    /// The allowed `bpf(2)` commands.
+    pub allowed_cmds: u64,
+    /// The allowed map types.
+    pub allowed_maps: u64,
+    /// The allowed program types.
+    pub allowed_progs: u64,
+    /// The allowed attach types.
+    pub allowed_attachs: u64,
+}
+
+impl BpfTokenInfo {
+    /// Parse the information from the contents of a token's fdinfo file.
+    fn parse(fdinfo: &str) -> Result<Self> {
+        let field = |name: &str| -> Result<u64> {
+            let value = fdinfo
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .map(str::trim)
+                .ok_or_else(|| {
+                    Error::with_io_error(
+                        io::ErrorKind::Unsupported,
+                        format!("BPF token information lacks `{name}`"),
+                    )
+                })?;
+
+            if value == "any" {
+                return Ok(u64::MAX)
+            }
+            let hex = value.strip_prefix("0x").unwrap_or(value);
+            u64::from_str_radix(hex, 16).map_err(|_| {
+                Error::with_invalid_data(format!("invalid value `{value}` for `{name}`"))
+            })
+        };
+
+        Ok(Self {
+            allowed_cmds: field("allowed_cmds")?,
+            allowed_maps: field("allowed_maps")?,
+            allowed_progs: field("allowed_progs")?,
+            allowed_attachs: field("allowed_attachs")?,
+        })
+    }
+
+    /// Check whether the `bpf(2)` command `cmd` is allowed.
+    pub fn allows_cmd(&self, cmd: libbpf_sys::bpf_cmd) -> bool {
+        cmd < 64 && self.allowed_cmds & (1 << cmd) != 0
+    }
+
+    /// Check whether maps of type `ty` can be created.
+    pub fn allows_map(&self, ty: MapType) -> bool {
+        let ty = ty as u32;
+        ty < 64 && self.allowed_maps & (1 << ty) != 0
+    }
+
+    /// Check whether programs of type `ty` can be loaded.
+    pub fn allows_prog(&self, ty: ProgramType) -> bool {
+        let ty = ty as u32;
+        ty < 64 && self.allowed_progs & (1 << ty) != 0
+    }
+
+    /// Check whether programs with expected attach type `ty` can be
+    /// loaded.
+    pub fn allows_attach(&self, ty: ProgramAttachType) -> bool {
+        let ty = ty as u32;
+        ty < 64 && self.allowed_attachs & (1 << ty) != 0
+    }
+
+    /// Retrieve all allowed map types known to this library.
+    pub fn map_types(&self) -> Vec<MapType> {
+        (0..64)
+            .map(MapType::from)
+            .filter(|ty| *ty != MapType::Unknown && self.allows_map(*ty))
+            .collect()
+    }
+
+    /// Retrieve all allowed program types known to this library.
+    pub fn prog_types(&self) -> Vec<ProgramType> {
+        (0..64)
+            .map(ProgramType::from)
+            .filter(|ty| *ty != ProgramType::Unknown && self.allows_prog(*ty))
+            .collect()
+    }
+
+    /// Retrieve all allowed attach types known to this library.
+    pub fn attach_types(&self) -> Vec<ProgramAttachType> {
+        (0..64)
+            .map(ProgramAttachType::from)
+            .filter(|ty| *ty != ProgramAttachType::Unknown && self.allows_attach(*ty))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that we can parse the fdinfo of a BPF token.
+    #[test]
+    fn fdinfo_parsing() {
+        let fdinfo = r#"pos:	0
+flags:	02000002
+mnt_id:	15
+ino:	1234
+allowed_cmds:	any
+allowed_maps:	0x4
+allowed_progs:	0x42
+allowed_attachs:	0x0
+"#;
+        let info = BpfTokenInfo::parse(fdinfo).unwrap();
+        assert!(info.allows_cmd(libbpf_sys::BPF_MAP_CREATE));
+        assert_eq!(info.map_types(), [MapType::Array]);
+        assert_eq!(
+            info.prog_types(),
+            [ProgramType::SocketFilter, ProgramType::Xdp]
+        );
+        assert!(info.attach_types().is_empty());
+        assert!(!info.allows_attach(ProgramAttachType::CgroupInetIngress));
+
+        let _err = BpfTokenInfo::parse("pos:\t0\n").unwrap_err();
+    }
+}
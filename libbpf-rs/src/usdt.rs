@@ -0,0 +1,339 @@
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::elf::Elf;
+use crate::elf::SHT_NOTE;
+use crate::util;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Link;
+use crate::ProgramMut;
+use crate::Result;
+use crate::UsdtOpts;
+
+
+/// The note type used for USDT probes.
+const NT_STAPSDT: u32 = 3;
+
+
+/// An argument of a USDT probe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsdtArg {
+    /// The size of the argument, in bytes.
+    ///
+    /// This is zero if the argument specification does not include the
+    /// size.
+    pub size: u8,
+    /// Whether the argument is a signed value.
+    pub signed: bool,
+    /// The architecture specific location of the argument, e.g.,
+    /// `%edi` or `-8(%rbp)`.
+    pub location: String,
+}
+
+impl UsdtArg {
+    /// Parse an argument specification of the form `-4@%edi`.
+    fn parse(spec: &str) -> Self {
+        match spec.split_once('@') {
+            Some((size, location)) => {
+                let size = size.parse::<i8>().unwrap_or(0);
+                Self {
+                    size: size.unsigned_abs(),
+                    signed: size < 0,
+                    location: location.to_string(),
+                }
+            }
+            None => Self {
+                size: 0,
+                signed: false,
+                location: spec.to_string(),
+            },
+        }
+    }
+}
+
+
+/// A USDT probe, as described by a `.note.stapsdt` ELF note.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsdtProbe {
+    /// The path to the binary containing the probe.
+    pub path: PathBuf,
+    /// The name of the probe's provider.
+    pub provider: String,
+    /// The name of the probe.
+    pub name: String,
+    /// The (link time) address of the probe.
+    pub address: u64,
+    /// The (link time) address of the probe's semaphore, if any.
+    pub semaphore: Option<u64>,
+    /// The arguments of the probe.
+    pub args: Vec<UsdtArg>,
+}
+
+
+/// An inspector of USDT probes contained in binaries.
+#[derive(Clone, Debug, Default)]
+pub struct UsdtInspector {
+    probes: Vec<UsdtProbe>,
+}
+
+impl UsdtInspector {
+    /// Collect the USDT probes contained in the binary at `path`.
+    pub fn from_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let elf = Elf::open(path)?;
+        let probes = parse_probes(&elf, path)?;
+        Ok(Self { probes })
+    }
+
+    /// Collect the USDT probes contained in the executable and all
+    /// shared objects mapped by the process with the given PID.
+    ///
+    /// Mapped files that cannot be parsed as ELF or that contain
+    /// malformed USDT notes are skipped, so that a single broken
+    /// library does not prevent inspecting the remaining ones.
+    pub fn from_pid(pid: u32) -> Result<Self> {
+        let maps = format!("/proc/{pid}/maps");
+        let maps = fs::read_to_string(&maps).with_context(|| format!("failed to read `{maps}`"))?;
+
+        let mut seen = HashSet::new();
+        let mut probes = Vec::new();
+        for path in mapped_executables(&maps) {
+            if !seen.insert(path) {
+                continue
+            }
+            let path = Path::new(path);
+            let Ok(elf) = Elf::open(path) else { continue };
+            if let Ok(lib_probes) = parse_probes(&elf, path) {
+                let () = probes.extend(lib_probes);
+            }
+        }
+        Ok(Self { probes })
+    }
+
+    /// Retrieve all probes.
+    pub fn probes(&self) -> &[UsdtProbe] {
+        &self.probes
+    }
+
+    /// Retrieve the sorted names of all providers.
+    pub fn providers(&self) -> Vec<&str> {
+        self.probes
+            .iter()
+            .map(|probe| probe.provider.as_str())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Retrieve all probes whose `provider:name` matches the glob
+    /// `pattern`, e.g., `libc:*` or `*:memory_*`.
+    pub fn matching<'slf>(
+        &'slf self,
+        pattern: &'slf str,
+    ) -> impl Iterator<Item = &'slf UsdtProbe> + 'slf {
+        self.probes.iter().filter(move |probe| {
+            util::glob_match(pattern, &format!("{}:{}", probe.provider, probe.name))
+        })
+    }
+
+    /// Attach `prog` to all probes whose `provider:name` matches the glob
+    /// `pattern`, using [`ProgramMut::attach_usdt_with_opts`].
+    ///
+    /// A probe contained at multiple locations of the same binary is
+    /// only attached to once, as libbpf takes care of all locations.
+    pub fn attach_matching(
+        &self,
+        prog: &mut ProgramMut<'_>,
+        pid: i32,
+        pattern: &str,
+        opts: UsdtOpts,
+    ) -> Result<Vec<Link>> {
+        let mut attached = HashSet::new();
+        let mut links = Vec::new();
+        for probe in self.matching(pattern) {
+            if !attached.insert((&probe.path, &probe.provider, &probe.name)) {
+                continue
+            }
+            let link = prog
+                .attach_usdt_with_opts(pid, &probe.path, &probe.provider, &probe.name, opts.clone())
+                .with_context(|| {
+                    format!(
+                        "failed to attach to USDT {}:{} in `{}`",
+                        probe.provider,
+                        probe.name,
+                        probe.path.display()
+                    )
+                })?;
+            let () = links.push(link);
+        }
+
+        if links.is_empty() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::NotFound,
+                format!("no USDT probe matches `{pattern}`"),
+            ))
+        }
+        Ok(links)
+    }
+}
+
+
+/// Extract the paths of all executable file mappings from the contents
+/// of a `/proc/<pid>/maps` file.
+pub(crate) fn mapped_executables(maps: &str) -> impl Iterator<Item = &str> {
+    maps.lines().filter_map(|line| {
+        // The path is padded with spaces and may contain spaces itself,
+        // so it spans the remainder of the line.
+        let mut fields = line.splitn(6, ' ');
+        let perms = fields.nth(1)?;
+        let path = fields.nth(3)?.trim_start();
+        // Mappings of deleted files carry a " (deleted)" suffix. The
+        // path may refer to a different file by now, so skip them.
+        if path.ends_with(" (deleted)") {
+            return None
+        }
+        (perms.contains('x') && path.starts_with('/')).then_some(path)
+    })
+}
+
+fn parse_probes(elf: &Elf, path: &Path) -> Result<Vec<UsdtProbe>> {
+    let base = elf.section(".stapsdt.base").map(|section| section.addr);
+    let addr_size = elf.addr_size() as usize;
+    let read_addr = |bytes: &[u8]| -> u64 {
+        match (addr_size, elf.is_le()) {
+            (8, true) => u64::from_le_bytes(bytes.try_into().unwrap()),
+            (8, false) => u64::from_be_bytes(bytes.try_into().unwrap()),
+            (_, true) => u32::from_le_bytes(bytes.try_into().unwrap()).into(),
+            (_, false) => u32::from_be_bytes(bytes.try_into().unwrap()).into(),
+        }
+    };
+
+    let mut probes = Vec::new();
+    for section in elf
+        .sections()
+        .iter()
+        .filter(|section| section.ty == SHT_NOTE && section.name == ".note.stapsdt")
+    {
+        for note in elf.notes(section)? {
+            if note.name != b"stapsdt" || note.ty != NT_STAPSDT {
+                continue
+            }
+            if note.desc.len() < 3 * addr_size {
+                return Err(Error::with_invalid_data("USDT note is truncated"))
+            }
+
+            let (addrs, strings) = note.desc.split_at(3 * addr_size);
+            let pc = read_addr(&addrs[..addr_size]);
+            let note_base = read_addr(&addrs[addr_size..2 * addr_size]);
+            let semaphore = read_addr(&addrs[2 * addr_size..]);
+
+            let mut strings = strings
+                .split(|b| *b == 0)
+                .map(|s| String::from_utf8_lossy(s).into_owned());
+            let provider = strings.next().unwrap_or_default();
+            let name = strings.next().unwrap_or_default();
+            let args = strings.next().unwrap_or_default();
+
+            // Prelinking may have moved the binary, which is accounted
+            // for by means of the `.stapsdt.base` section.
+            let address = match base {
+                Some(base) if note_base != 0 => pc.wrapping_add(base).wrapping_sub(note_base),
+                _ => pc,
+            };
+
+            let () = probes.push(UsdtProbe {
+                path: path.to_path_buf(),
+                provider,
+                name,
+                address,
+                semaphore: (semaphore != 0).then_some(semaphore),
+                args: args.split_whitespace().map(UsdtArg::parse).collect(),
+            });
+        }
+    }
+    Ok(probes)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env::current_exe;
+    use std::process;
+
+    use probe::probe;
+
+
+    /// Check that we can parse USDT argument specifications.
+    #[test]
+    fn arg_parsing() {
+        let arg = UsdtArg::parse("-4@%edi");
+        assert_eq!(arg.size, 4);
+        assert!(arg.signed);
+        assert_eq!(arg.location, "%edi");
+
+        let arg = UsdtArg::parse("8@-8(%rbp)");
+        assert_eq!(arg.size, 8);
+        assert!(!arg.signed);
+        assert_eq!(arg.location, "-8(%rbp)");
+
+        let arg = UsdtArg::parse("%rax");
+        assert_eq!(arg.size, 0);
+        assert_eq!(arg.location, "%rax");
+    }
+
+    /// Check that we extract executable mappings from a maps file.
+    #[test]
+    fn maps_parsing() {
+        let maps = r#"55d1c0a00000-55d1c0a02000 r--p 00000000 fd:01 1234 /usr/bin/cat
+55d1c0a02000-55d1c0a07000 r-xp 00002000 fd:01 1234 /usr/bin/cat
+7f2e8c000000-7f2e8c021000 rw-p 00000000 00:00 0
+7f2e8c400000-7f2e8c595000 r-xp 00028000 fd:01 5678 /usr/lib/libc.so.6
+7f2e8c600000-7f2e8c601000 r-xp 00000000 fd:01 9999                       /tmp/gone.so (deleted)
+7f2e8c700000-7f2e8c701000 r-xp 00000000 fd:01 4321                       /opt/my app/libfoo.so
+7ffd5a5f1000-7ffd5a5f3000 r-xp 00000000 00:00 0                          [vdso]
+"#;
+        let paths = mapped_executables(maps).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "/usr/bin/cat",
+                "/usr/lib/libc.so.6",
+                "/opt/my app/libfoo.so"
+            ]
+        );
+    }
+
+    /// Check that we find the USDT probes contained in the test binary
+    /// itself.
+    #[test]
+    fn own_probes() {
+        #[inline(never)]
+        fn trigger(x: i64) {
+            probe!(libbpf_rs, unit_test_probe, x);
+        }
+
+        let () = trigger(42);
+        let exe = current_exe().unwrap();
+        let inspector = UsdtInspector::from_binary(&exe).unwrap();
+        let probe = inspector
+            .matching("libbpf_rs:unit_test_*")
+            .next()
+            .expect("failed to find test probe");
+        assert_eq!(probe.path, exe);
+        assert_eq!(probe.name, "unit_test_probe");
+        assert_eq!(probe.args.len(), 1);
+        assert_eq!(probe.args[0].size, 8);
+        assert!(probe.args[0].signed);
+        assert!(inspector.providers().contains(&"libbpf_rs"));
+
+        let inspector = UsdtInspector::from_pid(process::id()).unwrap();
+        assert!(inspector.matching("libbpf_rs:unit_test_probe").count() > 0);
+    }
+}
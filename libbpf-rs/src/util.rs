@@ -1,6 +1,7 @@
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::fs;
+use std::mem;
 use std::mem::transmute;
 use std::ops::Deref;
 use std::os::fd::AsRawFd;
@@ -54,11 +55,59 @@ pub fn c_char_slice_to_cstr(s: &[c_char]) -> Option<&CStr> {
     Some(cstr)
 }
 
+/// Retrieve the release of the running kernel, as reported by `uname`.
+pub fn kernel_release() -> Option<String> {
+    let mut uts = unsafe { mem::zeroed::<libc::utsname>() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return None
+    }
+    // SAFETY: `uname` provides a NUL terminated string.
+    let release = unsafe { CStr::from_ptr(uts.release.as_ptr()) };
+    Some(release.to_string_lossy().into_owned())
+}
+
 /// Round up a number to the next multiple of `r`
 pub fn roundup(num: usize, r: usize) -> usize {
     ((num + (r - 1)) / r) * r
 }
 
+/// Check whether `s` matches the glob `pattern`, in which `*` matches any
+/// sequence of characters and `?` matches a single character.
+pub fn glob_match(pattern: &str, s: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let s = s.chars().collect::<Vec<_>>();
+    let (mut p, mut i) = (0, 0);
+    // The position of the last `*` in the pattern along with the input
+    // position it was matched at, for backtracking.
+    let mut star = None;
+
+    while i < s.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, i));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                i += 1;
+            }
+            Some(c) if *c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match star {
+                Some((star_p, star_i)) => {
+                    p = star_p + 1;
+                    i = star_i + 1;
+                    star = Some((star_p, star_i + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 /// Get the number of CPUs in the system, e.g., to interact with per-cpu maps.
 pub fn num_possible_cpus() -> Result<usize> {
     let ret = unsafe { libbpf_sys::libbpf_num_possible_cpus() };
@@ -181,6 +230,22 @@ mod tests {
         }
     }
 
+    /// Check that our glob matching works as expected.
+    #[test]
+    fn glob_matching() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("tcp_*", "tcp_v4_connect"));
+        assert!(glob_match("*_connect", "tcp_v4_connect"));
+        assert!(glob_match("tcp_v?_*t", "tcp_v6_connect"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("tcp_*", "udp_sendmsg"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("a*b", "aXbY"));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exactly"));
+    }
+
     #[test]
     fn test_num_possible_cpus() {
         let num = num_possible_cpus().unwrap();
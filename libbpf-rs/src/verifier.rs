@@ -0,0 +1,200 @@
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+
+
+/// The instruction at which the BPF verifier rejected a program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifierInsn {
+    /// The index of the instruction in the program.
+    pub index: usize,
+    /// The instruction, as rendered by the verifier (e.g.,
+    /// `r0 = *(u32 *)(r1 +0)`).
+    pub insn: String,
+    /// The source code line that the instruction is attributed to.
+    ///
+    /// This information is only available if the program was compiled
+    /// with BTF line information (i.e., with `-g`).
+    pub source_line: Option<String>,
+}
+
+
+/// The verifier log of a BPF program that failed to load.
+///
+/// An object of this type is attached to the [`Error`][crate::Error]
+/// reported by [`OpenObject::load`][crate::OpenObject::load] if one
+/// of the contained programs got rejected, and can be retrieved via
+/// [`Error::verifier_log`][crate::Error::verifier_log].
+#[derive(Clone, Debug)]
+pub struct VerifierLog {
+    /// The name of the program that failed to load.
+    program: OsString,
+    /// The complete log, as reported by the kernel.
+    log: String,
+    /// The last instruction that the verifier processed.
+    failed_insn: Option<VerifierInsn>,
+    /// The reason for the rejection, as reported by the verifier.
+    message: Option<String>,
+}
+
+impl VerifierLog {
+    /// Parse the log of the program with the given name.
+    pub(crate) fn new(program: &OsStr, log: String) -> Self {
+        let mut source_line = None;
+        let mut failed_insn = None;
+        let mut message = Vec::new();
+
+        for line in log.lines() {
+            if let Some(source) = line.strip_prefix("; ") {
+                source_line = Some(source.trim_end().to_string());
+            } else if let Some(insn) = parse_insn(line, source_line.take()) {
+                failed_insn = Some(insn);
+                message.clear();
+            } else if failed_insn.is_some() {
+                // Everything past the last processed instruction up to
+                // the statistics is what the verifier complained
+                // about.
+                if line.starts_with("processed ") {
+                    break
+                }
+                if !line.trim().is_empty() {
+                    message.push(line);
+                }
+            }
+        }
+
+        Self {
+            program: program.to_os_string(),
+            failed_insn,
+            message: (!message.is_empty()).then(|| message.join("\n")),
+            log,
+        }
+    }
+
+    /// Retrieve the name of the program that failed to load.
+    pub fn program(&self) -> &OsStr {
+        &self.program
+    }
+
+    /// Retrieve the complete verifier log.
+    pub fn log(&self) -> &str {
+        &self.log
+    }
+
+    /// Retrieve the last instruction the verifier processed before
+    /// rejecting the program, if it could be determined.
+    pub fn failed_insn(&self) -> Option<&VerifierInsn> {
+        self.failed_insn.as_ref()
+    }
+
+    /// Retrieve the reason for the rejection, as reported by the
+    /// verifier, if it could be determined.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl Display for VerifierLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let program = self.program.to_string_lossy();
+        match (&self.failed_insn, &self.message) {
+            (Some(insn), Some(message)) => {
+                let message = message.lines().next().unwrap_or_default();
+                write!(
+                    f,
+                    "BPF program `{program}` failed verification at instruction {}: {message}",
+                    insn.index
+                )
+            }
+            _ => write!(f, "BPF program `{program}` failed to load"),
+        }
+    }
+}
+
+/// Parse an instruction line of the form `12: (61) r3 = *(u32 *)(r2 +0)`,
+/// potentially followed by register state (`; R3_w=...`).
+fn parse_insn(line: &str, source_line: Option<String>) -> Option<VerifierInsn> {
+    let (index, rest) = line.split_once(": (")?;
+    let index = index.parse().ok()?;
+    let (opcode, insn) = rest.split_once(") ")?;
+    if opcode.len() != 2 || !opcode.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None
+    }
+    let insn = insn.split_once(';').map(|(insn, _)| insn).unwrap_or(insn);
+
+    Some(VerifierInsn {
+        index,
+        insn: insn.trim().to_string(),
+        source_line,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /// Check that we can parse a verifier log as emitted by a recent
+    /// kernel.
+    #[test]
+    fn log_parsing() {
+        let log = r#"0: R1=ctx() R10=fp0
+; int handle__tp(void *ctx) @ test.bpf.c:12
+0: (b7) r1 = 0                        ; R1_w=0
+; return *(int *)(long)value; @ test.bpf.c:14
+1: (61) r0 = *(u32 *)(r1 +0)
+R1 invalid mem access 'scalar'
+processed 2 insns (limit 1000000) max_states_per_insn 0 total_states 0 peak_states 0 mark_read 0
+"#;
+        let log = VerifierLog::new(OsStr::new("handle__tp"), log.to_string());
+        assert_eq!(log.program(), "handle__tp");
+        assert!(log.log().starts_with("0: R1=ctx()"));
+        assert_eq!(log.message(), Some("R1 invalid mem access 'scalar'"));
+
+        let insn = log.failed_insn().unwrap();
+        assert_eq!(insn.index, 1);
+        assert_eq!(insn.insn, "r0 = *(u32 *)(r1 +0)");
+        assert_eq!(
+            insn.source_line.as_deref(),
+            Some("return *(int *)(long)value; @ test.bpf.c:14")
+        );
+        assert_eq!(
+            log.to_string(),
+            "BPF program `handle__tp` failed verification at instruction 1: R1 invalid mem access 'scalar'"
+        );
+    }
+
+    /// Check that a source line is only attributed to the instruction
+    /// directly following it.
+    #[test]
+    fn log_parsing_no_source_line() {
+        let log = r#"; int x = 0;
+0: (b7) r0 = 0
+1: (95) exit
+At program exit the register R0 has value (0x1; 0x0) should have been in (0x0; 0x0)
+processed 2 insns (limit 1000000) max_states_per_insn 0 total_states 0 peak_states 0 mark_read 0
+"#;
+        let log = VerifierLog::new(OsStr::new("prog"), log.to_string());
+        let insn = log.failed_insn().unwrap();
+        assert_eq!(insn.index, 1);
+        assert_eq!(insn.insn, "exit");
+        assert_eq!(insn.source_line, None);
+        assert_eq!(
+            log.message(),
+            Some("At program exit the register R0 has value (0x1; 0x0) should have been in (0x0; 0x0)")
+        );
+    }
+
+    /// Check that we handle logs without any instructions gracefully.
+    #[test]
+    fn log_parsing_no_insns() {
+        let log = "unknown func bpf_foo#12345\n";
+        let log = VerifierLog::new(OsStr::new("prog"), log.to_string());
+        assert_eq!(log.failed_insn(), None);
+        assert_eq!(log.message(), None);
+        assert_eq!(log.to_string(), "BPF program `prog` failed to load");
+    }
+}
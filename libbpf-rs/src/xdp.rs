@@ -1,10 +1,36 @@
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::io;
 use std::mem::size_of;
+use std::os::unix::io::AsFd;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::path::Path;
+use std::path::PathBuf;
+use std::ptr::NonNull;
+use std::sync::OnceLock;
 
 use bitflags::bitflags;
 
 use crate::util;
+use crate::AluOp;
+use crate::Assembler;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::JmpOp;
+use crate::MapCore as _;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapType;
+use crate::OpenProgramMut;
+use crate::Program;
+use crate::ProgramHandle;
+use crate::ProgramLoadOpts;
+use crate::ProgramType;
+use crate::Reg;
 use crate::Result;
 
 bitflags! {
@@ -108,3 +134,707 @@ impl<'fd> Xdp<'fd> {
         util::parse_ret(ret)
     }
 }
+
+
+/// The default directory below which [`XdpDispatcher`] state is pinned.
+const DISPATCHER_PIN_ROOT: &str = "/sys/fs/bpf/libbpf-rs/xdp";
+/// The name of the dispatcher program.
+const DISPATCHER_NAME: &str = "xdp_dispatcher";
+/// The prefix of the names of the dispatcher's slot functions.
+const SLOT_PREFIX: &str = "prog";
+/// The number of instructions making up a slot function.
+const SLOT_LEN: usize = 2;
+/// The default priority of components.
+const DEFAULT_PRIORITY: u32 = 50;
+
+
+bitflags! {
+    /// A set of XDP actions, i.e., program return codes.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct XdpActions: u32 {
+        /// `XDP_ABORTED`
+        const ABORTED  = 1 << libbpf_sys::XDP_ABORTED;
+        /// `XDP_DROP`
+        const DROP     = 1 << libbpf_sys::XDP_DROP;
+        /// `XDP_PASS`
+        const PASS     = 1 << libbpf_sys::XDP_PASS;
+        /// `XDP_TX`
+        const TX       = 1 << libbpf_sys::XDP_TX;
+        /// `XDP_REDIRECT`
+        const REDIRECT = 1 << libbpf_sys::XDP_REDIRECT;
+    }
+}
+
+
+/// Options for adding a program to an [`XdpDispatcher`].
+#[derive(Clone, Debug)]
+pub struct XdpComponentOpts {
+    /// The priority of the program. Programs with lower values run
+    /// first.
+    pub priority: u32,
+    /// The return codes for which the next program in the chain runs.
+    /// On any other return code, packet processing ends with it.
+    pub continue_on: XdpActions,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl Default for XdpComponentOpts {
+    fn default() -> Self {
+        Self {
+            priority: DEFAULT_PRIORITY,
+            continue_on: XdpActions::PASS,
+            _non_exhaustive: (),
+        }
+    }
+}
+
+
+/// A program run by an [`XdpDispatcher`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XdpComponent {
+    /// The ID of the program.
+    pub prog_id: u32,
+    /// The priority of the program.
+    pub priority: u32,
+    /// The return codes for which the next program in the chain runs.
+    pub continue_on: XdpActions,
+}
+
+impl XdpComponent {
+    /// The size of a component's record in the pinned state map.
+    const STATE_SIZE: usize = 3 * size_of::<u32>();
+
+    fn to_state(&self) -> [u8; Self::STATE_SIZE] {
+        let mut state = [0; Self::STATE_SIZE];
+        let () = state[0..4].copy_from_slice(&self.prog_id.to_ne_bytes());
+        let () = state[4..8].copy_from_slice(&self.priority.to_ne_bytes());
+        let () = state[8..12].copy_from_slice(&self.continue_on.bits().to_ne_bytes());
+        state
+    }
+
+    fn from_state(state: &[u8]) -> Option<Self> {
+        let field = |idx: usize| -> Option<u32> {
+            let bytes = state.get(idx * 4..(idx + 1) * 4)?;
+            Some(u32::from_ne_bytes(bytes.try_into().unwrap()))
+        };
+        let prog_id = field(0)?;
+        (prog_id != 0).then(|| Self {
+            prog_id,
+            priority: field(1).unwrap_or(DEFAULT_PRIORITY),
+            continue_on: XdpActions::from_bits_truncate(field(2).unwrap_or(0)),
+        })
+    }
+}
+
+
+/// BTF information created in memory, freed on drop.
+struct BtfBuf(NonNull<libbpf_sys::btf>);
+
+impl BtfBuf {
+    fn add(&mut self, id: i32) -> Result<u32> {
+        util::parse_ret_i32(id).map(|id| id as u32)
+    }
+}
+
+impl Drop for BtfBuf {
+    fn drop(&mut self) {
+        unsafe { libbpf_sys::btf__free(self.0.as_ptr()) }
+    }
+}
+
+
+/// A loaded dispatcher program along with the BTF IDs of its slots.
+struct Dispatcher {
+    prog: ProgramHandle,
+    slots: Vec<u32>,
+}
+
+/// Assemble the instructions of a dispatcher calling one slot per entry
+/// of `continue_on`.
+///
+/// Each slot is a global function that can be replaced by a component
+/// program via `freplace`.
+fn dispatcher_insns(continue_on: &[XdpActions]) -> Result<Vec<libbpf_sys::bpf_insn>> {
+    let mut asm = Assembler::new();
+    let _asm = asm.mov64_reg(Reg::R6, Reg::R1);
+    for (idx, actions) in continue_on.iter().enumerate() {
+        let _asm = asm
+            .mov64_reg(Reg::R1, Reg::R6)
+            .call_local(&format!("{SLOT_PREFIX}{idx}"))
+            // Continue with the next slot only if the bit corresponding
+            // to the return code is set.
+            .jmp_imm(JmpOp::Gt, Reg::R0, 31, "out")
+            .alu32_imm(AluOp::Mov, Reg::R2, actions.bits() as i32)
+            .alu32_reg(AluOp::Rsh, Reg::R2, Reg::R0)
+            .alu32_imm(AluOp::And, Reg::R2, 1)
+            .jmp32_imm(JmpOp::Eq, Reg::R2, 0, "out");
+    }
+    let _asm = asm
+        .mov64_imm(Reg::R0, libbpf_sys::XDP_PASS as i32)
+        .exit()
+        .label("out")
+        .exit();
+    for idx in 0..continue_on.len() {
+        let _asm = asm
+            .label(&format!("{SLOT_PREFIX}{idx}"))
+            .mov64_imm(Reg::R0, libbpf_sys::XDP_PASS as i32)
+            .exit();
+    }
+    asm.finish()
+}
+
+/// Load a dispatcher calling one slot per entry of `continue_on`.
+fn load_dispatcher(continue_on: &[XdpActions]) -> Result<Dispatcher> {
+    let insns = dispatcher_insns(continue_on)?;
+    let slots_start = insns.len() - continue_on.len() * SLOT_LEN;
+
+    let btf = unsafe { libbpf_sys::btf__new_empty() };
+    let btf = util::validate_bpf_ret(btf).context("failed to create BTF")?;
+    let mut btf = BtfBuf(btf);
+    let ptr = btf.0.as_ptr();
+
+    let int = CString::new("int").unwrap();
+    let int = btf.add(unsafe {
+        libbpf_sys::btf__add_int(ptr, int.as_ptr(), 4, libbpf_sys::BTF_INT_SIGNED as _)
+    })?;
+    // The verifier identifies the context argument by the name of the
+    // type pointed to.
+    let xdp_md = CString::new("xdp_md").unwrap();
+    let xdp_md = btf.add(unsafe {
+        libbpf_sys::btf__add_struct(ptr, xdp_md.as_ptr(), size_of::<libbpf_sys::xdp_md>() as _)
+    })?;
+    let xdp_md_ptr = btf.add(unsafe { libbpf_sys::btf__add_ptr(ptr, xdp_md as _) })?;
+    let proto = btf.add(unsafe { libbpf_sys::btf__add_func_proto(ptr, int as _) })?;
+    let ctx = CString::new("ctx").unwrap();
+    let _param =
+        btf.add(unsafe { libbpf_sys::btf__add_func_param(ptr, ctx.as_ptr(), xdp_md_ptr as _) })?;
+
+    let mut add_func = |name: &str| -> Result<u32> {
+        let name = util::str_to_cstring(name)?;
+        btf.add(unsafe {
+            libbpf_sys::btf__add_func(ptr, name.as_ptr(), libbpf_sys::BTF_FUNC_GLOBAL, proto as _)
+        })
+    };
+    let mut func_info = vec![libbpf_sys::bpf_func_info {
+        insn_off: 0,
+        type_id: add_func(DISPATCHER_NAME)?,
+    }];
+    let mut slots = Vec::with_capacity(continue_on.len());
+    for idx in 0..continue_on.len() {
+        let type_id = add_func(&format!("{SLOT_PREFIX}{idx}"))?;
+        let () = slots.push(type_id);
+        let () = func_info.push(libbpf_sys::bpf_func_info {
+            insn_off: (slots_start + idx * SLOT_LEN) as u32,
+            type_id,
+        });
+    }
+
+    let ret = unsafe { libbpf_sys::btf__load_into_kernel(ptr) };
+    let () = util::parse_ret(ret).context("failed to load XDP dispatcher BTF")?;
+    let btf_fd = unsafe { libbpf_sys::btf__fd(ptr) };
+
+    let prog = ProgramHandle::load_with(
+        ProgramType::Xdp,
+        Some(DISPATCHER_NAME),
+        "GPL",
+        &insns,
+        &ProgramLoadOpts::default(),
+        |opts| {
+            opts.prog_btf_fd = btf_fd as _;
+            opts.func_info = func_info.as_ptr().cast();
+            opts.func_info_cnt = func_info.len() as _;
+            opts.func_info_rec_size = size_of::<libbpf_sys::bpf_func_info>() as _;
+        },
+    )
+    .context("failed to load XDP dispatcher")?;
+
+    Ok(Dispatcher { prog, slots })
+}
+
+/// Attach the `freplace` program `prog` to the slot function with BTF ID
+/// `slot` of `dispatcher`.
+fn attach_to_slot(prog: BorrowedFd<'_>, dispatcher: BorrowedFd<'_>, slot: u32) -> Result<OwnedFd> {
+    let opts = libbpf_sys::bpf_link_create_opts {
+        sz: size_of::<libbpf_sys::bpf_link_create_opts>() as _,
+        target_btf_id: slot,
+        ..Default::default()
+    };
+    let fd =
+        unsafe { libbpf_sys::bpf_link_create(prog.as_raw_fd(), dispatcher.as_raw_fd(), 0, &opts) };
+    let fd = util::parse_ret_i32(fd)?;
+    // SAFETY: `bpf_link_create` returns a valid file descriptor on
+    //         success, which we now own.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn pin_fd(fd: BorrowedFd<'_>, path: &Path) -> Result<()> {
+    let path_c = util::path_to_cstring(path)?;
+    let ret = unsafe { libbpf_sys::bpf_obj_pin(fd.as_raw_fd(), path_c.as_ptr()) };
+    util::parse_ret(ret).with_context(|| format!("failed to pin `{}`", path.display()))
+}
+
+/// Retrieve the dispatcher used as the attach target of programs while
+/// they are loaded, loading it on first use.
+fn template() -> Result<&'static ProgramHandle> {
+    static TEMPLATE: OnceLock<ProgramHandle> = OnceLock::new();
+
+    if let Some(template) = TEMPLATE.get() {
+        return Ok(template)
+    }
+    let template = load_dispatcher(&[XdpActions::PASS])?.prog;
+    Ok(TEMPLATE.get_or_init(|| template))
+}
+
+fn remove_pin(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(Error::from(err))
+            .with_context(|| format!("failed to remove pin `{}`", path.display())),
+        _ => Ok(()),
+    }
+}
+
+
+/// A dispatcher for running multiple XDP programs on a single
+/// interface, similar to the one provided by libxdp.
+///
+/// The dispatcher is an XDP program calling a set of "slot" functions
+/// in turn, each of which is replaced by a component program by means
+/// of `freplace`. Components run in order of their priority and the
+/// chain is only continued if a component returns one of its
+/// `continue_on` actions.
+///
+/// Whenever components are added or removed, a new dispatcher program
+/// is loaded and atomically swapped in. The dispatcher, the links of
+/// all components, and their configuration are pinned in bpffs, so
+/// that multiple processes can cooperate on the same interface.
+///
+/// Component programs have to be prepared via
+/// [`XdpDispatcher::prepare`] before being loaded, which turns them into
+/// `freplace` programs. They need to have the signature
+/// `int prog(struct xdp_md *ctx)`.
+#[derive(Debug)]
+pub struct XdpDispatcher {
+    ifindex: i32,
+    flags: u32,
+    dir: PathBuf,
+    /// A dispatcher used as the attach target of programs while loading.
+    template: &'static ProgramHandle,
+}
+
+impl XdpDispatcher {
+    /// The maximum number of programs a dispatcher can run.
+    pub const MAX_PROGS: usize = 10;
+
+    /// Create a dispatcher for the interface with the given index,
+    /// attaching with the provided flags (e.g., [`XdpFlags::SKB_MODE`]).
+    ///
+    /// All processes working with the dispatcher of an interface must
+    /// use the same attach mode.
+    pub fn new(ifindex: i32, flags: XdpFlags) -> Result<Self> {
+        Self::with_pin_root(ifindex, flags, DISPATCHER_PIN_ROOT)
+    }
+
+    /// Create a dispatcher for the interface with the given index,
+    /// pinning its state in a directory below `pin_root`.
+    pub fn with_pin_root<P: AsRef<Path>>(
+        ifindex: i32,
+        flags: XdpFlags,
+        pin_root: P,
+    ) -> Result<Self> {
+        let dir = pin_root.as_ref().join(ifindex.to_string());
+        let () = fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create directory `{}`", dir.display()))?;
+        let template = template()?;
+        let slf = Self {
+            ifindex,
+            flags: flags.bits(),
+            dir,
+            template,
+        };
+
+        let state = slf.dir.join("state");
+        if !state.exists() {
+            let _lock = slf.lock()?;
+            if !state.exists() {
+                let opts = libbpf_sys::bpf_map_create_opts {
+                    sz: size_of::<libbpf_sys::bpf_map_create_opts>() as _,
+                    ..Default::default()
+                };
+                let mut map = MapHandle::create(
+                    MapType::Array,
+                    Some("xdp_disp_state"),
+                    size_of::<u32>() as u32,
+                    XdpComponent::STATE_SIZE as u32,
+                    Self::MAX_PROGS as u32,
+                    &opts,
+                )?;
+                let () = map.pin(&state)?;
+            }
+        }
+        Ok(slf)
+    }
+
+    /// Retrieve the directory in which the dispatcher's state is pinned.
+    pub fn pin_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Prepare a program for use with the dispatcher, before it is
+    /// loaded.
+    ///
+    /// The program is turned into an `freplace` program targeting the
+    /// dispatcher. The `XdpDispatcher` has to stay alive until the
+    /// program is loaded.
+    pub fn prepare(&self, prog: &mut OpenProgramMut<'_>) -> Result<()> {
+        let () = prog.set_prog_type(ProgramType::Ext);
+        prog.set_attach_target(
+            self.template.as_fd().as_raw_fd(),
+            Some(format!("{SLOT_PREFIX}0")),
+        )
+    }
+
+    /// Retrieve all components, in the order in which they run.
+    pub fn components(&self) -> Result<Vec<XdpComponent>> {
+        let _lock = self.lock()?;
+        self.read_state()
+    }
+
+    /// Add a loaded program, previously prepared via
+    /// [`XdpDispatcher::prepare`], returning its ID.
+    ///
+    /// The dispatcher is attached to the interface when the first
+    /// component is added.
+    pub fn add(&self, prog: BorrowedFd<'_>, opts: &XdpComponentOpts) -> Result<u32> {
+        let prog_id = Program::id_from_fd(prog)?;
+        let _lock = self.lock()?;
+        let mut components = self.read_state()?;
+        if components.iter().any(|comp| comp.prog_id == prog_id) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::AlreadyExists,
+                format!("program {prog_id} is already part of the XDP dispatcher"),
+            ))
+        }
+        if components.len() >= Self::MAX_PROGS {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "XDP dispatcher already runs the maximum of {} programs",
+                    Self::MAX_PROGS
+                ),
+            ))
+        }
+
+        let () = components.push(XdpComponent {
+            prog_id,
+            priority: opts.priority,
+            continue_on: opts.continue_on,
+        });
+        let () = self.apply(components, Some(prog))?;
+        Ok(prog_id)
+    }
+
+    /// Remove the program with the given ID.
+    ///
+    /// The dispatcher is detached from the interface and all of its
+    /// state is removed when the last component is removed.
+    pub fn remove(&self, prog_id: u32) -> Result<()> {
+        let _lock = self.lock()?;
+        let mut components = self.read_state()?;
+        let len = components.len();
+        let () = components.retain(|comp| comp.prog_id != prog_id);
+        if components.len() == len {
+            return Err(Error::with_io_error(
+                io::ErrorKind::NotFound,
+                format!("program {prog_id} is not part of the XDP dispatcher"),
+            ))
+        }
+        self.apply(components, None)
+    }
+
+    /// Acquire the lock protecting the pinned state against concurrent
+    /// modification.
+    fn lock(&self) -> Result<File> {
+        let dir = File::open(&self.dir)
+            .with_context(|| format!("failed to open `{}`", self.dir.display()))?;
+        let ret = unsafe { libc::flock(dir.as_raw_fd(), libc::LOCK_EX) };
+        if ret < 0 {
+            return Err(Error::from(io::Error::last_os_error()))
+                .context("failed to lock XDP dispatcher state")
+        }
+        Ok(dir)
+    }
+
+    fn state_map(&self) -> Result<MapHandle> {
+        MapHandle::from_pinned_path(self.dir.join("state"))
+            .context("failed to open XDP dispatcher state")
+    }
+
+    fn read_state(&self) -> Result<Vec<XdpComponent>> {
+        let map = self.state_map()?;
+        let mut components = Vec::new();
+        for idx in 0..Self::MAX_PROGS as u32 {
+            if let Some(state) = map.lookup(&idx.to_ne_bytes(), MapFlags::ANY)? {
+                let () = components.extend(XdpComponent::from_state(&state));
+            }
+        }
+        Ok(components)
+    }
+
+    fn write_state(&self, components: &[XdpComponent]) -> Result<()> {
+        let map = self.state_map()?;
+        for idx in 0..Self::MAX_PROGS {
+            let state = components
+                .get(idx)
+                .map(XdpComponent::to_state)
+                .unwrap_or_default();
+            let () = map.update(&(idx as u32).to_ne_bytes(), &state, MapFlags::ANY)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the pinned links of all components not contained in
+    /// `keep`.
+    fn remove_link_pins(&self, keep: &[XdpComponent]) -> Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let stale = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("link-"))
+                .is_some_and(|id| !keep.iter().any(|comp| id == comp.prog_id.to_string()));
+            if stale {
+                let () = remove_pin(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pin `dispatcher` along with the `links` of its `components` at
+    /// temporary paths, returning pairs of temporary and final paths.
+    ///
+    /// Already created pins are removed if pinning fails.
+    fn pin_new(
+        &self,
+        components: &[XdpComponent],
+        links: &[OwnedFd],
+        dispatcher: BorrowedFd<'_>,
+    ) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let fds = links
+            .iter()
+            .map(AsFd::as_fd)
+            .chain([dispatcher])
+            .collect::<Vec<_>>();
+        let names = components
+            .iter()
+            .map(|comp| format!("link-{}", comp.prog_id))
+            .chain(["dispatcher".to_string()]);
+
+        let mut pins = Vec::<(PathBuf, PathBuf)>::with_capacity(fds.len());
+        for (fd, name) in fds.into_iter().zip(names) {
+            let tmp = self.dir.join(format!("new-{name}"));
+            let result = remove_pin(&tmp).and_then(|()| pin_fd(fd, &tmp));
+            if let Err(err) = result {
+                for (tmp, _path) in &pins {
+                    let _result = remove_pin(tmp);
+                }
+                return Err(err)
+            }
+            let () = pins.push((tmp, self.dir.join(name)));
+        }
+        Ok(pins)
+    }
+
+    /// Replace the dispatcher `new` attached to the interface with `old`,
+    /// or detach it if there is no `old` one.
+    fn restore(&self, old: Option<&ProgramHandle>, new: BorrowedFd<'_>) -> Result<()> {
+        let opts = libbpf_sys::bpf_xdp_attach_opts {
+            sz: size_of::<libbpf_sys::bpf_xdp_attach_opts>() as _,
+            old_prog_fd: new.as_raw_fd(),
+            ..Default::default()
+        };
+        let fd = old.map(|old| old.as_fd().as_raw_fd()).unwrap_or(-1);
+        let flags = self.flags | XdpFlags::REPLACE.bits();
+        let ret = unsafe { libbpf_sys::bpf_xdp_attach(self.ifindex, fd, flags, &opts) };
+        util::parse_ret(ret).with_context(|| {
+            format!(
+                "failed to restore previous XDP dispatcher on interface {}",
+                self.ifindex
+            )
+        })
+    }
+
+    /// Load and attach a dispatcher running `components`, with `new`
+    /// being the file descriptor of a component not yet running.
+    fn apply(&self, mut components: Vec<XdpComponent>, new: Option<BorrowedFd<'_>>) -> Result<()> {
+        let () = components.sort_by_key(|comp| (comp.priority, comp.prog_id));
+        let prev = self.read_state()?;
+        let dispatcher_pin = self.dir.join("dispatcher");
+        let old = ProgramHandle::from_pinned_path(&dispatcher_pin).ok();
+
+        if components.is_empty() {
+            if let Some(old) = &old {
+                let opts = libbpf_sys::bpf_xdp_attach_opts {
+                    sz: size_of::<libbpf_sys::bpf_xdp_attach_opts>() as _,
+                    old_prog_fd: old.as_fd().as_raw_fd(),
+                    ..Default::default()
+                };
+                let flags = self.flags | XdpFlags::REPLACE.bits();
+                let ret = unsafe { libbpf_sys::bpf_xdp_attach(self.ifindex, -1, flags, &opts) };
+                let () = util::parse_ret(ret).with_context(|| {
+                    format!(
+                        "failed to detach XDP dispatcher from interface {}",
+                        self.ifindex
+                    )
+                })?;
+            }
+            let () = self.remove_link_pins(&[])?;
+            let () = remove_pin(&dispatcher_pin)?;
+            return self.write_state(&components)
+        }
+
+        let continue_on = components
+            .iter()
+            .map(|comp| comp.continue_on)
+            .collect::<Vec<_>>();
+        let dispatcher = load_dispatcher(&continue_on)?;
+        let new_id = new.map(Program::id_from_fd).transpose()?;
+
+        let mut links = Vec::with_capacity(components.len());
+        for (comp, slot) in components.iter().zip(&dispatcher.slots) {
+            let fd = match new {
+                Some(new) if Some(comp.prog_id) == new_id => new.try_clone_to_owned()?,
+                _ => Program::fd_from_id(comp.prog_id)?,
+            };
+            let link =
+                attach_to_slot(fd.as_fd(), dispatcher.prog.as_fd(), *slot).with_context(|| {
+                    format!(
+                        "failed to attach program {} to XDP dispatcher",
+                        comp.prog_id
+                    )
+                })?;
+            let () = links.push(link);
+        }
+
+        let mut opts = libbpf_sys::bpf_xdp_attach_opts {
+            sz: size_of::<libbpf_sys::bpf_xdp_attach_opts>() as _,
+            ..Default::default()
+        };
+        let flags = if let Some(old) = &old {
+            opts.old_prog_fd = old.as_fd().as_raw_fd();
+            self.flags | XdpFlags::REPLACE.bits()
+        } else {
+            self.flags | XdpFlags::UPDATE_IF_NOEXIST.bits()
+        };
+        let ret = unsafe {
+            libbpf_sys::bpf_xdp_attach(
+                self.ifindex,
+                dispatcher.prog.as_fd().as_raw_fd(),
+                flags,
+                &opts,
+            )
+        };
+        let () = util::parse_ret(ret).with_context(|| {
+            format!(
+                "failed to attach XDP dispatcher to interface {}",
+                self.ifindex
+            )
+        })?;
+
+        // Now that the new dispatcher is running, pin it along with the
+        // links of its components. The old pins are only replaced once
+        // all new ones exist; until then, the old dispatcher (whose
+        // components are still attached by way of their pinned links)
+        // is put back in place should anything fail.
+        let pins = match self.pin_new(&components, &links, dispatcher.prog.as_fd()) {
+            Ok(pins) => pins,
+            Err(err) => {
+                let _result = self.restore(old.as_ref(), dispatcher.prog.as_fd());
+                return Err(err)
+            }
+        };
+        if let Err(err) = self.commit(&components, &pins) {
+            let _result = self.write_state(&prev);
+            for (tmp, _path) in &pins {
+                let _result = remove_pin(tmp);
+            }
+            let _result = self.restore(old.as_ref(), dispatcher.prog.as_fd());
+            return Err(err)
+        }
+
+        // The state is consistent at this point. The links of components
+        // that are no longer running only keep them attached to the old
+        // dispatcher and any we fail to remove are taken care of by the
+        // next update.
+        let _result = self.remove_link_pins(&components);
+        Ok(())
+    }
+
+    /// Record `components` as the dispatcher's state and move the `pins`
+    /// created by [`XdpDispatcher::pin_new`] into place, as the very last
+    /// step.
+    fn commit(&self, components: &[XdpComponent], pins: &[(PathBuf, PathBuf)]) -> Result<()> {
+        let () = self.write_state(components)?;
+        for (tmp, path) in pins {
+            let () = fs::rename(tmp, path)
+                .with_context(|| format!("failed to move pin `{}`", tmp.display()))?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Disassembler;
+
+
+    /// Check that component state round trips through its pinned
+    /// representation.
+    #[test]
+    fn component_state() {
+        let comp = XdpComponent {
+            prog_id: 42,
+            priority: 10,
+            continue_on: XdpActions::PASS | XdpActions::DROP,
+        };
+        assert_eq!(XdpComponent::from_state(&comp.to_state()), Some(comp));
+        assert_eq!(
+            XdpComponent::from_state(&[0; XdpComponent::STATE_SIZE]),
+            None
+        );
+    }
+
+    /// Check that we generate the expected dispatcher program.
+    #[test]
+    fn dispatcher_program() {
+        let insns =
+            dispatcher_insns(&[XdpActions::PASS, XdpActions::PASS | XdpActions::TX]).unwrap();
+        let expected = r#"   0: (bf) r6 = r1
+   1: (bf) r1 = r6
+   2: (85) call pc+15
+   3: (25) if r0 > 0x1f goto pc+13
+   4: (b4) w2 = 4
+   5: (7c) w2 >>= w0
+   6: (54) w2 &= 1
+   7: (16) if w2 == 0x0 goto pc+9
+   8: (bf) r1 = r6
+   9: (85) call pc+10
+  10: (25) if r0 > 0x1f goto pc+6
+  11: (b4) w2 = 12
+  12: (7c) w2 >>= w0
+  13: (54) w2 &= 1
+  14: (16) if w2 == 0x0 goto pc+2
+  15: (b7) r0 = 2
+  16: (95) exit
+  17: (95) exit
+  18: (b7) r0 = 2
+  19: (95) exit
+  20: (b7) r0 = 2
+  21: (95) exit
+"#;
+        assert_eq!(Disassembler::new().disassemble(&insns), expected);
+    }
+}
@@ -0,0 +1,17 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+const volatile u64 rodata_in = 0;
+u64 data_in = 1;
+u64 custom_in SEC(".data.custom") = 2;
+u64 bss_out = 0;
+
+SEC("xdp")
+int sum_globals(struct xdp_md *ctx)
+{
+    bss_out = rodata_in + data_in + custom_in;
+    return XDP_PASS;
+}
+
+char LICENSE[] SEC("license") = "GPL";
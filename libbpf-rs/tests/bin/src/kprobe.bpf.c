@@ -0,0 +1,11 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+SEC("kprobe")
+int handle__kprobe(void *ctx)
+{
+    return 0;
+}
+
+char _license[] SEC("license") = "GPL";
@@ -0,0 +1,15 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+#define NF_ACCEPT 1
+
+struct bpf_nf_ctx;
+
+SEC("netfilter")
+int handle_netfilter(struct bpf_nf_ctx *ctx) {
+    return NF_ACCEPT;
+}
+
+char _license[] SEC("license") = "GPL";
@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+struct {
+    __uint(type, BPF_MAP_TYPE_REUSEPORT_SOCKARRAY);
+    __uint(max_entries, 2);
+    __type(key, __u32);
+    __type(value, __u64);
+} reuseport_socks SEC(".maps");
+
+SEC("socket")
+int drop_all(struct __sk_buff *skb)
+{
+    return 0;
+}
+
+SEC("sk_reuseport")
+int select_first(struct sk_reuseport_md *reuse)
+{
+    __u32 key = 0;
+
+    if (bpf_sk_select_reuseport(reuse, &reuseport_socks, &key, 0))
+        return SK_DROP;
+    return SK_PASS;
+}
+
+char _license[] SEC("license") = "GPL";
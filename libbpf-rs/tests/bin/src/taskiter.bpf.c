@@ -9,6 +9,8 @@ struct index_pid_pair {
 };
 
 static uint32_t i = 0;
+/* Make sure the record type is part of the object's BTF. */
+struct index_pid_pair last_pair = {};
 
 SEC("iter/task")
 int dump_pid(struct bpf_iter__task *ctx)
@@ -23,6 +25,7 @@ int dump_pid(struct bpf_iter__task *ctx)
   p.i = i++;
   p.pid = task->tgid;
 
+  last_pair = p;
   bpf_seq_write(seq, &p, sizeof(p));
   return 0;
 }
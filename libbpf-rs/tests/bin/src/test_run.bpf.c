@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: GPL-2.0
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+SEC("tc")
+int handle_tc(struct __sk_buff *skb)
+{
+    skb->mark += 1;
+    return skb->cb[0];
+}
+
+SEC("xdp")
+int handle_xdp(struct xdp_md *ctx)
+{
+    return ctx->data_end - ctx->data == 64 ? XDP_PASS : XDP_DROP;
+}
+
+SEC("raw_tp/sys_enter")
+int handle_raw_tp(struct bpf_raw_tracepoint_args *ctx)
+{
+    return ctx->args[0] + ctx->args[1];
+}
+
+char LICENSE[] SEC("license") = "GPL";
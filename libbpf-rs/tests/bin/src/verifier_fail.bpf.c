@@ -0,0 +1,15 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+SEC("tracepoint/syscalls/sys_enter_getpid")
+int handle__invalid_access(void *ctx)
+{
+    int *value = (int *)(long)bpf_get_prandom_u32();
+
+    /* Dereferencing a scalar is rejected by the verifier. */
+    return *value;
+}
+
+char LICENSE[] SEC("license") = "GPL";
@@ -1,5 +1,19 @@
+use std::ffi::c_int;
+use std::ffi::CString;
+use std::fs;
 use std::io;
+use std::mem;
+use std::mem::size_of;
+use std::os::fd::AsFd;
+use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::panic;
+use std::panic::AssertUnwindSafe;
 use std::path::PathBuf;
+use std::ptr;
 
 use libbpf_rs::Map;
 use libbpf_rs::MapCore;
@@ -75,3 +89,151 @@ pub fn get_prog_mut<'obj>(object: &'obj mut Object, name: &str) -> ProgramMut<'o
         .find(|map| map.name() == name)
         .unwrap_or_else(|| panic!("failed to find program `{name}`"))
 }
+
+/// Send the file descriptor `fd` over `sock`.
+fn send_fd(sock: &UnixStream, fd: BorrowedFd<'_>) {
+    let mut data = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    // Use `u64` for proper alignment of the control message.
+    let mut control = [0u64; 8];
+    let mut msg = unsafe { mem::zeroed::<libc::msghdr>() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = unsafe { libc::CMSG_SPACE(size_of::<c_int>() as _) } as _;
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<c_int>() as _) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<c_int>(), fd.as_raw_fd());
+    }
+
+    let ret = unsafe { libc::sendmsg(sock.as_raw_fd(), &msg, 0) };
+    assert_eq!(ret, 1, "sendmsg failed: {}", io::Error::last_os_error());
+}
+
+/// Receive a file descriptor sent over `sock`.
+fn recv_fd(sock: &UnixStream) -> OwnedFd {
+    let mut data = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    let mut control = [0u64; 8];
+    let mut msg = unsafe { mem::zeroed::<libc::msghdr>() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = mem::size_of_val(&control) as _;
+
+    let ret = unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, 0) };
+    assert_eq!(ret, 1, "recvmsg failed: {}", io::Error::last_os_error());
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        assert!(!cmsg.is_null());
+        assert_eq!((*cmsg).cmsg_type, libc::SCM_RIGHTS);
+        let fd = ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<c_int>());
+        OwnedFd::from_raw_fd(fd)
+    }
+}
+
+/// Enter a new user and mount namespace, in which the current user is
+/// mapped to root.
+fn enter_userns() {
+    let uid = unsafe { libc::getuid() };
+    let gid = unsafe { libc::getgid() };
+    let ret = unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS) };
+    assert_eq!(ret, 0, "unshare failed: {}", io::Error::last_os_error());
+
+    let () = fs::write("/proc/self/setgroups", "deny").unwrap();
+    let () = fs::write("/proc/self/uid_map", format!("0 {uid} 1")).unwrap();
+    let () = fs::write("/proc/self/gid_map", format!("0 {gid} 1")).unwrap();
+}
+
+/// Run `f` in a child process inside a new user namespace, providing it
+/// with a BPF file system instance owned by this namespace, configured
+/// with the given `delegate_*` mount options, e.g.,
+/// `[("delegate_cmds", "any")]`.
+///
+/// The file system has to be configured by a process privileged in the
+/// initial user namespace, which is why this requires root.
+pub fn with_delegated_bpffs<F>(delegate: &[(&str, &str)], f: F)
+where
+    F: FnOnce(BorrowedFd<'_>),
+{
+    let (parent_sock, child_sock) = UnixStream::pair().unwrap();
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0, "fork failed: {}", io::Error::last_os_error());
+
+    if pid == 0 {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let () = drop(parent_sock);
+            let () = enter_userns();
+
+            let fstype = CString::new("bpf").unwrap();
+            let fs_fd = unsafe { libc::syscall(libc::SYS_fsopen, fstype.as_ptr(), 0) };
+            assert!(fs_fd >= 0, "fsopen failed: {}", io::Error::last_os_error());
+            let fs_fd = unsafe { OwnedFd::from_raw_fd(fs_fd as _) };
+
+            let () = send_fd(&child_sock, fs_fd.as_fd());
+            let mnt_fd = recv_fd(&child_sock);
+            f(mnt_fd.as_fd())
+        }));
+        unsafe { libc::_exit(if result.is_ok() { 0 } else { 1 }) }
+    }
+
+    let () = drop(child_sock);
+    let fs_fd = recv_fd(&parent_sock);
+    // Values taken from `enum fsconfig_command`.
+    const FSCONFIG_SET_STRING: c_int = 1;
+    const FSCONFIG_CMD_CREATE: c_int = 6;
+
+    for (key, value) in delegate {
+        let key = CString::new(*key).unwrap();
+        let value = CString::new(*value).unwrap();
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_fsconfig,
+                fs_fd.as_raw_fd(),
+                FSCONFIG_SET_STRING,
+                key.as_ptr(),
+                value.as_ptr(),
+                0,
+            )
+        };
+        assert_eq!(ret, 0, "fsconfig failed: {}", io::Error::last_os_error());
+    }
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_fsconfig,
+            fs_fd.as_raw_fd(),
+            FSCONFIG_CMD_CREATE,
+            ptr::null::<libc::c_char>(),
+            ptr::null::<libc::c_void>(),
+            0,
+        )
+    };
+    assert_eq!(ret, 0, "fsconfig failed: {}", io::Error::last_os_error());
+    let mnt_fd = unsafe { libc::syscall(libc::SYS_fsmount, fs_fd.as_raw_fd(), 0, 0) };
+    assert!(
+        mnt_fd >= 0,
+        "fsmount failed: {}",
+        io::Error::last_os_error()
+    );
+    let mnt_fd = unsafe { OwnedFd::from_raw_fd(mnt_fd as _) };
+    let () = send_fd(&parent_sock, mnt_fd.as_fd());
+
+    let mut status = 0;
+    let ret = unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert_eq!(ret, pid);
+    assert!(
+        libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0,
+        "child failed with status {status}"
+    );
+}
@@ -9,44 +9,104 @@ use std::ffi::c_int;
 use std::ffi::c_void;
 use std::ffi::OsStr;
 use std::fs;
+use std::future::poll_fn;
 use std::hint;
 use std::io;
 use std::io::Read;
 use std::mem::size_of;
 use std::mem::size_of_val;
+use std::net::Ipv4Addr;
+use std::net::UdpSocket;
 use std::os::unix::io::AsFd;
+use std::os::unix::io::FromRawFd;
 use std::path::Path;
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::process;
 use std::ptr;
 use std::ptr::addr_of;
+use std::ptr::NonNull;
 use std::slice;
 use std::sync::mpsc::channel;
+use std::task::Poll;
 use std::time::Duration;
 
+use futures_core::Stream;
+use libbpf_rs::__internal_skel::ObjectSubskeletonConfig;
+use libbpf_rs::__internal_skel::ObjectSubskeletonConfigBuilder;
+use libbpf_rs::btf::check_core_compat;
+use libbpf_rs::btf::find_custom_btf;
+use libbpf_rs::btf::types;
+use libbpf_rs::btf::BtfValue;
 use libbpf_rs::num_possible_cpus;
+use libbpf_rs::probe::FeatureReport;
+use libbpf_rs::query::IterLinkParams;
+use libbpf_rs::query::LinkInfoIter;
+use libbpf_rs::query::LinkTypeInfo;
+use libbpf_rs::query::ProgInfoIter;
+use libbpf_rs::query::ProgInfoQueryOptions;
+use libbpf_rs::AluOp;
 use libbpf_rs::AsRawLibbpf;
+use libbpf_rs::Assembler;
+use libbpf_rs::AsyncRingBuffer;
+use libbpf_rs::BpfToken;
+use libbpf_rs::Btf;
+use libbpf_rs::BtfDecoder;
+use libbpf_rs::Disassembler;
+use libbpf_rs::ErrorKind;
 use libbpf_rs::Iter;
+use libbpf_rs::IterOpts;
+use libbpf_rs::IterRecords;
+use libbpf_rs::JmpOp;
+use libbpf_rs::KernelProbeKind;
+use libbpf_rs::KernelTargets;
 use libbpf_rs::Linker;
+use libbpf_rs::LinkerFileOpts;
 use libbpf_rs::Map;
 use libbpf_rs::MapCore;
 use libbpf_rs::MapFlags;
 use libbpf_rs::MapHandle;
 use libbpf_rs::MapInfo;
 use libbpf_rs::MapType;
+use libbpf_rs::MemSize;
+use libbpf_rs::NetfilterHook;
+use libbpf_rs::NetfilterOpts;
+use libbpf_rs::NetfilterProtocolFamily;
 use libbpf_rs::Object;
 use libbpf_rs::ObjectBuilder;
+use libbpf_rs::PerfBufferStream;
+use libbpf_rs::PerfEvent;
+use libbpf_rs::PodDecoder;
 use libbpf_rs::Program;
+use libbpf_rs::ProgramHandle;
 use libbpf_rs::ProgramInput;
+use libbpf_rs::ProgramLoadOpts;
+use libbpf_rs::ProgramStatsSampler;
 use libbpf_rs::ProgramType;
+use libbpf_rs::RawTracepointContext;
+use libbpf_rs::Reg;
+use libbpf_rs::RingBufferReader;
+use libbpf_rs::RingBufferSource;
+use libbpf_rs::RingBufferStream;
+use libbpf_rs::SkbContext;
+use libbpf_rs::SocketArray;
+use libbpf_rs::StatsGuard;
+use libbpf_rs::StatsType;
 use libbpf_rs::TracepointOpts;
 use libbpf_rs::UprobeOpts;
+use libbpf_rs::UsdtInspector;
 use libbpf_rs::UsdtOpts;
 use libbpf_rs::UserRingBuffer;
+use libbpf_rs::XdpContext;
 use plain::Plain;
 use probe::probe;
 use scopeguard::defer;
+use tempfile::tempdir;
 use tempfile::NamedTempFile;
 use test_tag::tag;
+use tokio::runtime::Builder as RuntimeBuilder;
+use tokio::runtime::Runtime;
+use tokio::time::timeout;
 
 use crate::common::bump_rlimit_mlock;
 use crate::common::get_map;
@@ -55,6 +115,7 @@ use crate::common::get_prog_mut;
 use crate::common::get_test_object;
 use crate::common::get_test_object_path;
 use crate::common::open_test_object;
+use crate::common::with_delegated_bpffs;
 
 
 /// A helper function for instantiating a `RingBuffer` with a callback meant to
@@ -109,6 +170,120 @@ fn test_object_build_from_memory() {
     assert!(name == "memory name");
 }
 
+/// Check that `ObjectBuilder` validates open options.
+#[test]
+fn test_object_builder_option_validation() {
+    let mut builder = ObjectBuilder::default();
+    let _builder = builder
+        .kconfig([("CONFIG_HZ", "250"), ("CONFIG_BPF_SYSCALL", "y")])
+        .unwrap();
+    let _err = builder.kconfig([("HZ", "250")]).unwrap_err();
+    let _err = builder.kconfig([("CONFIG_HZ", "")]).unwrap_err();
+    let _err = builder
+        .kconfig([("CONFIG_HZ", "250\nCONFIG_X=y")])
+        .unwrap_err();
+    // No values leave the option unset.
+    let _builder = builder.kconfig([] as [(&str, &str); 0]).unwrap();
+    assert!(unsafe { builder.as_libbpf_object().as_ref() }
+        .kconfig
+        .is_null());
+
+    // The path is only checked to exist when setting it.
+    let btf_path = current_exe().unwrap();
+    let _builder = builder.btf_custom_path(&btf_path).unwrap();
+    let err = builder
+        .btf_custom_path("/libbpf-rs/does/not/exist")
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let _builder = builder.kernel_log_level(1 | 4).unwrap();
+    let _err = builder.kernel_log_level(8).unwrap_err();
+    let _err = builder.kernel_log_buf(16).unwrap_err();
+    let _builder = builder.kernel_log_buf(1024).unwrap();
+
+    let _builder = builder.bpf_token_path("").unwrap();
+    let _err = builder.bpf_token_path(&btf_path).unwrap_err();
+}
+
+/// Check that the object wide kernel log receives the verifier log of
+/// the object's programs.
+#[tag(root)]
+#[test]
+fn test_object_kernel_log() {
+    bump_rlimit_mlock();
+
+    let mut builder = ObjectBuilder::default();
+    let _builder = builder.kernel_log_level(1).unwrap();
+    let _builder = builder.kernel_log_buf(1024 * 1024).unwrap();
+    let obj = builder
+        .open_file(get_test_object_path("runqslower.bpf.o"))
+        .unwrap();
+    assert_eq!(obj.kernel_log(), Some(String::new()));
+    let obj = obj.load().unwrap();
+    let log = obj.kernel_log().unwrap();
+    assert!(log.contains("processed "), "{log}");
+
+    let obj = ObjectBuilder::default()
+        .open_file(get_test_object_path("runqslower.bpf.o"))
+        .unwrap();
+    assert_eq!(obj.kernel_log(), None);
+}
+
+/// Check that the verifier log of a rejected program is reported when
+/// the object wide kernel log is in use.
+#[tag(root)]
+#[test]
+fn test_object_kernel_log_verifier_log() {
+    bump_rlimit_mlock();
+
+    let err = ObjectBuilder::default()
+        .kernel_log_buf(1024 * 1024)
+        .unwrap()
+        .open_file(get_test_object_path("verifier_fail.bpf.o"))
+        .unwrap()
+        .load()
+        .unwrap_err();
+    let log = err.verifier_log().expect("no verifier log attached");
+    assert_eq!(log.program(), "handle__invalid_access");
+    let message = log.message().expect("failed to find verifier message");
+    assert!(message.contains("invalid mem access"), "{message}");
+}
+
+/// Check that we can create a BPF token from a delegated BPF file
+/// system and use it for creating maps.
+#[tag(root)]
+#[test]
+fn test_bpf_token() {
+    let delegate = [
+        ("delegate_cmds", "map_create"),
+        ("delegate_maps", "array"),
+        ("delegate_progs", "any"),
+        ("delegate_attachs", "any"),
+    ];
+
+    with_delegated_bpffs(&delegate, |bpffs| {
+        let token = BpfToken::from_bpffs(bpffs).unwrap();
+        let info = token.info().unwrap();
+        assert!(info.allows_cmd(libbpf_sys::BPF_MAP_CREATE));
+        assert!(!info.allows_cmd(libbpf_sys::BPF_PROG_LOAD));
+        assert_eq!(info.map_types(), [MapType::Array]);
+
+        let opts = libbpf_sys::bpf_map_create_opts {
+            sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+            ..Default::default()
+        };
+        // Without the token we lack the necessary privileges.
+        let _err = MapHandle::create(MapType::Array, Some("array"), 4, 8, 1, &opts).unwrap_err();
+        let _map =
+            MapHandle::create_with_token(MapType::Array, Some("array"), 4, 8, 1, &opts, &token)
+                .unwrap();
+        // Hash maps were not delegated.
+        let _err =
+            MapHandle::create_with_token(MapType::Hash, Some("hash"), 4, 8, 1, &opts, &token)
+                .unwrap_err();
+    });
+}
+
 #[test]
 fn test_object_build_from_memory_empty_name() {
     let obj_path = get_test_object_path("runqslower.bpf.o");
@@ -138,6 +313,58 @@ fn test_object_load_invalid() {
         .unwrap_err();
 }
 
+/// Check that the verifier log is attached to the error reported when
+/// the kernel rejects a program.
+#[tag(root)]
+#[test]
+fn test_object_load_verifier_log() {
+    bump_rlimit_mlock();
+
+    let err = open_test_object("verifier_fail.bpf.o").load().unwrap_err();
+    let log = err.verifier_log().expect("no verifier log attached");
+    assert_eq!(log.program(), "handle__invalid_access");
+    assert!(!log.log().is_empty());
+
+    let message = log.message().expect("failed to find verifier message");
+    assert!(message.contains("invalid mem access"), "{message}");
+    let insn = log
+        .failed_insn()
+        .expect("failed to find failing instruction");
+    assert!(insn.insn.starts_with("r0 = *(u32 *)"), "{}", insn.insn);
+    assert!(insn.source_line.is_some());
+}
+
+/// Check that we can disassemble a loaded program, with map references
+/// and helper calls being resolved.
+#[tag(root)]
+#[test]
+fn test_program_disassemble() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+    let disasm = Disassembler::for_program(&prog).disassemble(prog.insns());
+    assert!(disasm.contains("= map[ringbuf1]"), "{disasm}");
+    assert!(disasm.contains("= map[ringbuf2]"), "{disasm}");
+    assert!(disasm.contains("call bpf_ringbuf_reserve#131"), "{disasm}");
+    assert!(disasm.trim_end().ends_with("exit"), "{disasm}");
+
+    let opts = ProgInfoQueryOptions::default()
+        .include_xlated_prog_insns(true)
+        .include_func_info(true)
+        .include_line_info(true)
+        .include_map_ids(true);
+    let id = Program::id_from_fd(prog.as_fd()).unwrap();
+    let info = ProgInfoIter::with_query_opts(opts)
+        .find(|info| info.id == id)
+        .expect("failed to find program info");
+    let disasm = Disassembler::for_program_info(&info)
+        .unwrap()
+        .disassemble(&info.xlated_insns());
+    assert!(disasm.contains("map[ringbuf1]"), "{disasm}");
+    assert!(disasm.contains("; "), "{disasm}");
+}
+
 #[test]
 fn test_object_name() {
     let obj_path = get_test_object_path("runqslower.bpf.o");
@@ -771,6 +998,121 @@ fn test_program_loading_fd_from_pinned_path() {
     prog.unpin(path).expect("unpinning program failed");
 }
 
+/// Check that we can assemble and load a program referencing a map.
+#[tag(root)]
+#[test]
+fn test_program_handle_load() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Array, Some("counter"), 4, 8, 1, &opts).unwrap();
+
+    let mut asm = Assembler::new();
+    let insns = asm
+        .st_imm(MemSize::W, Reg::R10, -4, 0)
+        .mov64_reg(Reg::R2, Reg::R10)
+        .alu64_imm(AluOp::Add, Reg::R2, -4)
+        .ld_map_fd(Reg::R1, map.as_fd())
+        .call_helper(libbpf_sys::BPF_FUNC_map_lookup_elem)
+        .jmp_imm(JmpOp::Eq, Reg::R0, 0, "out")
+        .ldx(MemSize::DW, Reg::R1, Reg::R0, 0)
+        .alu64_imm(AluOp::Add, Reg::R1, 1)
+        .stx(MemSize::DW, Reg::R0, Reg::R1, 0)
+        .label("out")
+        .mov64_imm(Reg::R0, 0)
+        .exit()
+        .finish()
+        .unwrap();
+
+    let mut prog = ProgramHandle::load(
+        ProgramType::SocketFilter,
+        Some("counter"),
+        "GPL",
+        &insns,
+        &ProgramLoadOpts::default(),
+    )
+    .unwrap();
+    assert_eq!(prog.name(), "counter");
+
+    let data = [0u8; 64];
+    let input = ProgramInput {
+        data_in: Some(&data),
+        repeat: 3,
+        ..Default::default()
+    };
+    let _output = prog.test_run(input).unwrap();
+
+    let value = map
+        .lookup(&0u32.to_ne_bytes(), MapFlags::ANY)
+        .unwrap()
+        .unwrap();
+    assert_eq!(value, 3u64.to_ne_bytes());
+}
+
+/// Check that the verifier log is reported when loading a program
+/// from raw instructions fails.
+#[tag(root)]
+#[test]
+fn test_program_handle_load_failure() {
+    bump_rlimit_mlock();
+
+    // Reading from an uninitialized register is rejected.
+    let mut asm = Assembler::new();
+    let insns = asm.mov64_reg(Reg::R0, Reg::R5).exit().finish().unwrap();
+    let err = ProgramHandle::load(
+        ProgramType::SocketFilter,
+        Some("invalid"),
+        "GPL",
+        &insns,
+        &ProgramLoadOpts::default(),
+    )
+    .unwrap_err();
+    let log = err.verifier_log().expect("no verifier log attached");
+    assert!(log.log().contains("R5 !read_ok"), "{}", log.log());
+}
+
+/// Check that we can work with programs via a `ProgramHandle`.
+#[tag(root)]
+#[test]
+fn test_program_handle() {
+    bump_rlimit_mlock();
+
+    let path = "/sys/fs/bpf/myprog_test_program_handle";
+
+    let mut obj = get_test_object("test_run.bpf.o");
+    let prog = get_prog_mut(&mut obj, "handle_xdp");
+    let mut handle = ProgramHandle::try_from(&*prog).unwrap();
+    assert_eq!(handle.name(), "handle_xdp");
+    assert!(matches!(handle.prog_type(), ProgramType::Xdp));
+    assert_eq!(handle.id(), Program::id_from_fd(prog.as_fd()).unwrap());
+
+    handle.pin(path).expect("failed to pin program");
+    let pinned = ProgramHandle::from_pinned_path(path).unwrap();
+    assert_eq!(pinned.id(), handle.id());
+    assert_eq!(pinned.tag().0, handle.tag().0);
+    handle.unpin(path).expect("failed to unpin program");
+
+    let mut by_id = ProgramHandle::from_prog_id(handle.id()).unwrap();
+    let info = by_id
+        .info(&ProgInfoQueryOptions::default().include_xlated_prog_insns(true))
+        .unwrap();
+    assert!(!info.xlated_insns().is_empty());
+
+    let data = [0u8; 64];
+    let input = ProgramInput {
+        data_in: Some(&data),
+        ..Default::default()
+    };
+    let output = by_id.test_run(input).unwrap();
+    assert_eq!(output.return_value, 2 /* XDP_PASS */);
+
+    // Attach to the loopback device.
+    let _link = by_id.attach_xdp(1).unwrap();
+}
+
 #[tag(root)]
 #[test]
 fn test_program_loading_fd_from_pinned_path_with_wrong_pin_type() {
@@ -1230,6 +1572,210 @@ fn test_object_ringbuf_closure() {
     assert_eq!(v2, 2);
 }
 
+/// Check that we can retrieve owned samples from a `RingBufferSource`.
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_source() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let map = get_map(&obj, "ringbuf1");
+    let mut source = RingBufferSource::new(&map).expect("failed to create ringbuf source");
+
+    // Call getpid to ensure the BPF program runs
+    unsafe { libc::getpid() };
+
+    let sample = source
+        .try_next()
+        .expect("failed to consume ringbuf")
+        .expect("no sample available");
+    assert_eq!(sample, 1i32.to_ne_bytes());
+
+    unsafe { libc::getpid() };
+
+    let samples = source.drain().expect("failed to drain ringbuf");
+    assert!(!samples.is_empty());
+    assert!(samples.iter().all(|sample| sample == &1i32.to_ne_bytes()));
+}
+
+/// Check that we can read samples in place using a `RingBufferReader`.
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_reader() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let map = get_map(&obj, "ringbuf1");
+    let mut reader = RingBufferReader::new(&map).expect("failed to create ringbuf reader");
+
+    // Call getpid to ensure the BPF program runs
+    unsafe { libc::getpid() };
+    assert!(!reader.is_empty());
+
+    {
+        let batch = reader.batch();
+        let sample = batch.iter().next().expect("no sample available");
+        assert_eq!(sample, 1i32.to_ne_bytes());
+        // Without a commit, samples are handed out again by the next
+        // batch.
+    }
+
+    let batch = reader.batch();
+    let count = batch
+        .iter()
+        .inspect(|sample| assert_eq!(*sample, 1i32.to_ne_bytes()))
+        .count();
+    assert!(count > 0);
+    let () = batch.commit();
+}
+
+fn tokio_runtime() -> Runtime {
+    RuntimeBuilder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to create tokio runtime")
+}
+
+/// Check that we can asynchronously consume a `RingBuffer`.
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_async_consume() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let (sender, receiver) = channel();
+    let callback = move |data: &[u8]| -> i32 {
+        let mut value: i32 = 0;
+        plain::copy_from_bytes(&mut value, data).expect("Wrong size");
+
+        sender.send(value).expect("failed to send value");
+
+        0
+    };
+
+    let map = get_map(&obj, "ringbuf1");
+    let mut builder = libbpf_rs::RingBufferBuilder::new();
+    builder.add(&map, callback).expect("failed to add ringbuf");
+    let ringbuf = builder.build().expect("failed to build");
+
+    let () = tokio_runtime().block_on(async {
+        let mut ringbuf = AsyncRingBuffer::new(ringbuf).expect("failed to wrap ringbuf");
+
+        // Call getpid to ensure the BPF program runs
+        unsafe { libc::getpid() };
+
+        let count = timeout(Duration::from_secs(5), ringbuf.consume())
+            .await
+            .expect("timed out waiting for data")
+            .expect("failed to consume ringbuf");
+        assert!(count > 0);
+    });
+
+    let value = receiver.recv().expect("failed to receive value");
+    assert_eq!(value, 1);
+}
+
+/// Check that we can stream samples from a `ringbuf` map.
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_stream() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle__sys_enter_getpid");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let map = get_map(&obj, "ringbuf1");
+
+    let () = tokio_runtime().block_on(async {
+        let mut stream = RingBufferStream::new(&map).expect("failed to create ringbuf stream");
+
+        // Call getpid to ensure the BPF program runs
+        unsafe { libc::getpid() };
+
+        let next = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx));
+        let sample = timeout(Duration::from_secs(5), next)
+            .await
+            .expect("timed out waiting for sample")
+            .expect("stream ended unexpectedly")
+            .expect("failed to retrieve sample");
+        assert_eq!(sample, 1i32.to_ne_bytes());
+
+        unsafe { libc::getpid() };
+
+        // Samples not yet handed out are reported when closing the
+        // stream.
+        let samples = stream.close().expect("failed to close stream");
+        assert!(!samples.is_empty());
+    });
+}
+
+/// Check that we can stream events from the per-CPU buffers of a perf
+/// buffer.
+#[tag(root)]
+#[test]
+fn test_object_perf_buffer_stream() {
+    bump_rlimit_mlock();
+
+    let cookie_val = 42u16;
+    let mut obj = get_test_object("tracepoint.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle__tracepoint_with_cookie_pb");
+
+    let opts = TracepointOpts {
+        cookie: cookie_val.into(),
+        ..TracepointOpts::default()
+    };
+    let _link = prog
+        .attach_tracepoint_with_opts("syscalls", "sys_enter_getpid", opts)
+        .expect("failed to attach prog");
+
+    let map = get_map_mut(&mut obj, "pb");
+
+    let () = tokio_runtime().block_on(async {
+        let mut streams = PerfBufferStream::new(&map, 64).expect("failed to create streams");
+        assert!(!streams.is_empty());
+
+        // Make an action that the tracepoint will see
+        let _pid = unsafe { libc::getpid() };
+
+        // The sample may end up in the buffer of any CPU, so wait on all
+        // of them.
+        let next = poll_fn(|cx| {
+            for stream in &mut streams {
+                if let Poll::Ready(event) = Pin::new(stream).poll_next(cx) {
+                    return Poll::Ready(event)
+                }
+            }
+            Poll::Pending
+        });
+        let event = timeout(Duration::from_secs(5), next)
+            .await
+            .expect("timed out waiting for event")
+            .expect("stream ended unexpectedly")
+            .expect("failed to retrieve event");
+
+        match event {
+            PerfEvent::Sample { data, .. } => {
+                assert_eq!(data, i32::from(cookie_val).to_ne_bytes())
+            }
+            event => panic!("unexpected perf event: {event:?}"),
+        }
+
+        for stream in streams {
+            let _events = stream.close().expect("failed to close stream");
+        }
+    });
+}
+
 /// Check that `RingBuffer` works correctly even if the map file descriptors
 /// provided during construction are closed. This test validates that `libbpf`'s
 /// refcount behavior is correctly reflected in our `RingBuffer` lifetimes.
@@ -1402,6 +1948,85 @@ fn test_object_task_iter() {
     assert!(items.iter().any(|&item| item.pid == 1));
 }
 
+/// Check that we can read typed records from a task iterator, repeatedly.
+#[tag(root)]
+#[test]
+fn test_object_task_iter_records() {
+    bump_rlimit_mlock();
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct IndexPidPair {
+        i: u32,
+        pid: i32,
+    }
+
+    let mut obj = get_test_object("taskiter.bpf.o");
+    let link = get_prog_mut(&mut obj, "dump_pid")
+        .attach()
+        .expect("failed to attach prog");
+
+    let decoder = unsafe { PodDecoder::<IndexPidPair>::new() };
+    let mut records = IterRecords::new(&link, decoder).unwrap();
+    let first = records.snapshot().unwrap();
+    assert!(first.iter().any(|item| item.pid == 1));
+    // The index is kept in a global variable and, hence, continues
+    // counting across snapshots.
+    let second = records.snapshot().unwrap();
+    assert!(second.iter().any(|item| item.pid == 1));
+    assert!(second[0].i > first[first.len() - 1].i);
+
+    let btf = obj.btf().unwrap().unwrap();
+    let decoder = BtfDecoder::with_type_name(&btf, "index_pid_pair").unwrap();
+    let records = IterRecords::new(&link, decoder)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(records
+        .iter()
+        .any(|record| record.field("pid") == Some(&BtfValue::Int(1))));
+}
+
+/// Check that a task iterator can be scoped to a single process and
+/// that the scope is reported as part of the link information.
+#[tag(root)]
+#[test]
+fn test_object_task_iter_pid() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("taskiter.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "dump_pid");
+    let pid = process::id();
+    let link = prog
+        .attach_iter_with_opts(IterOpts::Pid(pid))
+        .expect("failed to attach prog");
+
+    let prog_id = Program::id_from_fd(prog.as_fd()).unwrap();
+    let info = LinkInfoIter::default()
+        .find(|link| link.prog_id == prog_id)
+        .expect("failed to find iterator link");
+    match info.info {
+        LinkTypeInfo::Iter(iter) => {
+            assert_eq!(iter.target_name, "task");
+            assert_eq!(iter.params, IterLinkParams::Task { tid: 0, pid });
+        }
+        other => panic!("unexpected link type info: {other:?}"),
+    }
+
+    let mut iter = Iter::new(&link).expect("failed to create iterator");
+    let mut buf = Vec::new();
+    let _bytes_read = iter
+        .read_to_end(&mut buf)
+        .expect("failed to read from iterator");
+    // Each item is an index followed by a PID.
+    let pids = buf
+        .chunks_exact(8)
+        .map(|item| i32::from_ne_bytes(item[4..].try_into().unwrap()))
+        .collect::<Vec<_>>();
+    assert!(!pids.is_empty());
+    assert!(pids.iter().all(|item| *item == pid as i32), "{pids:?}");
+}
+
 #[tag(root)]
 #[test]
 fn test_object_map_iter() {
@@ -1593,6 +2218,183 @@ fn test_object_usdt() {
     assert_eq!(result, 1);
 }
 
+/// Check that we can discover USDT probes in our own process and attach
+/// to all of them matching a pattern.
+#[tag(root)]
+#[test]
+fn test_object_usdt_inspector() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("usdt.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle__usdt");
+
+    let inspector = UsdtInspector::from_pid(process::id()).unwrap();
+    assert!(inspector.providers().contains(&"test_provider"));
+    let probe = inspector
+        .matching("test_provider:test_function")
+        .next()
+        .expect("failed to find USDT probe");
+    assert_eq!(probe.path, current_exe().unwrap());
+
+    let _links = inspector
+        .attach_matching(
+            &mut prog,
+            unsafe { libc::getpid() },
+            "test_provider:test_func*",
+            UsdtOpts::default(),
+        )
+        .expect("failed to attach prog");
+
+    let _err = inspector
+        .attach_matching(
+            &mut prog,
+            unsafe { libc::getpid() },
+            "no_provider:*",
+            UsdtOpts::default(),
+        )
+        .unwrap_err();
+
+    let map = get_map_mut(&mut obj, "ringbuf");
+    let action = || {
+        probe!(test_provider, test_function, 1);
+    };
+    let result = with_ringbuffer(&map, action);
+    assert_eq!(result, 1);
+}
+
+/// Check that the kernel attach target catalog knows about well-known
+/// functions.
+#[tag(root)]
+#[test]
+fn test_kernel_targets() {
+    let targets = KernelTargets::load().unwrap();
+    assert!(targets.matching("tcp_*").next().is_some());
+
+    let func = targets.matching("*").find(|func| func.in_kallsyms).unwrap();
+    assert!(targets.func(&func.name).is_some());
+    let _err = targets
+        .check("libbpf_rs_no_such_function", KernelProbeKind::Kprobe)
+        .unwrap_err();
+}
+
+/// Check that failing to attach a kprobe to a non-existent function
+/// reports a descriptive error.
+#[tag(root)]
+#[test]
+fn test_object_kprobe_attach_error() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("kprobe.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle__kprobe");
+    let err = prog
+        .attach_kprobe(false, "libbpf_rs_no_such_function")
+        .unwrap_err();
+    let msg = format!("{err:#}");
+    assert!(msg.contains("does not exist"), "{msg}");
+}
+
+/// Check that a socket filter drops packets only while attached.
+#[tag(root)]
+#[test]
+fn test_object_socket_filter() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("socket.bpf.o");
+    let prog = get_prog_mut(&mut obj, "drop_all");
+
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let () = receiver
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = receiver.local_addr().unwrap();
+    let mut buf = [0; 8];
+
+    let attachment = prog.attach_socket(&receiver).unwrap();
+    let _cnt = sender.send_to(b"dropped", addr).unwrap();
+    let _err = receiver.recv(&mut buf).unwrap_err();
+
+    let () = attachment.detach().unwrap();
+    let _cnt = sender.send_to(b"passed", addr).unwrap();
+    let cnt = receiver.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..cnt], b"passed");
+}
+
+/// Create a UDP socket with `SO_REUSEPORT` set, bound to the given
+/// port on the loopback interface.
+fn reuseport_socket(port: u16) -> UdpSocket {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
+    assert!(fd >= 0);
+    let sock = unsafe { UdpSocket::from_raw_fd(fd) };
+
+    let one: c_int = 1;
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            addr_of!(one).cast(),
+            size_of_val(&one) as _,
+        )
+    };
+    assert_eq!(ret, 0);
+
+    let addr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as _,
+        sin_port: port.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(Ipv4Addr::LOCALHOST).to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+    let ret = unsafe { libc::bind(fd, addr_of!(addr).cast(), size_of_val(&addr) as _) };
+    assert_eq!(ret, 0, "{}", io::Error::last_os_error());
+    sock
+}
+
+/// Check that a reuseport program steers packets to the socket stored
+/// in a socket array.
+#[tag(root)]
+#[test]
+fn test_object_reuseport_steering() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("socket.bpf.o");
+    let first = reuseport_socket(0);
+    let port = first.local_addr().unwrap().port();
+    let second = reuseport_socket(port);
+    for sock in [&first, &second] {
+        let () = sock
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+    }
+
+    let map = get_map(&obj, "reuseport_socks");
+    let array = SocketArray::new(&map).unwrap();
+    let () = array.insert(0, &second).unwrap();
+    let () = array.insert(1, &first).unwrap();
+    assert!(array.cookie(0).unwrap().is_some());
+    assert_ne!(array.cookie(0).unwrap(), array.cookie(1).unwrap());
+
+    let prog = obj
+        .progs()
+        .find(|prog| prog.name() == "select_first")
+        .unwrap();
+    let _attachment = prog.attach_reuseport(&first).unwrap();
+
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut buf = [0; 8];
+    for _ in 0..8 {
+        let _cnt = sender.send_to(b"steered", ("127.0.0.1", port)).unwrap();
+        let cnt = second.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..cnt], b"steered");
+    }
+    let _err = first.recv(&mut buf).unwrap_err();
+
+    let () = array.remove(1).unwrap();
+    assert_eq!(array.cookie(1).unwrap(), None);
+}
+
 #[tag(root)]
 #[test]
 fn test_object_usdt_cookie() {
@@ -1840,6 +2642,44 @@ fn test_object_link_files() {
     test(vec![obj_path1, obj_path2]);
 }
 
+/// Check that we can link object files held in memory into an in-memory
+/// buffer.
+#[test]
+fn test_object_link_buffers() {
+    let buf1 = fs::read(get_test_object_path("usdt.bpf.o")).unwrap();
+    let buf2 = fs::read(get_test_object_path("ringbuf.bpf.o")).unwrap();
+
+    let mut linker = Linker::in_memory().unwrap();
+    let () = linker.add_buf(&buf1).unwrap();
+    let opts = LinkerFileOpts {
+        name: Some("ringbuf".to_string()),
+        ..Default::default()
+    };
+    let () = linker.add_buf_with_opts(&buf2, &opts).unwrap();
+    let linked = linker.link_to_memory().unwrap();
+
+    // Check that we can load the resulting object file.
+    let _object = ObjectBuilder::default()
+        .debug(true)
+        .open_memory(&linked)
+        .unwrap();
+
+    // Invalid objects are reported under their configured name.
+    let mut linker = Linker::in_memory().unwrap();
+    let opts = LinkerFileOpts {
+        name: Some("garbage".to_string()),
+        ..Default::default()
+    };
+    let err = linker.add_buf_with_opts(b"garbage", &opts).unwrap_err();
+    assert!(format!("{err:#}").contains("`garbage`"), "{err:#}");
+
+    // Only in-memory linkers can link into memory.
+    let output_file = NamedTempFile::new().unwrap();
+    let linker = Linker::new(output_file.path()).unwrap();
+    let err = linker.link_to_memory().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
 /// Get access to the underlying per-cpu ring buffer data.
 fn buffer<'a>(perf: &'a libbpf_rs::PerfBuffer, buf_idx: usize) -> &'a [u8] {
     let perf_buff_ptr = perf.as_libbpf_object();
@@ -2059,3 +2899,331 @@ fn test_run_prog_fail() {
     let input = ProgramInput::default();
     let _err = prog.test_run(input).unwrap_err();
 }
+
+/// Check that we can test run a TC program with a typed context and
+/// multiple repetitions.
+#[tag(root)]
+#[test]
+fn test_run_prog_skb_context() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("test_run.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle_tc");
+
+    let mut ctx_in = SkbContext::default();
+    ctx_in.mark = 41;
+    ctx_in.cb[0] = 1337;
+    let mut ctx_out = SkbContext::default();
+    let data = [0u8; 64];
+    let input = ProgramInput {
+        context_in: Some(ctx_in.as_bytes_mut()),
+        context_out: Some(ctx_out.as_bytes_mut()),
+        data_in: Some(&data),
+        repeat: 100,
+        ..Default::default()
+    };
+    let output = prog.test_run(input).unwrap();
+    assert_eq!(output.return_value, 1337);
+    assert!(output.duration > Duration::ZERO);
+    assert_eq!(ctx_out.mark, 42);
+}
+
+/// Check that we can sample program run time statistics.
+#[tag(root)]
+#[test]
+fn test_program_stats_sampler() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("test_run.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle_tc");
+    let _guard = StatsGuard::new(StatsType::RunTime).unwrap();
+    let mut sampler = ProgramStatsSampler::new();
+    let () = sampler.add_program(prog.as_fd()).unwrap();
+
+    let data = [0u8; 64];
+    let input = ProgramInput {
+        data_in: Some(&data),
+        repeat: 1000,
+        ..Default::default()
+    };
+    let _output = prog.test_run(input).unwrap();
+
+    let stats = sampler.sample().unwrap();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].name.to_bytes(), b"handle_tc");
+    assert_eq!(stats[0].run_cnt, 1000);
+    assert!(stats[0].rate() > 0.0);
+    assert!(stats[0].avg_run_time_ns() > 0.0);
+
+    let stats = sampler.sample().unwrap();
+    assert_eq!(stats[0].run_cnt, 0);
+}
+
+/// Check that we can create a report of the kernel's BPF features.
+#[tag(root)]
+#[test]
+fn test_feature_probe() {
+    bump_rlimit_mlock();
+
+    let report = FeatureReport::probe().unwrap();
+    assert_eq!(report.program_types.get("socket_filter"), Some(&true));
+    assert_eq!(report.map_types.get("hash"), Some(&true));
+    assert_eq!(report.btf_kinds.get("int"), Some(&true));
+    assert!(report.ringbuf);
+    assert!(report.jit.enable.is_some());
+
+    let helpers = report.helpers.get("socket_filter").unwrap();
+    assert!(helpers.contains("bpf_map_lookup_elem"));
+    let attach_types = report.attach_types.unwrap();
+    assert!(attach_types.contains("cgroup_inet_ingress"));
+    let link_types = report.link_types.unwrap();
+    assert!(link_types.contains("xdp"));
+}
+
+/// Check that we can access global variables in the data sections of an
+/// object by name.
+#[tag(root)]
+#[test]
+fn test_object_globals() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("globals.bpf.o");
+    unsafe {
+        *open_obj.global_mut::<u64>("rodata_in").unwrap() = 40;
+        assert_eq!(*open_obj.global_mut::<u64>("data_in").unwrap(), 1);
+        assert_eq!(*open_obj.global_mut::<u64>("custom_in").unwrap(), 2);
+        *open_obj.global_mut::<u64>("custom_in").unwrap() = 1;
+
+        let err = open_obj.global_mut::<u32>("data_in").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err = open_obj.global_mut::<u64>("does_not_exist").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    let mut obj = open_obj.load().unwrap();
+    let mut prog = get_prog_mut(&mut obj, "sum_globals");
+    let data = [0u8; 64];
+    let input = ProgramInput {
+        data_in: Some(&data),
+        ..Default::default()
+    };
+    let _output = prog.test_run(input).unwrap();
+
+    unsafe {
+        assert_eq!(*obj.global::<u64>("rodata_in").unwrap(), 40);
+        assert_eq!(*obj.global::<u64>("bss_out").unwrap(), 42);
+
+        *obj.global_mut::<u64>("data_in").unwrap() = 2;
+        let err = obj.global_mut::<u64>("rodata_in").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    }
+
+    let mut prog = get_prog_mut(&mut obj, "sum_globals");
+    let input = ProgramInput {
+        data_in: Some(&data),
+        ..Default::default()
+    };
+    let _output = prog.test_run(input).unwrap();
+    assert_eq!(unsafe { *obj.global::<u64>("bss_out").unwrap() }, 43);
+}
+
+/// Check that we can bind to the maps, programs, and global variables
+/// of an object by means of a subskeleton configuration.
+#[tag(root)]
+#[test]
+fn test_object_subskeleton() {
+    fn build_config(object: NonNull<libbpf_sys::bpf_object>) -> ObjectSubskeletonConfig {
+        let mut builder = ObjectSubskeletonConfigBuilder::new();
+        builder
+            .map(".rodata", true)
+            .map(".bss", true)
+            .prog("sum_globals")
+            .var(0, "rodata_in")
+            .var(1, "bss_out");
+        builder.build(object).unwrap()
+    }
+
+    bump_rlimit_mlock();
+
+    let open_obj = open_test_object("globals.bpf.o");
+    let config = build_config(open_obj.as_libbpf_object());
+    assert!(!config.map_ptr(0).unwrap().is_null());
+    assert!(!config.prog_ptr(0).unwrap().is_null());
+    let err = config.var_ptr(2).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    unsafe { *config.var_ptr(0).unwrap().cast::<u64>() = 40 };
+
+    let mut obj = open_obj.load().unwrap();
+    let mut prog = get_prog_mut(&mut obj, "sum_globals");
+    let data = [0u8; 64];
+    let input = ProgramInput {
+        data_in: Some(&data),
+        ..Default::default()
+    };
+    let _output = prog.test_run(input).unwrap();
+
+    let config = build_config(obj.as_libbpf_object());
+    assert_eq!(unsafe { *config.var_ptr(1).unwrap().cast::<u64>() }, 43);
+
+    // Binding to a map the object does not have fails.
+    let mut builder = ObjectSubskeletonConfigBuilder::new();
+    builder.map("does_not_exist", false);
+    assert!(builder.build(obj.as_libbpf_object()).is_err());
+}
+
+/// Check that we can test run XDP and raw tracepoint programs with typed
+/// contexts.
+#[tag(root)]
+#[test]
+fn test_run_prog_xdp_raw_tp_context() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("test_run.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle_xdp");
+    let data = [0u8; 64];
+    let mut ctx = XdpContext::new(data.len());
+    ctx.ingress_ifindex = 1;
+    let input = ProgramInput {
+        context_in: Some(ctx.as_bytes_mut()),
+        data_in: Some(&data),
+        ..Default::default()
+    };
+    let output = prog.test_run(input).unwrap();
+    assert_eq!(output.return_value, 2 /* XDP_PASS */);
+
+    let input = ProgramInput {
+        data_in: Some(&data),
+        repeat: 10,
+        batch_size: 4,
+        xdp_live_frames: true,
+        ..Default::default()
+    };
+    let _output = prog.test_run(input).unwrap();
+
+    let mut prog = get_prog_mut(&mut obj, "handle_raw_tp");
+    let mut ctx = RawTracepointContext::new(&[40, 2]).unwrap();
+    let input = ProgramInput {
+        context_in: Some(ctx.as_bytes_mut()),
+        ..Default::default()
+    };
+    let output = prog.test_run(input).unwrap();
+    assert_eq!(output.return_value, 42);
+}
+
+/// Check that we can attach a program to a netfilter hook and query the
+/// resulting link.
+#[tag(root)]
+#[test]
+fn test_attach_netfilter() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("netfilter.bpf.o");
+    let mut prog = get_prog_mut(&mut obj, "handle_netfilter");
+    let opts = NetfilterOpts {
+        protocol_family: NetfilterProtocolFamily::Ipv4,
+        hook: NetfilterHook::LocalIn,
+        priority: -128,
+        ..Default::default()
+    };
+    let _link = prog
+        .attach_netfilter(opts)
+        .expect("failed to attach netfilter program");
+
+    let prog_id = Program::id_from_fd(prog.as_fd()).unwrap();
+    let info = LinkInfoIter::default()
+        .find(|link| link.prog_id == prog_id)
+        .expect("failed to find netfilter link");
+
+    match info.info {
+        LinkTypeInfo::Netfilter(netfilter) => {
+            assert_eq!(netfilter.protocol_family, NetfilterProtocolFamily::Ipv4);
+            assert_eq!(netfilter.hook, NetfilterHook::LocalIn);
+            assert_eq!(netfilter.priority, -128);
+            assert!(!netfilter.ip_defrag);
+        }
+        other => panic!("unexpected link type info: {other:?}"),
+    }
+}
+
+/// Check that we can minimize the kernel's BTF information to the types
+/// referenced by an object's CO-RE relocations and load the object using
+/// the result.
+#[tag(root)]
+#[test]
+fn test_btf_min_core() {
+    bump_rlimit_mlock();
+
+    let vmlinux = Btf::from_vmlinux().unwrap();
+    let obj_path = get_test_object_path("runqslower.bpf.o");
+    let min = vmlinux.min_core(&[&obj_path]).unwrap();
+    assert!(!min.is_empty());
+    assert!(min.len() < vmlinux.len() / 10);
+
+    let task = min
+        .type_by_name::<types::Struct<'_>>("task_struct")
+        .unwrap();
+    let members = task
+        .iter()
+        .filter_map(|member| member.name)
+        .collect::<HashSet<_>>();
+    assert!(members.contains(OsStr::new("pid")));
+    assert!(members.contains(OsStr::new("tgid")));
+    assert!(members.len() < 5, "{members:?}");
+
+    let file = NamedTempFile::new().unwrap();
+    let () = fs::write(file.path(), min.to_bytes().unwrap()).unwrap();
+    let _obj = ObjectBuilder::default()
+        .btf_custom_path(file.path())
+        .unwrap()
+        .open_file(&obj_path)
+        .unwrap()
+        .load()
+        .unwrap();
+
+    // The running kernel exposes BTF information itself, so no custom
+    // file should be picked.
+    assert_eq!(
+        find_custom_btf(file.path().parent().unwrap()).unwrap(),
+        None
+    );
+}
+
+/// Check that we can check the CO-RE compatibility of an object with
+/// the BTF information of a set of kernels.
+#[test]
+fn test_btf_check_core_compat() {
+    let vmlinux = Btf::from_vmlinux().unwrap();
+    let dir = tempdir().unwrap();
+    let _bytes = fs::copy("/sys/kernel/btf/vmlinux", dir.path().join("vmlinux")).unwrap();
+    // BTF information minimized for a different object lacks most of
+    // the types the object under test refers to.
+    let min = vmlinux
+        .min_core(&[get_test_object_path("runqslower.bpf.o")])
+        .unwrap();
+    let () = fs::write(dir.path().join("runqslower.btf"), min.to_bytes().unwrap()).unwrap();
+
+    let kernels = check_core_compat(get_test_object_path("taskiter.bpf.o"), dir.path()).unwrap();
+    assert_eq!(kernels.len(), 2);
+    assert_eq!(kernels[0].btf, dir.path().join("runqslower.btf"));
+    assert_eq!(kernels[1].btf, dir.path().join("vmlinux"));
+    assert!(kernels[1].is_compatible(), "{:#?}", kernels[1]);
+    assert!(!kernels[0].is_compatible());
+
+    let prog = kernels[0]
+        .programs
+        .iter()
+        .find(|prog| prog.name == "dump_pid")
+        .unwrap();
+    assert_eq!(prog.section, "iter/task");
+    assert!(prog.relos > prog.failures.len());
+    assert!(prog
+        .failures
+        .iter()
+        .any(|failure| failure.spec.starts_with("struct bpf_iter__task.task ")));
+
+    for failure in &prog.failures {
+        let source = failure.source.as_ref().unwrap();
+        assert!(source.file.ends_with("taskiter.bpf.c"), "{source:?}");
+        assert!(source.text.contains("->"), "{source:?}");
+    }
+}
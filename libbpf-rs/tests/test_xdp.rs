@@ -1,18 +1,25 @@
 #[allow(dead_code)]
 mod common;
 
+use std::fs;
 use std::os::fd::AsFd;
 
 use scopeguard::defer;
 
 use test_tag::tag;
 
+use libbpf_rs::Object;
+use libbpf_rs::ProgramHandle;
 use libbpf_rs::Xdp;
+use libbpf_rs::XdpActions;
+use libbpf_rs::XdpComponentOpts;
+use libbpf_rs::XdpDispatcher;
 use libbpf_rs::XdpFlags;
 
 use crate::common::bump_rlimit_mlock;
 use crate::common::get_prog_mut;
 use crate::common::get_test_object;
+use crate::common::open_test_object;
 
 
 const LO_IFINDEX: i32 = 1;
@@ -69,3 +76,69 @@ fn test_xdp() {
         .detach(LO_IFINDEX, XdpFlags::UPDATE_IF_NOEXIST)
         .is_ok());
 }
+
+
+/// Check that we can run multiple programs on an interface by means of
+/// an `XdpDispatcher`.
+#[tag(root)]
+#[test]
+fn test_xdp_dispatcher() {
+    bump_rlimit_mlock();
+
+    let pin_root = "/sys/fs/bpf/libbpf-rs-test-xdp-dispatcher";
+    let dispatcher =
+        XdpDispatcher::with_pin_root(LO_IFINDEX, XdpFlags::SKB_MODE, pin_root).unwrap();
+    defer! {
+        let _result = fs::remove_dir_all(pin_root);
+    }
+
+    let load = || -> Object {
+        let mut obj = open_test_object("xdp.bpf.o");
+        let mut prog = obj.progs_mut().next().unwrap();
+        let () = dispatcher.prepare(&mut prog).unwrap();
+        obj.load().unwrap()
+    };
+    let mut obj1 = load();
+    let prog1 = get_prog_mut(&mut obj1, "xdp_filter");
+    let mut obj2 = load();
+    let prog2 = get_prog_mut(&mut obj2, "xdp_filter");
+
+    let opts = XdpComponentOpts {
+        priority: 20,
+        ..Default::default()
+    };
+    let id1 = dispatcher.add(prog1.as_fd(), &opts).unwrap();
+    let opts = XdpComponentOpts {
+        priority: 10,
+        continue_on: XdpActions::PASS | XdpActions::DROP,
+        ..Default::default()
+    };
+    let id2 = dispatcher.add(prog2.as_fd(), &opts).unwrap();
+    let _err = dispatcher.add(prog2.as_fd(), &opts).unwrap_err();
+
+    let components = dispatcher.components().unwrap();
+    let ids = components
+        .iter()
+        .map(|comp| comp.prog_id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [id2, id1]);
+    assert_eq!(
+        components[0].continue_on,
+        XdpActions::PASS | XdpActions::DROP
+    );
+
+    let xdp = Xdp::new(prog1.as_fd());
+    let attached = xdp.query_id(LO_IFINDEX, XdpFlags::SKB_MODE).unwrap();
+    let pinned = ProgramHandle::from_pinned_path(dispatcher.pin_dir().join("dispatcher")).unwrap();
+    assert_eq!(attached, pinned.id());
+
+    let () = dispatcher.remove(id2).unwrap();
+    let components = dispatcher.components().unwrap();
+    assert_eq!(components.len(), 1);
+    assert_eq!(components[0].prog_id, id1);
+    let _err = dispatcher.remove(id2).unwrap_err();
+
+    let () = dispatcher.remove(id1).unwrap();
+    assert!(dispatcher.components().unwrap().is_empty());
+    assert_eq!(xdp.query_id(LO_IFINDEX, XdpFlags::SKB_MODE).unwrap(), 0);
+}